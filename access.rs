@@ -0,0 +1,159 @@
+use crate::error::{ErrorCode, WasmStorageError};
+use crate::origin::Origin;
+use crate::WasmStorage;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+
+/// Whether an operation on a key is allowed.
+#[derive(Clone)]
+enum Rule {
+    Allow,
+    Deny,
+    // `(key)` for reads, `(key, value)` for writes; truthy allows
+    Check(js_sys::Function),
+}
+
+impl Rule {
+    fn from_js(rule: &JsValue, name: &str) -> Result<Rule, JsValue> {
+        if rule.is_undefined() || rule.is_null() {
+            return Ok(Rule::Allow);
+        }
+        if let Some(check) = rule.dyn_ref::<js_sys::Function>() {
+            return Ok(Rule::Check(check.clone()));
+        }
+        match rule.as_bool() {
+            Some(true) => Ok(Rule::Allow),
+            Some(false) => Ok(Rule::Deny),
+            None => Err(WasmStorageError::invalid_argument(format!("Access rule \"{}\" must be a boolean or a function", name))
+                .with("rule", rule.clone())
+                .into()),
+        }
+    }
+
+    fn allows(&self, args: &[&JsValue]) -> Result<bool, JsValue> {
+        match self {
+            Rule::Allow => Ok(true),
+            Rule::Deny => Ok(false),
+            Rule::Check(check) => {
+                let args: js_sys::Array = args.iter().map(|&arg| arg.clone()).collect();
+                Ok(check.apply(&JsValue::NULL, &args)?.is_truthy())
+            }
+        }
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct AccessPolicy {
+    prefix: String,
+    read: Rule,
+    write: Rule,
+}
+
+/// Access policies by key prefix; the longest matching prefix applies.
+pub(crate) type AccessPolicies = Vec<AccessPolicy>;
+
+fn access_denied(key: &str, operation: &str) -> JsValue {
+    WasmStorageError::new(ErrorCode::AccessDenied, format!("{} access to \"{}\" is denied", operation, key))
+        .with("key", key)
+        .with("operation", operation)
+        .into()
+}
+
+#[wasm_bindgen]
+impl WasmStorage {
+    /// Restricts keys starting with `prefix`: `rules.read` guards
+    /// `get_state`, `get_many`, `get_state_cloned`, `watch` and the typed
+    /// getters, which fail on a denied key, and hides denied keys from
+    /// everything that enumerates or streams state: `get_all_state`,
+    /// `keys`, `values`, `entries`, `has`, `len`, the prefix, glob and
+    /// filter queries, `range`, views, listener events, exports and
+    /// recordings, and the snapshots sent to Service Worker clients and
+    /// frames. `rules.write` guards `set_state`, `remove_state`,
+    /// `clear_state` and the writes of `dispatch`. Each rule is a boolean or
+    /// a function, `(key)` for reads and `(key, value)` for writes (value
+    /// undefined for removals), returning truthy to allow; a missing rule
+    /// allows. Denied operations fail with ACCESS_DENIED. When prefixes
+    /// overlap, the longest one applies. Writes the store makes itself
+    /// (hydration, sync, replays) are not checked. Null `rules` removes the
+    /// prefix's policy.
+    #[wasm_bindgen]
+    pub fn set_access_policy(&mut self, prefix: &str, rules: JsValue) -> Result<(), JsValue> {
+        let mut policies = self.access_policies.lock().map_err(WasmStorageError::lock_poisoned)?;
+        policies.retain(|policy| policy.prefix != prefix);
+        if rules.is_undefined() || rules.is_null() {
+            return Ok(());
+        }
+        let rule = |name: &str| Rule::from_js(&js_sys::Reflect::get(&rules, &JsValue::from_str(name))?, name);
+        policies.push(AccessPolicy { prefix: prefix.to_string(), read: rule("read")?, write: rule("write")? });
+        Ok(())
+    }
+}
+
+impl WasmStorage {
+    // The policy for `key`, cloned so rule functions run without the lock
+    fn access_policy(&self, key: &str) -> Result<Option<AccessPolicy>, JsValue> {
+        let policies = self.access_policies.lock().map_err(WasmStorageError::lock_poisoned)?;
+        Ok(policies
+            .iter()
+            .filter(|policy| key.starts_with(policy.prefix.as_str()))
+            .max_by_key(|policy| policy.prefix.len())
+            .cloned())
+    }
+
+    pub(crate) fn ensure_readable(&self, key: &str) -> Result<(), JsValue> {
+        match self.access_policy(key)? {
+            Some(policy) if !policy.read.allows(&[&JsValue::from_str(key)])? => Err(access_denied(key, "read")),
+            _ => Ok(()),
+        }
+    }
+
+    /// Rejects a local write the policy for `key` denies; `value` is None
+    /// for removals.
+    pub(crate) fn ensure_write_allowed(&self, key: &str, value: Option<&JsValue>) -> Result<(), JsValue> {
+        if self.current_origin()? != Origin::Local {
+            return Ok(());
+        }
+        let value = value.cloned().unwrap_or(JsValue::UNDEFINED);
+        match self.access_policy(key)? {
+            Some(policy) if !policy.write.allows(&[&JsValue::from_str(key), &value])? => Err(access_denied(key, "write")),
+            _ => Ok(()),
+        }
+    }
+
+    // Whether any policy may deny a read, so unrestricted stores skip filtering
+    pub(crate) fn restricts_reads(&self) -> Result<bool, JsValue> {
+        let policies = self.access_policies.lock().map_err(WasmStorageError::lock_poisoned)?;
+        Ok(policies.iter().any(|policy| !matches!(policy.read, Rule::Allow)))
+    }
+
+    /// Whether `key` may be read; a rule that throws denies.
+    pub(crate) fn is_readable(&self, key: &str) -> Result<bool, JsValue> {
+        Ok(!self.restricts_reads()? || self.ensure_readable(key).is_ok())
+    }
+
+    /// `items` without those whose key (from `key_of`) can't be read. Call
+    /// it without holding the state lock, since rules may read the store.
+    pub(crate) fn retain_readable<T>(&self, items: Vec<T>, key_of: impl Fn(&T) -> &str) -> Result<Vec<T>, JsValue> {
+        if !self.restricts_reads()? {
+            return Ok(items);
+        }
+        Ok(items.into_iter().filter(|item| self.ensure_readable(key_of(item)).is_ok()).collect())
+    }
+
+    /// `all` without the keys whose reads are denied, or `all` itself when
+    /// no policy restricts reads.
+    pub(crate) fn filter_readable(&self, all: js_sys::Object) -> Result<js_sys::Object, JsValue> {
+        if !self.restricts_reads()? {
+            return Ok(all);
+        }
+        let readable = js_sys::Object::new();
+        for key in js_sys::Object::keys(&all).iter() {
+            let name = key.as_string().unwrap_or_default();
+            if self.ensure_readable(&name).is_ok() {
+                js_sys::Reflect::set(&readable, &key, &js_sys::Reflect::get(&all, &key)?)?;
+            }
+        }
+        js_sys::Object::freeze(&readable);
+        Ok(readable)
+    }
+}
@@ -0,0 +1,74 @@
+use crate::error::WasmStorageError;
+use crate::WasmStorage;
+use std::collections::BTreeSet;
+use wasm_bindgen::prelude::*;
+
+// Handled by the store itself, so always known
+const BUILT_IN: [&str; 3] = ["SET_STATE", "REMOVE_STATE", "CLEAR_STATE"];
+
+/// Action types declared with `register_action_types`, and whether
+/// dispatching any other type is an error.
+#[derive(Default)]
+pub(crate) struct ActionTypes {
+    registered: BTreeSet<String>,
+    strict: bool,
+}
+
+#[wasm_bindgen]
+impl WasmStorage {
+    /// Declares the action types the app dispatches and returns
+    /// `action_types`. Types must be non-empty strings; registering one
+    /// again does nothing.
+    #[wasm_bindgen]
+    pub fn register_action_types(&mut self, types: js_sys::Array) -> Result<js_sys::Object, JsValue> {
+        let types = types
+            .iter()
+            .map(|action_type| match action_type.as_string() {
+                Some(action_type) if !action_type.is_empty() => Ok(action_type),
+                _ => Err(WasmStorageError::invalid_argument("Action types must be non-empty strings").with("actionType", action_type).into()),
+            })
+            .collect::<Result<Vec<String>, JsValue>>()?;
+        self.action_types.lock().map_err(WasmStorageError::lock_poisoned)?.registered.extend(types);
+        self.action_types()
+    }
+
+    /// With `enabled`, dispatching a type that is neither registered, built
+    /// in (`SET_STATE`, `REMOVE_STATE`, `CLEAR_STATE`) nor handled by a
+    /// reducer fails with INVALID_ARGUMENT instead of going unhandled, so
+    /// typos surface at the call site.
+    #[wasm_bindgen]
+    pub fn set_strict_action_types(&mut self, enabled: bool) -> Result<(), JsValue> {
+        self.action_types.lock().map_err(WasmStorageError::lock_poisoned)?.strict = enabled;
+        Ok(())
+    }
+
+    /// A frozen `{ TYPE: "TYPE" }` object of the built-in and registered
+    /// action types, to dispatch by constant instead of by string.
+    #[wasm_bindgen(getter)]
+    pub fn action_types(&self) -> Result<js_sys::Object, JsValue> {
+        let action_types = self.action_types.lock().map_err(WasmStorageError::lock_poisoned)?;
+        let constants = js_sys::Object::new();
+        for action_type in BUILT_IN.iter().copied().chain(action_types.registered.iter().map(String::as_str)) {
+            js_sys::Reflect::set(&constants, &JsValue::from_str(action_type), &JsValue::from_str(action_type))?;
+        }
+        Ok(js_sys::Object::freeze(&constants))
+    }
+}
+
+impl WasmStorage {
+    /// Rejects an unknown action type in strict mode.
+    pub(crate) fn check_action_type(&self, action_type: &str) -> Result<(), JsValue> {
+        {
+            let action_types = self.action_types.lock().map_err(WasmStorageError::lock_poisoned)?;
+            if !action_types.strict || BUILT_IN.contains(&action_type) || action_types.registered.contains(action_type) {
+                return Ok(());
+            }
+        }
+        if self.reducers.lock().map_err(WasmStorageError::lock_poisoned)?.contains_key(action_type) {
+            return Ok(());
+        }
+        Err(WasmStorageError::invalid_argument(format!("Unknown action type \"{}\"; register it with register_action_types", action_type))
+            .with("actionType", action_type)
+            .into())
+    }
+}
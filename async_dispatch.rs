@@ -0,0 +1,141 @@
+use crate::error::WasmStorageError;
+use crate::WasmStorage;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::{future_to_promise, JsFuture};
+
+/// An async dispatch waiting for its payload, with the key that lets a
+/// newer dispatch supersede it.
+struct AsyncRun {
+    run: u32,
+    key: String,
+    controller: web_sys::AbortController,
+    // `options.signal` and the abort listener forwarding it to `controller`
+    external: Option<(web_sys::AbortSignal, Closure<dyn FnMut()>)>,
+}
+
+impl AsyncRun {
+    // Stops listening to `options.signal` once the run is settled
+    fn detach(self) -> web_sys::AbortController {
+        if let Some((signal, on_abort)) = &self.external {
+            let _ = signal.remove_event_listener_with_callback("abort", on_abort.as_ref().unchecked_ref());
+        }
+        self.controller
+    }
+}
+
+/// Async dispatches waiting for their payload, oldest first.
+#[derive(Default)]
+pub(crate) struct AsyncDispatches {
+    next_run: u32,
+    running: Vec<AsyncRun>,
+}
+
+impl AsyncDispatches {
+    // Whether the run was still in flight
+    fn finish(&mut self, run: u32) -> bool {
+        match self.running.iter().position(|running| running.run == run) {
+            Some(index) => {
+                self.running.remove(index).detach();
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn cancel(&mut self, key: &str) -> Vec<web_sys::AbortController> {
+        let (cancelled, kept): (Vec<AsyncRun>, Vec<AsyncRun>) =
+            std::mem::take(&mut self.running).into_iter().partition(|running| running.key == key);
+        self.running = kept;
+        cancelled.into_iter().map(AsyncRun::detach).collect()
+    }
+}
+
+#[wasm_bindgen]
+impl WasmStorage {
+    /// Calls `producer(signal)` for the payload (a value or a promise, e.g.
+    /// a `fetch` passed `signal`) and dispatches `action_type` with it once
+    /// it resolves. A newer `dispatch_async` with the same `options.key`
+    /// (default: the action type), `cancel_async(key)` or aborting
+    /// `options.signal` cancels it: `signal` aborts and the stale payload
+    /// is never dispatched. The listener on `options.signal` is removed
+    /// once the dispatch settles or is cancelled. Resolves with the
+    /// `StorageEvent`, or null when cancelled; rejects, dispatching
+    /// nothing, when the producer fails.
+    #[wasm_bindgen]
+    pub fn dispatch_async(&mut self, action_type: &str, producer: js_sys::Function, options: Option<js_sys::Object>) -> Result<js_sys::Promise, JsValue> {
+        self.ensure_alive()?;
+        let (key, external) = match &options {
+            Some(options) => (
+                js_sys::Reflect::get(options, &JsValue::from_str("key"))?.as_string(),
+                js_sys::Reflect::get(options, &JsValue::from_str("signal"))?.dyn_into::<web_sys::AbortSignal>().ok(),
+            ),
+            None => (None, None),
+        };
+        if external.as_ref().is_some_and(|signal| signal.aborted()) {
+            return Ok(js_sys::Promise::resolve(&JsValue::NULL));
+        }
+        let key = key.unwrap_or_else(|| action_type.to_string());
+
+        let controller = web_sys::AbortController::new()?;
+        let signal = controller.signal();
+        let external = match external {
+            Some(external) => {
+                let forwarded = controller.clone();
+                let on_abort = Closure::wrap(Box::new(move || forwarded.abort()) as Box<dyn FnMut()>);
+                external.add_event_listener_with_callback("abort", on_abort.as_ref().unchecked_ref())?;
+                Some((external, on_abort))
+            }
+            None => None,
+        };
+        let (run, superseded) = {
+            let mut dispatches = self.async_dispatches.lock().map_err(WasmStorageError::lock_poisoned)?;
+            let superseded = dispatches.cancel(&key);
+            dispatches.next_run += 1;
+            let run = dispatches.next_run;
+            dispatches.running.push(AsyncRun { run, key, controller, external });
+            (run, superseded)
+        };
+        // Aborted outside the lock, since abort listeners run synchronously
+        for controller in superseded {
+            controller.abort();
+        }
+
+        let produced = match producer.call1(&JsValue::NULL, &signal) {
+            Ok(produced) => produced,
+            Err(error) => {
+                self.async_dispatches.lock().map_err(WasmStorageError::lock_poisoned)?.finish(run);
+                return Err(error);
+            }
+        };
+        let mut store = self.clone();
+        let action_type = action_type.to_string();
+        Ok(future_to_promise(async move {
+            let payload = JsFuture::from(js_sys::Promise::resolve(&produced)).await;
+            let in_flight = store.async_dispatches.lock().map_err(WasmStorageError::lock_poisoned)?.finish(run);
+            // A cancelled producer rejecting with its AbortError isn't a failure
+            if !in_flight || signal.aborted() || store.destroyed()? {
+                log_debug!("Dropped cancelled async {}", action_type);
+                return Ok(JsValue::NULL);
+            }
+            Ok(store.dispatch(&action_type, payload?)?.into())
+        }))
+    }
+
+    /// Cancels the async dispatches in flight under `key` (by default their
+    /// action type) and returns how many there were.
+    #[wasm_bindgen]
+    pub fn cancel_async(&mut self, key: &str) -> Result<u32, JsValue> {
+        let cancelled = self.async_dispatches.lock().map_err(WasmStorageError::lock_poisoned)?.cancel(key);
+        for controller in &cancelled {
+            controller.abort();
+        }
+        Ok(cancelled.len() as u32)
+    }
+
+    /// How many async dispatches are still waiting for their payload.
+    #[wasm_bindgen]
+    pub fn pending_async(&self) -> Result<u32, JsValue> {
+        Ok(self.async_dispatches.lock().map_err(WasmStorageError::lock_poisoned)?.running.len() as u32)
+    }
+}
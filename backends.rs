@@ -0,0 +1,379 @@
+use crate::error::WasmStorageError;
+use crate::wal::wal_key;
+use crate::WasmStorage;
+use std::collections::HashMap;
+use crate::sync::{Lock, Shared};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::{future_to_promise, JsFuture};
+
+// localStorage budgets are ~5 MiB in every major browser
+const LOCAL_STORAGE_QUOTA_BYTES: f64 = 5.0 * 1024.0 * 1024.0;
+const DEFAULT_OPFS_DIRECTORY: &str = "wasm-storage";
+const DEFAULT_CACHE_NAME: &str = "wasm-storage";
+const DEFAULT_URL_PREFIX: &str = "/__wasm_storage__/";
+
+/// Where persistence keeps its snapshot and write-ahead log. Every backend
+/// is synchronous once registered, so persistence treats them all alike.
+#[derive(Clone)]
+pub(crate) enum Backend {
+    Local(web_sys::Storage),
+    // One file per item, opened when the backend was registered
+    Opfs(HashMap<String, web_sys::FileSystemSyncAccessHandle>),
+    Cache(CacheItems),
+    // Any object with getItem/setItem/removeItem, e.g. sessionStorage
+    Js(JsValue),
+}
+
+/// Backend writes running in the background (Cache Storage puts, the
+/// sealed and Web Locks writers), and the resolvers of promises waiting
+/// for them all to finish.
+#[derive(Default)]
+pub(crate) struct BackendWrites {
+    running: u32,
+    waiters: Vec<js_sys::Function>,
+}
+
+/// Runs `write` in the background, counted in `writes` until it is done.
+pub(crate) fn spawn_write(writes: &Shared<Lock<BackendWrites>>, write: impl std::future::Future<Output = ()> + 'static) {
+    if let Ok(mut writes) = writes.lock() {
+        writes.running += 1;
+    }
+    let writes = writes.clone();
+    wasm_bindgen_futures::spawn_local(async move {
+        write.await;
+        let waiters = match writes.lock() {
+            Ok(mut writes) => {
+                writes.running = writes.running.saturating_sub(1);
+                match writes.running {
+                    0 => std::mem::take(&mut writes.waiters),
+                    _ => Vec::new(),
+                }
+            }
+            Err(_) => Vec::new(),
+        };
+        for resolve in waiters {
+            let _ = resolve.call0(&JsValue::NULL);
+        }
+    });
+}
+
+/// Items stored in the Cache Storage API as responses to synthetic request
+/// URLs (`prefix` + item key). The cache is read once, at registration, and
+/// mirrored in memory; writes update the mirror and reach the cache in the
+/// background.
+#[derive(Clone)]
+pub(crate) struct CacheItems {
+    cache: web_sys::Cache,
+    prefix: String,
+    items: Shared<Lock<HashMap<String, String>>>,
+    writes: Shared<Lock<BackendWrites>>,
+}
+
+impl CacheItems {
+    fn url(&self, key: &str) -> String {
+        format!("{}{}", self.prefix, String::from(js_sys::encode_uri_component(key)))
+    }
+
+    fn get(&self, key: &str) -> Result<Option<String>, JsValue> {
+        Ok(self.items.lock().map_err(WasmStorageError::lock_poisoned)?.get(key).cloned())
+    }
+
+    fn set(&self, key: &str, value: &str) -> Result<(), JsValue> {
+        self.items.lock().map_err(WasmStorageError::lock_poisoned)?.insert(key.to_string(), value.to_string());
+        let response = web_sys::Response::new_with_opt_str(Some(value))?;
+        write_through(&self.writes, key, self.cache.put_with_str(&self.url(key), &response));
+        Ok(())
+    }
+
+    fn remove(&self, key: &str) -> Result<(), JsValue> {
+        self.items.lock().map_err(WasmStorageError::lock_poisoned)?.remove(key);
+        write_through(&self.writes, key, self.cache.delete_with_str(&self.url(key)));
+        Ok(())
+    }
+}
+
+fn write_through(writes: &Shared<Lock<BackendWrites>>, key: &str, operation: js_sys::Promise) {
+    let key = key.to_string();
+    spawn_write(writes, async move {
+        if let Err(e) = JsFuture::from(operation).await {
+            log_warn!("Cache Storage write for {} failed: {:?}", key, e);
+        }
+    });
+}
+
+impl WasmStorage {
+    /// Resolves once every background backend write has finished.
+    pub(crate) fn backend_writes_settled(&self) -> Result<js_sys::Promise, JsValue> {
+        let mut writes = self.backend_writes.lock().map_err(WasmStorageError::lock_poisoned)?;
+        if writes.running == 0 {
+            return Ok(js_sys::Promise::resolve(&JsValue::UNDEFINED));
+        }
+        let mut resolver = None;
+        let settled = js_sys::Promise::new(&mut |resolve, _| resolver = Some(resolve));
+        writes.waiters.extend(resolver);
+        Ok(settled)
+    }
+}
+
+impl Backend {
+    pub(crate) fn local_storage() -> Result<Backend, JsValue> {
+        let storage = js_sys::Reflect::get(&js_sys::global(), &JsValue::from_str("localStorage"))?;
+        if storage.is_undefined() || storage.is_null() {
+            return Err(WasmStorageError::unsupported("localStorage is not available in this environment").into());
+        }
+        Ok(Backend::Local(storage.unchecked_into()))
+    }
+
+    /// Quota assumed when persistence isn't given `quotaBytes`.
+    pub(crate) fn default_quota_bytes(&self) -> f64 {
+        match self {
+            Backend::Local(_) => LOCAL_STORAGE_QUOTA_BYTES,
+            Backend::Opfs(_) | Backend::Cache(_) | Backend::Js(_) => f64::INFINITY,
+        }
+    }
+
+    pub(crate) fn get_item(&self, key: &str) -> Result<Option<String>, JsValue> {
+        match self {
+            Backend::Local(storage) => storage.get_item(key),
+            Backend::Opfs(files) => {
+                let file = opfs_file(files, key)?;
+                let size = file.get_size()? as usize;
+                if size == 0 {
+                    return Ok(None);
+                }
+                let mut bytes = vec![0u8; size];
+                // Reads and writes always pass an offset, since the handle's cursor moves with each call
+                let options = web_sys::FileSystemReadWriteOptions::new();
+                options.set_at(0.0);
+                file.read_with_u8_array_and_options(&mut bytes, &options)?;
+                String::from_utf8(bytes)
+                    .map(Some)
+                    .map_err(|_| WasmStorageError::invalid_state(format!("OPFS file for {} is not valid UTF-8", key)).into())
+            }
+            Backend::Cache(items) => items.get(key),
+            Backend::Js(adapter) => {
+                let item = call(adapter, "getItem", &[JsValue::from_str(key)])?;
+                Ok(item.as_string())
+            }
+        }
+    }
+
+    pub(crate) fn set_item(&self, key: &str, value: &str) -> Result<(), JsValue> {
+        match self {
+            Backend::Local(storage) => storage.set_item(key, value),
+            Backend::Opfs(files) => {
+                let file = opfs_file(files, key)?;
+                file.truncate_with_u32(0)?;
+                write_at(file, value.as_bytes(), 0.0)?;
+                file.flush()
+            }
+            Backend::Cache(items) => items.set(key, value),
+            Backend::Js(adapter) => call(adapter, "setItem", &[JsValue::from_str(key), JsValue::from_str(value)]).map(drop),
+        }
+    }
+
+    pub(crate) fn remove_item(&self, key: &str) -> Result<(), JsValue> {
+        match self {
+            Backend::Local(storage) => storage.remove_item(key),
+            Backend::Opfs(files) => {
+                let file = opfs_file(files, key)?;
+                file.truncate_with_u32(0)?;
+                file.flush()
+            }
+            Backend::Cache(items) => items.remove(key),
+            Backend::Js(adapter) => call(adapter, "removeItem", &[JsValue::from_str(key)]).map(drop),
+        }
+    }
+
+    /// Appends a line to an item. OPFS writes just the new bytes; the other
+    /// backends have to rewrite the item.
+    pub(crate) fn append_line(&self, key: &str, line: &str) -> Result<(), JsValue> {
+        if let Backend::Opfs(files) = self {
+            let file = opfs_file(files, key)?;
+            let size = file.get_size()?;
+            let text = if size == 0.0 { line.to_string() } else { format!("\n{}", line) };
+            write_at(file, text.as_bytes(), size)?;
+            return file.flush();
+        }
+        let text = match self.get_item(key)? {
+            Some(existing) if !existing.is_empty() => format!("{}\n{}", existing, line),
+            _ => line.to_string(),
+        };
+        self.set_item(key, &text)
+    }
+}
+
+fn opfs_file<'a>(
+    files: &'a HashMap<String, web_sys::FileSystemSyncAccessHandle>,
+    key: &str,
+) -> Result<&'a web_sys::FileSystemSyncAccessHandle, JsValue> {
+    files.get(key).ok_or_else(|| {
+        WasmStorageError::invalid_state(format!("The OPFS backend was not registered with storage key {}", key))
+            .with("key", key)
+            .into()
+    })
+}
+
+fn write_at(file: &web_sys::FileSystemSyncAccessHandle, bytes: &[u8], at: f64) -> Result<(), JsValue> {
+    let options = web_sys::FileSystemReadWriteOptions::new();
+    options.set_at(at);
+    file.write_with_u8_array_and_options(bytes, &options)?;
+    Ok(())
+}
+
+fn call(adapter: &JsValue, method: &str, args: &[JsValue]) -> Result<JsValue, JsValue> {
+    let function = js_sys::Reflect::get(adapter, &JsValue::from_str(method))?
+        .dyn_into::<js_sys::Function>()
+        .map_err(|_| WasmStorageError::invalid_argument(format!("Persistence backend is missing {}()", method)))?;
+    let args: js_sys::Array = args.iter().collect();
+    function.apply(adapter, &args)
+}
+
+// OPFS file names can't contain path separators
+fn file_name(key: &str) -> String {
+    key.replace(['/', '\\'], "_")
+}
+
+#[wasm_bindgen]
+impl WasmStorage {
+    /// Registers `adapter`, any object with synchronous `getItem(key)`,
+    /// `setItem(key, value)` and `removeItem(key)` (such as sessionStorage),
+    /// as a persistence backend usable as `enable_persistence(key, {
+    /// backend: name })`. `"localStorage"` is always available.
+    #[wasm_bindgen]
+    pub fn register_backend(&mut self, name: &str, adapter: JsValue) -> Result<(), JsValue> {
+        for method in ["getItem", "setItem", "removeItem"] {
+            if !js_sys::Reflect::get(&adapter, &JsValue::from_str(method))?.is_function() {
+                return Err(WasmStorageError::invalid_argument(format!("Persistence backend is missing {}()", method)).into());
+            }
+        }
+        self.backends.lock().map_err(WasmStorageError::lock_poisoned)?.insert(name.to_string(), Backend::Js(adapter));
+        Ok(())
+    }
+
+    /// Registers an Origin Private File System backend under `name`, storing
+    /// each item in its own file through a `FileSystemSyncAccessHandle`, so
+    /// it only works inside a dedicated worker. Sync handles can only be
+    /// opened asynchronously, so the files for `storageKeys` (and their
+    /// write-ahead logs) are opened here, inside `directory` (default
+    /// `"wasm-storage"`). Resolves once the backend can be used.
+    #[wasm_bindgen]
+    pub fn register_opfs_backend(&self, name: &str, options: js_sys::Object) -> js_sys::Promise {
+        let store = self.clone();
+        let name = name.to_string();
+
+        future_to_promise(async move {
+            let storage_keys = js_sys::Reflect::get(&options, &JsValue::from_str("storageKeys"))?;
+            if !js_sys::Array::is_array(&storage_keys) {
+                return Err(WasmStorageError::invalid_argument("register_opfs_backend needs a storageKeys array").into());
+            }
+            let directory = js_sys::Reflect::get(&options, &JsValue::from_str("directory"))?
+                .as_string()
+                .unwrap_or_else(|| DEFAULT_OPFS_DIRECTORY.to_string());
+
+            let navigator = js_sys::Reflect::get(&js_sys::global(), &JsValue::from_str("navigator"))?;
+            let storage = js_sys::Reflect::get(&navigator, &JsValue::from_str("storage"))?;
+            if storage.is_undefined() || js_sys::Reflect::get(&storage, &JsValue::from_str("getDirectory"))?.is_undefined() {
+                return Err(WasmStorageError::unsupported("OPFS is not available in this environment").into());
+            }
+            let storage: web_sys::StorageManager = storage.unchecked_into();
+            let root: web_sys::FileSystemDirectoryHandle = JsFuture::from(storage.get_directory()).await?.unchecked_into();
+
+            let create = web_sys::FileSystemGetDirectoryOptions::new();
+            create.set_create(true);
+            let directory: web_sys::FileSystemDirectoryHandle =
+                JsFuture::from(root.get_directory_handle_with_options(&directory, &create)).await?.unchecked_into();
+
+            let mut files = HashMap::new();
+            for storage_key in js_sys::Array::from(&storage_keys).iter().filter_map(|key| key.as_string()) {
+                for item in [storage_key.clone(), wal_key(&storage_key)] {
+                    let create = web_sys::FileSystemGetFileOptions::new();
+                    create.set_create(true);
+                    let file: web_sys::FileSystemFileHandle =
+                        JsFuture::from(directory.get_file_handle_with_options(&file_name(&item), &create)).await?.unchecked_into();
+                    // Fails outside dedicated workers, where sync handles don't exist
+                    let handle = JsFuture::from(file.create_sync_access_handle()).await.map_err(|_| {
+                        WasmStorageError::unsupported("OPFS sync access handles are only available in dedicated workers")
+                    })?;
+                    files.insert(item, handle.unchecked_into());
+                }
+            }
+
+            let teardown_files: Vec<web_sys::FileSystemSyncAccessHandle> = files.values().cloned().collect();
+            store.backends.lock().map_err(WasmStorageError::lock_poisoned)?.insert(name.clone(), Backend::Opfs(files));
+            // Sync handles lock their files until closed
+            store.register_teardown(Box::new(move || {
+                for file in teardown_files.iter() {
+                    file.close();
+                }
+            }))?;
+            log_info!("OPFS backend {} registered", name);
+            Ok(JsValue::UNDEFINED)
+        })
+    }
+
+    /// Registers a Cache Storage backend under `name`, storing each item as
+    /// the response to `urlPrefix` + key (default `"/__wasm_storage__/"`) in
+    /// the cache `cacheName` (default `"wasm-storage"`). A Service Worker can
+    /// read the same entries with `caches.match()`, so both sides of an
+    /// offline-first app share one hydrated state. Resolves once existing
+    /// entries have been loaded.
+    #[wasm_bindgen]
+    pub fn register_cache_backend(&self, name: &str, options: Option<js_sys::Object>) -> js_sys::Promise {
+        let store = self.clone();
+        let name = name.to_string();
+        let options = options.map(JsValue::from).unwrap_or(JsValue::UNDEFINED);
+
+        future_to_promise(async move {
+            let option = |name: &str| js_sys::Reflect::get(&options, &JsValue::from_str(name)).ok().and_then(|value| value.as_string());
+            let cache_name = option("cacheName").unwrap_or_else(|| DEFAULT_CACHE_NAME.to_string());
+            let prefix = option("urlPrefix").unwrap_or_else(|| DEFAULT_URL_PREFIX.to_string());
+
+            let caches = js_sys::Reflect::get(&js_sys::global(), &JsValue::from_str("caches"))?;
+            if caches.is_undefined() || caches.is_null() {
+                return Err(WasmStorageError::unsupported("Cache Storage is not available in this environment").into());
+            }
+            let caches: web_sys::CacheStorage = caches.unchecked_into();
+            let cache: web_sys::Cache = JsFuture::from(caches.open(&cache_name)).await?.unchecked_into();
+
+            let mut items = HashMap::new();
+            let requests = js_sys::Array::from(&JsFuture::from(cache.keys()).await?);
+            for request in requests.iter() {
+                let request: web_sys::Request = request.unchecked_into();
+                // Request URLs are absolute, the prefix usually isn't
+                let url = request.url();
+                let Some(start) = url.find(&prefix) else {
+                    continue;
+                };
+                let Ok(key) = js_sys::decode_uri_component(&url[start + prefix.len()..]) else {
+                    continue;
+                };
+                let response: web_sys::Response = JsFuture::from(cache.match_with_request(&request)).await?.unchecked_into();
+                if let Some(text) = JsFuture::from(response.text()?).await?.as_string() {
+                    items.insert(String::from(key), text);
+                }
+            }
+
+            let backend = Backend::Cache(CacheItems { cache, prefix, items: Shared::new(Lock::new(items)), writes: store.backend_writes.clone() });
+            store.backends.lock().map_err(WasmStorageError::lock_poisoned)?.insert(name.clone(), backend);
+            log_info!("Cache Storage backend {} registered", name);
+            Ok(JsValue::UNDEFINED)
+        })
+    }
+}
+
+impl WasmStorage {
+    /// The backend registered as `name`.
+    pub(crate) fn backend(&self, name: &str) -> Result<Backend, JsValue> {
+        if name == "localStorage" {
+            return Backend::local_storage();
+        }
+        let backends = self.backends.lock().map_err(WasmStorageError::lock_poisoned)?;
+        backends.get(name).cloned().ok_or_else(|| {
+            WasmStorageError::invalid_argument(format!("No persistence backend named {}", name))
+                .with("backend", name)
+                .into()
+        })
+    }
+}
@@ -0,0 +1,96 @@
+use crate::error::WasmStorageError;
+use crate::subscriptions::Change;
+use crate::WasmStorage;
+use std::collections::HashMap;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+
+/// Changes waiting for the next microtask while batching is on. Repeated
+/// writes to a key collapse into one change that keeps the first `oldValue`.
+#[derive(Default)]
+pub(crate) struct Batch {
+    enabled: bool,
+    pending: Vec<Change>,
+    positions: HashMap<String, usize>,
+    scheduled: bool,
+}
+
+impl Batch {
+    fn take(&mut self) -> Vec<Change> {
+        self.positions.clear();
+        self.scheduled = false;
+        std::mem::take(&mut self.pending)
+    }
+}
+
+#[wasm_bindgen]
+impl WasmStorage {
+    /// When enabled, synchronous writes are delivered on the next microtask
+    /// as one change per key. Disabling delivers anything still pending.
+    #[wasm_bindgen]
+    pub fn set_batching(&mut self, enabled: bool) -> Result<(), JsValue> {
+        self.batch.lock().map_err(WasmStorageError::lock_poisoned)?.enabled = enabled;
+        if !enabled {
+            self.flush_sync()?;
+        }
+        Ok(())
+    }
+
+    /// Delivers pending batched changes now instead of on the next microtask.
+    #[wasm_bindgen]
+    pub fn flush_sync(&self) -> Result<(), JsValue> {
+        let pending = self.batch.lock().map_err(WasmStorageError::lock_poisoned)?.take();
+        let mut changes = Vec::with_capacity(pending.len());
+        for change in pending {
+            // A key written back to its original value is no change at all
+            if !self.unchanged(&change.old_value, &change.value)? {
+                changes.push(change);
+            }
+        }
+        if changes.is_empty() {
+            return Ok(());
+        }
+        self.deliver_changes(&changes)
+    }
+}
+
+impl WasmStorage {
+    /// Queues `change` when batching is on; returns false if it should be
+    /// delivered right away.
+    pub(crate) fn batch_change(&self, change: &Change) -> Result<bool, JsValue> {
+        let schedule = {
+            let mut batch = self.batch.lock().map_err(WasmStorageError::lock_poisoned)?;
+            if !batch.enabled {
+                return Ok(false);
+            }
+            match batch.positions.get(&change.key).copied() {
+                Some(position) => {
+                    let pending = &mut batch.pending[position];
+                    pending.value = change.value.clone();
+                    pending.origin = change.origin;
+                    pending.timestamp = change.timestamp;
+                }
+                None => {
+                    let position = batch.pending.len();
+                    batch.positions.insert(change.key.clone(), position);
+                    batch.pending.push(change.clone());
+                }
+            }
+            !std::mem::replace(&mut batch.scheduled, true)
+        };
+
+        if schedule {
+            let store = self.clone();
+            let flush = Closure::once_into_js(move || {
+                if let Err(e) = store.flush_sync() {
+                    log_warn!("Batched notification failed: {:?}", e);
+                }
+            });
+            let queue_microtask = js_sys::Reflect::get(&js_sys::global(), &JsValue::from_str("queueMicrotask"))?
+                .dyn_into::<js_sys::Function>()
+                .map_err(|_| JsValue::from_str("queueMicrotask is not available in this environment"))?;
+            queue_microtask.call1(&JsValue::NULL, &flush)?;
+        }
+        Ok(true)
+    }
+}
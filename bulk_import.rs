@@ -0,0 +1,81 @@
+use crate::error::WasmStorageError;
+use crate::origin::Origin;
+use crate::{next_tick, WasmStorage};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::future_to_promise;
+
+const DEFAULT_CHUNK_SIZE: u32 = 500;
+
+#[wasm_bindgen]
+impl WasmStorage {
+    /// Parses a JSON document of key/value pairs and imports it in chunks.
+    /// See `restore` for the supported options.
+    #[wasm_bindgen]
+    pub fn import_json(&self, json: &str, options: Option<js_sys::Object>) -> Result<js_sys::Promise, JsValue> {
+        let document = js_sys::JSON::parse(json)?;
+        let snapshot = document
+            .dyn_into::<js_sys::Object>()
+            .map_err(|_| WasmStorageError::invalid_argument("import_json expects a JSON object of key/value pairs"))?;
+        Ok(self.restore(snapshot, options))
+    }
+
+    /// Imports a snapshot object, yielding to the event loop between chunks.
+    /// Options: `chunkSize` (entries per chunk, default 500) and
+    /// `onProgress({ processed, total, percentage })`. Resolves to the number
+    /// of imported keys.
+    #[wasm_bindgen]
+    pub fn restore(&self, snapshot: js_sys::Object, options: Option<js_sys::Object>) -> js_sys::Promise {
+        let mut store = self.clone();
+        let options = options.map(JsValue::from).unwrap_or(JsValue::UNDEFINED);
+
+        future_to_promise(async move {
+            let chunk_size = js_sys::Reflect::get(&options, &JsValue::from_str("chunkSize"))
+                .ok()
+                .and_then(|size| size.as_f64())
+                .map(|size| (size as u32).max(1))
+                .unwrap_or(DEFAULT_CHUNK_SIZE);
+            let on_progress = js_sys::Reflect::get(&options, &JsValue::from_str("onProgress"))
+                .ok()
+                .and_then(|callback| callback.dyn_into::<js_sys::Function>().ok());
+
+            let entries = js_sys::Object::entries(&snapshot);
+            let total = entries.length();
+            let mut processed = 0;
+
+            while processed < total {
+                let end = (processed + chunk_size).min(total);
+                // Tagged per chunk: other writes may happen while we yield
+                store.with_origin(Origin::Hydration, |store| {
+                    for i in processed..end {
+                        let pair = js_sys::Array::from(&entries.get(i));
+                        let key = pair.get(0).as_string().unwrap_or_default();
+                        store.set_state(&key, pair.get(1))?;
+                    }
+                    Ok(())
+                })?;
+                processed = end;
+
+                if let Some(callback) = &on_progress {
+                    let progress = js_sys::Object::new();
+                    js_sys::Reflect::set(&progress, &JsValue::from_str("processed"), &JsValue::from(processed))?;
+                    js_sys::Reflect::set(&progress, &JsValue::from_str("total"), &JsValue::from(total))?;
+                    js_sys::Reflect::set(
+                        &progress,
+                        &JsValue::from_str("percentage"),
+                        &JsValue::from_f64(processed as f64 * 100.0 / total as f64),
+                    )?;
+                    callback.call1(&JsValue::NULL, &progress)?;
+                }
+
+                if processed < total {
+                    next_tick().await?;
+                }
+            }
+
+            log_info!("Imported {} keys", total);
+            store.finish_hydration("restore")?;
+            Ok(JsValue::from(total))
+        })
+    }
+}
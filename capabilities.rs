@@ -0,0 +1,93 @@
+use crate::WasmStorage;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+
+// Reads a nested global like `navigator.storage.getDirectory`; any failure
+// along the way (missing object, throwing getter) counts as absent
+fn global_path(path: &[&str]) -> Option<JsValue> {
+    let mut current: JsValue = js_sys::global().into();
+    for name in path {
+        current = js_sys::Reflect::get(&current, &JsValue::from_str(name)).ok()?;
+        if current.is_undefined() || current.is_null() {
+            return None;
+        }
+    }
+    Some(current)
+}
+
+fn has(path: &[&str]) -> bool {
+    global_path(path).is_some()
+}
+
+// localStorage can exist yet throw on access (sandboxed iframes, some private modes)
+fn has_local_storage() -> bool {
+    let Some(storage) = global_path(&["localStorage"]) else {
+        return false;
+    };
+    let storage: web_sys::Storage = storage.unchecked_into();
+    storage.set_item("__wasm_storage_probe__", "1").is_ok() && storage.remove_item("__wasm_storage_probe__").is_ok()
+}
+
+fn status(available: bool, degraded: bool) -> &'static str {
+    match (available, degraded) {
+        (true, _) => "enabled",
+        (false, true) => "degraded",
+        (false, false) => "unavailable",
+    }
+}
+
+#[wasm_bindgen]
+impl WasmStorage {
+    /// Reports which browser APIs this environment offers (`environment`)
+    /// and what that means for each store feature (`features`: `"enabled"`,
+    /// `"degraded"` when a fallback is used, or `"unavailable"`).
+    #[wasm_bindgen]
+    pub fn capabilities(&self) -> Result<js_sys::Object, JsValue> {
+        let local_storage = has_local_storage();
+        let in_window = has(&["window"]) && has(&["document"]);
+        let shared_array_buffer = has(&["SharedArrayBuffer"])
+            && global_path(&["crossOriginIsolated"]).is_some_and(|isolated| isolated.is_truthy());
+
+        let environment = [
+            ("localStorage", local_storage),
+            ("indexedDB", has(&["indexedDB"])),
+            ("broadcastChannel", has(&["BroadcastChannel"])),
+            ("webLocks", has(&["navigator", "locks"])),
+            ("opfs", has(&["navigator", "storage", "getDirectory"])),
+            ("sharedArrayBuffer", shared_array_buffer),
+            ("cryptoSubtle", has(&["crypto", "subtle"])),
+            ("queueMicrotask", has(&["queueMicrotask"])),
+            ("requestAnimationFrame", has(&["requestAnimationFrame"])),
+            ("requestIdleCallback", has(&["requestIdleCallback"])),
+            ("rtcDataChannel", has(&["RTCDataChannel"])),
+            ("window", in_window),
+        ];
+        let env = js_sys::Object::new();
+        for (name, available) in environment.iter() {
+            js_sys::Reflect::set(&env, &JsValue::from_str(name), &JsValue::from_bool(*available))?;
+        }
+
+        let features = [
+            ("persistence", status(local_storage, false)),
+            ("opfsPersistence", status(has(&["FileSystemSyncAccessHandle"]), false)),
+            ("cachePersistence", status(has(&["caches"]), false)),
+            ("encryption", status(has(&["crypto", "subtle"]), false)),
+            ("batching", status(has(&["queueMicrotask"]), false)),
+            ("frameDelivery", status(has(&["requestAnimationFrame"]), has(&["setTimeout"]))),
+            ("idleDelivery", status(has(&["requestIdleCallback"]), has(&["setTimeout"]))),
+            // Outside a window only end_transient_scope ends a scope
+            ("transientScopes", status(in_window, true)),
+            ("peerSync", status(has(&["RTCDataChannel"]), false)),
+            ("pushRefresh", status(has(&["PushEvent"]) && !in_window, false)),
+        ];
+        let feats = js_sys::Object::new();
+        for (name, state) in features.iter() {
+            js_sys::Reflect::set(&feats, &JsValue::from_str(name), &JsValue::from_str(state))?;
+        }
+
+        let report = js_sys::Object::new();
+        js_sys::Reflect::set(&report, &JsValue::from_str("environment"), &env)?;
+        js_sys::Reflect::set(&report, &JsValue::from_str("features"), &feats)?;
+        Ok(report)
+    }
+}
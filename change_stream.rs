@@ -0,0 +1,101 @@
+use crate::error::{ErrorCode, WasmStorageError};
+use crate::subscriptions::Subscription;
+use crate::WasmStorage;
+use std::collections::VecDeque;
+use crate::sync::{Lock, Shared};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+
+#[derive(Default)]
+struct StreamState {
+    queue: VecDeque<JsValue>,
+    // Resolver of a pending `next()` call, if the consumer is ahead of the producer
+    waiting: Option<js_sys::Function>,
+    closed: bool,
+    subscription: Option<Subscription>,
+    listener: Option<Closure<dyn FnMut(JsValue)>>,
+}
+
+#[wasm_bindgen]
+impl WasmStorage {
+    /// Returns an async iterator of change events for `for await` loops.
+    /// Breaking out of the loop (or calling `return()`) unsubscribes.
+    #[wasm_bindgen]
+    pub fn changes(&mut self) -> Result<JsValue, JsValue> {
+        let stream = Shared::new(Lock::new(StreamState::default()));
+
+        let listener_stream = stream.clone();
+        let listener = Closure::wrap(Box::new(move |event: JsValue| {
+            let Ok(mut state) = listener_stream.lock() else {
+                return;
+            };
+            match state.waiting.take() {
+                Some(resolve) => {
+                    let _ = resolve.call1(&JsValue::NULL, &iterator_result(&event, false));
+                }
+                None => state.queue.push_back(event),
+            }
+        }) as Box<dyn FnMut(JsValue)>);
+
+        let subscription = self.subscribe(listener.as_ref().unchecked_ref::<js_sys::Function>().clone(), None)?;
+        {
+            let mut state = stream.lock().map_err(WasmStorageError::lock_poisoned)?;
+            state.subscription = Some(subscription);
+            state.listener = Some(listener);
+        }
+
+        let next_stream = stream.clone();
+        let next = Closure::wrap(Box::new(move || -> js_sys::Promise {
+            let Ok(mut state) = next_stream.lock() else {
+                return js_sys::Promise::reject(&WasmStorageError::new(ErrorCode::LockPoisoned, "Change stream lock poisoned").into());
+            };
+            if let Some(event) = state.queue.pop_front() {
+                return js_sys::Promise::resolve(&iterator_result(&event, false));
+            }
+            if state.closed {
+                return js_sys::Promise::resolve(&iterator_result(&JsValue::UNDEFINED, true));
+            }
+            let mut waiting = None;
+            let promise = js_sys::Promise::new(&mut |resolve, _reject| waiting = Some(resolve));
+            state.waiting = waiting;
+            promise
+        }) as Box<dyn FnMut() -> js_sys::Promise>);
+
+        let return_stream = stream.clone();
+        let finish = Closure::wrap(Box::new(move || -> js_sys::Promise {
+            let (subscription, waiting) = match return_stream.lock() {
+                Ok(mut state) => {
+                    state.closed = true;
+                    state.queue.clear();
+                    state.listener = None;
+                    (state.subscription.take(), state.waiting.take())
+                }
+                Err(_) => (None, None),
+            };
+            if let Some(subscription) = subscription {
+                let _ = subscription.unsubscribe();
+            }
+            let done = iterator_result(&JsValue::UNDEFINED, true);
+            if let Some(resolve) = waiting {
+                let _ = resolve.call1(&JsValue::NULL, &done);
+            }
+            js_sys::Promise::resolve(&done)
+        }) as Box<dyn FnMut() -> js_sys::Promise>);
+
+        let iterator = js_sys::Object::new();
+        js_sys::Reflect::set(&iterator, &JsValue::from_str("next"), &next.into_js_value())?;
+        js_sys::Reflect::set(&iterator, &JsValue::from_str("return"), &finish.into_js_value())?;
+        let this_iterator = iterator.clone();
+        let async_iterator = Closure::wrap(Box::new(move || this_iterator.clone()) as Box<dyn FnMut() -> js_sys::Object>);
+        js_sys::Reflect::set(&iterator, &js_sys::Symbol::async_iterator(), &async_iterator.into_js_value())?;
+
+        Ok(iterator.into())
+    }
+}
+
+fn iterator_result(value: &JsValue, done: bool) -> JsValue {
+    let result = js_sys::Object::new();
+    let _ = js_sys::Reflect::set(&result, &JsValue::from_str("value"), value);
+    let _ = js_sys::Reflect::set(&result, &JsValue::from_str("done"), &JsValue::from_bool(done));
+    result.into()
+}
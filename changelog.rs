@@ -0,0 +1,245 @@
+use crate::digest::{sha256, to_hex};
+use crate::error::{ErrorCode, WasmStorageError};
+use crate::origin::Origin;
+use crate::signing::tampered;
+use crate::WasmStorage;
+use std::collections::HashMap;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+
+const EXPORT_FORMAT: &str = "wasm-storage-incremental";
+
+/// Sequence-numbered record of writes. Only the latest change per key is
+/// kept, which is all an incremental backup needs to reproduce the state.
+#[derive(Default)]
+pub(crate) struct ChangeLog {
+    seq: u64,
+    last_clear: u64,
+    key_seqs: HashMap<String, u64>,
+    // Highest sequence number applied through import_incremental
+    imported_to: u64,
+}
+
+impl ChangeLog {
+    fn record(&mut self, key: &str) {
+        self.seq += 1;
+        self.key_seqs.insert(key.to_string(), self.seq);
+    }
+
+    /// Sequence number of `key`'s last write; 0 if it hasn't changed since
+    /// the last clear.
+    pub(crate) fn last_write(&self, key: &str) -> u64 {
+        self.key_seqs.get(key).copied().unwrap_or(0)
+    }
+
+    /// The current seq and the keys written after `seq`.
+    pub(crate) fn changed_since(&self, seq: u64) -> (u64, Vec<String>) {
+        let keys = self.key_seqs.iter().filter(|(_, changed)| **changed > seq).map(|(key, _)| key.clone()).collect();
+        (self.seq, keys)
+    }
+
+    pub(crate) fn cleared_since(&self, seq: u64) -> bool {
+        self.last_clear > seq
+    }
+
+    fn record_clear(&mut self) {
+        self.seq += 1;
+        self.last_clear = self.seq;
+        self.key_seqs.clear();
+    }
+}
+
+#[wasm_bindgen]
+impl WasmStorage {
+    #[wasm_bindgen]
+    pub fn current_seq(&self) -> Result<f64, JsValue> {
+        let changelog = self.changelog.lock().map_err(WasmStorageError::lock_poisoned)?;
+        Ok(changelog.seq as f64)
+    }
+
+    /// Exports the changes made after `since_seq` as an ordered JSON stream
+    /// carrying a SHA-256 digest. Pass the returned `to` as the next `since_seq`.
+    #[wasm_bindgen]
+    pub fn export_incremental(&self, since_seq: f64) -> Result<String, JsValue> {
+        let since = since_seq.max(0.0) as u64;
+        let (to, last_clear, mut changed) = {
+            let changelog = self.changelog.lock().map_err(WasmStorageError::lock_poisoned)?;
+            let changed: Vec<(u64, String)> = changelog
+                .key_seqs
+                .iter()
+                .filter(|(_, seq)| **seq > since)
+                .map(|(key, seq)| (*seq, key.clone()))
+                .collect();
+            (changelog.seq, changelog.last_clear, changed)
+        };
+        changed.sort();
+        let changed = self.retain_readable(changed, |(_, key)| key)?;
+
+        let ops = js_sys::Array::new();
+        if last_clear > since {
+            let op = js_sys::Object::new();
+            js_sys::Reflect::set(&op, &JsValue::from_str("seq"), &JsValue::from_f64(last_clear as f64))?;
+            js_sys::Reflect::set(&op, &JsValue::from_str("op"), &JsValue::from_str("clear"))?;
+            ops.push(&op);
+        }
+
+        let changed: Vec<(u64, String, Option<JsValue>)> = {
+            let state = self.state.lock().map_err(WasmStorageError::lock_poisoned)?;
+            changed
+                .into_iter()
+                .map(|(seq, key)| {
+                    let value = state.get(&key).cloned();
+                    (seq, key, value)
+                })
+                .collect()
+        };
+        for (seq, key, value) in changed {
+            let op = js_sys::Object::new();
+            js_sys::Reflect::set(&op, &JsValue::from_str("seq"), &JsValue::from_f64(seq as f64))?;
+            js_sys::Reflect::set(&op, &JsValue::from_str("key"), &JsValue::from_str(&key))?;
+            match value {
+                Some(value) => {
+                    let value = self.compress_value(value)?;
+                    js_sys::Reflect::set(&op, &JsValue::from_str("op"), &JsValue::from_str("set"))?;
+                    js_sys::Reflect::set(&op, &JsValue::from_str("value"), &value)?;
+                }
+                None => {
+                    js_sys::Reflect::set(&op, &JsValue::from_str("op"), &JsValue::from_str("remove"))?;
+                }
+            }
+            ops.push(&op);
+        }
+
+        let ops_json = String::from(js_sys::JSON::stringify(&ops)?);
+        let stream = js_sys::Object::new();
+        js_sys::Reflect::set(&stream, &JsValue::from_str("format"), &JsValue::from_str(EXPORT_FORMAT))?;
+        js_sys::Reflect::set(&stream, &JsValue::from_str("from"), &JsValue::from_f64(since as f64))?;
+        js_sys::Reflect::set(&stream, &JsValue::from_str("to"), &JsValue::from_f64(to as f64))?;
+        js_sys::Reflect::set(&stream, &JsValue::from_str("ops"), &ops)?;
+        js_sys::Reflect::set(&stream, &JsValue::from_str("digest"), &JsValue::from_str(&stream_digest(since, to, &ops_json)))?;
+        if let Some(signing) = self.signing()? {
+            let signature = signing.signature(&stream_message(since, to, &ops_json));
+            js_sys::Reflect::set(&stream, &JsValue::from_str("signature"), &JsValue::from_str(&signature))?;
+        }
+
+        Ok(String::from(js_sys::JSON::stringify(&stream)?))
+    }
+
+    /// Verifies and applies a stream produced by `export_incremental`. Streams
+    /// must be applied without gaps; re-applying an overlapping stream is safe.
+    /// Returns the number of applied operations.
+    #[wasm_bindgen]
+    pub fn import_incremental(&mut self, stream_json: &str) -> Result<u32, JsValue> {
+        let stream = js_sys::JSON::parse(stream_json)?;
+        let format = js_sys::Reflect::get(&stream, &JsValue::from_str("format"))?.as_string();
+        if format.as_deref() != Some(EXPORT_FORMAT) {
+            return Err(WasmStorageError::invalid_argument("Not an incremental export stream").into());
+        }
+
+        let from = js_sys::Reflect::get(&stream, &JsValue::from_str("from"))?.as_f64().unwrap_or(0.0) as u64;
+        let to = js_sys::Reflect::get(&stream, &JsValue::from_str("to"))?.as_f64().unwrap_or(0.0) as u64;
+        let ops = js_sys::Array::from(&js_sys::Reflect::get(&stream, &JsValue::from_str("ops"))?);
+        let digest = js_sys::Reflect::get(&stream, &JsValue::from_str("digest"))?.as_string().unwrap_or_default();
+
+        let ops_json = String::from(js_sys::JSON::stringify(&ops)?);
+        if stream_digest(from, to, &ops_json) != digest {
+            return Err(WasmStorageError::new(ErrorCode::IntegrityFailed, "Incremental export failed integrity check").into());
+        }
+        if let Some(signing) = self.signing()? {
+            let signature = js_sys::Reflect::get(&stream, &JsValue::from_str("signature"))?.as_string();
+            match signature {
+                Some(signature) if signing.verify(&stream_message(from, to, &ops_json), &signature) => {}
+                None if signing.accepts_unsigned() => {}
+                _ => return Err(tampered("Incremental export failed signature verification").into()),
+            }
+        }
+
+        let imported_to = self.changelog.lock().map_err(WasmStorageError::lock_poisoned)?.imported_to;
+        if from > imported_to {
+            return Err(WasmStorageError::invalid_state(format!(
+                "Incremental export starts at seq {} but only seq {} has been imported",
+                from, imported_to
+            )).with("from", from as f64).with("importedTo", imported_to as f64).into());
+        }
+
+        self.with_origin(Origin::Replay, |store| {
+            for op in ops.iter() {
+                let kind = js_sys::Reflect::get(&op, &JsValue::from_str("op"))?.as_string().unwrap_or_default();
+                let key = js_sys::Reflect::get(&op, &JsValue::from_str("key"))?.as_string().unwrap_or_default();
+                match kind.as_str() {
+                    "clear" => store.clear_state()?,
+                    "set" => {
+                        let value = store.decompress_value(&js_sys::Reflect::get(&op, &JsValue::from_str("value"))?)?;
+                        let value = store.decode_value(&key, &value)?;
+                        store.set_state(&key, value)?
+                    }
+                    "remove" => store.remove_state(&key)?,
+                    _ => return Err(WasmStorageError::invalid_argument(format!("Unknown incremental op: {}", kind)).into()),
+                }
+            }
+            Ok(())
+        })?;
+
+        let mut changelog = self.changelog.lock().map_err(WasmStorageError::lock_poisoned)?;
+        changelog.imported_to = changelog.imported_to.max(to);
+        Ok(ops.length())
+    }
+}
+
+impl WasmStorage {
+    pub(crate) fn log_change(&self, key: &str) -> Result<(), JsValue> {
+        self.changelog.lock().map_err(WasmStorageError::lock_poisoned)?.record(key);
+        Ok(())
+    }
+
+    pub(crate) fn log_clear(&self) -> Result<(), JsValue> {
+        self.changelog.lock().map_err(WasmStorageError::lock_poisoned)?.record_clear();
+        Ok(())
+    }
+
+    /// Sequence numbers as a plain object, so they survive a module reload.
+    pub(crate) fn export_changelog(&self) -> Result<JsValue, JsValue> {
+        let changelog = self.changelog.lock().map_err(WasmStorageError::lock_poisoned)?;
+        let key_seqs = js_sys::Object::new();
+        for (key, seq) in changelog.key_seqs.iter() {
+            js_sys::Reflect::set(&key_seqs, &JsValue::from_str(key), &JsValue::from_f64(*seq as f64))?;
+        }
+        let history = js_sys::Object::new();
+        js_sys::Reflect::set(&history, &JsValue::from_str("seq"), &JsValue::from_f64(changelog.seq as f64))?;
+        js_sys::Reflect::set(&history, &JsValue::from_str("lastClear"), &JsValue::from_f64(changelog.last_clear as f64))?;
+        js_sys::Reflect::set(&history, &JsValue::from_str("importedTo"), &JsValue::from_f64(changelog.imported_to as f64))?;
+        js_sys::Reflect::set(&history, &JsValue::from_str("keySeqs"), &key_seqs)?;
+        Ok(history.into())
+    }
+
+    pub(crate) fn import_changelog(&self, history: &JsValue) -> Result<(), JsValue> {
+        let number = |name: &str| -> Result<u64, JsValue> {
+            Ok(js_sys::Reflect::get(history, &JsValue::from_str(name))?.as_f64().unwrap_or(0.0) as u64)
+        };
+        let mut restored = ChangeLog {
+            seq: number("seq")?,
+            last_clear: number("lastClear")?,
+            key_seqs: HashMap::new(),
+            imported_to: number("importedTo")?,
+        };
+        let key_seqs = js_sys::Reflect::get(history, &JsValue::from_str("keySeqs"))?;
+        if let Some(key_seqs) = key_seqs.dyn_ref::<js_sys::Object>() {
+            for entry in js_sys::Object::entries(key_seqs).iter() {
+                let pair = js_sys::Array::from(&entry);
+                let seq = pair.get(1).as_f64().unwrap_or(0.0) as u64;
+                restored.key_seqs.insert(pair.get(0).as_string().unwrap_or_default(), seq);
+            }
+        }
+        *self.changelog.lock().map_err(WasmStorageError::lock_poisoned)? = restored;
+        Ok(())
+    }
+}
+
+// What the digest and signature cover
+fn stream_message(from: u64, to: u64, ops_json: &str) -> String {
+    format!("{}:{}:{}", from, to, ops_json)
+}
+
+fn stream_digest(from: u64, to: u64, ops_json: &str) -> String {
+    to_hex(&sha256(stream_message(from, to, ops_json).as_bytes()))
+}
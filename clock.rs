@@ -0,0 +1,67 @@
+use crate::error::WasmStorageError;
+use crate::WasmStorage;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+
+/// Where action and change timestamps come from. Tests inject a provider;
+/// replays pin the recorded timestamp of the action being re-applied, so
+/// they produce the same events as the original run.
+#[derive(Default)]
+pub(crate) struct Clock {
+    provider: Option<js_sys::Function>,
+    pinned: Option<f64>,
+}
+
+#[wasm_bindgen]
+impl WasmStorage {
+    /// Reads timestamps for dispatched actions, change events and ULIDs from
+    /// `provider()` (milliseconds since the epoch) instead of `Date.now()`;
+    /// `None` restores the system clock.
+    #[wasm_bindgen]
+    pub fn set_time_provider(&mut self, provider: Option<js_sys::Function>) -> Result<(), JsValue> {
+        self.clock.lock().map_err(WasmStorageError::lock_poisoned)?.provider = provider;
+        Ok(())
+    }
+
+    /// The store's current time, as used for the next action.
+    #[wasm_bindgen]
+    pub fn now(&self) -> Result<f64, JsValue> {
+        let provider = {
+            let clock = self.clock.lock().map_err(WasmStorageError::lock_poisoned)?;
+            if let Some(pinned) = clock.pinned {
+                return Ok(pinned);
+            }
+            clock.provider.clone()
+        };
+        let Some(provider) = provider else {
+            return Ok(js_sys::Date::now());
+        };
+        let time = provider.call0(&JsValue::NULL)?;
+        time.as_f64()
+            .filter(|time| time.is_finite())
+            .ok_or_else(|| WasmStorageError::invalid_argument("The time provider must return a finite number of milliseconds").into())
+    }
+}
+
+impl WasmStorage {
+    /// Runs `f` with the clock stopped at `timestamp`.
+    pub(crate) fn at_time<T>(&mut self, timestamp: f64, f: impl FnOnce(&mut WasmStorage) -> Result<T, JsValue>) -> Result<T, JsValue> {
+        let previous = self.clock.lock().map_err(WasmStorageError::lock_poisoned)?.pinned.replace(timestamp);
+        let result = f(self);
+        self.clock.lock().map_err(WasmStorageError::lock_poisoned)?.pinned = previous;
+        result
+    }
+}
+
+/// Milliseconds from `performance.now()` for measuring durations, or
+/// `Date.now()` where there is no `performance`. Unlike `now`, it ignores
+/// the time provider.
+pub(crate) fn monotonic_now() -> f64 {
+    let performance = js_sys::Reflect::get(&js_sys::global(), &JsValue::from_str("performance")).unwrap_or(JsValue::UNDEFINED);
+    let now = js_sys::Reflect::get(&performance, &JsValue::from_str("now"))
+        .ok()
+        .and_then(|now| now.dyn_into::<js_sys::Function>().ok())
+        .and_then(|now| now.call0(&performance).ok())
+        .and_then(|time| time.as_f64());
+    now.unwrap_or_else(js_sys::Date::now)
+}
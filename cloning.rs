@@ -0,0 +1,68 @@
+use crate::error::WasmStorageError;
+use crate::WasmStorage;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+
+/// `structuredClone(value)`; primitives come back as they are. Values it
+/// can't copy (functions, DOM nodes) fail with INVALID_STATE.
+pub(crate) fn structured_clone(value: &JsValue, key: Option<&str>) -> Result<JsValue, JsValue> {
+    if !value.is_object() {
+        return Ok(value.clone());
+    }
+    let clone = js_sys::Reflect::get(&js_sys::global(), &JsValue::from_str("structuredClone"))?
+        .dyn_into::<js_sys::Function>()
+        .map_err(|_| WasmStorageError::unsupported("structuredClone is not available in this environment"))?;
+    clone.call1(&JsValue::NULL, value).map_err(|error| {
+        let reason = js_sys::Reflect::get(&error, &JsValue::from_str("message"))
+            .ok()
+            .and_then(|message| message.as_string())
+            .unwrap_or_default();
+        let failure = WasmStorageError::invalid_state(match key {
+            Some(key) => format!("The value of \"{}\" can't be cloned: {}", key, reason),
+            None => format!("The state can't be cloned: {}", reason),
+        })
+        .with("reason", reason.as_str());
+        match key {
+            Some(key) => failure.with("key", key).into(),
+            None => failure.into(),
+        }
+    })
+}
+
+#[wasm_bindgen]
+impl WasmStorage {
+    /// Makes every read that returns values (`get_state`, `get_many`,
+    /// `get_all_state`, `values`, `entries`, `get_by_prefix`, `query_keys`,
+    /// `query`, `find`, `range`) return `structuredClone` copies, so callers
+    /// can mutate results without touching the store. Reads get slower in proportion to the
+    /// size of what they return.
+    #[wasm_bindgen]
+    pub fn set_clone_on_read(&mut self, enabled: bool) -> Result<(), JsValue> {
+        *self.clone_on_read.lock().map_err(WasmStorageError::lock_poisoned)? = enabled;
+        Ok(())
+    }
+
+    /// A `structuredClone` copy of the value of `key` (null when missing),
+    /// regardless of `set_clone_on_read`.
+    #[wasm_bindgen]
+    pub fn get_state_cloned(&self, key: &str) -> Result<JsValue, JsValue> {
+        self.ensure_readable(key)?;
+        self.record_read(key)?;
+        let value = self.state.lock().map_err(WasmStorageError::lock_poisoned)?.get(key).cloned();
+        structured_clone(&value.unwrap_or(JsValue::NULL), Some(key))
+    }
+}
+
+impl WasmStorage {
+    /// Copies a read result when clone-on-read is enabled.
+    pub(crate) fn read_result(&self, value: JsValue, key: Option<&str>) -> Result<JsValue, JsValue> {
+        if *self.clone_on_read.lock().map_err(WasmStorageError::lock_poisoned)? {
+            return structured_clone(&value, key);
+        }
+        Ok(value)
+    }
+
+    pub(crate) fn clones_on_read(&self) -> Result<bool, JsValue> {
+        Ok(*self.clone_on_read.lock().map_err(WasmStorageError::lock_poisoned)?)
+    }
+}
@@ -0,0 +1,241 @@
+use crate::compression::{deflate_value, inflate_value};
+use crate::error::WasmStorageError;
+use crate::{glob_match, WasmStorage};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+
+const BASE64_ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Converts a key's value between the form callers pass and read back and
+/// the form the store holds, persists and exports. Both directions leave a
+/// value already in the target form as it is, since synced and restored
+/// values arrive encoded.
+#[derive(Clone)]
+pub(crate) enum Codec {
+    Js { encode: js_sys::Function, decode: js_sys::Function },
+    // Uint8Array <-> base64 string
+    Base64,
+    // Date objects <-> ISO-8601 strings, anywhere inside the value
+    Date,
+    // Any JSON value <-> `{ __deflate: base64 }`, for large values read rarely
+    Deflate,
+}
+
+impl Codec {
+    fn from_js(codec: &JsValue) -> Result<Codec, JsValue> {
+        if let Some(name) = codec.as_string() {
+            return match name.as_str() {
+                "base64" => Ok(Codec::Base64),
+                "date" => Ok(Codec::Date),
+                "deflate" => Ok(Codec::Deflate),
+                _ => Err(WasmStorageError::invalid_argument(format!("Unknown codec \"{}\" (expected base64, date or deflate)", name)).into()),
+            };
+        }
+        let function = |name: &str| {
+            js_sys::Reflect::get(codec, &JsValue::from_str(name))
+                .ok()
+                .and_then(|function| function.dyn_into::<js_sys::Function>().ok())
+        };
+        match (function("encode"), function("decode")) {
+            (Some(encode), Some(decode)) => Ok(Codec::Js { encode, decode }),
+            _ => Err(WasmStorageError::invalid_argument("A codec needs encode(value, key) and decode(value, key) functions").into()),
+        }
+    }
+
+    fn encode(&self, key: &str, value: &JsValue) -> Result<JsValue, JsValue> {
+        match self {
+            Codec::Js { encode, .. } => encode.call2(&JsValue::NULL, value, &JsValue::from_str(key)),
+            Codec::Base64 => match value.dyn_ref::<js_sys::Uint8Array>() {
+                Some(bytes) => Ok(JsValue::from_str(&base64_encode(&bytes.to_vec()))),
+                None => Ok(value.clone()),
+            },
+            // A JSON round trip turns dates into ISO strings
+            Codec::Date => match JsValue::from(js_sys::JSON::stringify(value)?).as_string() {
+                Some(json) => js_sys::JSON::parse(&json),
+                None => Ok(value.clone()),
+            },
+            Codec::Deflate => deflate_value(value),
+        }
+    }
+
+    fn decode(&self, key: &str, value: &JsValue) -> Result<JsValue, JsValue> {
+        match self {
+            Codec::Js { decode, .. } => decode.call2(&JsValue::NULL, value, &JsValue::from_str(key)),
+            Codec::Base64 => match value.as_string() {
+                Some(text) => {
+                    let bytes = base64_decode(&text)
+                        .ok_or_else(|| WasmStorageError::invalid_argument(format!("Value of {} is not valid base64", key)).with("key", key))?;
+                    Ok(js_sys::Uint8Array::from(bytes.as_slice()).into())
+                }
+                None => Ok(value.clone()),
+            },
+            Codec::Date => revive_dates(value),
+            Codec::Deflate => inflate_value(value),
+        }
+    }
+}
+
+/// Codecs by key or glob pattern; the first matching one applies.
+pub(crate) type Codecs = Vec<(String, Codec)>;
+
+#[wasm_bindgen]
+impl WasmStorage {
+    /// Registers a codec for keys matching `pattern` (a key or a glob such as
+    /// `files.*`): either `{ encode(value, key), decode(value, key) }` or the
+    /// name of a built-in, `"base64"` (Uint8Array values), `"date"` (revives
+    /// ISO-8601 strings into Date objects) or `"deflate"` (compresses the
+    /// value's JSON). `set_state` stores the encoded value and `get_state`,
+    /// `get_many` and the typed getters decode it, so persistence, exports
+    /// and sync carry the encoded form; listeners and bulk reads such as
+    /// `get_all_state` see it too. Encoded values are decoded again when
+    /// hydrated or imported. A JS codec should, like the built-ins, leave
+    /// values already in the target form as they are.
+    #[wasm_bindgen]
+    pub fn register_codec(&mut self, pattern: &str, codec: JsValue) -> Result<(), JsValue> {
+        let codec = Codec::from_js(&codec)?;
+        let mut codecs = self.codecs.lock().map_err(WasmStorageError::lock_poisoned)?;
+        codecs.retain(|(registered, _)| registered != pattern);
+        codecs.push((pattern.to_string(), codec));
+        Ok(())
+    }
+
+    #[wasm_bindgen]
+    pub fn unregister_codec(&mut self, pattern: &str) -> Result<(), JsValue> {
+        let mut codecs = self.codecs.lock().map_err(WasmStorageError::lock_poisoned)?;
+        codecs.retain(|(registered, _)| registered != pattern);
+        Ok(())
+    }
+}
+
+impl WasmStorage {
+    fn codec_for(&self, key: &str) -> Result<Option<Codec>, JsValue> {
+        let codecs = self.codecs.lock().map_err(WasmStorageError::lock_poisoned)?;
+        Ok(codecs.iter().find(|(pattern, _)| glob_match(pattern, key)).map(|(_, codec)| codec.clone()))
+    }
+
+    /// Caller's value -> stored form. Must not be called with the state
+    /// locked, since JS codecs may read the store.
+    pub(crate) fn encode_value(&self, key: &str, value: &JsValue) -> Result<JsValue, JsValue> {
+        match self.codec_for(key)? {
+            Some(codec) => codec.encode(key, value),
+            None => Ok(value.clone()),
+        }
+    }
+
+    pub(crate) fn decode_value(&self, key: &str, value: &JsValue) -> Result<JsValue, JsValue> {
+        match self.codec_for(key)? {
+            Some(codec) => codec.decode(key, value),
+            None => Ok(value.clone()),
+        }
+    }
+}
+
+pub(crate) fn base64_encode(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = (chunk[0] as u32) << 16
+            | (*chunk.get(1).unwrap_or(&0) as u32) << 8
+            | *chunk.get(2).unwrap_or(&0) as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(BASE64_ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+pub(crate) fn base64_decode(text: &str) -> Option<Vec<u8>> {
+    let digits: Vec<u8> = text.bytes().filter(|b| !b.is_ascii_whitespace()).collect();
+    if !digits.len().is_multiple_of(4) {
+        return None;
+    }
+    let mut out = Vec::with_capacity(digits.len() / 4 * 3);
+    for chunk in digits.chunks(4) {
+        let mut n = 0u32;
+        let mut padding = 0;
+        for &digit in chunk {
+            let value = match digit {
+                b'=' => {
+                    padding += 1;
+                    0
+                }
+                _ if padding > 0 => return None,
+                _ => BASE64_ALPHABET.iter().position(|&c| c == digit)? as u32,
+            };
+            n = n << 6 | value;
+        }
+        if padding > 2 {
+            return None;
+        }
+        let bytes = [(n >> 16) as u8, (n >> 8) as u8, n as u8];
+        out.extend_from_slice(&bytes[..3 - padding]);
+    }
+    Some(out)
+}
+
+// `YYYY-MM-DDTHH:MM:SS[.fff](Z|±HH:MM)`, the shape Date#toJSON produces
+fn is_iso_datetime(text: &str) -> bool {
+    let bytes = text.as_bytes();
+    let digits = |range: std::ops::Range<usize>| bytes.get(range).is_some_and(|part| part.iter().all(u8::is_ascii_digit));
+    if bytes.len() < 20
+        || !digits(0..4)
+        || bytes[4] != b'-'
+        || !digits(5..7)
+        || bytes[7] != b'-'
+        || !digits(8..10)
+        || bytes[10] != b'T'
+        || !digits(11..13)
+        || bytes[13] != b':'
+        || !digits(14..16)
+        || bytes[16] != b':'
+        || !digits(17..19)
+    {
+        return false;
+    }
+    let mut rest = &text[19..];
+    if let Some(fraction) = rest.strip_prefix('.') {
+        let length = fraction.bytes().take_while(u8::is_ascii_digit).count();
+        if length == 0 {
+            return false;
+        }
+        rest = &fraction[length..];
+    }
+    match rest.as_bytes() {
+        [b'Z'] => true,
+        [b'+' | b'-', h1, h2, b':', m1, m2] => [h1, h2, m1, m2].iter().all(|b| b.is_ascii_digit()),
+        _ => false,
+    }
+}
+
+fn revive_dates(value: &JsValue) -> Result<JsValue, JsValue> {
+    if value.is_instance_of::<js_sys::Date>() {
+        return Ok(value.clone());
+    }
+    if let Some(text) = value.as_string() {
+        if is_iso_datetime(&text) {
+            return Ok(js_sys::Date::new(value).into());
+        }
+        return Ok(value.clone());
+    }
+    if js_sys::Array::is_array(value) {
+        let revived = js_sys::Array::new();
+        for item in js_sys::Array::from(value).iter() {
+            let item = revive_dates(&item)?;
+            revived.push(&item);
+        }
+        return Ok(revived.into());
+    }
+    if value.is_object() && !value.is_function() {
+        let revived = js_sys::Object::new();
+        for entry in js_sys::Object::entries(value.unchecked_ref()).iter() {
+            let pair = js_sys::Array::from(&entry);
+            let field = revive_dates(&pair.get(1))?;
+            js_sys::Reflect::set(&revived, &pair.get(0), &field)?;
+        }
+        return Ok(revived.into());
+    }
+    Ok(value.clone())
+}
@@ -0,0 +1,386 @@
+use crate::codecs::{base64_decode, base64_encode};
+use crate::error::WasmStorageError;
+use crate::WasmStorage;
+use wasm_bindgen::prelude::*;
+
+const COMPRESSED_FIELD: &str = "__deflate";
+const DEFAULT_THRESHOLD_BYTES: u32 = 1024;
+
+const WINDOW_SIZE: usize = 32 * 1024;
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+const HASH_BITS: u32 = 15;
+// Longer chains find slightly better matches at a steep cost on large states
+const MAX_CHAIN: usize = 48;
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131, 163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0];
+const DIST_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537, 2049, 3073, 4097, 6145,
+    8193, 12289, 16385, 24577,
+];
+const DIST_EXTRA: [u8; 30] = [0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13, 13];
+// Order in which dynamic blocks list the code length code lengths
+const CODE_LENGTH_ORDER: [usize; 19] = [16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15];
+
+struct BitWriter {
+    out: Vec<u8>,
+    buffer: u32,
+    count: u32,
+}
+
+impl BitWriter {
+    fn bits(&mut self, value: u32, count: u32) {
+        self.buffer |= value << self.count;
+        self.count += count;
+        while self.count >= 8 {
+            self.out.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.count -= 8;
+        }
+    }
+
+    // Huffman codes are packed starting from their most significant bit
+    fn code(&mut self, code: u32, length: u32) {
+        self.bits(code.reverse_bits() >> (32 - length), length);
+    }
+
+    // Literal/length symbol in the fixed Huffman code of RFC 1951 §3.2.6
+    fn symbol(&mut self, symbol: u32) {
+        match symbol {
+            0..=143 => self.code(0x30 + symbol, 8),
+            144..=255 => self.code(0x190 + symbol - 144, 9),
+            256..=279 => self.code(symbol - 256, 7),
+            _ => self.code(0xc0 + symbol - 280, 8),
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.count > 0 {
+            self.out.push(self.buffer as u8);
+        }
+        self.out
+    }
+}
+
+fn hash(data: &[u8], at: usize) -> usize {
+    let value = (data[at] as u32) << 16 | (data[at + 1] as u32) << 8 | data[at + 2] as u32;
+    (value.wrapping_mul(2654435761) >> (32 - HASH_BITS)) as usize
+}
+
+/// Raw DEFLATE (RFC 1951): LZ77 over a 32 KiB window with hash chains,
+/// emitted as a single fixed-Huffman block. JSON is repetitive enough that
+/// this gets most of the benefit of dynamic codes.
+pub(crate) fn deflate(data: &[u8]) -> Vec<u8> {
+    let mut writer = BitWriter { out: Vec::with_capacity(data.len() / 3), buffer: 0, count: 0 };
+    writer.bits(1, 1); // final block
+    writer.bits(1, 2); // fixed Huffman codes
+
+    let mut head = vec![usize::MAX; 1 << HASH_BITS];
+    let mut prev = vec![usize::MAX; WINDOW_SIZE];
+    let insert = |head: &mut Vec<usize>, prev: &mut Vec<usize>, at: usize| {
+        if at + MIN_MATCH <= data.len() {
+            let h = hash(data, at);
+            prev[at % WINDOW_SIZE] = head[h];
+            head[h] = at;
+        }
+    };
+
+    let mut at = 0;
+    while at < data.len() {
+        let (mut best_length, mut best_distance) = (0, 0);
+        if at + MIN_MATCH <= data.len() {
+            let longest = MAX_MATCH.min(data.len() - at);
+            let mut candidate = head[hash(data, at)];
+            let mut chain = 0;
+            while candidate != usize::MAX && at - candidate <= WINDOW_SIZE && chain < MAX_CHAIN {
+                let length = data[candidate..].iter().zip(&data[at..at + longest]).take_while(|(a, b)| a == b).count();
+                if length > best_length {
+                    best_length = length;
+                    best_distance = at - candidate;
+                    if length == longest {
+                        break;
+                    }
+                }
+                // Slots are reused as the window slides; a stale link only costs a worse match
+                let next = prev[candidate % WINDOW_SIZE];
+                if next >= candidate {
+                    break;
+                }
+                candidate = next;
+                chain += 1;
+            }
+        }
+
+        if best_length >= MIN_MATCH {
+            let index = LENGTH_BASE.iter().rposition(|&base| base as usize <= best_length).unwrap_or(0);
+            writer.symbol(257 + index as u32);
+            writer.bits((best_length - LENGTH_BASE[index] as usize) as u32, LENGTH_EXTRA[index] as u32);
+            let index = DIST_BASE.iter().rposition(|&base| base as usize <= best_distance).unwrap_or(0);
+            writer.code(index as u32, 5);
+            writer.bits((best_distance - DIST_BASE[index] as usize) as u32, DIST_EXTRA[index] as u32);
+            for position in at..at + best_length {
+                insert(&mut head, &mut prev, position);
+            }
+            at += best_length;
+        } else {
+            writer.symbol(data[at] as u32);
+            insert(&mut head, &mut prev, at);
+            at += 1;
+        }
+    }
+    writer.symbol(256);
+    writer.finish()
+}
+
+struct BitReader<'a> {
+    data: &'a [u8],
+    position: usize,
+    buffer: u32,
+    count: u32,
+}
+
+impl BitReader<'_> {
+    fn bits(&mut self, count: u32) -> Option<u32> {
+        while self.count < count {
+            self.buffer |= (*self.data.get(self.position)? as u32) << self.count;
+            self.position += 1;
+            self.count += 8;
+        }
+        let value = self.buffer & ((1u32 << count) - 1);
+        self.buffer >>= count;
+        self.count -= count;
+        Some(value)
+    }
+}
+
+/// Canonical Huffman code: how many codes have each length, and the
+/// symbols in code order.
+struct Huffman {
+    counts: [u16; 16],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Huffman {
+        let mut counts = [0u16; 16];
+        for &length in lengths {
+            counts[length as usize] += 1;
+        }
+        counts[0] = 0;
+        let mut offsets = [0u16; 16];
+        for length in 1..16 {
+            offsets[length] = offsets[length - 1] + counts[length - 1];
+        }
+        let mut symbols = vec![0u16; lengths.len()];
+        for (symbol, &length) in lengths.iter().enumerate() {
+            if length != 0 {
+                symbols[offsets[length as usize] as usize] = symbol as u16;
+                offsets[length as usize] += 1;
+            }
+        }
+        Huffman { counts, symbols }
+    }
+
+    fn decode(&self, reader: &mut BitReader) -> Option<u16> {
+        let (mut code, mut first, mut index) = (0i32, 0i32, 0i32);
+        for length in 1..16 {
+            code |= reader.bits(1)? as i32;
+            let count = self.counts[length] as i32;
+            if code - count < first {
+                return self.symbols.get((index + code - first) as usize).copied();
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        None
+    }
+}
+
+fn fixed_codes() -> (Huffman, Huffman) {
+    let mut lengths = [0u8; 288];
+    lengths[..144].fill(8);
+    lengths[144..256].fill(9);
+    lengths[256..280].fill(7);
+    lengths[280..].fill(8);
+    (Huffman::new(&lengths), Huffman::new(&[5; 30]))
+}
+
+fn dynamic_codes(reader: &mut BitReader) -> Option<(Huffman, Huffman)> {
+    let literals = reader.bits(5)? as usize + 257;
+    let distances = reader.bits(5)? as usize + 1;
+    let code_lengths = reader.bits(4)? as usize + 4;
+
+    let mut lengths = [0u8; 19];
+    for &slot in CODE_LENGTH_ORDER.iter().take(code_lengths) {
+        lengths[slot] = reader.bits(3)? as u8;
+    }
+    let length_code = Huffman::new(&lengths);
+
+    let mut lengths = Vec::with_capacity(literals + distances);
+    while lengths.len() < literals + distances {
+        let (value, repeat) = match length_code.decode(reader)? {
+            symbol @ 0..=15 => (symbol as u8, 1),
+            16 => (*lengths.last()?, 3 + reader.bits(2)?),
+            17 => (0, 3 + reader.bits(3)?),
+            18 => (0, 11 + reader.bits(7)?),
+            _ => return None,
+        };
+        lengths.extend(std::iter::repeat_n(value, repeat as usize));
+    }
+    if lengths.len() != literals + distances {
+        return None;
+    }
+    Some((Huffman::new(&lengths[..literals]), Huffman::new(&lengths[literals..])))
+}
+
+/// Decompresses raw DEFLATE data (stored, fixed and dynamic blocks), or
+/// returns None if it is malformed.
+pub(crate) fn inflate(data: &[u8]) -> Option<Vec<u8>> {
+    let mut reader = BitReader { data, position: 0, buffer: 0, count: 0 };
+    let mut out = Vec::with_capacity(data.len() * 4);
+    loop {
+        let last = reader.bits(1)? == 1;
+        match reader.bits(2)? {
+            0 => {
+                // Stored blocks start on a byte boundary
+                reader.buffer = 0;
+                reader.count = 0;
+                let header = data.get(reader.position..reader.position + 4)?;
+                let length = u16::from_le_bytes([header[0], header[1]]);
+                if length != !u16::from_le_bytes([header[2], header[3]]) {
+                    return None;
+                }
+                let start = reader.position + 4;
+                out.extend_from_slice(data.get(start..start + length as usize)?);
+                reader.position = start + length as usize;
+            }
+            kind @ (1 | 2) => {
+                let (literals, distances) = if kind == 1 { fixed_codes() } else { dynamic_codes(&mut reader)? };
+                loop {
+                    let symbol = literals.decode(&mut reader)? as usize;
+                    if symbol < 256 {
+                        out.push(symbol as u8);
+                        continue;
+                    }
+                    if symbol == 256 {
+                        break;
+                    }
+                    let index = symbol - 257;
+                    let length = *LENGTH_BASE.get(index)? as usize + reader.bits(*LENGTH_EXTRA.get(index)? as u32)? as usize;
+                    let index = distances.decode(&mut reader)? as usize;
+                    let distance = *DIST_BASE.get(index)? as usize + reader.bits(*DIST_EXTRA.get(index)? as u32)? as usize;
+                    if distance > out.len() {
+                        return None;
+                    }
+                    // Byte by byte: a match may overlap the bytes it produces
+                    let start = out.len() - distance;
+                    for offset in 0..length {
+                        out.push(out[start + offset]);
+                    }
+                }
+            }
+            _ => return None,
+        }
+        if last {
+            return Some(out);
+        }
+    }
+}
+
+/// `{ "__deflate": base64 }` holding the value's JSON, deflated; values
+/// JSON can't represent and values already compressed stay as they are.
+pub(crate) fn deflate_value(value: &JsValue) -> Result<JsValue, JsValue> {
+    if is_compressed(value)? {
+        return Ok(value.clone());
+    }
+    let Some(json) = JsValue::from(js_sys::JSON::stringify(value)?).as_string() else {
+        return Ok(value.clone());
+    };
+    let compressed = js_sys::Object::new();
+    js_sys::Reflect::set(&compressed, &JsValue::from_str(COMPRESSED_FIELD), &JsValue::from_str(&base64_encode(&deflate(json.as_bytes()))))?;
+    Ok(compressed.into())
+}
+
+/// Reverses `deflate_value`; anything else is returned as it is.
+pub(crate) fn inflate_value(value: &JsValue) -> Result<JsValue, JsValue> {
+    if !is_compressed(value)? {
+        return Ok(value.clone());
+    }
+    let encoded = js_sys::Reflect::get(value, &JsValue::from_str(COMPRESSED_FIELD))?.as_string().unwrap_or_default();
+    let corrupt = || WasmStorageError::invalid_state("Compressed value is corrupt");
+    let json = base64_decode(&encoded)
+        .and_then(|bytes| inflate(&bytes))
+        .and_then(|bytes| String::from_utf8(bytes).ok())
+        .ok_or_else(corrupt)?;
+    js_sys::JSON::parse(&json)
+}
+
+fn is_compressed(value: &JsValue) -> Result<bool, JsValue> {
+    Ok(value.is_object() && js_sys::Reflect::get(value, &JsValue::from_str(COMPRESSED_FIELD))?.is_string())
+}
+
+#[wasm_bindgen]
+impl WasmStorage {
+    /// Compresses serialized values of at least `thresholdBytes` (default
+    /// 1024) with DEFLATE before they are persisted or exported. Values in
+    /// memory stay uncompressed, and compressed values are always inflated
+    /// on hydration and import, even after compression is disabled again.
+    #[wasm_bindgen]
+    pub fn enable_compression(&mut self, options: Option<js_sys::Object>) -> Result<(), JsValue> {
+        let threshold = options
+            .and_then(|options| js_sys::Reflect::get(&options, &JsValue::from_str("thresholdBytes")).ok())
+            .and_then(|threshold| threshold.as_f64())
+            .map_or(DEFAULT_THRESHOLD_BYTES, |threshold| threshold.max(0.0) as u32);
+        *self.compression.lock().map_err(WasmStorageError::lock_poisoned)? = Some(threshold);
+        self.write_snapshot()
+    }
+
+    #[wasm_bindgen]
+    pub fn disable_compression(&mut self) -> Result<(), JsValue> {
+        *self.compression.lock().map_err(WasmStorageError::lock_poisoned)? = None;
+        self.write_snapshot()
+    }
+}
+
+impl WasmStorage {
+    /// Serialized value JSON -> the JSON to store, a `{ "__deflate": base64 }`
+    /// wrapper when the value is large enough to compress.
+    pub(crate) fn compress_json(&self, json: String) -> Result<String, JsValue> {
+        let threshold = *self.compression.lock().map_err(WasmStorageError::lock_poisoned)?;
+        match threshold {
+            Some(threshold) if json.len() >= threshold as usize => {
+                Ok(format!("{{\"{}\":\"{}\"}}", COMPRESSED_FIELD, base64_encode(&deflate(json.as_bytes()))))
+            }
+            _ => Ok(json),
+        }
+    }
+
+    /// Like `compress_json`, for values that are exported as JS values.
+    pub(crate) fn compress_value(&self, value: JsValue) -> Result<JsValue, JsValue> {
+        let Some(json) = JsValue::from(js_sys::JSON::stringify(&value)?).as_string() else {
+            return Ok(value);
+        };
+        let stored = self.compress_json(json.clone())?;
+        if stored == json {
+            return Ok(value);
+        }
+        js_sys::JSON::parse(&stored)
+    }
+
+    pub(crate) fn decompress_value(&self, value: &JsValue) -> Result<JsValue, JsValue> {
+        inflate_value(value)
+    }
+
+    /// Inflates every compressed value of persisted `data` in place.
+    pub(crate) fn decompress_entries(&self, data: &js_sys::Object) -> Result<(), JsValue> {
+        for entry in js_sys::Object::entries(data).iter() {
+            let pair = js_sys::Array::from(&entry);
+            js_sys::Reflect::set(data, &pair.get(0), &self.decompress_value(&pair.get(1))?)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,111 @@
+use crate::error::{ErrorCode, WasmStorageError};
+use crate::error_handler::ErrorSource;
+use crate::origin::Origin;
+use crate::WasmStorage;
+use std::collections::{HashMap, HashSet};
+use wasm_bindgen::prelude::*;
+
+/// A key whose value is derived from other keys.
+#[derive(Clone)]
+pub(crate) struct Computed {
+    deps: Vec<String>,
+    compute: js_sys::Function,
+}
+
+pub(crate) type ComputedKeys = HashMap<String, Computed>;
+
+// True when `key` is reachable from `from` through computed dependencies
+fn reaches(computed: &ComputedKeys, from: &str, key: &str) -> bool {
+    let mut visited = HashSet::new();
+    let mut stack = vec![from.to_string()];
+    while let Some(current) = stack.pop() {
+        if current == key {
+            return true;
+        }
+        if let Some(definition) = computed.get(&current) {
+            for dep in definition.deps.iter() {
+                if visited.insert(dep.clone()) {
+                    stack.push(dep.clone());
+                }
+            }
+        }
+    }
+    false
+}
+
+#[wasm_bindgen]
+impl WasmStorage {
+    /// Keeps `key` equal to `compute(...depValues)`, recomputing and storing
+    /// it whenever one of `deps` changes. Computed keys may depend on other
+    /// computed keys, but not on themselves. A direct write to a computed key
+    /// lasts until the next recompute.
+    #[wasm_bindgen]
+    pub fn define_computed(&mut self, key: &str, deps: js_sys::Array, compute: js_sys::Function) -> Result<(), JsValue> {
+        let deps: Vec<String> = deps.iter().filter_map(|dep| dep.as_string()).collect();
+        {
+            let mut computed = self.computed.lock().map_err(WasmStorageError::lock_poisoned)?;
+            if let Some(dep) = deps.iter().find(|dep| reaches(&computed, dep, key)) {
+                return Err(WasmStorageError::new(ErrorCode::DependencyCycle, format!(
+                    "Computed key {} cannot depend on {}: {} is derived from {}",
+                    key, dep, dep, key
+                )).with("key", key).into());
+            }
+            computed.insert(key.to_string(), Computed { deps, compute });
+        }
+        self.recompute(key)
+    }
+
+    /// Stops recomputing `key`; its last value stays in the store.
+    #[wasm_bindgen]
+    pub fn remove_computed(&mut self, key: &str) -> Result<(), JsValue> {
+        self.computed.lock().map_err(WasmStorageError::lock_poisoned)?.remove(key);
+        Ok(())
+    }
+}
+
+impl WasmStorage {
+    /// Recomputes every computed key that lists `dep` as a dependency.
+    pub(crate) fn recompute_dependents(&mut self, dep: &str) -> Result<(), JsValue> {
+        let keys: Vec<String> = {
+            let computed = self.computed.lock().map_err(WasmStorageError::lock_poisoned)?;
+            computed
+                .iter()
+                .filter(|(_, definition)| definition.deps.iter().any(|d| d == dep))
+                .map(|(key, _)| key.clone())
+                .collect()
+        };
+        for key in keys {
+            self.recompute(&key)?;
+        }
+        Ok(())
+    }
+
+    /// Recomputes all computed keys, e.g. after `clear_state`.
+    pub(crate) fn recompute_all(&mut self) -> Result<(), JsValue> {
+        let keys: Vec<String> = self.computed.lock().map_err(WasmStorageError::lock_poisoned)?.keys().cloned().collect();
+        for key in keys {
+            self.recompute(&key)?;
+        }
+        Ok(())
+    }
+
+    // A throwing compute function is reported and leaves the previous value
+    fn recompute(&mut self, key: &str) -> Result<(), JsValue> {
+        let Some(definition) = self.computed.lock().map_err(WasmStorageError::lock_poisoned)?.get(key).cloned() else {
+            return Ok(());
+        };
+        let args = js_sys::Array::new();
+        for dep in definition.deps.iter() {
+            let value = self.get_state(dep)?;
+            args.push(&value);
+        }
+
+        match definition.compute.apply(&JsValue::NULL, &args) {
+            Ok(value) => self.with_origin(Origin::System, |store| store.set_state(key, value)),
+            Err(error) => {
+                let origin = ErrorSource { source: "computed", key: Some(key), action: None, timestamp: js_sys::Date::now() };
+                self.report_error(&error, origin)
+            }
+        }
+    }
+}
@@ -0,0 +1,81 @@
+use crate::error::WasmStorageError;
+use crate::origin::Origin;
+use crate::{values_equal, WasmStorage};
+use wasm_bindgen::prelude::*;
+
+/// How `merge_pushed` settled a write pushed by a sync channel.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum Merge {
+    /// No conflict, or no resolver: the remote value was applied
+    Remote,
+    /// The resolver kept the remote value, superseding the local change
+    ResolvedRemote,
+    /// The resolver picked another value, written as a local change so it
+    /// goes back out
+    ResolvedLocal,
+}
+
+#[wasm_bindgen]
+impl WasmStorage {
+    /// Calls `resolver(key, local, remote, base)` when remote sync
+    /// (`connect_remote`, `sync_pull` / `sync_push`) finds a key that changed
+    /// both locally and remotely since the two sides last agreed, and stores
+    /// its return value (sending it back when it differs from `remote`).
+    /// `base` is the last agreed value, or null. Without a resolver the
+    /// remote value wins; pass null to remove it. Peer sync
+    /// (`sync_with_peer`) merges the same way.
+    #[wasm_bindgen]
+    pub fn set_conflict_resolver(&mut self, resolver: Option<js_sys::Function>) -> Result<(), JsValue> {
+        *self.conflict_resolver.lock().map_err(WasmStorageError::lock_poisoned)? = resolver;
+        Ok(())
+    }
+}
+
+impl WasmStorage {
+    /// The registered resolver's value for a divergent key, or None when no
+    /// resolver is set.
+    pub(crate) fn resolve_conflict(&self, key: &str, local: &JsValue, remote: &JsValue, base: &JsValue) -> Result<Option<JsValue>, JsValue> {
+        let resolver = self.conflict_resolver.lock().map_err(WasmStorageError::lock_poisoned)?.clone();
+        match resolver {
+            Some(resolver) => Ok(Some(resolver.call4(&JsValue::NULL, &JsValue::from_str(key), local, remote, base)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Applies a value a sync channel pushed for `key` (null removes it),
+    /// given `base`, the last value both sides agreed on. A key changed on
+    /// both sides since goes to the resolver first. Remote values are
+    /// applied with the "sync" origin, now or through the inbox.
+    pub(crate) fn merge_pushed(&mut self, key: String, value: JsValue, base: &JsValue) -> Result<Merge, JsValue> {
+        let local = self.state.lock().map_err(WasmStorageError::lock_poisoned)?.get(&key).cloned().unwrap_or(JsValue::NULL);
+        let mut merge = Merge::Remote;
+
+        // Both sides changed the key since they last agreed
+        if !values_equal(&local, base) && !values_equal(&value, base) && !values_equal(&local, &value) {
+            if let Some(resolved) = self.resolve_conflict(&key, &local, &value, base)? {
+                if !values_equal(&resolved, &value) {
+                    self.with_origin(Origin::Local, |store| {
+                        if resolved.is_null() || resolved.is_undefined() {
+                            store.remove_state(&key)
+                        } else {
+                            store.set_state(&key, resolved)
+                        }
+                    })?;
+                    return Ok(Merge::ResolvedLocal);
+                }
+                merge = Merge::ResolvedRemote;
+            }
+        }
+
+        // Applied now or, with the inbox enabled, in a later frame
+        let target = key.clone();
+        self.accept_remote(&target, None, Box::new(move |store| {
+            if value.is_null() || value.is_undefined() {
+                store.remove_state(&key)
+            } else {
+                store.set_state(&key, value)
+            }
+        }))?;
+        Ok(merge)
+    }
+}
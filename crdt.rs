@@ -0,0 +1,188 @@
+use crate::error::WasmStorageError;
+use crate::origin::Origin;
+use crate::WasmStorage;
+use std::collections::HashMap;
+use wasm_bindgen::prelude::*;
+
+/// Lamport timestamp of a key's last write, ordered by counter and then
+/// actor ID so every replica picks the same winner.
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub(crate) struct Stamp {
+    counter: u64,
+    actor: String,
+}
+
+impl Stamp {
+    fn parse(stamp: &JsValue) -> Result<Stamp, JsValue> {
+        let counter = js_sys::Reflect::get(stamp, &JsValue::from_str("counter"))?.as_f64();
+        let actor = js_sys::Reflect::get(stamp, &JsValue::from_str("actor"))?.as_string();
+        match (counter, actor) {
+            (Some(counter), Some(actor)) if counter >= 0.0 => Ok(Stamp { counter: counter as u64, actor }),
+            _ => Err(WasmStorageError::invalid_argument("A CRDT stamp needs a non-negative counter and an actor").into()),
+        }
+    }
+
+    fn to_object(&self) -> Result<js_sys::Object, JsValue> {
+        let stamp = js_sys::Object::new();
+        js_sys::Reflect::set(&stamp, &JsValue::from_str("counter"), &JsValue::from_f64(self.counter as f64))?;
+        js_sys::Reflect::set(&stamp, &JsValue::from_str("actor"), &JsValue::from_str(&self.actor))?;
+        Ok(stamp)
+    }
+}
+
+/// Last-writer-wins register per key. Removed keys keep their stamp as a
+/// tombstone so an older write arriving late can't bring them back.
+pub(crate) struct Crdt {
+    actor: String,
+    counter: u64,
+    stamps: HashMap<String, Stamp>,
+    // Set while a winning remote write is applied, which carries its own stamp
+    merging: bool,
+}
+
+#[wasm_bindgen]
+impl WasmStorage {
+    /// Stamps every write with a Lamport timestamp and `actor_id`, so writes
+    /// merged from other replicas (`crdt_merge`, or `connect_remote`
+    /// messages carrying a `clock`) resolve the same way everywhere: the
+    /// higher `(counter, actor)` wins. Existing keys start at counter 0.
+    #[wasm_bindgen]
+    pub fn enable_crdt(&mut self, actor_id: &str) -> Result<(), JsValue> {
+        if actor_id.is_empty() {
+            return Err(WasmStorageError::invalid_argument("CRDT actor ID must not be empty").into());
+        }
+        let keys: Vec<String> = self.state.lock().map_err(WasmStorageError::lock_poisoned)?.keys().cloned().collect();
+        let stamps = keys
+            .into_iter()
+            .map(|key| (key, Stamp { counter: 0, actor: actor_id.to_string() }))
+            .collect();
+        *self.crdt.lock().map_err(WasmStorageError::lock_poisoned)? =
+            Some(Crdt { actor: actor_id.to_string(), counter: 0, stamps, merging: false });
+        Ok(())
+    }
+
+    /// `{ counter, actor }` of `key`'s last write or removal, or null.
+    #[wasm_bindgen]
+    pub fn crdt_stamp(&self, key: &str) -> Result<JsValue, JsValue> {
+        Ok(self.stamp_of(key)?.unwrap_or(JsValue::NULL))
+    }
+
+    /// Applies a write from another replica if its stamp beats the local
+    /// one; a null value is a removal. Returns whether it won. The stamp is
+    /// only kept once the write is stored, so a rejected write (validator,
+    /// schema, access policy) can't outrank later ones.
+    #[wasm_bindgen]
+    pub fn crdt_merge(&mut self, key: &str, value: JsValue, stamp: JsValue) -> Result<bool, JsValue> {
+        let stamp = Stamp::parse(&stamp)?;
+        {
+            let mut guard = self.crdt.lock().map_err(WasmStorageError::lock_poisoned)?;
+            let crdt = guard.as_mut().ok_or_else(|| WasmStorageError::invalid_state("CRDT mode is not enabled"))?;
+            crdt.counter = crdt.counter.max(stamp.counter);
+            if crdt.stamps.get(key).is_some_and(|current| *current >= stamp) {
+                return Ok(false);
+            }
+            crdt.merging = true;
+        }
+
+        let applied = self.with_origin(Origin::Sync, |store| {
+            if value.is_null() || value.is_undefined() {
+                store.remove_state(key)
+            } else {
+                store.set_state(key, value)
+            }
+        });
+        if let Some(crdt) = self.crdt.lock().map_err(WasmStorageError::lock_poisoned)?.as_mut() {
+            crdt.merging = false;
+            if applied.is_ok() {
+                crdt.stamps.insert(key.to_string(), stamp);
+            }
+        }
+        applied.map(|_| true)
+    }
+
+    /// Every stamped key as `{ key: { value, counter, actor } }`, with null
+    /// values for tombstones; feed it to `crdt_merge_all` on another replica.
+    #[wasm_bindgen]
+    pub fn crdt_export(&self) -> Result<js_sys::Object, JsValue> {
+        let stamps: Vec<(String, Stamp)> = {
+            let guard = self.crdt.lock().map_err(WasmStorageError::lock_poisoned)?;
+            let crdt = guard.as_ref().ok_or_else(|| WasmStorageError::invalid_state("CRDT mode is not enabled"))?;
+            crdt.stamps.iter().map(|(key, stamp)| (key.clone(), stamp.clone())).collect()
+        };
+        let stamps = self.retain_readable(stamps, |(key, _)| key)?;
+        let state = self.state.lock().map_err(WasmStorageError::lock_poisoned)?;
+        let exported = js_sys::Object::new();
+        for (key, stamp) in stamps {
+            let entry = stamp.to_object()?;
+            js_sys::Reflect::set(&entry, &JsValue::from_str("value"), state.get(&key).unwrap_or(&JsValue::NULL))?;
+            js_sys::Reflect::set(&exported, &JsValue::from_str(&key), &entry)?;
+        }
+        Ok(exported)
+    }
+
+    /// Merges a `crdt_export` from another replica; returns how many of its
+    /// entries won.
+    #[wasm_bindgen]
+    pub fn crdt_merge_all(&mut self, exported: js_sys::Object) -> Result<u32, JsValue> {
+        let mut won = 0;
+        for entry in js_sys::Object::entries(&exported).iter() {
+            let pair = js_sys::Array::from(&entry);
+            let key = pair.get(0).as_string().unwrap_or_default();
+            let value = js_sys::Reflect::get(&pair.get(1), &JsValue::from_str("value"))?;
+            if self.crdt_merge(&key, value, pair.get(1))? {
+                won += 1;
+            }
+        }
+        Ok(won)
+    }
+}
+
+impl WasmStorage {
+    /// Gives a local write or removal of `key` a new stamp. Restored values
+    /// aren't new writes, so they only get the initial stamp.
+    pub(crate) fn stamp_write(&self, key: &str) -> Result<(), JsValue> {
+        let hydrating = self.current_origin()? == Origin::Hydration;
+        if let Some(crdt) = self.crdt.lock().map_err(WasmStorageError::lock_poisoned)?.as_mut() {
+            if crdt.merging {
+                return Ok(());
+            }
+            if hydrating {
+                let initial = Stamp { counter: 0, actor: crdt.actor.clone() };
+                crdt.stamps.entry(key.to_string()).or_insert(initial);
+                return Ok(());
+            }
+            crdt.counter += 1;
+            let stamp = Stamp { counter: crdt.counter, actor: crdt.actor.clone() };
+            crdt.stamps.insert(key.to_string(), stamp);
+        }
+        Ok(())
+    }
+
+    /// A clear removes every key, so each gets a tombstone.
+    pub(crate) fn stamp_clear(&self) -> Result<(), JsValue> {
+        if let Some(crdt) = self.crdt.lock().map_err(WasmStorageError::lock_poisoned)?.as_mut() {
+            crdt.counter += 1;
+            let stamp = Stamp { counter: crdt.counter, actor: crdt.actor.clone() };
+            for current in crdt.stamps.values_mut() {
+                *current = stamp.clone();
+            }
+        }
+        Ok(())
+    }
+
+    /// Merges a write a sync channel pushed with its stamp (`clock`), now
+    /// or through the inbox; in CRDT mode the stamp decides, not arrival
+    /// order.
+    pub(crate) fn accept_stamped(&mut self, key: String, value: JsValue, clock: JsValue) -> Result<(), JsValue> {
+        let target = key.clone();
+        self.accept_remote(&target, None, Box::new(move |store| store.crdt_merge(&key, value, clock).map(|_| ())))
+    }
+
+    pub(crate) fn stamp_of(&self, key: &str) -> Result<Option<JsValue>, JsValue> {
+        let crdt = self.crdt.lock().map_err(WasmStorageError::lock_poisoned)?;
+        match crdt.as_ref().and_then(|crdt| crdt.stamps.get(key)) {
+            Some(stamp) => Ok(Some(stamp.to_object()?.into())),
+            None => Ok(None),
+        }
+    }
+}
@@ -0,0 +1,51 @@
+use crate::error::WasmStorageError;
+use crate::origin::Origin;
+use crate::WasmStorage;
+use wasm_bindgen::prelude::*;
+
+/// Values from `set_defaults`, by key, in the order they were first given.
+pub(crate) type Defaults = Vec<(String, JsValue)>;
+
+#[wasm_bindgen]
+impl WasmStorage {
+    /// Sets every key of `defaults` that is unset, now and again whenever a
+    /// hydration (persistence, `restore`, HMR) finishes, so persisted values
+    /// win and keys they lack still get a value. The writes have origin
+    /// `"system"`. Calling it again adds to or replaces the defaults per key.
+    #[wasm_bindgen]
+    pub fn set_defaults(&mut self, defaults: js_sys::Object) -> Result<(), JsValue> {
+        self.ensure_alive()?;
+        {
+            let mut stored = self.defaults.lock().map_err(WasmStorageError::lock_poisoned)?;
+            for entry in js_sys::Object::entries(&defaults).iter() {
+                let pair = js_sys::Array::from(&entry);
+                let (key, value) = (pair.get(0).as_string().unwrap_or_default(), pair.get(1));
+                match stored.iter_mut().find(|(stored_key, _)| *stored_key == key) {
+                    Some(default) => default.1 = value,
+                    None => stored.push((key, value)),
+                }
+            }
+        }
+        self.apply_defaults()
+    }
+}
+
+impl WasmStorage {
+    /// Writes the defaults of the keys that are unset.
+    pub(crate) fn apply_defaults(&mut self) -> Result<(), JsValue> {
+        let missing: Vec<(String, JsValue)> = {
+            let defaults = self.defaults.lock().map_err(WasmStorageError::lock_poisoned)?;
+            if defaults.is_empty() {
+                return Ok(());
+            }
+            let state = self.state.lock().map_err(WasmStorageError::lock_poisoned)?;
+            defaults.iter().filter(|(key, _)| !state.contains_key(key)).cloned().collect()
+        };
+        self.with_origin(Origin::System, |store| {
+            for (key, value) in missing {
+                store.set_state(&key, value)?;
+            }
+            Ok(())
+        })
+    }
+}
@@ -0,0 +1,149 @@
+use crate::error::WasmStorageError;
+use crate::error_handler::ErrorSource;
+use crate::subscriptions::{Change, Listener};
+use crate::WasmStorage;
+use crate::sync::{Lock, Shared};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+
+// Fallback frame length where requestAnimationFrame is unavailable (workers, Node)
+const FALLBACK_FRAME_MS: i32 = 16;
+
+/// When a subscription hears about changes.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum Delivery {
+    Sync,
+    Microtask,
+    Frame,
+    Idle,
+}
+
+impl Delivery {
+    pub(crate) fn parse(delivery: &str) -> Result<Delivery, JsValue> {
+        match delivery {
+            "sync" => Ok(Delivery::Sync),
+            "microtask" => Ok(Delivery::Microtask),
+            "frame" => Ok(Delivery::Frame),
+            "idle" => Ok(Delivery::Idle),
+            _ => Err(WasmStorageError::invalid_argument(format!(
+                "Unknown delivery mode \"{}\" (expected sync, microtask, frame or idle)",
+                delivery
+            )).into()),
+        }
+    }
+}
+
+/// Changes queued for a deferred subscription until its next delivery.
+pub(crate) type DeferredQueue = Shared<Lock<Vec<Change>>>;
+
+impl WasmStorage {
+    /// Queues `changes` for a non-synchronous listener, scheduling a delivery
+    /// if none is pending yet.
+    pub(crate) fn defer_changes(&self, listener: &Listener, queue: &DeferredQueue, changes: Vec<Change>) -> Result<(), JsValue> {
+        if changes.is_empty() {
+            return Ok(());
+        }
+        let schedule = {
+            let mut pending = queue.lock().map_err(WasmStorageError::lock_poisoned)?;
+            let was_empty = pending.is_empty();
+            for change in changes {
+                match pending.iter_mut().find(|queued| listener.coalesce && queued.key == change.key) {
+                    // Keep the oldValue from before the first queued change
+                    Some(queued) => {
+                        queued.value = change.value;
+                        queued.origin = change.origin;
+                        queued.timestamp = change.timestamp;
+                    }
+                    None => pending.push(change),
+                }
+            }
+            was_empty
+        };
+        if !schedule {
+            return Ok(());
+        }
+
+        let delivery = listener.delivery;
+        let store = self.clone();
+        let listener = listener.clone();
+        let queue = queue.clone();
+        let deliver = Closure::once_into_js(move || {
+            if let Err(e) = store.deliver_deferred(&listener, &queue) {
+                log_warn!("Deferred notification failed: {:?}", e);
+            }
+        });
+        schedule_delivery(delivery, &deliver)
+    }
+
+    fn deliver_deferred(&self, listener: &Listener, queue: &DeferredQueue) -> Result<(), JsValue> {
+        let changes = std::mem::take(&mut *queue.lock().map_err(WasmStorageError::lock_poisoned)?);
+        // Unsubscribed while the delivery was pending
+        if !self.listeners.lock().map_err(WasmStorageError::lock_poisoned)?.contains(listener.id) {
+            return Ok(());
+        }
+
+        self.meter_notified(if listener.aggregate { 1 } else { changes.len() as u32 })?;
+        if listener.aggregate {
+            let events = js_sys::Array::new();
+            for change in changes.iter() {
+                let event = change.to_event()?;
+                events.push(&event);
+            }
+            let timestamp = js_sys::Date::now();
+            let batch_event = js_sys::Object::new();
+            js_sys::Reflect::set(&batch_event, &JsValue::from_str("changes"), &events)?;
+            js_sys::Reflect::set(&batch_event, &JsValue::from_str("timestamp"), &JsValue::from_f64(timestamp))?;
+            if let Err(error) = listener.callback.call1(&JsValue::NULL, &batch_event) {
+                self.report_error(&error, ErrorSource { source: "listener", key: None, action: None, timestamp })?;
+            }
+            return Ok(());
+        }
+
+        for change in changes.iter() {
+            let event = change.to_event()?;
+            if let Err(error) = listener.callback.call1(&JsValue::NULL, &event) {
+                let origin = ErrorSource { source: "listener", key: Some(&change.key), action: None, timestamp: change.timestamp };
+                self.report_error(&error, origin)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+pub(crate) fn schedule_delivery(delivery: Delivery, callback: &JsValue) -> Result<(), JsValue> {
+    let global = js_sys::global();
+    let function = |name: &str| {
+        js_sys::Reflect::get(&global, &JsValue::from_str(name))
+            .ok()
+            .and_then(|function| function.dyn_into::<js_sys::Function>().ok())
+    };
+    let set_timeout = |delay: i32| -> Result<(), JsValue> {
+        let set_timeout = function("setTimeout").ok_or_else(|| WasmStorageError::unsupported("setTimeout is not available"))?;
+        set_timeout.call2(&JsValue::NULL, callback, &JsValue::from(delay))?;
+        Ok(())
+    };
+
+    match delivery {
+        Delivery::Sync => {
+            callback.unchecked_ref::<js_sys::Function>().call0(&JsValue::NULL)?;
+        }
+        Delivery::Microtask => {
+            let queue_microtask = function("queueMicrotask")
+                .ok_or_else(|| WasmStorageError::unsupported("queueMicrotask is not available in this environment"))?;
+            queue_microtask.call1(&JsValue::NULL, callback)?;
+        }
+        Delivery::Frame => match function("requestAnimationFrame") {
+            Some(request_animation_frame) => {
+                request_animation_frame.call1(&JsValue::NULL, callback)?;
+            }
+            None => set_timeout(FALLBACK_FRAME_MS)?,
+        },
+        Delivery::Idle => match function("requestIdleCallback") {
+            Some(request_idle_callback) => {
+                request_idle_callback.call1(&JsValue::NULL, callback)?;
+            }
+            None => set_timeout(0)?,
+        },
+    }
+    Ok(())
+}
@@ -0,0 +1,198 @@
+use crate::error::{ErrorCode, WasmStorageError};
+use crate::origin::Origin;
+use crate::WasmStorage;
+use std::collections::{HashMap, HashSet};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+
+/// What happens to a dependent key when its parent changes.
+#[derive(Clone)]
+pub(crate) enum Cascade {
+    Clear,
+    Invalidate,
+    Refetch(js_sys::Function),
+}
+
+impl Cascade {
+    fn from_options(options: &JsValue) -> Result<Cascade, JsValue> {
+        let refetch = js_sys::Reflect::get(options, &JsValue::from_str("refetch"))
+            .ok()
+            .and_then(|refetch| refetch.dyn_into::<js_sys::Function>().ok());
+        let strategy = js_sys::Reflect::get(options, &JsValue::from_str("strategy"))
+            .ok()
+            .and_then(|strategy| strategy.as_string());
+
+        match (strategy.as_deref(), refetch) {
+            (None, Some(refetch)) | (Some("refetch"), Some(refetch)) => Ok(Cascade::Refetch(refetch)),
+            (Some("refetch"), None) => Err(WasmStorageError::invalid_argument("The refetch strategy needs a refetch(key, parentValue) function").into()),
+            (None, None) | (Some("clear"), _) => Ok(Cascade::Clear),
+            (Some("invalidate"), _) => Ok(Cascade::Invalidate),
+            (Some(other), _) => Err(WasmStorageError::invalid_argument(format!(
+                "Unknown dependency strategy \"{}\" (expected clear, invalidate or refetch)",
+                other
+            )).into()),
+        }
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct DependencyGraph {
+    // parent -> dependents, in declaration order
+    edges: HashMap<String, Vec<(String, Cascade)>>,
+    invalidated: HashSet<String>,
+}
+
+impl DependencyGraph {
+    // Path from `from` to `to` following parent -> dependent edges, if any
+    fn path(&self, from: &str, to: &str) -> Option<Vec<String>> {
+        if from == to {
+            return Some(vec![from.to_string()]);
+        }
+        let mut visited = HashSet::new();
+        let mut stack = vec![vec![from.to_string()]];
+        while let Some(path) = stack.pop() {
+            let last = path.last().cloned().unwrap_or_default();
+            for (dependent, _) in self.edges.get(&last).into_iter().flatten() {
+                if dependent == to {
+                    let mut found = path.clone();
+                    found.push(dependent.clone());
+                    return Some(found);
+                }
+                if visited.insert(dependent.clone()) {
+                    let mut next = path.clone();
+                    next.push(dependent.clone());
+                    stack.push(next);
+                }
+            }
+        }
+        None
+    }
+}
+
+#[wasm_bindgen]
+impl WasmStorage {
+    /// Declares that every key in `dependents` is derived from `parent`, so
+    /// writing or removing `parent` cascades to them. `options.strategy` is
+    /// `"clear"` (default), `"invalidate"` or `"refetch"`; refetching calls
+    /// `options.refetch(key, parentValue)` and stores its (possibly async) result.
+    /// Declarations that would form a cycle are rejected.
+    #[wasm_bindgen]
+    pub fn depends_on(&mut self, parent: &str, dependents: js_sys::Array, options: Option<js_sys::Object>) -> Result<(), JsValue> {
+        let cascade = Cascade::from_options(&options.map(JsValue::from).unwrap_or(JsValue::UNDEFINED))?;
+        let dependents: Vec<String> = dependents.iter().filter_map(|key| key.as_string()).collect();
+
+        let mut graph = self.dependencies.lock().map_err(WasmStorageError::lock_poisoned)?;
+        for dependent in dependents.iter() {
+            if let Some(cycle) = graph.path(dependent, parent) {
+                return Err(WasmStorageError::new(ErrorCode::DependencyCycle, format!(
+                    "Dependency cycle: {} -> {}",
+                    parent,
+                    cycle.join(" -> ")
+                )).with("key", parent).into());
+            }
+        }
+
+        let edges = graph.edges.entry(parent.to_string()).or_default();
+        for dependent in dependents {
+            edges.retain(|(key, _)| *key != dependent);
+            edges.push((dependent, cascade.clone()));
+        }
+        Ok(())
+    }
+
+    #[wasm_bindgen]
+    pub fn remove_dependency(&mut self, parent: &str, dependent: &str) -> Result<(), JsValue> {
+        let mut graph = self.dependencies.lock().map_err(WasmStorageError::lock_poisoned)?;
+        if let Some(edges) = graph.edges.get_mut(parent) {
+            edges.retain(|(key, _)| key != dependent);
+            if edges.is_empty() {
+                graph.edges.remove(parent);
+            }
+        }
+        Ok(())
+    }
+
+    /// True when an ancestor of `key` changed under the `"invalidate"`
+    /// strategy and `key` hasn't been written since.
+    #[wasm_bindgen]
+    pub fn is_invalidated(&self, key: &str) -> Result<bool, JsValue> {
+        let graph = self.dependencies.lock().map_err(WasmStorageError::lock_poisoned)?;
+        Ok(graph.invalidated.contains(key))
+    }
+}
+
+impl WasmStorage {
+    /// Applies each dependent's strategy after `parent` changed. Cycles are
+    /// rejected when declared, so the recursion always terminates.
+    pub(crate) fn cascade(&mut self, parent: &str, parent_value: &JsValue) -> Result<(), JsValue> {
+        let dependents = {
+            let graph = self.dependencies.lock().map_err(WasmStorageError::lock_poisoned)?;
+            match graph.edges.get(parent) {
+                Some(edges) => edges.clone(),
+                None => return Ok(()),
+            }
+        };
+        self.with_origin(Origin::System, |store| store.cascade_to(dependents, parent_value))
+    }
+
+    fn cascade_to(&mut self, dependents: Vec<(String, Cascade)>, parent_value: &JsValue) -> Result<(), JsValue> {
+        for (dependent, cascade) in dependents {
+            match cascade {
+                Cascade::Clear => {
+                    if self.has(&dependent)? {
+                        self.remove_state(&dependent)?;
+                    } else {
+                        self.cascade(&dependent, &JsValue::NULL)?;
+                    }
+                }
+                Cascade::Invalidate => {
+                    self.dependencies
+                        .lock()
+                        .map_err(WasmStorageError::lock_poisoned)?
+                        .invalidated
+                        .insert(dependent.clone());
+                    let value = self.get_state(&dependent)?;
+                    self.cascade(&dependent, &value)?;
+                }
+                Cascade::Refetch(refetch) => {
+                    let result = refetch.call2(&JsValue::NULL, &JsValue::from_str(&dependent), parent_value)?;
+                    match result.dyn_into::<js_sys::Promise>() {
+                        Ok(promise) => {
+                            let mut store = self.clone();
+                            wasm_bindgen_futures::spawn_local(async move {
+                                match wasm_bindgen_futures::JsFuture::from(promise).await {
+                                    Ok(value) if !value.is_undefined() => {
+                                        let _ = store.with_origin(Origin::System, |store| store.set_state(&dependent, value));
+                                    }
+                                    Ok(_) => {}
+                                    Err(error) => log_warn!("Refetch of {} failed: {:?}", dependent, error),
+                                }
+                            });
+                        }
+                        Err(value) if !value.is_undefined() => self.set_state(&dependent, value)?,
+                        Err(_) => {}
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub(crate) fn revalidate(&self, key: &str) -> Result<(), JsValue> {
+        self.dependencies
+            .lock()
+            .map_err(WasmStorageError::lock_poisoned)?
+            .invalidated
+            .remove(key);
+        Ok(())
+    }
+
+    pub(crate) fn revalidate_all(&self) -> Result<(), JsValue> {
+        self.dependencies
+            .lock()
+            .map_err(WasmStorageError::lock_poisoned)?
+            .invalidated
+            .clear();
+        Ok(())
+    }
+}
@@ -0,0 +1,82 @@
+use crate::error::WasmStorageError;
+use crate::logging;
+use crate::WasmStorage;
+use std::cell::Cell;
+use std::sync::Once;
+use wasm_bindgen::prelude::*;
+
+thread_local! {
+    // Set by `init_diagnostics`, for every store in the module
+    static TAG_ERRORS: Cell<bool> = const { Cell::new(false) };
+}
+
+static PANIC_HOOK: Once = Once::new();
+
+// Logs panics with their message and a JS stack trace before the module
+// traps, like the console_error_panic_hook crate does
+fn install_panic_hook() {
+    PANIC_HOOK.call_once(|| {
+        std::panic::set_hook(Box::new(|info| {
+            let stack = js_sys::Reflect::get(&js_sys::Error::new(""), &JsValue::from_str("stack")).ok().and_then(|stack| stack.as_string()).unwrap_or_default();
+            web_sys::console::error_1(&JsValue::from_str(&format!("{}\n\nStack:\n\n{}", info, stack)));
+        }));
+    });
+}
+
+/// Turns on debugging aids for every store in the module. `options.panicHook`
+/// (default true) logs Rust panics, e.g. in a reducer, with their message
+/// and stack instead of an opaque "unreachable executed"; `options.verbose`
+/// logs the stores' internal debug messages, even in release builds. From
+/// then on, errors thrown by `set_state` and `dispatch` and errors passed
+/// to the error handler carry the store's `instance_id` as
+/// `context.storeId`.
+#[wasm_bindgen]
+pub fn init_diagnostics(options: Option<js_sys::Object>) {
+    let options = options.map(JsValue::from).unwrap_or(JsValue::UNDEFINED);
+    let option = |name: &str| js_sys::Reflect::get(&options, &JsValue::from_str(name)).unwrap_or(JsValue::UNDEFINED);
+    if option("panicHook").as_bool().unwrap_or(true) {
+        install_panic_hook();
+    }
+    if option("verbose").is_truthy() {
+        logging::force_level(logging::LogLevel::Debug);
+    }
+    TAG_ERRORS.with(|tag| tag.set(true));
+}
+
+#[wasm_bindgen]
+impl WasmStorage {
+    /// An ID unique to this store, generated on first use with the store's
+    /// ID strategy (scope `"store"`), to tell stores apart in diagnostics.
+    #[wasm_bindgen(getter)]
+    pub fn instance_id(&self) -> Result<String, JsValue> {
+        if let Some(id) = self.instance_id.lock().map_err(WasmStorageError::lock_poisoned)?.clone() {
+            return Ok(id);
+        }
+        let id = self.generate_id("store")?;
+        *self.instance_id.lock().map_err(WasmStorageError::lock_poisoned)? = Some(id.clone());
+        Ok(id)
+    }
+}
+
+impl WasmStorage {
+    pub(crate) fn tags_errors(&self) -> bool {
+        TAG_ERRORS.with(Cell::get)
+    }
+
+    /// Adds `storeId` to the context of a `WasmStorageError` once
+    /// `init_diagnostics` ran; other errors pass through untouched.
+    pub(crate) fn tag_error(&self, error: JsValue) -> JsValue {
+        if !self.tags_errors() {
+            return error;
+        }
+        let Ok(context) = js_sys::Reflect::get(&error, &JsValue::from_str("context")) else {
+            return error;
+        };
+        if context.is_object() {
+            if let Ok(id) = self.instance_id() {
+                let _ = js_sys::Reflect::set(&context, &JsValue::from_str("storeId"), &JsValue::from_str(&id));
+            }
+        }
+        error
+    }
+}
@@ -0,0 +1,113 @@
+use crate::error::WasmStorageError;
+use crate::persistent_map::PersistentMap;
+use crate::{values_equal, WasmStorage};
+use std::collections::HashSet;
+use wasm_bindgen::prelude::*;
+
+/// Tracks which keys differ from a baseline snapshot ("unsaved changes").
+pub(crate) struct DirtyTracker {
+    // Shares structure with the state it was taken from
+    baseline: PersistentMap<JsValue>,
+    dirty: HashSet<String>,
+}
+
+impl DirtyTracker {
+    fn new(baseline: PersistentMap<JsValue>) -> DirtyTracker {
+        DirtyTracker { baseline, dirty: HashSet::new() }
+    }
+
+    fn record(&mut self, key: &str, value: Option<&JsValue>) {
+        let unchanged = match (self.baseline.get(key), value) {
+            (Some(original), Some(current)) => values_equal(original, current),
+            (None, None) => true,
+            _ => false,
+        };
+        if unchanged {
+            self.dirty.remove(key);
+        } else {
+            self.dirty.insert(key.to_string());
+        }
+    }
+
+    fn record_clear(&mut self) {
+        self.dirty = self.baseline.keys().cloned().collect();
+    }
+}
+
+#[wasm_bindgen]
+impl WasmStorage {
+    /// Starts tracking changes against `baseline_snapshot` (an object of
+    /// key/value pairs), or against the current state when omitted.
+    #[wasm_bindgen]
+    pub fn track_dirty(&mut self, baseline_snapshot: Option<js_sys::Object>) -> Result<(), JsValue> {
+        let baseline = match baseline_snapshot {
+            Some(snapshot) => {
+                let mut baseline = PersistentMap::new();
+                let entries = js_sys::Object::entries(&snapshot);
+                for entry in entries.iter() {
+                    let pair = js_sys::Array::from(&entry);
+                    baseline.insert(pair.get(0).as_string().unwrap_or_default(), pair.get(1));
+                }
+                baseline
+            }
+            None => self.state.lock().map_err(WasmStorageError::lock_poisoned)?.clone(),
+        };
+
+        let mut tracker = DirtyTracker::new(baseline);
+        let state = self.state.lock().map_err(WasmStorageError::lock_poisoned)?;
+        let keys: HashSet<&String> = state.keys().chain(tracker.baseline.keys()).collect();
+        let keys: Vec<String> = keys.into_iter().cloned().collect();
+        for key in keys {
+            tracker.record(&key, state.get(&key));
+        }
+        drop(state);
+
+        *self.dirty.lock().map_err(WasmStorageError::lock_poisoned)? = Some(tracker);
+        Ok(())
+    }
+
+    #[wasm_bindgen]
+    pub fn dirty_keys(&self) -> Result<js_sys::Array, JsValue> {
+        let dirty = self.dirty.lock().map_err(WasmStorageError::lock_poisoned)?;
+        Ok(dirty
+            .iter()
+            .flat_map(|tracker| tracker.dirty.iter())
+            .map(|key| JsValue::from_str(key))
+            .collect())
+    }
+
+    #[wasm_bindgen]
+    pub fn is_dirty(&self) -> Result<bool, JsValue> {
+        let dirty = self.dirty.lock().map_err(WasmStorageError::lock_poisoned)?;
+        Ok(dirty.as_ref().is_some_and(|tracker| !tracker.dirty.is_empty()))
+    }
+
+    /// Makes the current state the new baseline.
+    #[wasm_bindgen]
+    pub fn mark_clean(&mut self) -> Result<(), JsValue> {
+        let snapshot = self.state.lock().map_err(WasmStorageError::lock_poisoned)?.clone();
+        let mut dirty = self.dirty.lock().map_err(WasmStorageError::lock_poisoned)?;
+        if let Some(tracker) = dirty.as_mut() {
+            *tracker = DirtyTracker::new(snapshot);
+        }
+        Ok(())
+    }
+}
+
+impl WasmStorage {
+    pub(crate) fn record_dirty(&self, key: &str, value: Option<&JsValue>) -> Result<(), JsValue> {
+        let mut dirty = self.dirty.lock().map_err(WasmStorageError::lock_poisoned)?;
+        if let Some(tracker) = dirty.as_mut() {
+            tracker.record(key, value);
+        }
+        Ok(())
+    }
+
+    pub(crate) fn record_dirty_clear(&self) -> Result<(), JsValue> {
+        let mut dirty = self.dirty.lock().map_err(WasmStorageError::lock_poisoned)?;
+        if let Some(tracker) = dirty.as_mut() {
+            tracker.record_clear();
+        }
+        Ok(())
+    }
+}
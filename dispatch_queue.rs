@@ -0,0 +1,72 @@
+use crate::error::WasmStorageError;
+use crate::{StorageEvent, WasmStorage};
+use std::collections::VecDeque;
+use wasm_bindgen::prelude::*;
+
+/// Actions dispatched while another dispatch is running, from listeners,
+/// effects or middleware (`context.dispatch`). They run in order once the
+/// outer dispatch is done, so dispatches never re-enter and listeners hear
+/// every action's changes before the next action's.
+#[derive(Default)]
+pub(crate) struct DispatchQueue {
+    queue: VecDeque<(String, JsValue)>,
+    running: bool,
+}
+
+impl DispatchQueue {
+    pub(crate) fn push(&mut self, action_type: String, payload: JsValue) {
+        self.queue.push_back((action_type, payload));
+    }
+}
+
+impl WasmStorage {
+    /// Runs the action, or queues it behind the dispatch in progress. A
+    /// queued action's event carries the payload as given, since middleware
+    /// has not seen it yet, and its errors surface from the outer dispatch.
+    pub(crate) fn serialized_dispatch(&mut self, action_type: &str, payload: JsValue) -> Result<StorageEvent, JsValue> {
+        {
+            let mut queue = self.dispatch_queue.lock().map_err(WasmStorageError::lock_poisoned)?;
+            if queue.running {
+                drop(queue);
+                // Typos still fail where they are made
+                self.check_action_type(action_type)?;
+                let timestamp = self.now()?;
+                self.dispatch_queue.lock().map_err(WasmStorageError::lock_poisoned)?.push(action_type.to_string(), payload.clone());
+                log_debug!("Queued nested dispatch: {}", action_type);
+                return Ok(StorageEvent { action_type: action_type.to_string(), payload, timestamp, changes: None });
+            }
+            queue.running = true;
+        }
+
+        let result = self.dispatch_action(action_type, payload).and_then(|event| self.drain_queued_actions().map(|()| event));
+
+        let mut queue = self.dispatch_queue.lock().map_err(WasmStorageError::lock_poisoned)?;
+        queue.running = false;
+        if result.is_err() {
+            queue.queue.clear();
+        }
+        result
+    }
+
+    // Runs the queued actions, including any they queue in turn
+    fn drain_queued_actions(&mut self) -> Result<(), JsValue> {
+        loop {
+            let next = self.dispatch_queue.lock().map_err(WasmStorageError::lock_poisoned)?.queue.pop_front();
+            let Some((action_type, payload)) = next else {
+                return Ok(());
+            };
+            self.dispatch_action(&action_type, payload)?;
+        }
+    }
+
+    /// Whether a dispatch is running, so a new one would be queued.
+    pub(crate) fn dispatching(&self) -> Result<bool, JsValue> {
+        Ok(self.dispatch_queue.lock().map_err(WasmStorageError::lock_poisoned)?.running)
+    }
+
+    /// Drops queued actions that have not run yet.
+    pub(crate) fn cancel_queued_actions(&self) -> Result<(), JsValue> {
+        self.dispatch_queue.lock().map_err(WasmStorageError::lock_poisoned)?.queue.clear();
+        Ok(())
+    }
+}
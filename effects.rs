@@ -0,0 +1,226 @@
+use crate::error::WasmStorageError;
+use crate::error_handler::ErrorSource;
+use crate::{glob_match, WasmStorage};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::JsFuture;
+
+/// A long-lived workflow started by matching actions. Every run gets its
+/// own `AbortSignal`; with `latest`, a new matching action aborts the runs
+/// still in flight and their later dispatches are dropped.
+struct Effect {
+    id: u32,
+    pattern: String,
+    callback: js_sys::Function,
+    latest: bool,
+    running: Vec<(u32, web_sys::AbortController)>,
+    // (runId, type, payload), bound to a run ID per invocation
+    dispatch: js_sys::Function,
+    get_state: js_sys::Function,
+}
+
+#[derive(Default)]
+pub(crate) struct Effects {
+    next_id: u32,
+    next_run: u32,
+    effects: Vec<Effect>,
+}
+
+impl Effects {
+    fn effect_mut(&mut self, id: u32) -> Option<&mut Effect> {
+        self.effects.iter_mut().find(|effect| effect.id == id)
+    }
+
+    fn is_running(&self, id: u32, run: u32) -> bool {
+        self.effects
+            .iter()
+            .find(|effect| effect.id == id)
+            .is_some_and(|effect| effect.running.iter().any(|(running, _)| *running == run))
+    }
+
+    fn finish(&mut self, id: u32, run: u32) {
+        if let Some(effect) = self.effect_mut(id) {
+            effect.running.retain(|(running, _)| *running != run);
+        }
+    }
+}
+
+struct EffectRun {
+    effect: u32,
+    run: u32,
+    callback: js_sys::Function,
+    signal: web_sys::AbortSignal,
+    dispatch: js_sys::Function,
+    get_state: js_sys::Function,
+}
+
+#[wasm_bindgen]
+impl WasmStorage {
+    /// Runs `effect(action, dispatch, getState, signal)` after every action
+    /// whose type matches `pattern` (a type or glob) has been applied. The
+    /// effect may return a promise. `options.mode` is "latest" (default): a
+    /// newer matching action aborts `signal` for the runs still in flight,
+    /// and their `dispatch` calls are ignored from then on; or "every": runs
+    /// overlap. Errors thrown or rejected by an effect go to the error
+    /// handler. Returns an ID for `unregister_effect`.
+    #[wasm_bindgen]
+    pub fn register_effect(&mut self, pattern: &str, effect: js_sys::Function, options: Option<js_sys::Object>) -> Result<u32, JsValue> {
+        let mode = match &options {
+            Some(options) => js_sys::Reflect::get(options, &JsValue::from_str("mode"))?.as_string(),
+            None => None,
+        };
+        let latest = match mode.as_deref() {
+            None | Some("latest") => true,
+            Some("every") => false,
+            Some(other) => {
+                return Err(WasmStorageError::invalid_argument(format!("Unknown effect mode \"{}\" (expected latest or every)", other))
+                    .with("mode", other)
+                    .into())
+            }
+        };
+
+        let mut effects = self.effects.lock().map_err(WasmStorageError::lock_poisoned)?;
+        effects.next_id += 1;
+        let id = effects.next_id;
+
+        let mut store = self.clone();
+        let dispatch = Closure::wrap(Box::new(move |run: f64, action_type: String, payload: JsValue| -> Result<bool, JsValue> {
+            let running = store.effects.lock().map_err(WasmStorageError::lock_poisoned)?.is_running(id, run as u32);
+            if !running {
+                return Ok(false);
+            }
+            store.dispatch(&action_type, payload)?;
+            Ok(true)
+        }) as Box<dyn FnMut(f64, String, JsValue) -> Result<bool, JsValue>>);
+        let reader = self.clone();
+        let get_state = Closure::wrap(Box::new(move |key: JsValue| -> Result<JsValue, JsValue> {
+            match key.as_string() {
+                Some(key) => reader.get_state(&key),
+                None => reader.get_all_state(),
+            }
+        }) as Box<dyn FnMut(JsValue) -> Result<JsValue, JsValue>>);
+
+        effects.effects.push(Effect {
+            id,
+            pattern: pattern.to_string(),
+            callback: effect,
+            latest,
+            running: Vec::new(),
+            dispatch: dispatch.into_js_value().unchecked_into(),
+            get_state: get_state.into_js_value().unchecked_into(),
+        });
+        Ok(id)
+    }
+
+    /// Removes an effect, aborting its runs in flight.
+    #[wasm_bindgen]
+    pub fn unregister_effect(&mut self, id: u32) -> Result<(), JsValue> {
+        let removed = {
+            let mut effects = self.effects.lock().map_err(WasmStorageError::lock_poisoned)?;
+            let index = effects.effects.iter().position(|effect| effect.id == id);
+            index.map(|index| effects.effects.remove(index))
+        };
+        for (_, controller) in removed.map(|effect| effect.running).unwrap_or_default() {
+            controller.abort();
+        }
+        Ok(())
+    }
+
+    /// How many effect runs are still in flight.
+    #[wasm_bindgen]
+    pub fn running_effects(&self) -> Result<u32, JsValue> {
+        let effects = self.effects.lock().map_err(WasmStorageError::lock_poisoned)?;
+        Ok(effects.effects.iter().map(|effect| effect.running.len() as u32).sum())
+    }
+}
+
+impl WasmStorage {
+    /// Drops every effect, aborting its runs in flight. Each effect's
+    /// `dispatch` and `getState` hold a store handle, so `destroy` clears
+    /// them to free the store.
+    pub(crate) fn clear_effects(&self) -> Result<(), JsValue> {
+        let effects = std::mem::take(&mut self.effects.lock().map_err(WasmStorageError::lock_poisoned)?.effects);
+        for (_, controller) in effects.into_iter().flat_map(|effect| effect.running) {
+            controller.abort();
+        }
+        Ok(())
+    }
+
+    pub(crate) fn has_effect_for(&self, action_type: &str) -> Result<bool, JsValue> {
+        let effects = self.effects.lock().map_err(WasmStorageError::lock_poisoned)?;
+        Ok(effects.effects.iter().any(|effect| glob_match(&effect.pattern, action_type)))
+    }
+
+    /// Starts the effects matching a handled action.
+    pub(crate) fn run_effects(&self, action_type: &str, payload: &JsValue, timestamp: f64) -> Result<(), JsValue> {
+        let mut runs = Vec::new();
+        let mut cancelled = Vec::new();
+        {
+            let mut guard = self.effects.lock().map_err(WasmStorageError::lock_poisoned)?;
+            let effects = &mut *guard;
+            for effect in effects.effects.iter_mut().filter(|effect| glob_match(&effect.pattern, action_type)) {
+                if effect.latest {
+                    cancelled.extend(effect.running.drain(..).map(|(_, controller)| controller));
+                }
+                effects.next_run += 1;
+                let controller = web_sys::AbortController::new()?;
+                runs.push(EffectRun {
+                    effect: effect.id,
+                    run: effects.next_run,
+                    callback: effect.callback.clone(),
+                    signal: controller.signal(),
+                    dispatch: effect.dispatch.clone(),
+                    get_state: effect.get_state.clone(),
+                });
+                effect.running.push((effects.next_run, controller));
+            }
+        }
+        // Aborted outside the lock, since abort listeners run synchronously
+        for controller in cancelled {
+            controller.abort();
+        }
+        if runs.is_empty() {
+            return Ok(());
+        }
+
+        let action = js_sys::Object::new();
+        js_sys::Reflect::set(&action, &JsValue::from_str("type"), &JsValue::from_str(action_type))?;
+        js_sys::Reflect::set(&action, &JsValue::from_str("payload"), payload)?;
+        js_sys::Reflect::set(&action, &JsValue::from_str("timestamp"), &JsValue::from_f64(timestamp))?;
+        for run in runs {
+            let dispatch = run.dispatch.bind1(&JsValue::NULL, &JsValue::from(run.run));
+            let args = js_sys::Array::of4(&action, &dispatch, &run.get_state, &run.signal);
+            let result = run.callback.apply(&JsValue::NULL, &args);
+            match result {
+                Ok(value) if value.has_type::<js_sys::Promise>() => self.await_effect(run, value.unchecked_into(), action_type),
+                Ok(_) => {
+                    self.effects.lock().map_err(WasmStorageError::lock_poisoned)?.finish(run.effect, run.run);
+                }
+                Err(error) => {
+                    self.effects.lock().map_err(WasmStorageError::lock_poisoned)?.finish(run.effect, run.run);
+                    let source = ErrorSource { source: "effect", key: None, action: Some(action_type), timestamp };
+                    self.report_error(&error, source)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn await_effect(&self, run: EffectRun, promise: js_sys::Promise, action_type: &str) {
+        let store = self.clone();
+        let action_type = action_type.to_string();
+        wasm_bindgen_futures::spawn_local(async move {
+            let result = JsFuture::from(promise).await;
+            if let Ok(mut effects) = store.effects.lock() {
+                effects.finish(run.effect, run.run);
+            }
+            // A cancelled run rejecting with its AbortError isn't a failure
+            if let Err(error) = result {
+                if !run.signal.aborted() {
+                    let source = ErrorSource { source: "effect", key: None, action: Some(&action_type), timestamp: js_sys::Date::now() };
+                    let _ = store.report_error(&error, source);
+                }
+            }
+        });
+    }
+}
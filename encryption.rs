@@ -0,0 +1,264 @@
+use crate::backends::spawn_write;
+use crate::codecs::{base64_decode, base64_encode};
+use crate::error::WasmStorageError;
+use crate::error_handler::ErrorSource;
+use crate::WasmStorage;
+use std::collections::HashMap;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::{future_to_promise, JsFuture};
+
+const SEALED_FIELD: &str = "__sealed";
+const IV_BYTES: usize = 12;
+const DEFAULT_SALT: &str = "wasm-storage";
+// OWASP's recommendation for PBKDF2-HMAC-SHA256
+const DEFAULT_ITERATIONS: u32 = 600_000;
+
+/// Encryption of persisted entries with AES-GCM. Web Crypto is async, so
+/// sealed snapshots and log entries are written by a background task, in
+/// the order they were queued.
+pub(crate) struct Encryption {
+    key: web_sys::CryptoKey,
+    // Entry JSON -> its sealed form from the last snapshot, so unchanged
+    // entries aren't encrypted again on every write
+    sealed: HashMap<String, (String, String)>,
+    snapshot: Option<Vec<(String, String)>>,
+    wal: Vec<(Option<String>, Option<String>)>,
+    writing: bool,
+}
+
+fn subtle() -> Result<web_sys::SubtleCrypto, JsValue> {
+    let crypto = js_sys::Reflect::get(&js_sys::global(), &JsValue::from_str("crypto"))?;
+    if crypto.is_undefined() || js_sys::Reflect::get(&crypto, &JsValue::from_str("subtle"))?.is_undefined() {
+        return Err(WasmStorageError::unsupported("Web Crypto is not available in this environment").into());
+    }
+    Ok(crypto.unchecked_into::<web_sys::Crypto>().subtle())
+}
+
+fn random_iv() -> Result<[u8; IV_BYTES], JsValue> {
+    let crypto: web_sys::Crypto = js_sys::Reflect::get(&js_sys::global(), &JsValue::from_str("crypto"))?.unchecked_into();
+    let mut iv = [0u8; IV_BYTES];
+    crypto.get_random_values_with_u8_array(&mut iv)?;
+    Ok(iv)
+}
+
+fn aes_gcm(iv: &[u8]) -> Result<js_sys::Object, JsValue> {
+    let algorithm = js_sys::Object::new();
+    js_sys::Reflect::set(&algorithm, &JsValue::from_str("name"), &JsValue::from_str("AES-GCM"))?;
+    js_sys::Reflect::set(&algorithm, &JsValue::from_str("iv"), &js_sys::Uint8Array::from(iv))?;
+    Ok(algorithm)
+}
+
+async fn derive_key(passphrase: &str, salt: &str, iterations: u32) -> Result<web_sys::CryptoKey, JsValue> {
+    let subtle = subtle()?;
+    let usages: js_sys::Array = ["deriveKey"].iter().map(|usage| JsValue::from_str(usage)).collect();
+    let material = js_sys::Uint8Array::from(passphrase.as_bytes());
+    let base = JsFuture::from(subtle.import_key_with_str("raw", &material, "PBKDF2", false, &usages)?).await?;
+
+    let params = js_sys::Object::new();
+    js_sys::Reflect::set(&params, &JsValue::from_str("name"), &JsValue::from_str("PBKDF2"))?;
+    js_sys::Reflect::set(&params, &JsValue::from_str("salt"), &js_sys::Uint8Array::from(salt.as_bytes()))?;
+    js_sys::Reflect::set(&params, &JsValue::from_str("iterations"), &JsValue::from(iterations))?;
+    js_sys::Reflect::set(&params, &JsValue::from_str("hash"), &JsValue::from_str("SHA-256"))?;
+    let derived = js_sys::Object::new();
+    js_sys::Reflect::set(&derived, &JsValue::from_str("name"), &JsValue::from_str("AES-GCM"))?;
+    js_sys::Reflect::set(&derived, &JsValue::from_str("length"), &JsValue::from(256))?;
+    let usages: js_sys::Array = ["encrypt", "decrypt"].iter().map(|usage| JsValue::from_str(usage)).collect();
+
+    let key = subtle.derive_key_with_object_and_object(&params, &base.unchecked_into(), &derived, false, &usages)?;
+    Ok(JsFuture::from(key).await?.unchecked_into())
+}
+
+/// Encrypts `json` under a fresh IV; the result is itself JSON, an object
+/// carrying the IV and ciphertext.
+async fn seal(key: &web_sys::CryptoKey, json: &str) -> Result<String, JsValue> {
+    let iv = random_iv()?;
+    let ciphertext = JsFuture::from(subtle()?.encrypt_with_object_and_u8_array(&aes_gcm(&iv)?, key, json.as_bytes())?).await?;
+    let ciphertext = js_sys::Uint8Array::new(&ciphertext).to_vec();
+    Ok(format!("{{\"{}\":\"{}:{}\"}}", SEALED_FIELD, base64_encode(&iv), base64_encode(&ciphertext)))
+}
+
+async fn unseal(key: &web_sys::CryptoKey, sealed: &str) -> Result<String, JsValue> {
+    let corrupt = || WasmStorageError::invalid_state("Encrypted entry is corrupt");
+    let (iv, ciphertext) = sealed.split_once(':').ok_or_else(corrupt)?;
+    let iv = base64_decode(iv).ok_or_else(corrupt)?;
+    let ciphertext = base64_decode(ciphertext).ok_or_else(corrupt)?;
+    let plaintext = subtle()?.decrypt_with_object_and_u8_array(&aes_gcm(&iv)?, key, &ciphertext)?;
+    // AES-GCM authenticates, so a wrong key or tampered entry fails here
+    let plaintext = JsFuture::from(plaintext)
+        .await
+        .map_err(|_| WasmStorageError::invalid_state("Encrypted entry could not be decrypted (wrong key?)"))?;
+    String::from_utf8(js_sys::Uint8Array::new(&plaintext).to_vec()).map_err(|_| corrupt().into())
+}
+
+fn sealed_text(value: &JsValue) -> Option<String> {
+    if !value.is_object() {
+        return None;
+    }
+    js_sys::Reflect::get(value, &JsValue::from_str(SEALED_FIELD)).ok()?.as_string()
+}
+
+/// True when persisted `data` holds entries only a key can open.
+pub(crate) fn contains_sealed(data: &js_sys::Object) -> bool {
+    js_sys::Object::values(data).iter().any(|value| sealed_text(&value).is_some())
+}
+
+#[wasm_bindgen]
+impl WasmStorage {
+    /// Encrypts every value before it reaches a persistence backend, with
+    /// AES-GCM and a fresh IV per entry. `key` is an AES-GCM `CryptoKey` or a
+    /// passphrase, stretched with PBKDF2 (options: `salt`, `iterations`).
+    /// Call it (and wait for it) before `enable_persistence`, which then
+    /// decrypts the saved state; hydration finishes asynchronously, so wait
+    /// for `on_init`. Encrypted writes complete in the background.
+    #[wasm_bindgen]
+    pub fn enable_encryption(&self, key: JsValue, options: Option<js_sys::Object>) -> js_sys::Promise {
+        let mut store = self.clone();
+        let options = options.map(JsValue::from).unwrap_or(JsValue::UNDEFINED);
+
+        future_to_promise(async move {
+            let key = match key.as_string() {
+                Some(passphrase) => {
+                    let option = |name: &str| js_sys::Reflect::get(&options, &JsValue::from_str(name)).unwrap_or(JsValue::UNDEFINED);
+                    let salt = option("salt").as_string().unwrap_or_else(|| DEFAULT_SALT.to_string());
+                    let iterations = option("iterations").as_f64().map_or(DEFAULT_ITERATIONS, |n| n as u32);
+                    derive_key(&passphrase, &salt, iterations).await?
+                }
+                None => key.dyn_into::<web_sys::CryptoKey>().map_err(|_| {
+                    WasmStorageError::invalid_argument("enable_encryption needs a CryptoKey or a passphrase")
+                })?,
+            };
+            *store.encryption.lock().map_err(WasmStorageError::lock_poisoned)? = Some(Encryption {
+                key,
+                sealed: HashMap::new(),
+                snapshot: None,
+                wal: Vec::new(),
+                writing: false,
+            });
+            // Replace any plaintext snapshot right away
+            store.write_snapshot()?;
+            log_info!("Encryption enabled");
+            Ok(JsValue::UNDEFINED)
+        })
+    }
+}
+
+impl WasmStorage {
+    pub(crate) fn encrypting(&self) -> Result<bool, JsValue> {
+        Ok(self.encryption.lock().map_err(WasmStorageError::lock_poisoned)?.is_some())
+    }
+
+    /// Queues a snapshot of serialized entries to be sealed and written;
+    /// only the latest queued snapshot is written.
+    pub(crate) fn queue_sealed_snapshot(&self, entries: Vec<(String, String)>) -> Result<(), JsValue> {
+        if let Some(encryption) = self.encryption.lock().map_err(WasmStorageError::lock_poisoned)?.as_mut() {
+            encryption.snapshot = Some(entries);
+        }
+        self.start_sealed_writer()
+    }
+
+    pub(crate) fn queue_sealed_wal(&self, key: Option<&str>, json: Option<String>) -> Result<(), JsValue> {
+        if let Some(encryption) = self.encryption.lock().map_err(WasmStorageError::lock_poisoned)?.as_mut() {
+            encryption.wal.push((key.map(str::to_string), json));
+        }
+        self.start_sealed_writer()
+    }
+
+    /// Decrypts the sealed values of persisted `data` in place; plaintext
+    /// values (written before encryption was enabled) are left alone.
+    pub(crate) async fn unseal_entries(&self, data: &js_sys::Object) -> Result<(), JsValue> {
+        let key = self.encryption_key()?;
+        for entry in js_sys::Object::entries(data).iter() {
+            let pair = js_sys::Array::from(&entry);
+            if let Some(sealed) = sealed_text(&pair.get(1)) {
+                let json = unseal(&key, &sealed).await?;
+                js_sys::Reflect::set(data, &pair.get(0), &js_sys::JSON::parse(&json)?)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn encryption_key(&self) -> Result<web_sys::CryptoKey, JsValue> {
+        let encryption = self.encryption.lock().map_err(WasmStorageError::lock_poisoned)?;
+        encryption
+            .as_ref()
+            .map(|encryption| encryption.key.clone())
+            .ok_or_else(|| WasmStorageError::invalid_state("Encryption is not enabled").into())
+    }
+
+    fn start_sealed_writer(&self) -> Result<(), JsValue> {
+        {
+            let mut encryption = self.encryption.lock().map_err(WasmStorageError::lock_poisoned)?;
+            let Some(encryption) = encryption.as_mut() else {
+                return Ok(());
+            };
+            if encryption.writing {
+                return Ok(());
+            }
+            encryption.writing = true;
+        }
+        let mut store = self.clone();
+        spawn_write(&self.backend_writes, async move {
+            if let Err(error) = store.drain_sealed().await {
+                if let Ok(Some(encryption)) = store.encryption.lock().as_deref_mut() {
+                    encryption.writing = false;
+                }
+                let source = ErrorSource { source: "encryption", key: None, action: None, timestamp: js_sys::Date::now() };
+                let _ = store.report_error(&error, source);
+            }
+        });
+        Ok(())
+    }
+
+    async fn drain_sealed(&mut self) -> Result<(), JsValue> {
+        let key = self.encryption_key()?;
+        loop {
+            let (wal, snapshot) = {
+                let mut encryption = self.encryption.lock().map_err(WasmStorageError::lock_poisoned)?;
+                let Some(encryption) = encryption.as_mut() else {
+                    return Ok(());
+                };
+                if encryption.wal.is_empty() && encryption.snapshot.is_none() {
+                    // Cleared under the lock, so nothing queued after this is missed
+                    encryption.writing = false;
+                    return Ok(());
+                }
+                (std::mem::take(&mut encryption.wal), encryption.snapshot.take())
+            };
+
+            for (entry_key, json) in wal {
+                let sealed = match json {
+                    Some(json) => Some(seal(&key, &json).await?),
+                    None => None,
+                };
+                self.append_wal(entry_key.as_deref(), sealed.as_deref())?;
+            }
+
+            if let Some(entries) = snapshot {
+                let mut sealed_entries = Vec::with_capacity(entries.len());
+                for (entry_key, json) in entries {
+                    let cached = self
+                        .encryption
+                        .lock()
+                        .map_err(WasmStorageError::lock_poisoned)?
+                        .as_ref()
+                        .and_then(|encryption| encryption.sealed.get(&entry_key))
+                        .filter(|(plain, _)| *plain == json)
+                        .map(|(_, sealed)| sealed.clone());
+                    let sealed = match cached {
+                        Some(sealed) => sealed,
+                        None => {
+                            let sealed = seal(&key, &json).await?;
+                            if let Some(encryption) = self.encryption.lock().map_err(WasmStorageError::lock_poisoned)?.as_mut() {
+                                encryption.sealed.insert(entry_key.clone(), (json, sealed.clone()));
+                            }
+                            sealed
+                        }
+                    };
+                    sealed_entries.push((entry_key, sealed));
+                }
+                self.write_entries(sealed_entries)?;
+            }
+        }
+    }
+}
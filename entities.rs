@@ -0,0 +1,50 @@
+use crate::error::WasmStorageError;
+use crate::WasmStorage;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+
+// `"<collection>:<id>"`, the layout `get_by_prefix` reads back
+fn entity_key(collection: &str, id: &str) -> String {
+    format!("{}:{}", collection, id)
+}
+
+#[wasm_bindgen]
+impl WasmStorage {
+    /// Stores `entity` in `collection` under a new ID from the ID generator
+    /// (scope `"entity"`) and returns the ID. The key is `"<collection>:<id>"`,
+    /// and an object entity is stored as a copy with the ID as its `id`.
+    #[wasm_bindgen]
+    pub fn add_entity(&mut self, collection: &str, entity: JsValue) -> Result<String, JsValue> {
+        if collection.is_empty() || collection.contains(':') {
+            return Err(WasmStorageError::invalid_argument("Entity collections need a name without ':'").with("collection", collection).into());
+        }
+        let id = self.generate_id("entity")?;
+        let entity = if entity.is_object() && !js_sys::Array::is_array(&entity) && !entity.is_function() {
+            let copy = js_sys::Object::assign(&js_sys::Object::new(), entity.unchecked_ref());
+            js_sys::Reflect::set(&copy, &JsValue::from_str("id"), &JsValue::from_str(&id))?;
+            copy.into()
+        } else {
+            entity
+        };
+        self.set_state(&entity_key(collection, &id), entity)?;
+        Ok(id)
+    }
+
+    /// The entities of `collection` by ID.
+    #[wasm_bindgen]
+    pub fn get_entities(&self, collection: &str) -> Result<js_sys::Object, JsValue> {
+        let prefix = entity_key(collection, "");
+        let entities = js_sys::Object::new();
+        for entry in js_sys::Object::entries(self.get_by_prefix(&prefix, None)?.unchecked_ref()).iter() {
+            let pair = js_sys::Array::from(&entry);
+            let id = pair.get(0).as_string().unwrap_or_default();
+            js_sys::Reflect::set(&entities, &JsValue::from_str(&id[prefix.len()..]), &pair.get(1))?;
+        }
+        Ok(entities)
+    }
+
+    #[wasm_bindgen]
+    pub fn remove_entity(&mut self, collection: &str, id: &str) -> Result<(), JsValue> {
+        self.remove_state(&entity_key(collection, id))
+    }
+}
@@ -0,0 +1,131 @@
+use wasm_bindgen::prelude::*;
+
+/// Stable error kinds, exposed to JS as `error.code`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum ErrorCode {
+    LockPoisoned,
+    KeyMissing,
+    TypeMismatch,
+    ValidationFailed,
+    QuotaExceeded,
+    InvalidArgument,
+    InvalidState,
+    Unsupported,
+    DependencyCycle,
+    IntegrityFailed,
+    SignatureInvalid,
+    Destroyed,
+    Frozen,
+    AccessDenied,
+}
+
+impl ErrorCode {
+    fn as_str(self) -> &'static str {
+        match self {
+            ErrorCode::LockPoisoned => "LOCK_POISONED",
+            ErrorCode::KeyMissing => "KEY_MISSING",
+            ErrorCode::TypeMismatch => "TYPE_MISMATCH",
+            ErrorCode::ValidationFailed => "VALIDATION_FAILED",
+            ErrorCode::QuotaExceeded => "QUOTA_EXCEEDED",
+            ErrorCode::InvalidArgument => "INVALID_ARGUMENT",
+            ErrorCode::InvalidState => "INVALID_STATE",
+            ErrorCode::Unsupported => "UNSUPPORTED",
+            ErrorCode::DependencyCycle => "DEPENDENCY_CYCLE",
+            ErrorCode::IntegrityFailed => "INTEGRITY_FAILED",
+            ErrorCode::SignatureInvalid => "SIGNATURE_INVALID",
+            ErrorCode::Destroyed => "DESTROYED",
+            ErrorCode::Frozen => "FROZEN",
+            ErrorCode::AccessDenied => "ACCESS_DENIED",
+        }
+    }
+}
+
+/// Error thrown by every store API. `code` is stable for branching (see
+/// `ErrorCode`), `message` is for humans and `context` carries details such
+/// as the key involved. Exceptions thrown by user callbacks (listeners,
+/// middleware, codecs) are passed through unchanged.
+#[wasm_bindgen]
+#[derive(Clone, Debug)]
+pub struct WasmStorageError {
+    code: ErrorCode,
+    message: String,
+    context: JsValue,
+}
+
+impl WasmStorageError {
+    pub(crate) fn new(code: ErrorCode, message: impl Into<String>) -> WasmStorageError {
+        WasmStorageError { code, message: message.into(), context: js_sys::Object::new().into() }
+    }
+
+    /// Adds a field to `context`.
+    pub(crate) fn with(self, name: &str, value: impl Into<JsValue>) -> WasmStorageError {
+        let _ = js_sys::Reflect::set(&self.context, &JsValue::from_str(name), &value.into());
+        self
+    }
+
+    /// A store lock that was poisoned or, single-threaded, already in use.
+    pub(crate) fn lock_poisoned(error: impl std::fmt::Display) -> WasmStorageError {
+        WasmStorageError::new(ErrorCode::LockPoisoned, error.to_string())
+    }
+
+    pub(crate) fn invalid_argument(message: impl Into<String>) -> WasmStorageError {
+        WasmStorageError::new(ErrorCode::InvalidArgument, message)
+    }
+
+    pub(crate) fn invalid_state(message: impl Into<String>) -> WasmStorageError {
+        WasmStorageError::new(ErrorCode::InvalidState, message)
+    }
+
+    pub(crate) fn unsupported(message: impl Into<String>) -> WasmStorageError {
+        WasmStorageError::new(ErrorCode::Unsupported, message)
+    }
+
+    pub(crate) fn key_missing(key: &str) -> WasmStorageError {
+        WasmStorageError::new(ErrorCode::KeyMissing, format!("Key \"{}\" is not set", key)).with("key", key)
+    }
+
+    pub(crate) fn type_mismatch(key: &str, expected: &str, value: &JsValue) -> WasmStorageError {
+        let actual = type_name(value);
+        WasmStorageError::new(
+            ErrorCode::TypeMismatch,
+            format!("Key \"{}\" holds {} but {} was expected", key, actual, expected),
+        )
+        .with("key", key)
+        .with("expected", expected)
+        .with("actual", actual)
+    }
+}
+
+#[wasm_bindgen]
+impl WasmStorageError {
+    #[wasm_bindgen(getter)]
+    pub fn code(&self) -> String {
+        self.code.as_str().to_string()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn message(&self) -> String {
+        self.message.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn context(&self) -> JsValue {
+        self.context.clone()
+    }
+
+    #[wasm_bindgen(js_name = toString)]
+    pub fn to_js_string(&self) -> String {
+        format!("WasmStorageError [{}]: {}", self.code.as_str(), self.message)
+    }
+}
+
+// Name of a value's type as it appears in error messages
+pub(crate) fn type_name(value: &JsValue) -> String {
+    if value.is_null() {
+        "null".to_string()
+    } else if js_sys::Array::is_array(value) {
+        "array".to_string()
+    } else {
+        value.js_typeof().as_string().unwrap_or_default()
+    }
+}
@@ -0,0 +1,56 @@
+use crate::WasmStorage;
+use crate::error::WasmStorageError;
+use wasm_bindgen::prelude::*;
+
+/// Where a reported exception was thrown, passed to the error handler as
+/// `context` next to the error itself.
+pub(crate) struct ErrorSource<'a> {
+    pub(crate) source: &'a str,
+    pub(crate) key: Option<&'a str>,
+    pub(crate) action: Option<&'a str>,
+    pub(crate) timestamp: f64,
+}
+
+#[wasm_bindgen]
+impl WasmStorage {
+    /// Routes exceptions thrown by listeners and middleware to
+    /// `handler(error, { source, key, action, timestamp })`. A throwing
+    /// listener never stops the remaining listeners; with a handler set, a
+    /// throwing middleware is skipped instead of failing the dispatch.
+    #[wasm_bindgen]
+    pub fn set_error_handler(&mut self, handler: Option<js_sys::Function>) -> Result<(), JsValue> {
+        *self.error_handler.lock().map_err(WasmStorageError::lock_poisoned)? = handler;
+        Ok(())
+    }
+}
+
+impl WasmStorage {
+    pub(crate) fn has_error_handler(&self) -> Result<bool, JsValue> {
+        Ok(self.error_handler.lock().map_err(WasmStorageError::lock_poisoned)?.is_some())
+    }
+
+    /// Passes `error` to the error handler, or logs it when none is set.
+    /// Exceptions thrown by the handler itself are logged and dropped.
+    pub(crate) fn report_error(&self, error: &JsValue, origin: ErrorSource) -> Result<(), JsValue> {
+        let handler = self.error_handler.lock().map_err(WasmStorageError::lock_poisoned)?.clone();
+        let Some(handler) = handler else {
+            log_error!("Uncaught {} error: {:?}", origin.source, error);
+            return Ok(());
+        };
+
+        let context = js_sys::Object::new();
+        let optional = |value: Option<&str>| value.map_or(JsValue::NULL, JsValue::from_str);
+        js_sys::Reflect::set(&context, &JsValue::from_str("source"), &JsValue::from_str(origin.source))?;
+        js_sys::Reflect::set(&context, &JsValue::from_str("key"), &optional(origin.key))?;
+        js_sys::Reflect::set(&context, &JsValue::from_str("action"), &optional(origin.action))?;
+        js_sys::Reflect::set(&context, &JsValue::from_str("timestamp"), &JsValue::from_f64(origin.timestamp))?;
+        if self.tags_errors() {
+            js_sys::Reflect::set(&context, &JsValue::from_str("storeId"), &JsValue::from_str(&self.instance_id()?))?;
+        }
+
+        if let Err(handler_error) = handler.call2(&JsValue::NULL, error, &context) {
+            log_error!("Error handler threw: {:?}", handler_error);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,288 @@
+use crate::backends::Backend;
+use crate::error::WasmStorageError;
+use crate::origin::Origin;
+use crate::persistence::js_string;
+use crate::signing::Signing;
+use crate::{StorageEvent, WasmStorage};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+
+const DEFAULT_LOG_KEY: &str = "wasm-storage::events";
+
+struct LoggedAction {
+    seq: u64,
+    action_type: String,
+    payload_json: String,
+    timestamp: f64,
+}
+
+impl LoggedAction {
+    fn line(&self) -> String {
+        format!(
+            "{{\"seq\":{},\"type\":{},\"payload\":{},\"timestamp\":{}}}",
+            self.seq,
+            js_string(&self.action_type),
+            self.payload_json,
+            self.timestamp
+        )
+    }
+}
+
+/// Append-only log of every dispatched action, persisted in a backend, with
+/// an optional snapshot the log continues from. The state is rebuilt by
+/// folding the logged actions over the snapshot.
+pub(crate) struct EventLog {
+    backend: Backend,
+    key: String,
+    // JSON object of the state the log starts from, and the last action it contains
+    snapshot: Option<String>,
+    snapshot_seq: u64,
+    seq: u64,
+    actions: Vec<LoggedAction>,
+}
+
+fn snapshot_key(log_key: &str) -> String {
+    format!("{}::snapshot", log_key)
+}
+
+fn field(value: &JsValue, name: &str) -> JsValue {
+    js_sys::Reflect::get(value, &JsValue::from_str(name)).unwrap_or(JsValue::UNDEFINED)
+}
+
+fn sign(signing: Option<&Signing>, json: String) -> String {
+    match signing {
+        Some(signing) => signing.sign_object(&json),
+        None => json,
+    }
+}
+
+impl EventLog {
+    /// Actions logged since the last compaction.
+    pub(crate) fn len(&self) -> usize {
+        self.actions.len()
+    }
+
+    // Reads the snapshot and the actions after it. A torn final line (the
+    // page died mid-write) is ignored; a line failing verification is an error.
+    fn load(backend: Backend, key: String, signing: Option<&Signing>) -> Result<EventLog, JsValue> {
+        let mut log = EventLog { backend, key, snapshot: None, snapshot_seq: 0, seq: 0, actions: Vec::new() };
+        if let Some(saved) = log.backend.get_item(&snapshot_key(&log.key))? {
+            let saved = match signing {
+                Some(signing) => signing.verify_object(&saved, "Event log snapshot")?,
+                None => saved,
+            };
+            let saved = js_sys::JSON::parse(&saved)?;
+            log.snapshot_seq = field(&saved, "seq").as_f64().unwrap_or(0.0) as u64;
+            log.seq = log.snapshot_seq;
+            log.snapshot = js_sys::JSON::stringify(&field(&saved, "data"))?.as_string();
+        }
+        let Some(lines) = log.backend.get_item(&log.key)? else {
+            return Ok(log);
+        };
+        for line in lines.lines() {
+            let Ok(mut entry) = js_sys::JSON::parse(line) else {
+                continue;
+            };
+            if let Some(signing) = signing {
+                entry = js_sys::JSON::parse(&signing.verify_object(line, "Event log entry")?)?;
+            }
+            let seq = field(&entry, "seq").as_f64().unwrap_or(0.0) as u64;
+            if seq <= log.snapshot_seq {
+                continue;
+            }
+            log.seq = log.seq.max(seq);
+            log.actions.push(LoggedAction {
+                seq,
+                action_type: field(&entry, "type").as_string().unwrap_or_default(),
+                payload_json: js_sys::JSON::stringify(&field(&entry, "payload"))?.as_string().unwrap_or_else(|| "null".to_string()),
+                timestamp: field(&entry, "timestamp").as_f64().unwrap_or(0.0),
+            });
+        }
+        Ok(log)
+    }
+
+    // Rewrites the whole log item, after actions were dropped from it
+    fn rewrite(&self, signing: Option<&Signing>) -> Result<(), JsValue> {
+        if self.actions.is_empty() {
+            return self.backend.remove_item(&self.key);
+        }
+        let lines: Vec<String> = self.actions.iter().map(|action| sign(signing, action.line())).collect();
+        self.backend.set_item(&self.key, &lines.join("\n"))
+    }
+}
+
+#[wasm_bindgen]
+impl WasmStorage {
+    /// Switches to event sourcing: every dispatched action (after middleware)
+    /// is appended to a log in the backend registered as `backend` (default
+    /// localStorage) under `logKey` (default "wasm-storage::events"), and the
+    /// state is rebuilt from that log right away. Entries are signed when
+    /// signing is enabled. Returns the number of actions replayed. Direct
+    /// `set_state` writes aren't logged, so they don't survive a replay.
+    #[wasm_bindgen]
+    pub fn enable_event_sourcing(&mut self, options: Option<js_sys::Object>) -> Result<u32, JsValue> {
+        let options = options.map(JsValue::from).unwrap_or(JsValue::UNDEFINED);
+        let backend = self.backend(&field(&options, "backend").as_string().unwrap_or_else(|| "localStorage".to_string()))?;
+        let key = field(&options, "logKey").as_string().unwrap_or_else(|| DEFAULT_LOG_KEY.to_string());
+        let log = EventLog::load(backend, key, self.signing()?.as_ref())?;
+        log_info!("Event sourcing enabled with {} logged actions", log.actions.len());
+        *self.event_log.lock().map_err(WasmStorageError::lock_poisoned)? = Some(log);
+        self.replay()
+    }
+
+    /// Rebuilds the state from scratch: clears it, restores the compacted
+    /// snapshot, then re-applies every logged action in order without
+    /// running middleware. Changes carry the "replay" origin. Returns the
+    /// number of actions applied.
+    #[wasm_bindgen]
+    pub fn replay(&mut self) -> Result<u32, JsValue> {
+        self.ensure_alive()?;
+        let (snapshot, events) = {
+            let log = self.event_log.lock().map_err(WasmStorageError::lock_poisoned)?;
+            let log = log.as_ref().ok_or_else(|| WasmStorageError::invalid_state("Event sourcing is not enabled"))?;
+            let mut events = Vec::with_capacity(log.actions.len());
+            for action in &log.actions {
+                events.push(StorageEvent {
+                    action_type: action.action_type.clone(),
+                    payload: js_sys::JSON::parse(&action.payload_json)?,
+                    timestamp: action.timestamp,
+                    changes: None,
+                });
+            }
+            (log.snapshot.as_deref().map(js_sys::JSON::parse).transpose()?, events)
+        };
+        let applied = events.len() as u32;
+        self.with_origin(Origin::Replay, |store| {
+            store.clear_state()?;
+            if let Some(snapshot) = snapshot.as_ref().and_then(|snapshot| snapshot.dyn_ref::<js_sys::Object>()) {
+                for entry in js_sys::Object::entries(snapshot).iter() {
+                    let pair = js_sys::Array::from(&entry);
+                    store.set_state(&pair.get(0).as_string().unwrap_or_default(), pair.get(1))?;
+                }
+            }
+            for event in &events {
+                store.at_time(event.timestamp, |store| store.handle_action(event))?;
+            }
+            Ok(())
+        })?;
+        log_info!("Replayed {} actions", applied);
+        Ok(applied)
+    }
+
+    /// Folds the log into a snapshot of the current state and empties it, so
+    /// replays and the stored log stay small. Returns the sequence number the
+    /// snapshot covers.
+    #[wasm_bindgen]
+    pub fn compact_to_snapshot(&mut self) -> Result<f64, JsValue> {
+        let data = js_sys::Object::new();
+        {
+            let state = self.state.lock().map_err(WasmStorageError::lock_poisoned)?;
+            for (key, value) in state.iter() {
+                js_sys::Reflect::set(&data, &JsValue::from_str(key), value)?;
+            }
+        }
+        let data = js_sys::JSON::stringify(&data)?.as_string().unwrap_or_else(|| "{}".to_string());
+        let signing = self.signing()?;
+        let mut log = self.event_log.lock().map_err(WasmStorageError::lock_poisoned)?;
+        let log = log.as_mut().ok_or_else(|| WasmStorageError::invalid_state("Event sourcing is not enabled"))?;
+        let snapshot = format!("{{\"seq\":{},\"data\":{}}}", log.seq, data);
+        log.backend.set_item(&snapshot_key(&log.key), &sign(signing.as_ref(), snapshot))?;
+        log.backend.remove_item(&log.key)?;
+        log.snapshot = Some(data);
+        log.snapshot_seq = log.seq;
+        log.actions.clear();
+        log_info!("Compacted event log at action {}", log.seq);
+        Ok(log.seq as f64)
+    }
+
+    /// Drops every logged action after `seq` and replays, rewinding the state
+    /// to just after that action. Actions already compacted into the
+    /// snapshot can't be dropped.
+    #[wasm_bindgen]
+    pub fn truncate_event_log(&mut self, seq: f64) -> Result<u32, JsValue> {
+        let seq = seq.max(0.0) as u64;
+        {
+            let signing = self.signing()?;
+            let mut log = self.event_log.lock().map_err(WasmStorageError::lock_poisoned)?;
+            let log = log.as_mut().ok_or_else(|| WasmStorageError::invalid_state("Event sourcing is not enabled"))?;
+            if seq < log.snapshot_seq {
+                return Err(WasmStorageError::invalid_argument(format!(
+                    "Actions up to {} are compacted into the snapshot; cannot truncate to {}",
+                    log.snapshot_seq, seq
+                ))
+                .with("snapshotSeq", log.snapshot_seq as f64)
+                .into());
+            }
+            log.actions.retain(|action| action.seq <= seq);
+            log.seq = log.seq.min(seq);
+            log.rewrite(signing.as_ref())?;
+        }
+        self.replay()
+    }
+
+    /// The logged actions after `from_seq` (default all) as
+    /// `{ seq, type, payload, timestamp }`, oldest first.
+    #[wasm_bindgen]
+    pub fn event_log(&self, from_seq: Option<f64>) -> Result<js_sys::Array, JsValue> {
+        let from_seq = from_seq.unwrap_or(0.0).max(0.0) as u64;
+        let log = self.event_log.lock().map_err(WasmStorageError::lock_poisoned)?;
+        let log = log.as_ref().ok_or_else(|| WasmStorageError::invalid_state("Event sourcing is not enabled"))?;
+        let entries = js_sys::Array::new();
+        for action in log.actions.iter().filter(|action| action.seq > from_seq) {
+            let entry = js_sys::Object::new();
+            js_sys::Reflect::set(&entry, &JsValue::from_str("seq"), &JsValue::from_f64(action.seq as f64))?;
+            js_sys::Reflect::set(&entry, &JsValue::from_str("type"), &JsValue::from_str(&action.action_type))?;
+            js_sys::Reflect::set(&entry, &JsValue::from_str("payload"), &js_sys::JSON::parse(&action.payload_json)?)?;
+            js_sys::Reflect::set(&entry, &JsValue::from_str("timestamp"), &JsValue::from_f64(action.timestamp))?;
+            entries.push(&entry);
+        }
+        Ok(entries)
+    }
+}
+
+impl WasmStorage {
+    /// The payload JSON to log for `event`, checked before the action is
+    /// handled so an action that can't be logged changes nothing. None when
+    /// event sourcing is off.
+    pub(crate) fn loggable_action(&self, event: &StorageEvent) -> Result<Option<String>, JsValue> {
+        if self.event_log.lock().map_err(WasmStorageError::lock_poisoned)?.is_none() {
+            return Ok(None);
+        }
+        let json = if event.payload.is_undefined() {
+            Some("null".to_string())
+        } else {
+            js_sys::JSON::stringify(&event.payload).ok().and_then(|json| json.as_string())
+        };
+        match json {
+            Some(json) => Ok(Some(json)),
+            None => Err(WasmStorageError::invalid_argument(format!(
+                "The payload of {} can't be logged as JSON",
+                event.action_type
+            ))
+            .with("action", event.action_type.as_str())
+            .into()),
+        }
+    }
+
+    /// Appends a handled action to the log.
+    pub(crate) fn log_action(&self, event: &StorageEvent, payload_json: Option<String>) -> Result<(), JsValue> {
+        let Some(payload_json) = payload_json else {
+            return Ok(());
+        };
+        let signing = self.signing()?;
+        let mut log = self.event_log.lock().map_err(WasmStorageError::lock_poisoned)?;
+        let Some(log) = log.as_mut() else {
+            return Ok(());
+        };
+        let action = LoggedAction {
+            seq: log.seq + 1,
+            action_type: event.action_type.clone(),
+            payload_json,
+            timestamp: event.timestamp,
+        };
+        log.backend.append_line(&log.key, &sign(signing.as_ref(), action.line()))?;
+        log.seq = action.seq;
+        log.actions.push(action);
+        Ok(())
+    }
+}
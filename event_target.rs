@@ -0,0 +1,73 @@
+use crate::error::WasmStorageError;
+use crate::subscriptions::Subscription;
+use crate::WasmStorage;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+
+/// The DOM `EventTarget` behind `add_event_listener`, fed by a store
+/// subscription created with the first listener.
+pub(crate) struct StoreEvents {
+    target: web_sys::EventTarget,
+    _subscription: Subscription,
+}
+
+// Calls `method` on the target with the caller's arguments untouched, so
+// listener objects and every addEventListener option keep working
+fn forward(target: &web_sys::EventTarget, method: &str, event_type: &str, listener: &JsValue, options: &JsValue) -> Result<(), JsValue> {
+    let method: js_sys::Function = js_sys::Reflect::get(target, &JsValue::from_str(method))?.unchecked_into();
+    method.call3(target, &JsValue::from_str(event_type), listener, options)?;
+    Ok(())
+}
+
+#[wasm_bindgen]
+impl WasmStorage {
+    /// `EventTarget.addEventListener` for store changes: `"change"` fires
+    /// for every change and `"change:<key>"` for changes to that key, as a
+    /// `CustomEvent` whose `detail` is the `subscribe` change event.
+    /// `listener` and `options` (`once`, `signal`, ...) are the DOM's.
+    #[wasm_bindgen]
+    pub fn add_event_listener(&mut self, event_type: &str, listener: JsValue, options: JsValue) -> Result<(), JsValue> {
+        let target = self.event_target()?;
+        forward(&target, "addEventListener", event_type, &listener, &options)
+    }
+
+    #[wasm_bindgen]
+    pub fn remove_event_listener(&mut self, event_type: &str, listener: JsValue, options: JsValue) -> Result<(), JsValue> {
+        let target = self.events.lock().map_err(WasmStorageError::lock_poisoned)?.as_ref().map(|events| events.target.clone());
+        match target {
+            Some(target) => forward(&target, "removeEventListener", event_type, &listener, &options),
+            None => Ok(()),
+        }
+    }
+
+    /// Dispatches `event` to the store's listeners, like
+    /// `EventTarget.dispatchEvent`.
+    #[wasm_bindgen]
+    pub fn dispatch_event(&mut self, event: web_sys::Event) -> Result<bool, JsValue> {
+        self.event_target()?.dispatch_event(&event)
+    }
+}
+
+impl WasmStorage {
+    fn event_target(&mut self) -> Result<web_sys::EventTarget, JsValue> {
+        if let Some(events) = &*self.events.lock().map_err(WasmStorageError::lock_poisoned)? {
+            return Ok(events.target.clone());
+        }
+        let target = web_sys::EventTarget::new()?;
+        let emitter = target.clone();
+        let on_change = Closure::wrap(Box::new(move |change: JsValue| -> Result<(), JsValue> {
+            let key = js_sys::Reflect::get(&change, &JsValue::from_str("key"))?.as_string().unwrap_or_default();
+            for event_type in ["change".to_string(), format!("change:{}", key)] {
+                let init = web_sys::CustomEventInit::new();
+                init.set_detail(&change);
+                let event = web_sys::CustomEvent::new_with_event_init_dict(&event_type, &init)?;
+                emitter.dispatch_event(&event)?;
+            }
+            Ok(())
+        }) as Box<dyn FnMut(JsValue) -> Result<(), JsValue>>);
+        let subscription = self.subscribe(on_change.into_js_value().unchecked_into(), None)?;
+
+        *self.events.lock().map_err(WasmStorageError::lock_poisoned)? = Some(StoreEvents { target: target.clone(), _subscription: subscription });
+        Ok(target)
+    }
+}
@@ -0,0 +1,57 @@
+use crate::error::WasmStorageError;
+use crate::subscriptions::ChangeDetection;
+use crate::WasmStorage;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use wasm_bindgen::prelude::*;
+
+/// The last content hash taken of a key, with the value it was taken of.
+/// A different stored value (written some other way) makes it stale.
+pub(crate) struct Fingerprint {
+    value: JsValue,
+    hash: Option<u64>,
+}
+
+// A hash of the value's JSON; None for values JSON can't represent
+fn content_hash(value: &JsValue) -> Option<u64> {
+    let json = js_sys::JSON::stringify(value).ok()?.as_string()?;
+    let mut hasher = DefaultHasher::new();
+    json.hash(&mut hasher);
+    Some(hasher.finish())
+}
+
+impl WasmStorage {
+    /// In `"hash"` change detection, whether writing `value` to `key` would
+    /// store the same content again. The value stored before keeps its
+    /// fingerprint, so each write serializes only the incoming value, and a
+    /// value mutated in place since it was stored still counts as changed.
+    pub(crate) fn same_content(&self, key: &str, value: &JsValue) -> Result<bool, JsValue> {
+        if *self.change_detection.lock().map_err(WasmStorageError::lock_poisoned)? != ChangeDetection::Hash {
+            return Ok(false);
+        }
+        let current = self.state.lock().map_err(WasmStorageError::lock_poisoned)?.get(key).cloned();
+        let hash = content_hash(value);
+        let mut fingerprints = self.fingerprints.lock().map_err(WasmStorageError::lock_poisoned)?;
+        let previous = match (&current, fingerprints.get(key)) {
+            (None, _) => None,
+            (Some(current), Some(fingerprint)) if js_sys::Object::is(current, &fingerprint.value) => fingerprint.hash,
+            (Some(current), _) => content_hash(current),
+        };
+        let same = previous.is_some() && previous == hash;
+        // Keyed to whichever value ends up stored: the old one when unchanged
+        let stored = current.filter(|_| same).unwrap_or_else(|| value.clone());
+        fingerprints.insert(key.to_string(), Fingerprint { value: stored, hash });
+        Ok(same)
+    }
+
+    pub(crate) fn forget_fingerprint(&self, key: Option<&str>) -> Result<(), JsValue> {
+        let mut fingerprints = self.fingerprints.lock().map_err(WasmStorageError::lock_poisoned)?;
+        match key {
+            Some(key) => {
+                fingerprints.remove(key);
+            }
+            None => fingerprints.clear(),
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,131 @@
+use crate::error::{ErrorCode, WasmStorageError};
+use crate::persistent_map::PersistentMap;
+use crate::{changed_keys, WasmStorage};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+
+/// How `merge_from` treats a key both stores changed since the fork.
+enum MergeStrategy {
+    // The child's value wins
+    Theirs,
+    // The parent keeps its value
+    Ours,
+    // Nothing is merged
+    Fail,
+    Resolver(js_sys::Function),
+}
+
+impl MergeStrategy {
+    fn from_js(strategy: &JsValue) -> Result<MergeStrategy, JsValue> {
+        if strategy.is_undefined() || strategy.is_null() {
+            return Ok(MergeStrategy::Theirs);
+        }
+        if let Some(resolver) = strategy.dyn_ref::<js_sys::Function>() {
+            return Ok(MergeStrategy::Resolver(resolver.clone()));
+        }
+        match strategy.as_string().as_deref() {
+            Some("theirs") => Ok(MergeStrategy::Theirs),
+            Some("ours") => Ok(MergeStrategy::Ours),
+            Some("fail") => Ok(MergeStrategy::Fail),
+            _ => Err(WasmStorageError::invalid_argument(format!(
+                "Unknown merge strategy {:?} (expected \"theirs\", \"ours\", \"fail\" or a function)",
+                strategy
+            ))
+            .with("strategy", strategy.clone())
+            .into()),
+        }
+    }
+}
+
+// A missing key reads as undefined
+fn value_of(state: &PersistentMap<JsValue>, key: &str) -> JsValue {
+    state.get(key).cloned().unwrap_or(JsValue::UNDEFINED)
+}
+
+#[wasm_bindgen]
+impl WasmStorage {
+    /// An isolated scratch copy of the store for what-if editing. It starts
+    /// with the same state (copy-on-write, so forking is cheap), reducers
+    /// and validators, but has its own listeners and no persistence or
+    /// sync: nothing written to it reaches the parent until `merge_from`.
+    /// Dropping the fork cancels the edit.
+    #[wasm_bindgen]
+    pub fn fork(&self) -> Result<WasmStorage, JsValue> {
+        self.ensure_alive()?;
+        let child = WasmStorage::new(None);
+        let state = self.state.lock().map_err(WasmStorageError::lock_poisoned)?.clone();
+        *child.state.lock().map_err(WasmStorageError::lock_poisoned)? = state.clone();
+        *child.fork_base.lock().map_err(WasmStorageError::lock_poisoned)? = Some(state);
+        *child.reducers.lock().map_err(WasmStorageError::lock_poisoned)? = self.reducers.lock().map_err(WasmStorageError::lock_poisoned)?.clone();
+        *child.validators.lock().map_err(WasmStorageError::lock_poisoned)? = self.validators.lock().map_err(WasmStorageError::lock_poisoned)?.clone();
+        Ok(child)
+    }
+
+    /// Writes the keys `child` (from `fork`) changed since it was forked or
+    /// last merged into this store, notifying listeners as usual, and
+    /// returns them sorted. Where this store changed a key too, `strategy`
+    /// decides: `"theirs"` (the default) takes the child's value, `"ours"`
+    /// keeps this store's, `"fail"` merges nothing and throws INVALID_STATE
+    /// listing the keys in `context.conflicts`, and a function
+    /// `(key, ours, theirs, base)` returns the value to keep (undefined
+    /// removes the key).
+    #[wasm_bindgen]
+    pub fn merge_from(&mut self, child: &WasmStorage, strategy: JsValue) -> Result<js_sys::Array, JsValue> {
+        self.ensure_alive()?;
+        self.ensure_writable()?;
+        let strategy = MergeStrategy::from_js(&strategy)?;
+        let base = child
+            .fork_base
+            .lock()
+            .map_err(WasmStorageError::lock_poisoned)?
+            .clone()
+            .ok_or_else(|| WasmStorageError::invalid_argument("merge_from expects a store created by fork"))?;
+        let theirs_state = child.state.lock().map_err(WasmStorageError::lock_poisoned)?.clone();
+        let ours_state = self.state.lock().map_err(WasmStorageError::lock_poisoned)?.clone();
+
+        // Keys the child changed, by reference, since its base
+        let changed = changed_keys(&base, &theirs_state);
+
+        let mut merges = Vec::with_capacity(changed.len());
+        let mut conflicts = Vec::new();
+        for key in changed {
+            let (ours, theirs, original) = (value_of(&ours_state, key), value_of(&theirs_state, key), value_of(&base, key));
+            let conflicting = !js_sys::Object::is(&ours, &original) && !js_sys::Object::is(&ours, &theirs);
+            let value = match (&strategy, conflicting) {
+                (_, false) | (MergeStrategy::Theirs, true) => theirs,
+                (MergeStrategy::Ours, true) => continue,
+                (MergeStrategy::Fail, true) => {
+                    conflicts.push(key.clone());
+                    continue;
+                }
+                (MergeStrategy::Resolver(resolver), true) => {
+                    let args = js_sys::Array::of4(&JsValue::from_str(key), &ours, &theirs, &original);
+                    resolver.apply(&JsValue::NULL, &args)?
+                }
+            };
+            merges.push((key.clone(), value));
+        }
+        if !conflicts.is_empty() {
+            let keys: js_sys::Array = conflicts.iter().map(|key| JsValue::from_str(key)).collect();
+            return Err(WasmStorageError::new(
+                ErrorCode::InvalidState,
+                format!("Merge conflicts on {}", conflicts.join(", ")),
+            )
+            .with("conflicts", keys)
+            .into());
+        }
+
+        let merged = js_sys::Array::new();
+        for (key, value) in merges {
+            if value.is_undefined() {
+                self.remove_state(&key)?;
+            } else {
+                self.set_state(&key, value)?;
+            }
+            merged.push(&JsValue::from_str(&key));
+        }
+        // Later merges only bring what changes from here on
+        *child.fork_base.lock().map_err(WasmStorageError::lock_poisoned)? = Some(theirs_state);
+        Ok(merged)
+    }
+}
@@ -0,0 +1,299 @@
+use crate::error::WasmStorageError;
+use crate::origin::Origin;
+use crate::subscriptions::Subscription;
+use crate::{glob_match, WasmStorage};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+
+const PROTOCOL: &str = "__wasmStorageFrame";
+
+/// What an embedded frame mirrors from its host: the host's key globs and
+/// whether the frame may write them back.
+#[derive(Clone)]
+pub(crate) struct FrameMirror {
+    keys: Vec<String>,
+    read_only: bool,
+    // Host origin confirmed by its first snapshot
+    host_origin: Option<String>,
+}
+
+impl FrameMirror {
+    fn covers(&self, key: &str) -> bool {
+        self.keys.is_empty() || self.keys.iter().any(|pattern| glob_match(pattern, key))
+    }
+}
+
+/// One end of a store mirror between a host page and an embedded iframe.
+#[wasm_bindgen]
+pub struct FrameBridge {
+    store: WasmStorage,
+    window: web_sys::Window,
+    subscription: Option<Subscription>,
+    _on_message: Closure<dyn FnMut(web_sys::MessageEvent)>,
+    _listener: Closure<dyn FnMut(JsValue)>,
+}
+
+fn frame_message(kind: &str) -> Result<js_sys::Object, JsValue> {
+    let message = js_sys::Object::new();
+    js_sys::Reflect::set(&message, &JsValue::from_str(PROTOCOL), &JsValue::from_str(kind))?;
+    Ok(message)
+}
+
+fn post(target: &JsValue, message: &JsValue, origin: &str) -> Result<(), JsValue> {
+    let post_message = js_sys::Reflect::get(target, &JsValue::from_str("postMessage"))?.dyn_into::<js_sys::Function>()?;
+    post_message.call2(target, message, &JsValue::from_str(origin))?;
+    Ok(())
+}
+
+fn field(value: &JsValue, name: &str) -> JsValue {
+    js_sys::Reflect::get(value, &JsValue::from_str(name)).unwrap_or(JsValue::UNDEFINED)
+}
+
+fn string_list(value: &JsValue) -> Vec<String> {
+    if js_sys::Array::is_array(value) {
+        js_sys::Array::from(value).iter().filter_map(|item| item.as_string()).collect()
+    } else {
+        Vec::new()
+    }
+}
+
+fn apply_mirrored(store: &mut WasmStorage, key: String, value: JsValue) -> Result<(), JsValue> {
+    let target = key.clone();
+    store.accept_remote(&target, None, Box::new(move |store| {
+        if value.is_null() || value.is_undefined() {
+            store.remove_state(&key)
+        } else {
+            store.set_state(&key, value)
+        }
+    }))
+}
+
+#[wasm_bindgen]
+impl WasmStorage {
+    /// Mirrors this store into the iframe `frame` (the element or its
+    /// window), whose store calls `connect_to_host`. Messages only go to and
+    /// are only accepted from `frame_origin`, which must be exact. Options:
+    /// `keys` (keys or globs, default all) and `readOnly` (default true);
+    /// with `readOnly: false` the frame's writes to mirrored keys apply here
+    /// with the "sync" origin.
+    #[wasm_bindgen]
+    pub fn bridge_to_frame(&mut self, frame: JsValue, frame_origin: &str, options: Option<js_sys::Object>) -> Result<FrameBridge, JsValue> {
+        if frame_origin.is_empty() || frame_origin == "*" {
+            return Err(WasmStorageError::invalid_argument("bridge_to_frame needs the frame's exact origin, not \"*\"").into());
+        }
+        let window = web_sys::window().ok_or_else(|| WasmStorageError::unsupported("bridge_to_frame needs a window"))?;
+        let content_window = field(&frame, "contentWindow");
+        let target = if content_window.is_object() { content_window } else { frame };
+        let options = options.map(JsValue::from).unwrap_or(JsValue::UNDEFINED);
+        let mirror = FrameMirror {
+            keys: string_list(&field(&options, "keys")),
+            read_only: field(&options, "readOnly").as_bool().unwrap_or(true),
+            host_origin: None,
+        };
+        let origin = frame_origin.to_string();
+        let terms = mirror.clone();
+        let listener_terms = mirror.clone();
+
+        let mut store = self.clone();
+        let message_target = target.clone();
+        let message_origin = origin.clone();
+        let on_message = Closure::wrap(Box::new(move |event: web_sys::MessageEvent| {
+            let from_frame = event.origin() == message_origin
+                && event.source().is_some_and(|source| JsValue::from(source) == message_target);
+            if !from_frame {
+                return;
+            }
+            if let Err(e) = store.serve_frame(&mirror, &message_target, &message_origin, &event.data()) {
+                log_warn!("Frame bridge failed to handle a message from {}: {:?}", message_origin, e);
+            }
+        }) as Box<dyn FnMut(web_sys::MessageEvent)>);
+        window.add_event_listener_with_callback("message", on_message.as_ref().unchecked_ref())?;
+
+        // Every change of a mirrored key, including those the frame made
+        let change_target = target.clone();
+        let change_origin = origin.clone();
+        let listener = Closure::wrap(Box::new(move |event: JsValue| {
+            let key = field(&event, "key").as_string().unwrap_or_default();
+            if !listener_terms.covers(&key) {
+                return;
+            }
+            let posted = frame_message("change").and_then(|message| {
+                let value = field(&event, "value");
+                let value = if value.is_undefined() { JsValue::NULL } else { value };
+                js_sys::Reflect::set(&message, &JsValue::from_str("key"), &JsValue::from_str(&key))?;
+                js_sys::Reflect::set(&message, &JsValue::from_str("value"), &value)?;
+                post(&change_target, &message, &change_origin)
+            });
+            if let Err(e) = posted {
+                log_warn!("Frame bridge failed to post a change: {:?}", e);
+            }
+        }) as Box<dyn FnMut(JsValue)>);
+        let subscription = self.subscribe(listener.as_ref().unchecked_ref::<js_sys::Function>().clone(), None)?;
+
+        // The frame may already be listening; otherwise its hello asks again
+        self.post_frame_snapshot(&terms, &target, &origin)?;
+        log_info!("Bridging store to frame at {} ({})", origin, if terms.read_only { "read-only" } else { "read-write" });
+        Ok(FrameBridge { store: self.clone(), window, subscription: Some(subscription), _on_message: on_message, _listener: listener })
+    }
+
+    /// Mirrors the host page's store into this iframe's store, accepting
+    /// messages only from `window.parent` at one of `allowed_origins`. While
+    /// the host bridge is read-only, local writes to mirrored keys throw
+    /// INVALID_STATE; otherwise they are sent to the host.
+    #[wasm_bindgen]
+    pub fn connect_to_host(&mut self, allowed_origins: js_sys::Array) -> Result<FrameBridge, JsValue> {
+        let allowed = string_list(&allowed_origins);
+        if allowed.is_empty() || allowed.iter().any(|origin| origin == "*") {
+            return Err(WasmStorageError::invalid_argument("connect_to_host needs the host's exact origins, not \"*\"").into());
+        }
+        let window = web_sys::window().ok_or_else(|| WasmStorageError::unsupported("connect_to_host needs a window"))?;
+        let parent: JsValue = window.parent()?.map(JsValue::from).unwrap_or(JsValue::NULL);
+
+        let mut store = self.clone();
+        let message_parent = parent.clone();
+        let on_message = Closure::wrap(Box::new(move |event: web_sys::MessageEvent| {
+            let origin = event.origin();
+            let from_host = allowed.contains(&origin)
+                && event.source().is_some_and(|source| JsValue::from(source) == message_parent);
+            if !from_host {
+                return;
+            }
+            if let Err(e) = store.mirror_host(&origin, &event.data()) {
+                log_warn!("Frame bridge failed to handle a message from {}: {:?}", origin, e);
+            }
+        }) as Box<dyn FnMut(web_sys::MessageEvent)>);
+        window.add_event_listener_with_callback("message", on_message.as_ref().unchecked_ref())?;
+
+        // Forward this frame's own writes when the host accepts them
+        let listener_store = self.clone();
+        let listener_parent = parent.clone();
+        let listener = Closure::wrap(Box::new(move |event: JsValue| {
+            if field(&event, "origin").as_string().as_deref() != Some("local") {
+                return;
+            }
+            if let Err(e) = listener_store.forward_to_host(&listener_parent, &event) {
+                log_warn!("Frame bridge failed to forward a change: {:?}", e);
+            }
+        }) as Box<dyn FnMut(JsValue)>);
+        let subscription = self.subscribe(listener.as_ref().unchecked_ref::<js_sys::Function>().clone(), None)?;
+
+        let hello = frame_message("hello")?;
+        for origin in string_list(&allowed_origins) {
+            // Delivered only to the origin the host page actually has
+            post(&parent, &hello, &origin)?;
+        }
+        Ok(FrameBridge { store: self.clone(), window, subscription: Some(subscription), _on_message: on_message, _listener: listener })
+    }
+}
+
+#[wasm_bindgen]
+impl FrameBridge {
+    /// Stops mirroring; a frame's store keeps its last values and may
+    /// write them again.
+    #[wasm_bindgen]
+    pub fn close(&mut self) -> Result<(), JsValue> {
+        self.window
+            .remove_event_listener_with_callback("message", self._on_message.as_ref().unchecked_ref())?;
+        if let Some(subscription) = self.subscription.take() {
+            subscription.unsubscribe()?;
+        }
+        *self.store.frame_mirror.lock().map_err(WasmStorageError::lock_poisoned)? = None;
+        Ok(())
+    }
+}
+
+impl WasmStorage {
+    /// Rejects local writes to keys mirrored read-only from a host frame.
+    pub(crate) fn check_frame_writable(&self, key: &str) -> Result<(), JsValue> {
+        let read_only = self
+            .frame_mirror
+            .lock()
+            .map_err(WasmStorageError::lock_poisoned)?
+            .as_ref()
+            .is_some_and(|mirror| mirror.read_only && mirror.covers(key));
+        if read_only && self.current_origin()? == Origin::Local {
+            return Err(WasmStorageError::invalid_state(format!("\"{}\" is mirrored read-only from the host page", key))
+                .with("key", key)
+                .into());
+        }
+        Ok(())
+    }
+
+    // Host side: answers a frame's hello and applies its writes
+    fn serve_frame(&mut self, mirror: &FrameMirror, target: &JsValue, origin: &str, data: &JsValue) -> Result<(), JsValue> {
+        match field(data, PROTOCOL).as_string().as_deref() {
+            Some("hello") => self.post_frame_snapshot(mirror, target, origin),
+            Some("set") if !mirror.read_only => {
+                let key = field(data, "key").as_string().unwrap_or_default();
+                if !mirror.covers(&key) {
+                    return Err(WasmStorageError::invalid_argument(format!("Frame wrote unmirrored key \"{}\"", key)).with("key", key.as_str()).into());
+                }
+                apply_mirrored(self, key, field(data, "value"))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    // The snapshot also tells the frame which keys it mirrors and whether
+    // it may write them
+    fn post_frame_snapshot(&self, mirror: &FrameMirror, target: &JsValue, origin: &str) -> Result<(), JsValue> {
+        let mirrored: Vec<(String, JsValue)> = {
+            let state = self.state.lock().map_err(WasmStorageError::lock_poisoned)?;
+            state.iter().filter(|(key, _)| mirror.covers(key)).map(|(key, value)| (key.clone(), value.clone())).collect()
+        };
+        let entries = js_sys::Object::new();
+        for (key, value) in self.retain_readable(mirrored, |(key, _)| key)? {
+            js_sys::Reflect::set(&entries, &JsValue::from_str(&key), &value)?;
+        }
+        let message = frame_message("snapshot")?;
+        js_sys::Reflect::set(&message, &JsValue::from_str("entries"), &entries)?;
+        let keys: js_sys::Array = mirror.keys.iter().map(|key| JsValue::from_str(key)).collect();
+        js_sys::Reflect::set(&message, &JsValue::from_str("keys"), &keys)?;
+        js_sys::Reflect::set(&message, &JsValue::from_str("readOnly"), &JsValue::from_bool(mirror.read_only))?;
+        post(target, &message, origin)
+    }
+
+    // Frame side: applies the host's snapshot and changes
+    fn mirror_host(&mut self, origin: &str, data: &JsValue) -> Result<(), JsValue> {
+        match field(data, PROTOCOL).as_string().as_deref() {
+            Some("snapshot") => {
+                *self.frame_mirror.lock().map_err(WasmStorageError::lock_poisoned)? = Some(FrameMirror {
+                    keys: string_list(&field(data, "keys")),
+                    read_only: field(data, "readOnly").as_bool().unwrap_or(true),
+                    host_origin: Some(origin.to_string()),
+                });
+                let entries = field(data, "entries").dyn_into::<js_sys::Object>().unwrap_or_default();
+                for entry in js_sys::Object::entries(&entries).iter() {
+                    let pair = js_sys::Array::from(&entry);
+                    apply_mirrored(self, pair.get(0).as_string().unwrap_or_default(), pair.get(1))?;
+                }
+                Ok(())
+            }
+            Some("change") => {
+                let key = field(data, "key").as_string().unwrap_or_default();
+                apply_mirrored(self, key, field(data, "value"))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn forward_to_host(&self, parent: &JsValue, event: &JsValue) -> Result<(), JsValue> {
+        let key = field(event, "key").as_string().unwrap_or_default();
+        let host_origin = {
+            let mirror = self.frame_mirror.lock().map_err(WasmStorageError::lock_poisoned)?;
+            match mirror.as_ref() {
+                Some(mirror) if !mirror.read_only && mirror.covers(&key) => mirror.host_origin.clone(),
+                _ => None,
+            }
+        };
+        let Some(host_origin) = host_origin else {
+            return Ok(());
+        };
+        let value = field(event, "value");
+        let value = if value.is_undefined() { JsValue::NULL } else { value };
+        let message = frame_message("set")?;
+        js_sys::Reflect::set(&message, &JsValue::from_str("key"), &JsValue::from_str(&key))?;
+        js_sys::Reflect::set(&message, &JsValue::from_str("value"), &value)?;
+        post(parent, &message, &host_origin)
+    }
+}
@@ -0,0 +1,133 @@
+use crate::error::{ErrorCode, WasmStorageError};
+use crate::origin::Origin;
+use crate::subscriptions::Subscription;
+use crate::WasmStorage;
+use wasm_bindgen::prelude::*;
+
+/// Whether local writes are currently refused.
+#[derive(Default)]
+pub(crate) struct Freeze {
+    frozen: bool,
+}
+
+impl Freeze {
+    pub(crate) fn new(frozen: bool) -> Freeze {
+        Freeze { frozen }
+    }
+}
+
+/// A handle on a whole store that can read and subscribe but has no
+/// write, configuration or lifecycle methods, from `read_only_view`.
+#[wasm_bindgen]
+pub struct ReadOnlyStore {
+    store: WasmStorage,
+}
+
+fn frozen_error(message: &str) -> JsValue {
+    WasmStorageError::new(ErrorCode::Frozen, message).into()
+}
+
+#[wasm_bindgen]
+impl WasmStorage {
+    /// Makes `set_state`, `remove_state`, `clear_state` and `dispatch` fail
+    /// with FROZEN until `unfreeze`. Writes the store makes itself
+    /// (hydration, replays, sync, expiry) still apply, so the state can be
+    /// restored or inspected while nothing else changes it.
+    #[wasm_bindgen]
+    pub fn freeze(&mut self) -> Result<(), JsValue> {
+        self.freeze.lock().map_err(WasmStorageError::lock_poisoned)?.frozen = true;
+        Ok(())
+    }
+
+    #[wasm_bindgen]
+    pub fn unfreeze(&mut self) -> Result<(), JsValue> {
+        self.freeze.lock().map_err(WasmStorageError::lock_poisoned)?.frozen = false;
+        Ok(())
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn frozen(&self) -> Result<bool, JsValue> {
+        Ok(self.freeze.lock().map_err(WasmStorageError::lock_poisoned)?.frozen)
+    }
+
+    /// A handle on the same state and listeners that only reads and
+    /// subscribes, for passing to plugins that must not change the store:
+    /// it has no way to write, add middleware, change settings or destroy
+    /// the store. Reads follow the store's access policies and
+    /// `clone_on_read`.
+    #[wasm_bindgen]
+    pub fn read_only_view(&self) -> ReadOnlyStore {
+        ReadOnlyStore { store: self.clone() }
+    }
+}
+
+#[wasm_bindgen]
+impl ReadOnlyStore {
+    #[wasm_bindgen]
+    pub fn get_state(&self, key: &str) -> Result<JsValue, JsValue> {
+        self.store.get_state(key)
+    }
+
+    #[wasm_bindgen]
+    pub fn get_many(&self, keys: js_sys::Array) -> Result<js_sys::Object, JsValue> {
+        self.store.get_many(keys)
+    }
+
+    #[wasm_bindgen]
+    pub fn get_all_state(&self) -> Result<JsValue, JsValue> {
+        self.store.get_all_state()
+    }
+
+    #[wasm_bindgen]
+    pub fn has(&self, key: &str) -> Result<bool, JsValue> {
+        self.store.has(key)
+    }
+
+    #[wasm_bindgen]
+    pub fn keys(&self) -> Result<js_sys::Array, JsValue> {
+        self.store.keys()
+    }
+
+    #[wasm_bindgen]
+    pub fn values(&self) -> Result<js_sys::Array, JsValue> {
+        self.store.values()
+    }
+
+    #[wasm_bindgen]
+    pub fn entries(&self, prefix: Option<String>) -> Result<js_sys::Array, JsValue> {
+        self.store.entries(prefix)
+    }
+
+    #[wasm_bindgen]
+    pub fn len(&self) -> Result<u32, JsValue> {
+        self.store.len()
+    }
+
+    #[wasm_bindgen]
+    pub fn is_empty(&self) -> Result<bool, JsValue> {
+        self.store.is_empty()
+    }
+
+    /// Whether the store itself is frozen; the view never writes either way.
+    #[wasm_bindgen(getter)]
+    pub fn frozen(&self) -> Result<bool, JsValue> {
+        self.store.frozen()
+    }
+
+    /// The store's `subscribe`, with the same options.
+    #[wasm_bindgen]
+    pub fn subscribe(&mut self, callback: js_sys::Function, options: Option<js_sys::Object>) -> Result<Subscription, JsValue> {
+        self.store.subscribe(callback, options)
+    }
+}
+
+impl WasmStorage {
+    /// Rejects a local write while the store is frozen.
+    pub(crate) fn ensure_writable(&self) -> Result<(), JsValue> {
+        let frozen = self.freeze.lock().map_err(WasmStorageError::lock_poisoned)?.frozen;
+        if frozen && self.current_origin()? == Origin::Local {
+            return Err(frozen_error("The store is frozen; call unfreeze() to write"));
+        }
+        Ok(())
+    }
+}
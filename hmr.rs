@@ -0,0 +1,90 @@
+use crate::origin::Origin;
+use crate::WasmStorage;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+
+// Global object holding stashed stores by slot name; it outlives module instances
+const HMR_GLOBAL: &str = "__WASM_STORAGE_HMR__";
+const DEFAULT_SLOT: &str = "default";
+
+fn hmr_slots() -> Result<js_sys::Object, JsValue> {
+    let global = js_sys::global();
+    let slots = js_sys::Reflect::get(&global, &JsValue::from_str(HMR_GLOBAL))?;
+    if let Some(slots) = slots.dyn_ref::<js_sys::Object>() {
+        return Ok(slots.clone());
+    }
+    let slots = js_sys::Object::new();
+    js_sys::Reflect::set(&global, &JsValue::from_str(HMR_GLOBAL), &slots)?;
+    Ok(slots)
+}
+
+#[wasm_bindgen]
+impl WasmStorage {
+    /// Stashes the state in a global slot for `resume_from_hmr` in the next
+    /// module instance. Options: `slot` (default `"default"`, one per store)
+    /// and `history` (also keep the change log sequence numbers, so
+    /// incremental exports continue where they left off).
+    #[wasm_bindgen]
+    pub fn preserve_for_hmr(&self, options: Option<js_sys::Object>) -> Result<(), JsValue> {
+        let options = options.map(JsValue::from).unwrap_or(JsValue::UNDEFINED);
+        let slot = js_sys::Reflect::get(&options, &JsValue::from_str("slot"))
+            .ok()
+            .and_then(|slot| slot.as_string())
+            .unwrap_or_else(|| DEFAULT_SLOT.to_string());
+        let with_history = js_sys::Reflect::get(&options, &JsValue::from_str("history"))?.is_truthy();
+
+        let state = js_sys::Object::new();
+        for (key, value) in self.snapshot_entries()? {
+            js_sys::Reflect::set(&state, &JsValue::from_str(&key), &value)?;
+        }
+        let stash = js_sys::Object::new();
+        js_sys::Reflect::set(&stash, &JsValue::from_str("state"), &state)?;
+        if with_history {
+            let history = self.export_changelog()?;
+            js_sys::Reflect::set(&stash, &JsValue::from_str("history"), &history)?;
+        }
+        js_sys::Reflect::set(&stash, &JsValue::from_str("preservedAt"), &JsValue::from_f64(js_sys::Date::now()))?;
+
+        let slots = hmr_slots()?;
+        js_sys::Reflect::set(&slots, &JsValue::from_str(&slot), &stash)?;
+        log_info!("Preserved {} keys for HMR in slot {}", state_len(&state), slot);
+        Ok(())
+    }
+
+    /// Creates a store from the state stashed by `preserve_for_hmr`, or an
+    /// empty one when `slot` holds nothing (first load). The slot is consumed.
+    #[wasm_bindgen]
+    pub fn resume_from_hmr(slot: Option<String>) -> Result<WasmStorage, JsValue> {
+        let mut store = WasmStorage::new(None);
+        let slot = slot.unwrap_or_else(|| DEFAULT_SLOT.to_string());
+        let slots = hmr_slots()?;
+        let stash = js_sys::Reflect::get(&slots, &JsValue::from_str(&slot))?;
+        if stash.is_undefined() || stash.is_null() {
+            return Ok(store);
+        }
+        js_sys::Reflect::delete_property(&slots, &JsValue::from_str(&slot))?;
+
+        let state = js_sys::Reflect::get(&stash, &JsValue::from_str("state"))?;
+        if let Some(state) = state.dyn_ref::<js_sys::Object>() {
+            store.with_origin(Origin::Hydration, |store| {
+                for entry in js_sys::Object::entries(state).iter() {
+                    let pair = js_sys::Array::from(&entry);
+                    store.set_state(&pair.get(0).as_string().unwrap_or_default(), pair.get(1))?;
+                }
+                Ok(())
+            })?;
+        }
+        let history = js_sys::Reflect::get(&stash, &JsValue::from_str("history"))?;
+        if !history.is_undefined() {
+            store.import_changelog(&history)?;
+        }
+
+        log_info!("Resumed HMR state from slot {}", slot);
+        store.finish_hydration("hmr")?;
+        Ok(store)
+    }
+}
+
+fn state_len(state: &js_sys::Object) -> u32 {
+    js_sys::Object::keys(state).length()
+}
@@ -0,0 +1,87 @@
+use crate::error::WasmStorageError;
+use wasm_bindgen::prelude::*;
+
+const CROCKFORD_BASE32: &[u8; 32] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+const NANOID_ALPHABET: &[u8; 64] = b"useandom-26T198340PX75pxJACKVERYMINDBUSHWOLF_GQZbfghjklqvwyzrict";
+const NANOID_LENGTH: usize = 21;
+
+/// Strategy used for every ID the store hands out (action IDs, sync instance
+/// IDs, ...). `Custom` receives the scope name so tests can return fixed values.
+pub(crate) enum IdGenerator {
+    Uuid,
+    Ulid,
+    NanoId,
+    Custom(js_sys::Function),
+}
+
+impl IdGenerator {
+    pub(crate) fn from_js(spec: &JsValue) -> Result<IdGenerator, JsValue> {
+        if let Some(callback) = spec.dyn_ref::<js_sys::Function>() {
+            return Ok(IdGenerator::Custom(callback.clone()));
+        }
+        match spec.as_string().as_deref() {
+            Some("uuid") => Ok(IdGenerator::Uuid),
+            Some("ulid") => Ok(IdGenerator::Ulid),
+            Some("nanoid") => Ok(IdGenerator::NanoId),
+            _ => Err(WasmStorageError::invalid_argument("ID generator must be \"uuid\", \"ulid\", \"nanoid\" or a function").into()),
+        }
+    }
+
+    pub(crate) fn generate(&self, scope: &str, now_ms: f64) -> Result<String, JsValue> {
+        match self {
+            IdGenerator::Uuid => Ok(uuid_v4()),
+            IdGenerator::Ulid => Ok(ulid(now_ms)),
+            IdGenerator::NanoId => Ok(nanoid()),
+            IdGenerator::Custom(callback) => {
+                let id = callback.call1(&JsValue::NULL, &JsValue::from_str(scope))?;
+                id.as_string()
+                    .or_else(|| id.as_f64().map(|n| n.to_string()))
+                    .ok_or_else(|| WasmStorageError::invalid_argument("Custom ID generator must return a string or number").into())
+            }
+        }
+    }
+}
+
+fn random_bytes<const N: usize>() -> [u8; N] {
+    let mut bytes = [0u8; N];
+    for byte in bytes.iter_mut() {
+        *byte = (js_sys::Math::random() * 256.0) as u8;
+    }
+    bytes
+}
+
+fn uuid_v4() -> String {
+    let mut bytes = random_bytes::<16>();
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+
+    let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    format!("{}-{}-{}-{}-{}", &hex[0..8], &hex[8..12], &hex[12..16], &hex[16..20], &hex[20..32])
+}
+
+fn ulid(now_ms: f64) -> String {
+    let mut chars = [0u8; 26];
+
+    // 48-bit millisecond timestamp in the first 10 characters
+    let mut time = now_ms as u64;
+    for slot in chars[..10].iter_mut().rev() {
+        *slot = CROCKFORD_BASE32[(time % 32) as usize];
+        time /= 32;
+    }
+
+    // 80 bits of randomness in the remaining 16
+    let mut randomness = random_bytes::<10>().iter().fold(0u128, |acc, b| (acc << 8) | *b as u128);
+    for slot in chars[10..].iter_mut().rev() {
+        *slot = CROCKFORD_BASE32[(randomness % 32) as usize];
+        randomness /= 32;
+    }
+
+    String::from_utf8_lossy(&chars).into_owned()
+}
+
+fn nanoid() -> String {
+    random_bytes::<NANOID_LENGTH>()
+        .iter()
+        .map(|b| NANOID_ALPHABET[(b & 63) as usize] as char)
+        .collect()
+}
@@ -0,0 +1,261 @@
+use crate::delivery::{schedule_delivery, Delivery};
+use crate::error::WasmStorageError;
+use crate::error_handler::ErrorSource;
+use crate::origin::Origin;
+use crate::{glob_match, WasmStorage};
+use std::collections::VecDeque;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+
+const DEFAULT_BATCH_SIZE: usize = 50;
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub(crate) enum InboxPriority {
+    // User-visible data
+    High,
+    Normal,
+    // Telemetry and other background data
+    Low,
+}
+
+impl InboxPriority {
+    fn parse(priority: &str) -> Result<InboxPriority, JsValue> {
+        match priority {
+            "high" => Ok(InboxPriority::High),
+            "normal" => Ok(InboxPriority::Normal),
+            "low" => Ok(InboxPriority::Low),
+            _ => Err(WasmStorageError::invalid_argument(format!(
+                "Unknown inbox priority \"{}\" (expected high, normal or low)",
+                priority
+            )).into()),
+        }
+    }
+}
+
+pub(crate) type RemoteApply = Box<dyn FnOnce(&mut WasmStorage) -> Result<(), JsValue>>;
+
+struct InboxItem {
+    // Key or action type, for priority matching and error reports
+    target: String,
+    enqueued_at: f64,
+    apply: RemoteApply,
+}
+
+/// Remote changes waiting to be applied, one queue per priority.
+pub(crate) struct Inbox {
+    batch_size: usize,
+    max_queued: Option<usize>,
+    priorities: Vec<(String, InboxPriority)>,
+    queues: [VecDeque<InboxItem>; 3],
+    scheduled: bool,
+    processed: u64,
+    dropped: u64,
+    batches: u64,
+    high_watermark: usize,
+    last_batch_ms: f64,
+}
+
+impl Inbox {
+    fn queued(&self) -> usize {
+        self.queues.iter().map(VecDeque::len).sum()
+    }
+
+    fn priority_for(&self, target: &str) -> InboxPriority {
+        self.priorities
+            .iter()
+            .find(|(pattern, _)| glob_match(pattern, target))
+            .map_or(InboxPriority::Normal, |(_, priority)| *priority)
+    }
+
+    fn next(&mut self) -> Option<InboxItem> {
+        self.queues.iter_mut().find_map(VecDeque::pop_front)
+    }
+}
+
+#[wasm_bindgen]
+impl WasmStorage {
+    /// Buffers remote changes (peer sync and `receive_remote`) and applies
+    /// them in batches of `batchSize` (default 50) per animation frame,
+    /// highest priority first. `priorities` maps key or action-type globs to
+    /// `"high"`, `"normal"` (default) or `"low"`. With `maxQueued`, the oldest
+    /// lowest-priority items are dropped once the inbox is full.
+    #[wasm_bindgen]
+    pub fn enable_inbox(&mut self, options: Option<js_sys::Object>) -> Result<(), JsValue> {
+        let options = options.map(JsValue::from).unwrap_or(JsValue::UNDEFINED);
+        let option = |name: &str| js_sys::Reflect::get(&options, &JsValue::from_str(name)).unwrap_or(JsValue::UNDEFINED);
+
+        let mut priorities = Vec::new();
+        if let Some(configured) = option("priorities").dyn_ref::<js_sys::Object>() {
+            for entry in js_sys::Object::entries(configured).iter() {
+                let pair = js_sys::Array::from(&entry);
+                let priority = InboxPriority::parse(&pair.get(1).as_string().unwrap_or_default())?;
+                priorities.push((pair.get(0).as_string().unwrap_or_default(), priority));
+            }
+        }
+
+        self.flush_inbox()?;
+        *self.inbox.lock().map_err(WasmStorageError::lock_poisoned)? = Some(Inbox {
+            batch_size: option("batchSize").as_f64().map_or(DEFAULT_BATCH_SIZE, |size| (size as usize).max(1)),
+            max_queued: option("maxQueued").as_f64().map(|max| max as usize),
+            priorities,
+            queues: Default::default(),
+            scheduled: false,
+            processed: 0,
+            dropped: 0,
+            batches: 0,
+            high_watermark: 0,
+            last_batch_ms: 0.0,
+        });
+        Ok(())
+    }
+
+    /// Applies everything still queued, then applies remote changes directly again.
+    #[wasm_bindgen]
+    pub fn disable_inbox(&mut self) -> Result<(), JsValue> {
+        self.flush_inbox()?;
+        *self.inbox.lock().map_err(WasmStorageError::lock_poisoned)? = None;
+        Ok(())
+    }
+
+    /// Entry point for JS sync adapters: dispatches a remote action through
+    /// the inbox (or right away when it is disabled) with origin `"sync"`.
+    /// `priority` overrides the configured priority for `action_type`.
+    #[wasm_bindgen]
+    pub fn receive_remote(&mut self, action_type: &str, payload: JsValue, priority: Option<String>) -> Result<(), JsValue> {
+        let priority = priority.map(|priority| InboxPriority::parse(&priority)).transpose()?;
+        let action = action_type.to_string();
+        self.accept_remote(action_type, priority, Box::new(move |store| store.dispatch(&action, payload).map(|_| ())))
+    }
+
+    /// Applies all queued remote changes now, e.g. before reading state in a test.
+    #[wasm_bindgen]
+    pub fn flush_inbox(&mut self) -> Result<(), JsValue> {
+        while self.process_inbox(usize::MAX)? {}
+        Ok(())
+    }
+
+    /// Backpressure metrics: `{ queued, high, normal, low, processed, dropped,
+    /// batches, highWatermark, lastBatchMs, oldestWaitMs }`.
+    #[wasm_bindgen]
+    pub fn inbox_metrics(&self) -> Result<js_sys::Object, JsValue> {
+        let inbox = self.inbox.lock().map_err(WasmStorageError::lock_poisoned)?;
+        let inbox = inbox.as_ref().ok_or_else(|| WasmStorageError::invalid_state("The inbox is not enabled"))?;
+        let now = js_sys::Date::now();
+        let oldest = inbox
+            .queues
+            .iter()
+            .filter_map(|queue| queue.front().map(|item| item.enqueued_at))
+            .fold(now, f64::min);
+
+        let metrics = js_sys::Object::new();
+        let set = |name: &str, value: f64| js_sys::Reflect::set(&metrics, &JsValue::from_str(name), &JsValue::from_f64(value));
+        set("queued", inbox.queued() as f64)?;
+        set("high", inbox.queues[0].len() as f64)?;
+        set("normal", inbox.queues[1].len() as f64)?;
+        set("low", inbox.queues[2].len() as f64)?;
+        set("processed", inbox.processed as f64)?;
+        set("dropped", inbox.dropped as f64)?;
+        set("batches", inbox.batches as f64)?;
+        set("highWatermark", inbox.high_watermark as f64)?;
+        set("lastBatchMs", inbox.last_batch_ms)?;
+        set("oldestWaitMs", now - oldest)?;
+        Ok(metrics)
+    }
+}
+
+impl WasmStorage {
+    /// Queues a remote change for `target` (a key or action type), or applies
+    /// it immediately with origin `"sync"` when the inbox is disabled.
+    pub(crate) fn accept_remote(&mut self, target: &str, priority: Option<InboxPriority>, apply: RemoteApply) -> Result<(), JsValue> {
+        let schedule = {
+            let mut guard = self.inbox.lock().map_err(WasmStorageError::lock_poisoned)?;
+            let Some(inbox) = guard.as_mut() else {
+                drop(guard);
+                return self.with_origin(Origin::Sync, apply);
+            };
+            let priority = priority.unwrap_or_else(|| inbox.priority_for(target));
+            inbox.queues[priority as usize].push_back(InboxItem {
+                target: target.to_string(),
+                enqueued_at: js_sys::Date::now(),
+                apply,
+            });
+            if let Some(max) = inbox.max_queued {
+                while inbox.queued() > max {
+                    if let Some(queue) = inbox.queues.iter_mut().rev().find(|queue| !queue.is_empty()) {
+                        queue.pop_front();
+                        inbox.dropped += 1;
+                    }
+                }
+            }
+            inbox.high_watermark = inbox.high_watermark.max(inbox.queued());
+            !std::mem::replace(&mut inbox.scheduled, true)
+        };
+        if schedule {
+            self.schedule_inbox()?;
+        }
+        Ok(())
+    }
+
+    fn schedule_inbox(&self) -> Result<(), JsValue> {
+        let mut store = self.clone();
+        let process = Closure::once_into_js(move || {
+            let batch_size = match store.inbox.lock() {
+                Ok(mut inbox) => match inbox.as_mut() {
+                    Some(inbox) => {
+                        inbox.scheduled = false;
+                        inbox.batch_size
+                    }
+                    None => return,
+                },
+                Err(_) => return,
+            };
+            match store.process_inbox(batch_size) {
+                Ok(true) => {
+                    let reschedule = match store.inbox.lock() {
+                        Ok(mut inbox) => inbox.as_mut().is_some_and(|inbox| !std::mem::replace(&mut inbox.scheduled, true)),
+                        Err(_) => false,
+                    };
+                    if reschedule {
+                        if let Err(e) = store.schedule_inbox() {
+                            log_warn!("Failed to schedule inbox batch: {:?}", e);
+                        }
+                    }
+                }
+                Ok(false) => {}
+                Err(e) => log_warn!("Inbox batch failed: {:?}", e),
+            }
+        });
+        schedule_delivery(Delivery::Frame, &process)
+    }
+
+    // Applies up to `limit` items; returns true if more are waiting
+    fn process_inbox(&mut self, limit: usize) -> Result<bool, JsValue> {
+        let started = js_sys::Date::now();
+        let mut applied = 0;
+        while applied < limit {
+            let item = match self.inbox.lock().map_err(WasmStorageError::lock_poisoned)?.as_mut() {
+                Some(inbox) => inbox.next(),
+                None => return Ok(false),
+            };
+            let Some(item) = item else {
+                break;
+            };
+            if let Err(error) = self.with_origin(Origin::Sync, item.apply) {
+                let source = ErrorSource { source: "inbox", key: Some(&item.target), action: None, timestamp: js_sys::Date::now() };
+                self.report_error(&error, source)?;
+            }
+            applied += 1;
+        }
+
+        let mut guard = self.inbox.lock().map_err(WasmStorageError::lock_poisoned)?;
+        let Some(inbox) = guard.as_mut() else {
+            return Ok(false);
+        };
+        if applied > 0 {
+            inbox.processed += applied as u64;
+            inbox.batches += 1;
+            inbox.last_batch_ms = js_sys::Date::now() - started;
+        }
+        Ok(inbox.queued() > 0)
+    }
+}
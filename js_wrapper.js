@@ -1,9 +1,8 @@
 // wasm-storage.js - Main wrapper
 class WasmStorageWrapper {
-    constructor(wasmModule) {
-        this.storage = new wasmModule.WasmStorage();
-        this.subscriptions = new Map();
-        this.subscriptionId = 0;
+    constructor(wasmModule, storage) {
+        this.wasmModule = wasmModule;
+        this.storage = storage || new wasmModule.WasmStorage();
     }
 
     // Core API
@@ -15,31 +14,560 @@ class WasmStorageWrapper {
         return this.storage.get_state(key);
     }
 
+    // Only fills unset keys, now and after every hydration
+    setDefaults(defaults) {
+        return this.storage.set_defaults(defaults);
+    }
+
+    // Sets the key to defaultValue first when it is unset
+    getOrInsert(key, defaultValue) {
+        return this.storage.get_or_insert(key, defaultValue);
+    }
+
+    // factory(key) only runs when the key is unset
+    getOrInsertWith(key, factory) {
+        return this.storage.get_or_insert_with(key, factory);
+    }
+
+    // { values: { [key]: value }, missing: [key, ...] } in one call
+    getMany(keys) {
+        return this.storage.get_many(keys);
+    }
+
+    // A structuredClone copy, safe to mutate
+    getStateCloned(key) {
+        return this.storage.get_state_cloned(key);
+    }
+
+    // Typed reads; throw a WasmStorageError (KEY_MISSING / TYPE_MISMATCH) otherwise
+    getString(key) {
+        return this.storage.get_string(key);
+    }
+
+    getNumber(key) {
+        return this.storage.get_number(key);
+    }
+
+    getBool(key) {
+        return this.storage.get_bool(key);
+    }
+
+    getObject(key) {
+        return this.storage.get_object(key);
+    }
+
     getAllState() {
         return this.storage.get_all_state();
     }
 
+    // Proxy over the state: reads call getState, writes (even nested) call setState
+    getLiveProxy() {
+        return this.storage.get_live_proxy();
+    }
+
+    get state() {
+        return this.storage.get_live_proxy();
+    }
+
+    keys() {
+        return this.storage.keys();
+    }
+
+    values() {
+        return this.storage.values();
+    }
+
+    entries(prefix) {
+        return this.storage.entries(prefix);
+    }
+
+    has(key) {
+        return this.storage.has(key);
+    }
+
+    len() {
+        return this.storage.len();
+    }
+
+    isEmpty() {
+        return this.storage.is_empty();
+    }
+
+    getByPrefix(prefix, asArray = false) {
+        return this.storage.get_by_prefix(prefix, asArray);
+    }
+
+    queryKeys(pattern, asArray = false) {
+        return this.storage.query_keys(pattern, asArray);
+    }
+
+    enableOrderedIndex() {
+        return this.storage.enable_ordered_index();
+    }
+
+    disableOrderedIndex() {
+        return this.storage.disable_ordered_index();
+    }
+
+    range(startKey, endKey, limit) {
+        return this.storage.range(startKey, endKey, limit);
+    }
+
+    query(filterFn, asArray = false) {
+        return this.storage.query(filterFn, asArray);
+    }
+
+    find(filterFn) {
+        return this.storage.find(filterFn);
+    }
+
+    // Returns the StorageEvent: { action_type, payload, timestamp } getters, toJSON()
     dispatch(actionType, payload = null) {
         return this.storage.dispatch(actionType, payload);
     }
 
-    subscribe(callback) {
-        const id = this.subscriptionId++;
-        const wasmIndex = this.storage.subscribe(callback);
-        this.subscriptions.set(id, wasmIndex);
-        return id;
+    // callback({ type, payload, timestamp }) for actions no reducer or effect handles
+    onUnhandledAction(callback) {
+        return this.storage.on_unhandled_action(callback);
     }
 
-    unsubscribe(id) {
-        const wasmIndex = this.subscriptions.get(id);
-        if (wasmIndex !== undefined) {
-            this.storage.unsubscribe(wasmIndex);
-            this.subscriptions.delete(id);
-        }
+    // 'ignore' (default) | 'error' | 'legacy' (store the payload under __actions_<type>)
+    setUnhandledActions(mode) {
+        return this.storage.set_unhandled_actions(mode);
+    }
+
+    // Returns the frozen { TYPE: 'TYPE' } constants of the built-in and registered types
+    registerActionTypes(types) {
+        return this.storage.register_action_types(types);
+    }
+
+    // Unregistered action types throw INVALID_ARGUMENT instead of going unhandled
+    setStrictActionTypes(enabled) {
+        return this.storage.set_strict_action_types(enabled);
+    }
+
+    get actionTypes() {
+        return this.storage.action_types;
+    }
+
+    // Same, with event.changes: { [key]: newValue } for every key the action changed
+    dispatchWithChanges(actionType, payload = null) {
+        return this.storage.dispatch_with_changes(actionType, payload);
+    }
+
+    // producer(signal) returns the payload or a promise of it; a newer call with the
+    // same options.key (default: actionType) cancels this one. Resolves null when cancelled
+    dispatchAsync(actionType, producer, options = undefined) {
+        return this.storage.dispatch_async(actionType, producer, options);
+    }
+
+    cancelAsync(key) {
+        return this.storage.cancel_async(key);
+    }
+
+    pendingAsync() {
+        return this.storage.pending_async();
+    }
+
+    // Returns a handle with cancel() and an active getter
+    dispatchAfter(ms, actionType, payload = null) {
+        return this.storage.dispatch_after(ms, actionType, payload);
+    }
+
+    // Repeats every ms until the handle is cancelled or the store is destroyed
+    dispatchInterval(ms, actionType, payload = null) {
+        return this.storage.dispatch_interval(ms, actionType, payload);
+    }
+
+    // Applies the action now and rolls it back if confirmPromise rejects
+    dispatchOptimistic(actionType, payload, confirmPromise) {
+        return this.storage.dispatch_optimistic(actionType, payload, Promise.resolve(confirmPromise));
+    }
+
+    pendingOptimistic() {
+        return this.storage.pending_optimistic();
+    }
+
+    // effectFn(action, dispatch, getState, signal) after matching actions;
+    // options: { mode: 'latest' (default) | 'every' }
+    registerEffect(pattern, effectFn, options) {
+        return this.storage.register_effect(pattern, effectFn, options);
     }
 
-    addMiddleware(middlewareFn) {
-        return this.storage.add_middleware(middlewareFn);
+    unregisterEffect(id) {
+        return this.storage.unregister_effect(id);
+    }
+
+    runningEffects() {
+        return this.storage.running_effects();
+    }
+
+    // Returns a Subscription handle; options: { signal, origins, aggregate, delivery, throttle, immediate, priority }
+    subscribe(callback, options) {
+        return this.storage.subscribe(callback, options);
+    }
+
+    // callback(value, previous, event) when comparator(previous, value) is truthy;
+    // options: { immediate }
+    watch(key, comparator, callback, options) {
+        return this.storage.watch(key, comparator, callback, options);
+    }
+
+    // Promise of the key's next value (that passes predicate(value, event))
+    once(key, predicate) {
+        return this.storage.once(key, predicate);
+    }
+
+    // Like once, but resolves at once if the current value passes (default: not null)
+    waitFor(key, predicate) {
+        return this.storage.wait_for(key, predicate);
+    }
+
+    // listener(selected, previous) when selector(state) changes; options.equalityFn compares
+    subscribeWithSelector(selector, listener, options) {
+        return this.storage.subscribe_with_selector(selector, listener, options);
+    }
+
+    // State as first read, for useSyncExternalStore's getServerSnapshot
+    getServerSnapshot() {
+        return this.storage.get_server_snapshot();
+    }
+
+    // EventTarget surface: 'change' and 'change:<key>' CustomEvents, change in event.detail
+    addEventListener(type, listener, options) {
+        return this.storage.add_event_listener(type, listener, options);
+    }
+
+    removeEventListener(type, listener, options) {
+        return this.storage.remove_event_listener(type, listener, options);
+    }
+
+    dispatchEvent(event) {
+        return this.storage.dispatch_event(event);
+    }
+
+    // Buffer remote changes and apply them in prioritized batches per frame;
+    // options: { batchSize, maxQueued, priorities: { 'telemetry.*': 'low' } }
+    enableInbox(options) {
+        return this.storage.enable_inbox(options);
+    }
+
+    disableInbox() {
+        return this.storage.disable_inbox();
+    }
+
+    // For JS sync adapters: dispatch a remote action through the inbox
+    receiveRemote(actionType, payload, priority) {
+        return this.storage.receive_remote(actionType, payload, priority);
+    }
+
+    flushInbox() {
+        return this.storage.flush_inbox();
+    }
+
+    inboxMetrics() {
+        return this.storage.inbox_metrics();
+    }
+
+    // Tags writes made inside callback with origin ('local', 'sync', 'hydration', 'replay', 'system')
+    runWithOrigin(origin, callback) {
+        return this.storage.run_with_origin(origin, callback);
+    }
+
+    // 'reference' (default), 'deep' or 'always'
+    setChangeDetection(mode) {
+        return this.storage.set_change_detection(mode);
+    }
+
+    // Coalesce synchronous writes into one notification per key on the next microtask
+    setBatching(enabled) {
+        return this.storage.set_batching(enabled);
+    }
+
+    flushSync() {
+        return this.storage.flush_sync();
+    }
+
+    // Accepts a Subscription handle or its numeric id
+    unsubscribe(subscription) {
+        const id = typeof subscription === 'string' ? subscription : subscription.id;
+        return this.storage.unsubscribe(id);
+    }
+
+    // Async iterator of change events: for await (const change of storage.changes())
+    changes() {
+        return this.storage.changes();
+    }
+
+    // The same change events as a ReadableStream
+    changesStream() {
+        const iterator = this.storage.changes();
+        return new ReadableStream({
+            async pull(controller) {
+                const { value, done } = await iterator.next();
+                if (done) {
+                    controller.close();
+                } else {
+                    controller.enqueue(value);
+                }
+            },
+            cancel() {
+                return iterator.return();
+            }
+        });
+    }
+
+    // Event sourcing: log every dispatched action and rebuild state from the log;
+    // options: { backend, logKey }
+    enableEventSourcing(options) {
+        return this.storage.enable_event_sourcing(options);
+    }
+
+    replay() {
+        return this.storage.replay();
+    }
+
+    compactToSnapshot() {
+        return this.storage.compact_to_snapshot();
+    }
+
+    // Drops the actions after seq and rewinds the state to it
+    truncateEventLog(seq) {
+        return this.storage.truncate_event_log(seq);
+    }
+
+    // [{ seq, type, payload, timestamp }] after fromSeq
+    eventLog(fromSeq) {
+        return this.storage.event_log(fromSeq);
+    }
+
+    // Expression reducers, e.g. 'increment cart.count by payload.qty; merge cart.items = payload.items'
+    registerReducer(actionType, expression) {
+        return this.storage.register_reducer(actionType, expression);
+    }
+
+    unregisterReducer(actionType) {
+        return this.storage.unregister_reducer(actionType);
+    }
+
+    // Codec applied when persisting/exporting: 'base64', 'date' or { encode, decode }
+    registerCodec(pattern, codec) {
+        return this.storage.register_codec(pattern, codec);
+    }
+
+    unregisterCodec(pattern) {
+        return this.storage.unregister_codec(pattern);
+    }
+
+    // Validate writes to a key or glob with a function (value, key) or a JSON Schema object
+    registerValidator(pattern, validator) {
+        return this.storage.register_validator(pattern, validator);
+    }
+
+    unregisterValidator(pattern) {
+        return this.storage.unregister_validator(pattern);
+    }
+
+    // { key: jsonSchema } validators that typeDefinitions() turns into TS types
+    defineSchema(schema) {
+        return this.storage.define_schema(schema);
+    }
+
+    typeDefinitions() {
+        return this.storage.type_definitions();
+    }
+
+    // Restrict a key to { states, transitions: { from: [to, ...] }, initial }
+    defineStateMachine(key, definition) {
+        return this.storage.define_state_machine(key, definition);
+    }
+
+    removeStateMachine(key) {
+        return this.storage.remove_state_machine(key);
+    }
+
+    allowedTransitions(key) {
+        return this.storage.allowed_transitions(key);
+    }
+
+    // Console group per action; options: { level, collapsed, diff }
+    enableLogger(options) {
+        return this.storage.enable_logger(options);
+    }
+
+    disableLogger() {
+        return this.storage.disable_logger();
+    }
+
+    // 'debug' | 'info' | 'warn' | 'error' | 'silent', for every store of the module
+    setLogLevel(level) {
+        return this.wasmModule.set_log_level(level);
+    }
+
+    // sink({ level, message, data }) replaces the console; null restores it
+    setLogger(sink) {
+        return this.wasmModule.set_logger(sink);
+    }
+
+    // { panicHook = true, verbose }: readable panics, debug logging, storeId on errors
+    initDiagnostics(options) {
+        return this.wasmModule.init_diagnostics(options);
+    }
+
+    get instanceId() {
+        return this.storage.instance_id;
+    }
+
+    // Set for stores from createWasmStorage(path, { name }); null otherwise
+    get name() {
+        return this.storage.name ?? null;
+    }
+
+    // Names of the module's live named stores
+    listInstances() {
+        return this.wasmModule.WasmStorage.list_instances();
+    }
+
+    // An isolated copy-on-write scratch store; nothing reaches this one until mergeFrom
+    fork() {
+        return new WasmStorageWrapper(this.wasmModule, this.storage.fork());
+    }
+
+    // strategy: 'theirs' (default) | 'ours' | 'fail' | (key, ours, theirs, base) => value
+    mergeFrom(child, strategy) {
+        return this.storage.merge_from(child.storage, strategy);
+    }
+
+    // Middleware receives (action, context); context: { getState, dispatch, metrics, logger }.
+    // Returns an ID; options: { name, priority, enabled }
+    addMiddleware(middlewareFn, options) {
+        return this.storage.add_middleware(middlewareFn, options);
+    }
+
+    insertMiddleware(position, middlewareFn, options) {
+        return this.storage.insert_middleware(position, middlewareFn, options);
+    }
+
+    // By ID or name
+    removeMiddleware(handle) {
+        return this.storage.remove_middleware(handle);
+    }
+
+    setMiddlewareEnabled(handle, enabled) {
+        return this.storage.set_middleware_enabled(handle, enabled);
+    }
+
+    listMiddleware() {
+        return this.storage.list_middleware();
+    }
+
+    middlewareMetrics() {
+        return this.storage.middleware_metrics();
+    }
+
+    // handler(error, { source, key, action, timestamp }) for listener/middleware exceptions
+    setErrorHandler(handler) {
+        return this.storage.set_error_handler(handler);
+    }
+
+    // Transient state dropped on navigation, visibility or session boundaries
+    setTransient(key, value, scope) {
+        return this.storage.set_transient(key, value, scope);
+    }
+
+    isTransient(key) {
+        return this.storage.is_transient(key);
+    }
+
+    endTransientScope(scope) {
+        return this.storage.end_transient_scope(scope);
+    }
+
+    // Dirty tracking for "unsaved changes" prompts
+    trackDirty(baselineSnapshot) {
+        return this.storage.track_dirty(baselineSnapshot);
+    }
+
+    dirtyKeys() {
+        return this.storage.dirty_keys();
+    }
+
+    isDirty() {
+        return this.storage.is_dirty();
+    }
+
+    markClean() {
+        return this.storage.mark_clean();
+    }
+
+    // Cascade changes of a parent key; options: { strategy, refetch }
+    dependsOn(parent, dependents, options) {
+        return this.storage.depends_on(parent, dependents, options);
+    }
+
+    removeDependency(parent, dependent) {
+        return this.storage.remove_dependency(parent, dependent);
+    }
+
+    isInvalidated(key) {
+        return this.storage.is_invalidated(key);
+    }
+
+    // Keep key equal to compute(...depValues), recomputed when a dependency changes
+    defineComputed(key, deps, compute) {
+        return this.storage.define_computed(key, deps, compute);
+    }
+
+    removeComputed(key) {
+        return this.storage.remove_computed(key);
+    }
+
+    // Capture the state and every dispatched action as a JSON fixture
+    startRecording() {
+        return this.storage.start_recording();
+    }
+
+    stopRecording() {
+        return this.storage.stop_recording();
+    }
+
+    isRecording() {
+        return this.storage.is_recording();
+    }
+
+    // Replace the state with a fixture (object or JSON text) and replay its actions
+    loadFixture(fixture) {
+        return this.storage.load_fixture(fixture);
+    }
+
+    // Clock for action and change timestamps: () => ms, or null for Date.now()
+    setTimeProvider(provider) {
+        return this.storage.set_time_provider(provider);
+    }
+
+    now() {
+        return this.storage.now();
+    }
+
+    setIdGenerator(generator) {
+        return this.storage.set_id_generator(generator);
+    }
+
+    generateId(scope) {
+        return this.storage.generate_id(scope);
+    }
+
+    // Stores the entity as "<collection>:<id>" and returns the generated ID
+    addEntity(collection, entity) {
+        return this.storage.add_entity(collection, entity);
+    }
+
+    getEntities(collection) {
+        return this.storage.get_entities(collection);
+    }
+
+    removeEntity(collection, id) {
+        return this.storage.remove_entity(collection, id);
     }
 
     clear() {
@@ -49,6 +577,350 @@ class WasmStorageWrapper {
     remove(key) {
         return this.storage.remove_state(key);
     }
+
+    // Rate-limit writes to a noisy key: { debounceMs } or { throttleMs }; null removes the policy
+    setKeyPolicy(key, policy) {
+        return this.storage.set_key_policy(key, policy);
+    }
+
+    flushKey(key) {
+        return this.storage.flush_key(key);
+    }
+
+    // Runs after persistence hydration or a restore completes (immediately if already done)
+    onInit(callback) {
+        return this.storage.on_init(callback);
+    }
+
+    onDestroy(callback) {
+        return this.storage.on_destroy(callback);
+    }
+
+    // Flushes pending writes, persists, disconnects sync and drops all listeners
+    destroy() {
+        return this.storage.destroy();
+    }
+
+    isDestroyed() {
+        return this.storage.destroyed;
+    }
+
+    // Deep-freeze stored values so in-place mutation of read results throws
+    enableStrictMode() {
+        return this.storage.enable_strict_mode();
+    }
+
+    disableStrictMode() {
+        return this.storage.disable_strict_mode();
+    }
+
+    // Reads return structuredClone copies while enabled
+    setCloneOnRead(enabled) {
+        return this.storage.set_clone_on_read(enabled);
+    }
+
+    // While frozen, local writes and dispatches throw FROZEN
+    freeze() {
+        return this.storage.freeze();
+    }
+
+    unfreeze() {
+        return this.storage.unfreeze();
+    }
+
+    isFrozen() {
+        return this.storage.frozen;
+    }
+
+    // Same state and subscriptions, with only the read methods
+    readOnlyView() {
+        return new ReadOnlyStoreWrapper(this.storage.read_only_view());
+    }
+
+    // rules: { read, write }, each a boolean or (key[, value]) => boolean; null removes the policy
+    setAccessPolicy(prefix, rules) {
+        return this.storage.set_access_policy(prefix, rules);
+    }
+
+    // Read-only handle on some keys (an array of keys or a prefix) with its own subscribe
+    createView(keysOrPrefix) {
+        return new StoreViewWrapper(this.storage.create_view(keysOrPrefix));
+    }
+
+    // localStorage persistence; options: { priorities, quotaBytes, threshold, evict, onReport }
+    enablePersistence(storageKey, options) {
+        return this.storage.enable_persistence(storageKey, options);
+    }
+
+    setPersistPriority(key, priority) {
+        return this.storage.set_persist_priority(key, priority);
+    }
+
+    droppedPersistKeys() {
+        return this.storage.dropped_persist_keys();
+    }
+
+    // AES-GCM encryption at rest; key is a CryptoKey or a passphrase ({ salt, iterations })
+    enableEncryption(key, options) {
+        return this.storage.enable_encryption(key, options);
+    }
+
+    // HMAC-sign snapshots and exports; tampered data throws SIGNATURE_INVALID
+    enableSigning(key, options) {
+        return this.storage.enable_signing(key, options);
+    }
+
+    // Deflate persisted and exported values of at least thresholdBytes
+    enableCompression(options) {
+        return this.storage.enable_compression(options);
+    }
+
+    disableCompression() {
+        return this.storage.disable_compression();
+    }
+
+    // Take a navigator.locks lock around persistence writes; options: { name, timeoutMs }
+    enableWebLocks(options) {
+        return this.storage.enable_web_locks(options);
+    }
+
+    onLockContended(callback) {
+        return this.storage.on_lock_contended(callback);
+    }
+
+    // 'none' | 'oldestFirst' | 'largestFirst' | ({ candidates }) => keys
+    setEvictionPolicy(policy) {
+        return this.storage.set_eviction_policy(policy);
+    }
+
+    onQuotaExceeded(callback) {
+        return this.storage.on_quota_exceeded(callback);
+    }
+
+    // Persistence backends for enablePersistence(key, { backend: name })
+    registerBackend(name, adapter) {
+        return this.storage.register_backend(name, adapter);
+    }
+
+    // OPFS (dedicated workers only); options: { storageKeys, directory }
+    registerOpfsBackend(name, options) {
+        return this.storage.register_opfs_backend(name, options);
+    }
+
+    // Cache Storage, shared with a Service Worker; options: { cacheName, urlPrefix }
+    registerCacheBackend(name, options) {
+        return this.storage.register_cache_backend(name, options);
+    }
+
+    // Writes pending write-behind changes now
+    flush() {
+        return this.storage.flush();
+    }
+
+    // Resolves once async backends (encryption, Web Locks, Cache Storage) have written too
+    flushAsync() {
+        return this.storage.flush_async();
+    }
+
+    // Which keys reach disk: { include, exclude, keyPrefixes }
+    persistConfig(config) {
+        return this.storage.persist_config(config);
+    }
+
+    // Persisted snapshots older than the schema version are migrated on hydration
+    setSchemaVersion(version) {
+        return this.storage.set_schema_version(version);
+    }
+
+    get schemaVersion() {
+        return this.storage.schema_version;
+    }
+
+    registerMigration(from, to, migrate) {
+        return this.storage.register_migration(from, to, migrate);
+    }
+
+    // Chunked bulk import; options: { chunkSize, onProgress }
+    importJson(json, options) {
+        return this.storage.import_json(json, options);
+    }
+
+    restore(snapshot, options) {
+        return this.storage.restore(snapshot, options);
+    }
+
+    // Incremental backups: export changes since a sequence number
+    currentSeq() {
+        return this.storage.current_seq();
+    }
+
+    exportIncremental(sinceSeq = 0) {
+        return this.storage.export_incremental(sinceSeq);
+    }
+
+    importIncremental(stream) {
+        return this.storage.import_incremental(stream);
+    }
+
+    // { environment: { localStorage, indexedDB, ... }, features: { persistence: 'enabled' | 'degraded' | 'unavailable', ... } }
+    capabilities() {
+        return this.storage.capabilities();
+    }
+
+    // Opt-in per-key read/write counters, hottest keys first
+    trackKeyspace(enabled = true) {
+        return this.storage.track_keyspace(enabled);
+    }
+
+    keyspaceStats(limit) {
+        return this.storage.keyspace_stats(limit);
+    }
+
+    resetKeyspaceStats() {
+        return this.storage.reset_keyspace_stats();
+    }
+
+    // Opt-in dispatch timings; onEvent({ type, action, duration, middleware, listenersNotified, timestamp })
+    enableMetrics(onEvent) {
+        return this.storage.enable_metrics(onEvent);
+    }
+
+    disableMetrics() {
+        return this.storage.disable_metrics();
+    }
+
+    resetMetrics() {
+        return this.storage.reset_metrics();
+    }
+
+    getMetrics() {
+        return this.storage.get_metrics();
+    }
+
+    // { entries, estimatedBytes, listeners, middleware, historyDepth, hydration, pendingFlushes }
+    getStats() {
+        return this.storage.get_stats();
+    }
+
+    // performance.measure entries named wasm-storage:<dispatch|middleware|notify>:<action>
+    setPerformanceMarks(enabled = true) {
+        return this.storage.set_performance_marks(enabled);
+    }
+
+    // Stash state for the next module instance; options: { slot, history }
+    preserveForHmr(options) {
+        return this.storage.preserve_for_hmr(options);
+    }
+
+    // Mirror state into an iframe (element or window); options: { keys, readOnly = true }
+    bridgeToFrame(frame, frameOrigin, options) {
+        return this.storage.bridge_to_frame(frame, frameOrigin, options);
+    }
+
+    // Inside the iframe: mirror the host page's bridged state
+    connectToHost(allowedOrigins) {
+        return this.storage.connect_to_host(allowedOrigins);
+    }
+
+    // Inside a Service Worker: share the given keys with the pages it serves
+    shareWithClients(keys) {
+        return this.storage.share_with_clients(keys);
+    }
+
+    // In a page: mirror the slice shared by its Service Worker
+    connectToServiceWorker() {
+        return this.storage.connect_to_service_worker();
+    }
+
+    // Inside a worker: serve this store to WorkerStoreClients (endpoint defaults to self)
+    hostWorkerClients(endpoint) {
+        return new this.wasmModule.WorkerStoreHost(this.storage, endpoint);
+    }
+
+    // Mirror keys into a SharedArrayBuffer for workers; options: { slotBytes }
+    enableSharedMemory(keys, options) {
+        return this.storage.enable_shared_memory(keys, options);
+    }
+
+    attachSharedMemory(buffer) {
+        return this.storage.attach_shared_memory(buffer);
+    }
+
+    sharedValue(key) {
+        return this.storage.shared_value(key);
+    }
+
+    sharedVersion() {
+        return this.storage.shared_version();
+    }
+
+    // Only the elected tab writes backends; options: { heartbeatMs, timeoutMs, onChange }
+    enableLeaderElection(channelName, options) {
+        return this.storage.enable_leader_election(channelName, options);
+    }
+
+    isLeader() {
+        return this.storage.is_leader();
+    }
+
+    leaderId() {
+        return this.storage.leader_id();
+    }
+
+    // Peer-to-peer sync over an open RTCDataChannel
+    syncWithPeer(channel, keys) {
+        return this.storage.sync_with_peer(channel, keys);
+    }
+
+    // Last-writer-wins stamps ({ counter, actor }) for multi-device merges
+    enableCrdt(actorId) {
+        return this.storage.enable_crdt(actorId);
+    }
+
+    crdtStamp(key) {
+        return this.storage.crdt_stamp(key);
+    }
+
+    crdtMerge(key, value, stamp) {
+        return this.storage.crdt_merge(key, value, stamp);
+    }
+
+    crdtExport() {
+        return this.storage.crdt_export();
+    }
+
+    crdtMergeAll(exported) {
+        return this.storage.crdt_merge_all(exported);
+    }
+
+    // resolver(key, local, remote, base) -> value to keep when sync finds divergent writes
+    setConflictResolver(resolver) {
+        return this.storage.set_conflict_resolver(resolver);
+    }
+
+    // Realtime sync with a WebSocket server; options: { keys, maxQueued, onStatus }
+    connectRemote(url, options) {
+        return this.storage.connect_remote(url, options);
+    }
+
+    // mapper({ event, data, lastEventId }) -> { type, payload } | [...] | null
+    subscribeSse(url, mapper, options) {
+        return this.storage.subscribe_sse(url, mapper, options);
+    }
+
+    // Pull/push sync with a plain REST endpoint; options: { headers, onConflict }
+    syncPull(url, options) {
+        return this.storage.sync_pull(url, options);
+    }
+
+    syncPush(url, options) {
+        return this.storage.sync_push(url, options);
+    }
+
+    // Service Worker only: dispatch a refetch action on incoming pushes
+    enablePushRefresh(actionType, topic) {
+        return this.storage.enable_push_refresh(actionType, topic);
+    }
 }
 
 // Redux Adapter
@@ -128,7 +1000,8 @@ class VuexWasmAdapter {
             commit: (type, payload) => {
                 const mutation = this.mutations.get(type);
                 if (mutation) {
-                    const currentState = this.wasmStorage.getAllState();
+                    // getAllState() is frozen, so mutations get their own copy
+                    const currentState = { ...this.wasmStorage.getAllState() };
                     mutation(currentState, payload);
                     // Update WASM storage with modified state
                     Object.entries(currentState).forEach(([key, value]) => {
@@ -188,10 +1061,10 @@ class ReactWasmProvider {
             });
 
             React.useEffect(() => {
-                const unsubscribe = this.wasmStorage.subscribe(() => {
+                const subscription = this.wasmStorage.subscribe(() => {
                     setState(this.wasmStorage.getAllState());
                 });
-                return () => unsubscribe;
+                return () => subscription.unsubscribe();
             }, []);
 
             const dispatch = React.useCallback((actionType, payload) => {
@@ -222,6 +1095,56 @@ class ReactWasmProvider {
 
         return { WasmStorageProvider, useWasmStorage, WasmStorageContext };
     }
+
+    // useSyncExternalStore bindings: components re-render only when what
+    // they read changes, and never see two different states in one render
+    createHooks(React) {
+        const storage = this.wasmStorage;
+        const subscribe = (onChange) => {
+            const subscription = storage.subscribe(onChange);
+            return () => subscription.unsubscribe();
+        };
+
+        const useStorageState = () =>
+            React.useSyncExternalStore(subscribe, () => storage.getAllState(), () => storage.getServerSnapshot());
+
+        const useStorageKey = (key) => {
+            const subscribeKey = React.useCallback((onChange) => {
+                const subscription = storage.subscribeWithSelector((state) => state[key], onChange);
+                return () => subscription.unsubscribe();
+            }, [key]);
+            return React.useSyncExternalStore(
+                subscribeKey,
+                () => storage.getState(key),
+                () => storage.getServerSnapshot()[key] ?? null
+            );
+        };
+
+        // The selection is cached per state snapshot (and kept while
+        // isEqual says it is unchanged), so selectors may build new objects
+        const useStorageSelector = (selector, isEqual = Object.is) => {
+            const cache = React.useRef(null);
+            const select = (state) => {
+                const cached = cache.current;
+                if (cached && cached.state === state && cached.selector === selector) {
+                    return cached.selected;
+                }
+                const selected = selector(state);
+                const kept = cached && isEqual(cached.selected, selected) ? cached.selected : selected;
+                cache.current = { state, selector, selected: kept };
+                return kept;
+            };
+            return React.useSyncExternalStore(
+                subscribe,
+                () => select(storage.getAllState()),
+                () => select(storage.getServerSnapshot())
+            );
+        };
+
+        const useDispatch = () => React.useCallback((actionType, payload) => storage.dispatch(actionType, payload), []);
+
+        return { useStorageState, useStorageKey, useStorageSelector, useDispatch };
+    }
 }
 
 // Solid.js Adapter
@@ -304,13 +1227,158 @@ class AngularWasmService {
     }
 }
 
-// Factory function to create storage with adapters
-async function createWasmStorage(wasmModulePath) {
+// Scoped read-only view from createView; reading keys outside it throws
+class StoreViewWrapper {
+    constructor(view) {
+        this.view = view;
+    }
+
+    get(key) {
+        return this.view.get(key);
+    }
+
+    has(key) {
+        return this.view.has(key);
+    }
+
+    keys() {
+        return this.view.keys();
+    }
+
+    getAll() {
+        return this.view.get_all();
+    }
+
+    subscribe(callback, options) {
+        return this.view.subscribe(callback, options);
+    }
+}
+
+// Whole-store read-only handle from readOnlyView; it has no write,
+// configuration or lifecycle methods
+class ReadOnlyStoreWrapper {
+    constructor(view) {
+        this.view = view;
+    }
+
+    getState(key) {
+        return this.view.get_state(key);
+    }
+
+    getMany(keys) {
+        return this.view.get_many(keys);
+    }
+
+    getAllState() {
+        return this.view.get_all_state();
+    }
+
+    has(key) {
+        return this.view.has(key);
+    }
+
+    keys() {
+        return this.view.keys();
+    }
+
+    values() {
+        return this.view.values();
+    }
+
+    entries(prefix) {
+        return this.view.entries(prefix);
+    }
+
+    len() {
+        return this.view.len();
+    }
+
+    isEmpty() {
+        return this.view.is_empty();
+    }
+
+    isFrozen() {
+        return this.view.frozen;
+    }
+
+    subscribe(callback, options) {
+        return this.view.subscribe(callback, options);
+    }
+}
+
+// Main-thread proxy for a store hosted in a worker; every call returns a promise
+class WorkerStoreClientWrapper {
+    constructor(client) {
+        this.client = client;
+    }
+
+    getState(key) {
+        return this.client.get_state(key);
+    }
+
+    getAllState() {
+        return this.client.get_all_state();
+    }
+
+    setState(key, value) {
+        return this.client.set_state(key, value);
+    }
+
+    removeState(key) {
+        return this.client.remove_state(key);
+    }
+
+    dispatch(actionType, payload) {
+        return this.client.dispatch(actionType, payload);
+    }
+
+    // Resolves to an ID for unsubscribe(); options: { origins, aggregate, delivery }
+    subscribe(callback, options) {
+        return this.client.subscribe(callback, options);
+    }
+
+    unsubscribe(id) {
+        return this.client.unsubscribe(id);
+    }
+
+    close() {
+        return this.client.close();
+    }
+}
+
+async function createWorkerStoreClient(wasmModulePath, endpoint) {
+    const wasmModule = await import(wasmModulePath);
+    await wasmModule.default();
+    return new WorkerStoreClientWrapper(new wasmModule.WorkerStoreClient(endpoint));
+}
+
+// Factory function to create storage with adapters; options.hmr (true or a
+// slot name) resumes state stashed by preserveForHmr(), options.frozen starts
+// the store frozen, options.cloneOnRead makes reads return copies
+async function createWasmStorage(wasmModulePath, options = {}) {
     // Load WASM module
     const wasmModule = await import(wasmModulePath);
     await wasmModule.default();
 
-    const wasmStorage = new WasmStorageWrapper(wasmModule);
+    // A named store is shared with every other caller using the same name
+    const existing = options.name
+        ? wasmModule.WasmStorage.get_or_create(options.name, options.storeOptions)
+        : options.hmr
+            ? wasmModule.WasmStorage.resume_from_hmr(options.hmr === true ? undefined : options.hmr)
+            : new wasmModule.WasmStorage({ initialState: options.initialState });
+    const wasmStorage = new WasmStorageWrapper(wasmModule, existing);
+    if (options.frozen) {
+        wasmStorage.freeze();
+    }
+    if (options.cloneOnRead) {
+        wasmStorage.setCloneOnRead(true);
+    }
+    if (options.unhandledActions) {
+        wasmStorage.setUnhandledActions(options.unhandledActions);
+    }
+    if (options.defaults) {
+        wasmStorage.setDefaults(options.defaults);
+    }
 
     return {
         storage: wasmStorage,
@@ -326,7 +1394,9 @@ async function createWasmStorage(wasmModulePath) {
 
 export {
     createWasmStorage,
+    createWorkerStoreClient,
     WasmStorageWrapper,
+    StoreViewWrapper,
     ReduxWasmAdapter,
     VuexWasmAdapter,
     ReactWasmProvider,
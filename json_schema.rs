@@ -0,0 +1,322 @@
+use crate::error::WasmStorageError;
+use crate::values_equal;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum JsonType {
+    Null,
+    Boolean,
+    Number,
+    Integer,
+    String,
+    Array,
+    Object,
+}
+
+impl JsonType {
+    fn parse(name: &str) -> Result<JsonType, JsValue> {
+        match name {
+            "null" => Ok(JsonType::Null),
+            "boolean" => Ok(JsonType::Boolean),
+            "number" => Ok(JsonType::Number),
+            "integer" => Ok(JsonType::Integer),
+            "string" => Ok(JsonType::String),
+            "array" => Ok(JsonType::Array),
+            "object" => Ok(JsonType::Object),
+            _ => Err(WasmStorageError::invalid_argument(format!(
+                "Unknown schema type \"{}\" (expected null, boolean, number, integer, string, array or object)",
+                name
+            )).into()),
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            JsonType::Null => "null",
+            JsonType::Boolean => "boolean",
+            JsonType::Number => "number",
+            JsonType::Integer => "integer",
+            JsonType::String => "string",
+            JsonType::Array => "array",
+            JsonType::Object => "object",
+        }
+    }
+
+    fn matches(self, value: &JsValue) -> bool {
+        match self {
+            JsonType::Null => value.is_null(),
+            JsonType::Boolean => value.as_bool().is_some(),
+            JsonType::Number => value.as_f64().is_some_and(f64::is_finite),
+            JsonType::Integer => value.as_f64().is_some_and(|n| n.is_finite() && n.fract() == 0.0),
+            JsonType::String => value.is_string(),
+            JsonType::Array => js_sys::Array::is_array(value),
+            JsonType::Object => value.is_object() && !js_sys::Array::is_array(value),
+        }
+    }
+}
+
+/// The subset of JSON Schema checked inside the module: `type`, `enum`,
+/// `const`, `properties`, `required`, `additionalProperties: false`,
+/// `items`, `minimum`/`maximum` (and exclusive variants), `minLength`/
+/// `maxLength`, `pattern` and `minItems`/`maxItems`.
+#[derive(Clone, Default)]
+pub(crate) struct Schema {
+    types: Option<Vec<JsonType>>,
+    allowed: Option<Vec<JsValue>>,
+    constant: Option<JsValue>,
+    properties: Vec<(String, Schema)>,
+    required: Vec<String>,
+    closed: bool,
+    items: Option<Box<Schema>>,
+    minimum: Option<f64>,
+    maximum: Option<f64>,
+    exclusive_minimum: Option<f64>,
+    exclusive_maximum: Option<f64>,
+    min_length: Option<usize>,
+    max_length: Option<usize>,
+    pattern: Option<js_sys::RegExp>,
+    min_items: Option<u32>,
+    max_items: Option<u32>,
+}
+
+fn field(schema: &JsValue, name: &str) -> Result<Option<JsValue>, JsValue> {
+    let value = js_sys::Reflect::get(schema, &JsValue::from_str(name))?;
+    Ok(if value.is_undefined() { None } else { Some(value) })
+}
+
+fn number(schema: &JsValue, name: &str) -> Result<Option<f64>, JsValue> {
+    match field(schema, name)? {
+        Some(value) => value
+            .as_f64()
+            .map(Some)
+            .ok_or_else(|| WasmStorageError::invalid_argument(format!("Schema keyword {} must be a number", name)).into()),
+        None => Ok(None),
+    }
+}
+
+impl Schema {
+    /// Compiles a schema object once, at registration.
+    pub(crate) fn compile(schema: &JsValue) -> Result<Schema, JsValue> {
+        if !schema.is_object() {
+            return Err(WasmStorageError::invalid_argument("A JSON schema must be an object").into());
+        }
+
+        let types = match field(schema, "type")? {
+            Some(names) if js_sys::Array::is_array(&names) => {
+                let mut types = Vec::new();
+                for name in js_sys::Array::from(&names).iter() {
+                    types.push(JsonType::parse(&name.as_string().unwrap_or_default())?);
+                }
+                Some(types)
+            }
+            Some(name) => Some(vec![JsonType::parse(&name.as_string().unwrap_or_default())?]),
+            None => None,
+        };
+
+        let mut properties = Vec::new();
+        if let Some(declared) = field(schema, "properties")? {
+            let declared = declared
+                .dyn_into::<js_sys::Object>()
+                .map_err(|_| WasmStorageError::invalid_argument("Schema keyword properties must be an object"))?;
+            for entry in js_sys::Object::entries(&declared).iter() {
+                let pair = js_sys::Array::from(&entry);
+                properties.push((pair.get(0).as_string().unwrap_or_default(), Schema::compile(&pair.get(1))?));
+            }
+        }
+
+        let pattern = field(schema, "pattern")?.map(|pattern| js_sys::RegExp::new(&pattern.as_string().unwrap_or_default(), "u"));
+
+        Ok(Schema {
+            types,
+            allowed: field(schema, "enum")?.map(|values| js_sys::Array::from(&values).iter().collect()),
+            constant: field(schema, "const")?,
+            properties,
+            required: field(schema, "required")?
+                .map(|keys| js_sys::Array::from(&keys).iter().filter_map(|key| key.as_string()).collect())
+                .unwrap_or_default(),
+            closed: field(schema, "additionalProperties")?.is_some_and(|allowed| allowed.as_bool() == Some(false)),
+            items: match field(schema, "items")? {
+                Some(items) => Some(Box::new(Schema::compile(&items)?)),
+                None => None,
+            },
+            minimum: number(schema, "minimum")?,
+            maximum: number(schema, "maximum")?,
+            exclusive_minimum: number(schema, "exclusiveMinimum")?,
+            exclusive_maximum: number(schema, "exclusiveMaximum")?,
+            min_length: number(schema, "minLength")?.map(|n| n as usize),
+            max_length: number(schema, "maxLength")?.map(|n| n as usize),
+            pattern,
+            min_items: number(schema, "minItems")?.map(|n| n as u32),
+            max_items: number(schema, "maxItems")?.map(|n| n as u32),
+        })
+    }
+
+    /// Returns a message naming the first violation, with `path` a JSON-path
+    /// like `$.address.zip`.
+    pub(crate) fn validate(&self, value: &JsValue, path: &str) -> Result<(), String> {
+        if let Some(types) = &self.types {
+            if !types.iter().any(|t| t.matches(value)) {
+                let expected: Vec<&str> = types.iter().map(|t| t.as_str()).collect();
+                return Err(format!("{} must be of type {}", path, expected.join(" or ")));
+            }
+        }
+        if let Some(allowed) = &self.allowed {
+            if !allowed.iter().any(|candidate| values_equal(candidate, value)) {
+                return Err(format!("{} must be one of the enumerated values", path));
+            }
+        }
+        if let Some(constant) = &self.constant {
+            if !values_equal(constant, value) {
+                return Err(format!("{} must equal the const value", path));
+            }
+        }
+
+        if let Some(n) = value.as_f64() {
+            if self.minimum.is_some_and(|min| n < min) {
+                return Err(format!("{} must be >= {}", path, self.minimum.unwrap_or_default()));
+            }
+            if self.maximum.is_some_and(|max| n > max) {
+                return Err(format!("{} must be <= {}", path, self.maximum.unwrap_or_default()));
+            }
+            if self.exclusive_minimum.is_some_and(|min| n <= min) {
+                return Err(format!("{} must be > {}", path, self.exclusive_minimum.unwrap_or_default()));
+            }
+            if self.exclusive_maximum.is_some_and(|max| n >= max) {
+                return Err(format!("{} must be < {}", path, self.exclusive_maximum.unwrap_or_default()));
+            }
+        }
+
+        if let Some(text) = value.as_string() {
+            let length = text.chars().count();
+            if self.min_length.is_some_and(|min| length < min) {
+                return Err(format!("{} must be at least {} characters", path, self.min_length.unwrap_or_default()));
+            }
+            if self.max_length.is_some_and(|max| length > max) {
+                return Err(format!("{} must be at most {} characters", path, self.max_length.unwrap_or_default()));
+            }
+            if let Some(pattern) = &self.pattern {
+                if !pattern.test(&text) {
+                    return Err(format!("{} must match /{}/", path, String::from(pattern.source())));
+                }
+            }
+        }
+
+        if js_sys::Array::is_array(value) {
+            let items = js_sys::Array::from(value);
+            if self.min_items.is_some_and(|min| items.length() < min) {
+                return Err(format!("{} must have at least {} items", path, self.min_items.unwrap_or_default()));
+            }
+            if self.max_items.is_some_and(|max| items.length() > max) {
+                return Err(format!("{} must have at most {} items", path, self.max_items.unwrap_or_default()));
+            }
+            if let Some(schema) = &self.items {
+                for (index, item) in items.iter().enumerate() {
+                    schema.validate(&item, &format!("{}[{}]", path, index))?;
+                }
+            }
+        } else if value.is_object() {
+            for key in self.required.iter() {
+                let present = js_sys::Reflect::has(value, &JsValue::from_str(key)).unwrap_or(false);
+                if !present {
+                    return Err(format!("{} is missing required property {}", path, key));
+                }
+            }
+            for (key, schema) in self.properties.iter() {
+                let property = js_sys::Reflect::get(value, &JsValue::from_str(key)).unwrap_or(JsValue::UNDEFINED);
+                if !property.is_undefined() {
+                    schema.validate(&property, &format!("{}.{}", path, key))?;
+                }
+            }
+            if self.closed {
+                let object: &js_sys::Object = value.unchecked_ref();
+                for key in js_sys::Object::keys(object).iter() {
+                    let key = key.as_string().unwrap_or_default();
+                    if !self.properties.iter().any(|(declared, _)| *declared == key) {
+                        return Err(format!("{} has unexpected property {}", path, key));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The TypeScript type of the values this schema accepts; members of
+    /// nested objects are indented `depth + 1` levels.
+    pub(crate) fn typescript(&self, depth: usize) -> String {
+        if let Some(literal) = self.constant.as_ref().and_then(literal) {
+            return literal;
+        }
+        if let Some(allowed) = &self.allowed {
+            let literals: Option<Vec<String>> = allowed.iter().map(literal).collect();
+            if let Some(literals) = literals.filter(|literals| !literals.is_empty()) {
+                return literals.join(" | ");
+            }
+        }
+        let types = match &self.types {
+            Some(types) => types.clone(),
+            None if !self.properties.is_empty() => vec![JsonType::Object],
+            None if self.items.is_some() => vec![JsonType::Array],
+            None => return "unknown".to_string(),
+        };
+        let mut names: Vec<String> = Vec::new();
+        for json_type in types {
+            let name = match json_type {
+                JsonType::Null => "null".to_string(),
+                JsonType::Boolean => "boolean".to_string(),
+                JsonType::Number | JsonType::Integer => "number".to_string(),
+                JsonType::String => "string".to_string(),
+                JsonType::Array => match &self.items {
+                    Some(items) => format!("Array<{}>", items.typescript(depth)),
+                    None => "unknown[]".to_string(),
+                },
+                JsonType::Object => self.object_type(depth),
+            };
+            if !names.contains(&name) {
+                names.push(name);
+            }
+        }
+        names.join(" | ")
+    }
+
+    fn object_type(&self, depth: usize) -> String {
+        if self.properties.is_empty() {
+            return "Record<string, unknown>".to_string();
+        }
+        let indent = "    ".repeat(depth + 1);
+        let mut members: Vec<String> = self
+            .properties
+            .iter()
+            .map(|(name, schema)| {
+                let optional = if self.required.contains(name) { "" } else { "?" };
+                format!("{}{}{}: {};", indent, property_name(name), optional, schema.typescript(depth + 1))
+            })
+            .collect();
+        if !self.closed {
+            members.push(format!("{}[key: string]: unknown;", indent));
+        }
+        format!("{{\n{}\n{}}}", members.join("\n"), "    ".repeat(depth))
+    }
+}
+
+// A primitive as a TypeScript literal type
+fn literal(value: &JsValue) -> Option<String> {
+    if value.is_object() || value.is_undefined() {
+        return None;
+    }
+    js_sys::JSON::stringify(value).ok()?.as_string()
+}
+
+/// `name` as a property key: bare when it is an identifier, quoted otherwise.
+pub(crate) fn property_name(name: &str) -> String {
+    let mut chars = name.chars();
+    let identifier = chars.next().is_some_and(|first| first.is_ascii_alphabetic() || first == '_' || first == '$')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '$');
+    if identifier {
+        return name.to_string();
+    }
+    js_sys::JSON::stringify(&JsValue::from_str(name))
+        .ok()
+        .and_then(|quoted| quoted.as_string())
+        .unwrap_or_else(|| format!("{:?}", name))
+}
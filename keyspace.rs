@@ -0,0 +1,111 @@
+use crate::WasmStorage;
+use crate::error::WasmStorageError;
+use std::collections::HashMap;
+use wasm_bindgen::prelude::*;
+
+#[derive(Default, Clone, Copy)]
+struct KeyAccess {
+    reads: u32,
+    writes: u32,
+    last_read: Option<f64>,
+    last_write: Option<f64>,
+}
+
+impl KeyAccess {
+    fn last_access(&self) -> Option<f64> {
+        match (self.last_read, self.last_write) {
+            (Some(read), Some(write)) => Some(read.max(write)),
+            (read, write) => read.or(write),
+        }
+    }
+}
+
+/// Per-key access counters, only kept while tracking is enabled.
+#[derive(Default)]
+pub(crate) struct KeyspaceStats {
+    keys: HashMap<String, KeyAccess>,
+}
+
+#[wasm_bindgen]
+impl WasmStorage {
+    /// Starts (or stops and discards) per-key read/write tracking.
+    #[wasm_bindgen]
+    pub fn track_keyspace(&mut self, enabled: bool) -> Result<(), JsValue> {
+        let mut keyspace = self.keyspace.lock().map_err(WasmStorageError::lock_poisoned)?;
+        match (enabled, keyspace.is_some()) {
+            (true, false) => *keyspace = Some(KeyspaceStats::default()),
+            (false, _) => *keyspace = None,
+            (true, true) => {}
+        }
+        Ok(())
+    }
+
+    /// Returns `[{ key, reads, writes, lastReadAt, lastWriteAt, lastAccessAt,
+    /// present }]`, hottest keys first. Keys held in the store but never
+    /// accessed are included with zero counts, which makes them easy to spot
+    /// as eviction or lazy-hydration candidates. `limit` caps the result.
+    #[wasm_bindgen]
+    pub fn keyspace_stats(&self, limit: Option<u32>) -> Result<js_sys::Array, JsValue> {
+        let mut tracked = {
+            let keyspace = self.keyspace.lock().map_err(WasmStorageError::lock_poisoned)?;
+            let keyspace = keyspace
+                .as_ref()
+                .ok_or_else(|| WasmStorageError::invalid_state("Keyspace tracking is not enabled"))?;
+            keyspace.keys.clone()
+        };
+        let state = self.state.lock().map_err(WasmStorageError::lock_poisoned)?;
+        for key in state.keys() {
+            tracked.entry(key.clone()).or_default();
+        }
+        let mut entries: Vec<(String, KeyAccess)> = tracked.into_iter().collect();
+        entries.sort_by(|(a_key, a), (b_key, b)| {
+            (b.reads + b.writes)
+                .cmp(&(a.reads + a.writes))
+                .then_with(|| a_key.cmp(b_key))
+        });
+
+        let timestamp = |at: Option<f64>| at.map_or(JsValue::NULL, JsValue::from_f64);
+        let stats = js_sys::Array::new();
+        for (key, access) in entries.into_iter().take(limit.unwrap_or(u32::MAX) as usize) {
+            let entry = js_sys::Object::new();
+            js_sys::Reflect::set(&entry, &JsValue::from_str("key"), &JsValue::from_str(&key))?;
+            js_sys::Reflect::set(&entry, &JsValue::from_str("reads"), &JsValue::from(access.reads))?;
+            js_sys::Reflect::set(&entry, &JsValue::from_str("writes"), &JsValue::from(access.writes))?;
+            js_sys::Reflect::set(&entry, &JsValue::from_str("lastReadAt"), &timestamp(access.last_read))?;
+            js_sys::Reflect::set(&entry, &JsValue::from_str("lastWriteAt"), &timestamp(access.last_write))?;
+            js_sys::Reflect::set(&entry, &JsValue::from_str("lastAccessAt"), &timestamp(access.last_access()))?;
+            js_sys::Reflect::set(&entry, &JsValue::from_str("present"), &JsValue::from_bool(state.contains_key(&key)))?;
+            stats.push(&entry);
+        }
+        Ok(stats)
+    }
+
+    /// Zeroes all counters without turning tracking off.
+    #[wasm_bindgen]
+    pub fn reset_keyspace_stats(&mut self) -> Result<(), JsValue> {
+        if let Some(keyspace) = self.keyspace.lock().map_err(WasmStorageError::lock_poisoned)?.as_mut() {
+            keyspace.keys.clear();
+        }
+        Ok(())
+    }
+}
+
+impl WasmStorage {
+    pub(crate) fn record_read(&self, key: &str) -> Result<(), JsValue> {
+        if let Some(keyspace) = self.keyspace.lock().map_err(WasmStorageError::lock_poisoned)?.as_mut() {
+            let access = keyspace.keys.entry(key.to_string()).or_default();
+            access.reads += 1;
+            access.last_read = Some(js_sys::Date::now());
+        }
+        Ok(())
+    }
+
+    pub(crate) fn record_write(&self, key: &str) -> Result<(), JsValue> {
+        if let Some(keyspace) = self.keyspace.lock().map_err(WasmStorageError::lock_poisoned)?.as_mut() {
+            let access = keyspace.keys.entry(key.to_string()).or_default();
+            access.writes += 1;
+            access.last_write = Some(js_sys::Date::now());
+        }
+        Ok(())
+    }
+}
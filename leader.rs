@@ -0,0 +1,269 @@
+use crate::error::WasmStorageError;
+use crate::rate_limit::{clear_timeout, set_timeout};
+use crate::WasmStorage;
+use crate::sync::{Lock, Shared};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+
+const DEFAULT_HEARTBEAT_MS: f64 = 1000.0;
+const DEFAULT_TIMEOUT_MS: f64 = 3000.0;
+
+struct ElectionState {
+    leader: Option<String>,
+    // When the leader's last heartbeat arrived
+    leader_seen: f64,
+    timer: Option<JsValue>,
+    stopped: bool,
+}
+
+struct ElectionShared {
+    id: String,
+    // None where BroadcastChannel is missing: the only tab leads
+    channel: Option<web_sys::BroadcastChannel>,
+    heartbeat_ms: f64,
+    timeout_ms: f64,
+    on_change: Option<js_sys::Function>,
+    state: Lock<ElectionState>,
+}
+
+/// Heartbeat-based election among the tabs sharing a BroadcastChannel. The
+/// leader announces itself every heartbeat; when it goes quiet for the
+/// timeout (or resigns), the other tabs claim leadership, and competing
+/// claims settle on the lowest tab ID.
+pub(crate) struct Election {
+    shared: Shared<ElectionShared>,
+    _on_message: Option<Closure<dyn FnMut(web_sys::MessageEvent)>>,
+}
+
+#[wasm_bindgen]
+impl WasmStorage {
+    /// Elects one leader among the tabs whose stores join `channel_name`.
+    /// Only the leader writes snapshots, the write-ahead log and remote sync
+    /// mutations, so tabs kept in step by cross-tab sync don't write the
+    /// same backend at once. When the leader closes or stops responding
+    /// another tab takes over and writes a fresh snapshot. Options:
+    /// `heartbeatMs` (default 1000), `timeoutMs` (silence before failover,
+    /// default 3000) and `onChange({ isLeader, leaderId })`.
+    #[wasm_bindgen]
+    pub fn enable_leader_election(&mut self, channel_name: &str, options: Option<js_sys::Object>) -> Result<(), JsValue> {
+        if self.leader.lock().map_err(WasmStorageError::lock_poisoned)?.is_some() {
+            return Err(WasmStorageError::invalid_state("Leader election is already enabled").into());
+        }
+        let options = options.map(JsValue::from).unwrap_or(JsValue::UNDEFINED);
+        let option = |name: &str| js_sys::Reflect::get(&options, &JsValue::from_str(name)).unwrap_or(JsValue::UNDEFINED);
+        let heartbeat_ms = option("heartbeatMs").as_f64().unwrap_or(DEFAULT_HEARTBEAT_MS);
+        let timeout_ms = option("timeoutMs").as_f64().unwrap_or(DEFAULT_TIMEOUT_MS);
+        if heartbeat_ms <= 0.0 || timeout_ms <= heartbeat_ms {
+            return Err(WasmStorageError::invalid_argument("Leader election needs heartbeatMs > 0 and timeoutMs > heartbeatMs")
+                .with("heartbeatMs", heartbeat_ms)
+                .with("timeoutMs", timeout_ms)
+                .into());
+        }
+
+        let has_channel = js_sys::Reflect::has(&js_sys::global(), &JsValue::from_str("BroadcastChannel"))?;
+        let channel = if has_channel { Some(web_sys::BroadcastChannel::new(channel_name)?) } else { None };
+        let shared = Shared::new(ElectionShared {
+            id: self.generate_id("tab")?,
+            channel,
+            heartbeat_ms,
+            timeout_ms,
+            on_change: option("onChange").dyn_into::<js_sys::Function>().ok(),
+            state: Lock::new(ElectionState { leader: None, leader_seen: js_sys::Date::now(), timer: None, stopped: false }),
+        });
+
+        let on_message = match shared.channel.as_ref() {
+            Some(channel) => {
+                let message_shared = shared.clone();
+                let mut store = self.clone();
+                let on_message = Closure::wrap(Box::new(move |event: web_sys::MessageEvent| {
+                    if let Err(e) = message_shared.receive(&mut store, &event.data()) {
+                        log_warn!("Leader election failed to handle a message: {:?}", e);
+                    }
+                }) as Box<dyn FnMut(web_sys::MessageEvent)>);
+                channel.set_onmessage(Some(on_message.as_ref().unchecked_ref()));
+                Some(on_message)
+            }
+            None => None,
+        };
+        *self.leader.lock().map_err(WasmStorageError::lock_poisoned)? =
+            Some(Election { shared: shared.clone(), _on_message: on_message });
+
+        // A sitting leader answers right away instead of at its next heartbeat
+        shared.post("hello")?;
+        if shared.channel.is_none() {
+            shared.elect(self, Some(shared.id.clone()))?;
+        }
+        shared.schedule_tick(self.clone())?;
+
+        let teardown_shared = shared.clone();
+        self.register_teardown(Box::new(move || {
+            let _ = teardown_shared.resign();
+        }))?;
+        log_info!("Leader election joined {} as {}", channel_name, shared.id);
+        Ok(())
+    }
+
+    /// Whether this store may write shared backends: true while it is the
+    /// elected leader, and always when leader election isn't enabled.
+    #[wasm_bindgen]
+    pub fn is_leader(&self) -> Result<bool, JsValue> {
+        let election = self.leader.lock().map_err(WasmStorageError::lock_poisoned)?;
+        match election.as_ref() {
+            Some(election) => election.shared.is_leader(),
+            None => Ok(true),
+        }
+    }
+
+    /// ID of the current leader (this tab's when `is_leader`), or null before
+    /// one is known or without leader election.
+    #[wasm_bindgen]
+    pub fn leader_id(&self) -> Result<Option<String>, JsValue> {
+        let election = self.leader.lock().map_err(WasmStorageError::lock_poisoned)?;
+        match election.as_ref() {
+            Some(election) => Ok(election.shared.state.lock().map_err(WasmStorageError::lock_poisoned)?.leader.clone()),
+            None => Ok(None),
+        }
+    }
+}
+
+impl ElectionShared {
+    fn is_leader(&self) -> Result<bool, JsValue> {
+        let state = self.state.lock().map_err(WasmStorageError::lock_poisoned)?;
+        Ok(state.leader.as_deref() == Some(self.id.as_str()))
+    }
+
+    fn post(&self, kind: &str) -> Result<(), JsValue> {
+        let Some(channel) = self.channel.as_ref() else {
+            return Ok(());
+        };
+        let message = js_sys::Object::new();
+        js_sys::Reflect::set(&message, &JsValue::from_str("kind"), &JsValue::from_str(kind))?;
+        js_sys::Reflect::set(&message, &JsValue::from_str("id"), &JsValue::from_str(&self.id))?;
+        channel.post_message(&message)
+    }
+
+    fn receive(&self, store: &mut WasmStorage, message: &JsValue) -> Result<(), JsValue> {
+        let kind = js_sys::Reflect::get(message, &JsValue::from_str("kind"))?.as_string().unwrap_or_default();
+        let Some(from) = js_sys::Reflect::get(message, &JsValue::from_str("id"))?.as_string() else {
+            return Ok(());
+        };
+        match kind.as_str() {
+            "heartbeat" => {
+                let (leader, stale) = {
+                    let state = self.state.lock().map_err(WasmStorageError::lock_poisoned)?;
+                    (state.leader.clone(), js_sys::Date::now() - state.leader_seen > self.timeout_ms)
+                };
+                match leader {
+                    // Two leaders after a split: the higher ID steps down
+                    Some(leader) if leader == self.id && from > self.id => self.post("heartbeat"),
+                    Some(leader) if leader != from && from > leader && !stale => Ok(()),
+                    _ => self.elect(store, Some(from)),
+                }
+            }
+            "hello" if self.is_leader()? => self.post("heartbeat"),
+            "resign" => {
+                let resigned = {
+                    let state = self.state.lock().map_err(WasmStorageError::lock_poisoned)?;
+                    state.leader.as_deref() == Some(from.as_str())
+                };
+                if resigned {
+                    // Every follower claims at once; the heartbeats settle it
+                    self.post("heartbeat")?;
+                    self.elect(store, Some(self.id.clone()))?;
+                }
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    // Heartbeats while leading; claims leadership once the leader is quiet
+    fn tick(&self, store: &mut WasmStorage) -> Result<(), JsValue> {
+        let (leading, quiet) = {
+            let state = self.state.lock().map_err(WasmStorageError::lock_poisoned)?;
+            let leading = state.leader.as_deref() == Some(self.id.as_str());
+            let quiet = state.leader.is_none() || js_sys::Date::now() - state.leader_seen > self.timeout_ms;
+            (leading, quiet)
+        };
+        if leading || quiet {
+            self.post("heartbeat")?;
+        }
+        if !leading && quiet {
+            self.elect(store, Some(self.id.clone()))?;
+        }
+        Ok(())
+    }
+
+    fn schedule_tick(self: &Shared<Self>, store: WasmStorage) -> Result<(), JsValue> {
+        let shared = self.clone();
+        let tick = Closure::once_into_js(move || {
+            let mut store = store;
+            if let Ok(mut state) = shared.state.lock() {
+                state.timer = None;
+                if state.stopped {
+                    return;
+                }
+            }
+            if let Err(e) = shared.tick(&mut store) {
+                log_warn!("Leader election heartbeat failed: {:?}", e);
+            }
+            let _ = shared.schedule_tick(store);
+        });
+        let timer = set_timeout(&tick, self.heartbeat_ms)?;
+        self.state.lock().map_err(WasmStorageError::lock_poisoned)?.timer = Some(timer);
+        Ok(())
+    }
+
+    // Records the current leader; a tab that just took over writes a
+    // snapshot, since the previous leader may have gone before flushing
+    fn elect(&self, store: &mut WasmStorage, leader: Option<String>) -> Result<(), JsValue> {
+        let (was_leader, is_leader) = {
+            let mut state = self.state.lock().map_err(WasmStorageError::lock_poisoned)?;
+            let was_leader = state.leader.as_deref() == Some(self.id.as_str());
+            state.leader = leader;
+            state.leader_seen = js_sys::Date::now();
+            (was_leader, state.leader.as_deref() == Some(self.id.as_str()))
+        };
+        if was_leader == is_leader {
+            return Ok(());
+        }
+        if is_leader {
+            store.write_snapshot()?;
+        }
+        if let Some(on_change) = self.on_change.as_ref() {
+            let event = js_sys::Object::new();
+            js_sys::Reflect::set(&event, &JsValue::from_str("isLeader"), &JsValue::from_bool(is_leader))?;
+            let leader_id = self.state.lock().map_err(WasmStorageError::lock_poisoned)?.leader.clone();
+            js_sys::Reflect::set(&event, &JsValue::from_str("leaderId"), &leader_id.map_or(JsValue::NULL, |id| JsValue::from_str(&id)))?;
+            on_change.call1(&JsValue::NULL, &event)?;
+        }
+        Ok(())
+    }
+
+    fn resign(&self) -> Result<(), JsValue> {
+        let leading = {
+            let mut state = self.state.lock().map_err(WasmStorageError::lock_poisoned)?;
+            state.stopped = true;
+            if let Some(timer) = state.timer.take() {
+                clear_timeout(&timer)?;
+            }
+            state.leader.as_deref() == Some(self.id.as_str())
+        };
+        if leading {
+            self.post("resign")?;
+        }
+        if let Some(channel) = self.channel.as_ref() {
+            channel.set_onmessage(None);
+            channel.close();
+        }
+        Ok(())
+    }
+}
+
+impl WasmStorage {
+    /// False while another tab holds leadership, in which case persistence
+    /// and remote sync leave the shared backends alone.
+    pub(crate) fn writes_backends(&self) -> Result<bool, JsValue> {
+        self.is_leader()
+    }
+}
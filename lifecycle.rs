@@ -0,0 +1,144 @@
+use crate::error::{ErrorCode, WasmStorageError};
+use crate::error_handler::ErrorSource;
+use crate::WasmStorage;
+use wasm_bindgen::prelude::*;
+
+/// Init/destroy hooks plus the cleanup registered by subsystems that hold
+/// outside resources (data channels, global event listeners).
+#[derive(Default)]
+pub(crate) struct Lifecycle {
+    destroyed: bool,
+    hydrated: bool,
+    init_hooks: Vec<js_sys::Function>,
+    destroy_hooks: Vec<js_sys::Function>,
+    teardowns: Vec<Box<dyn FnOnce()>>,
+}
+
+#[wasm_bindgen]
+impl WasmStorage {
+    /// Runs `callback({ source })` whenever hydration from persistence or a
+    /// bulk restore completes; runs it right away if that already happened.
+    #[wasm_bindgen]
+    pub fn on_init(&mut self, callback: js_sys::Function) -> Result<(), JsValue> {
+        let hydrated = {
+            let mut lifecycle = self.lifecycle.lock().map_err(WasmStorageError::lock_poisoned)?;
+            lifecycle.init_hooks.push(callback.clone());
+            lifecycle.hydrated
+        };
+        if hydrated {
+            self.run_hook(&callback, "init", &hook_event("init", None)?)?;
+        }
+        Ok(())
+    }
+
+    #[wasm_bindgen]
+    pub fn on_destroy(&mut self, callback: js_sys::Function) -> Result<(), JsValue> {
+        let mut lifecycle = self.lifecycle.lock().map_err(WasmStorageError::lock_poisoned)?;
+        lifecycle.destroy_hooks.push(callback);
+        Ok(())
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn destroyed(&self) -> Result<bool, JsValue> {
+        Ok(self.lifecycle.lock().map_err(WasmStorageError::lock_poisoned)?.destroyed)
+    }
+
+    /// Shuts the store down: commits rate-limited writes and batched
+    /// notifications, persists, disconnects sync channels and global event
+    /// listeners, runs `on_destroy` hooks, then drops all listeners,
+    /// middleware and effects (aborting their runs). Later writes fail.
+    /// Calling it again does nothing.
+    #[wasm_bindgen]
+    pub fn destroy(&mut self) -> Result<(), JsValue> {
+        if self.destroyed()? {
+            return Ok(());
+        }
+
+        let pending_keys: Vec<String> = self
+            .key_policies
+            .lock()
+            .map_err(WasmStorageError::lock_poisoned)?
+            .keys()
+            .cloned()
+            .collect();
+        for key in pending_keys {
+            self.flush_key(&key)?;
+        }
+        self.flush_sync()?;
+        self.flush()?;
+        self.cancel_queued_actions()?;
+
+        let (teardowns, destroy_hooks) = {
+            let mut lifecycle = self.lifecycle.lock().map_err(WasmStorageError::lock_poisoned)?;
+            lifecycle.destroyed = true;
+            (std::mem::take(&mut lifecycle.teardowns), std::mem::take(&mut lifecycle.destroy_hooks))
+        };
+        for teardown in teardowns {
+            teardown();
+        }
+        self.remove_transient_hooks()?;
+
+        let event = hook_event("destroy", None)?;
+        for hook in destroy_hooks.iter() {
+            self.run_hook(hook, "destroy", &event)?;
+        }
+
+        *self.listeners.lock().map_err(WasmStorageError::lock_poisoned)? = Default::default();
+        self.middleware.lock().map_err(WasmStorageError::lock_poisoned)?.clear();
+        self.clear_middleware_context()?;
+        self.clear_effects()?;
+        self.lifecycle.lock().map_err(WasmStorageError::lock_poisoned)?.init_hooks.clear();
+        log_debug!("WasmStorage destroyed");
+        Ok(())
+    }
+}
+
+impl WasmStorage {
+    /// Registers cleanup for `destroy`, e.g. closing a sync channel.
+    pub(crate) fn register_teardown(&self, teardown: Box<dyn FnOnce()>) -> Result<(), JsValue> {
+        self.lifecycle.lock().map_err(WasmStorageError::lock_poisoned)?.teardowns.push(teardown);
+        Ok(())
+    }
+
+    pub(crate) fn ensure_alive(&self) -> Result<(), JsValue> {
+        if self.destroyed()? {
+            return Err(WasmStorageError::new(ErrorCode::Destroyed, "WasmStorage has been destroyed").into());
+        }
+        Ok(())
+    }
+
+    pub(crate) fn is_hydrated(&self) -> Result<bool, JsValue> {
+        Ok(self.lifecycle.lock().map_err(WasmStorageError::lock_poisoned)?.hydrated)
+    }
+
+    /// Marks hydration from `source` as complete, fills in the defaults of
+    /// keys it left unset and runs the init hooks.
+    pub(crate) fn finish_hydration(&mut self, source: &str) -> Result<(), JsValue> {
+        self.apply_defaults()?;
+        let hooks = {
+            let mut lifecycle = self.lifecycle.lock().map_err(WasmStorageError::lock_poisoned)?;
+            lifecycle.hydrated = true;
+            lifecycle.init_hooks.clone()
+        };
+        let event = hook_event("init", Some(source))?;
+        for hook in hooks.iter() {
+            self.run_hook(hook, "init", &event)?;
+        }
+        Ok(())
+    }
+
+    fn run_hook(&self, hook: &js_sys::Function, phase: &str, event: &JsValue) -> Result<(), JsValue> {
+        if let Err(error) = hook.call1(&JsValue::NULL, event) {
+            let source = if phase == "init" { "init hook" } else { "destroy hook" };
+            self.report_error(&error, ErrorSource { source, key: None, action: None, timestamp: js_sys::Date::now() })?;
+        }
+        Ok(())
+    }
+}
+
+fn hook_event(phase: &str, source: Option<&str>) -> Result<JsValue, JsValue> {
+    let event = js_sys::Object::new();
+    js_sys::Reflect::set(&event, &JsValue::from_str("phase"), &JsValue::from_str(phase))?;
+    js_sys::Reflect::set(&event, &JsValue::from_str("source"), &source.map_or(JsValue::NULL, JsValue::from_str))?;
+    Ok(event.into())
+}
@@ -0,0 +1,164 @@
+use crate::WasmStorage;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+
+type Trap = Box<dyn FnMut(JsValue, JsValue, JsValue) -> Result<JsValue, JsValue>>;
+
+fn is_object(value: &JsValue) -> bool {
+    value.is_object() || value.is_function()
+}
+
+fn trap(handler: &js_sys::Object, name: &str, trap: Trap) -> Result<(), JsValue> {
+    let trap = Closure::wrap(trap);
+    js_sys::Reflect::set(handler, &JsValue::from_str(name), &trap.into_js_value())?;
+    Ok(())
+}
+
+// The key's current value followed down `path`, or undefined where it ends
+fn resolve(root: JsValue, path: &[JsValue]) -> Result<JsValue, JsValue> {
+    let mut value = root;
+    for property in path {
+        if !is_object(&value) {
+            return Ok(JsValue::UNDEFINED);
+        }
+        value = js_sys::Reflect::get(&value, property)?;
+    }
+    Ok(value)
+}
+
+// A copy of `base` with `property` at `path` set to `value` (deleted when
+// None), copying each object on the way so stored values are never mutated
+fn with_property(base: &JsValue, path: &[JsValue], property: &JsValue, value: Option<&JsValue>) -> Result<JsValue, JsValue> {
+    let copy: JsValue = if js_sys::Array::is_array(base) {
+        js_sys::Array::from(base).into()
+    } else if is_object(base) {
+        js_sys::Object::assign(&js_sys::Object::new(), base.unchecked_ref()).into()
+    } else {
+        js_sys::Object::new().into()
+    };
+    match path.split_first() {
+        Some((next, rest)) => {
+            let child = with_property(&js_sys::Reflect::get(&copy, next)?, rest, property, value)?;
+            js_sys::Reflect::set(&copy, next, &child)?;
+        }
+        None => match value {
+            Some(value) => {
+                js_sys::Reflect::set(&copy, property, value)?;
+            }
+            None => {
+                js_sys::Reflect::delete_property(copy.unchecked_ref::<js_sys::Object>(), property)?;
+            }
+        },
+    }
+    Ok(copy)
+}
+
+fn descriptor(value: &JsValue) -> Result<JsValue, JsValue> {
+    let descriptor = js_sys::Object::new();
+    js_sys::Reflect::set(&descriptor, &JsValue::from_str("value"), value)?;
+    for flag in ["writable", "enumerable", "configurable"] {
+        js_sys::Reflect::set(&descriptor, &JsValue::from_str(flag), &JsValue::TRUE)?;
+    }
+    Ok(descriptor.into())
+}
+
+#[wasm_bindgen]
+impl WasmStorage {
+    /// A `Proxy` over the whole state for quick prototypes:
+    /// `state.user.name = "x"` reads through `get_state` and writes a copy
+    /// of `user` through `set_state`, so middleware, validators and
+    /// listeners all see it. Nested objects come back as proxies too, and
+    /// `delete` and `in` map to `remove_state` and `has`.
+    #[wasm_bindgen]
+    pub fn get_live_proxy(&self) -> Result<js_sys::Proxy, JsValue> {
+        self.live_proxy(None, Vec::new())
+    }
+}
+
+impl WasmStorage {
+    // `key` is None for the top level and `path` the properties below it
+    fn live_proxy(&self, key: Option<String>, path: Vec<JsValue>) -> Result<js_sys::Proxy, JsValue> {
+        let handler = js_sys::Object::new();
+
+        let (store, root, at) = (self.clone(), key.clone(), path.clone());
+        trap(&handler, "get", Box::new(move |_, property, _| {
+            let Some(key) = root.clone().or_else(|| property.as_string()) else {
+                return Ok(JsValue::UNDEFINED);
+            };
+            let mut path = at.clone();
+            if root.is_some() {
+                path.push(property);
+            }
+            let value = resolve(store.get_state(&key)?, &path)?;
+            match value.is_object() {
+                true => Ok(store.live_proxy(Some(key), path)?.into()),
+                false => Ok(value),
+            }
+        }))?;
+
+        let (mut store, root, at) = (self.clone(), key.clone(), path.clone());
+        trap(&handler, "set", Box::new(move |_, property, value| {
+            match &root {
+                Some(key) => {
+                    let updated = with_property(&store.get_state(key)?, &at, &property, Some(&value))?;
+                    store.set_state(key, updated)?;
+                }
+                None => store.set_state(&property.as_string().unwrap_or_default(), value)?,
+            }
+            Ok(JsValue::TRUE)
+        }))?;
+
+        let (mut store, root, at) = (self.clone(), key.clone(), path.clone());
+        trap(&handler, "deleteProperty", Box::new(move |_, property, _| {
+            match &root {
+                Some(key) => {
+                    let updated = with_property(&store.get_state(key)?, &at, &property, None)?;
+                    store.set_state(key, updated)?;
+                }
+                None => store.remove_state(&property.as_string().unwrap_or_default())?,
+            }
+            Ok(JsValue::TRUE)
+        }))?;
+
+        let (store, root, at) = (self.clone(), key.clone(), path.clone());
+        trap(&handler, "has", Box::new(move |_, property, _| match &root {
+            Some(key) => {
+                let value = resolve(store.get_state(key)?, &at)?;
+                Ok(JsValue::from_bool(is_object(&value) && js_sys::Reflect::has(&value, &property)?))
+            }
+            None => Ok(JsValue::from_bool(property.as_string().map(|key| store.has(&key)).transpose()?.unwrap_or(false))),
+        }))?;
+
+        let (store, root, at) = (self.clone(), key.clone(), path.clone());
+        trap(&handler, "ownKeys", Box::new(move |_, _, _| match &root {
+            Some(key) => {
+                let value = resolve(store.get_state(key)?, &at)?;
+                match is_object(&value) {
+                    true => Ok(js_sys::Reflect::own_keys(value.unchecked_ref())?.into()),
+                    false => Ok(js_sys::Array::new().into()),
+                }
+            }
+            None => Ok(store.keys()?.into()),
+        }))?;
+
+        let (store, root, at) = (self.clone(), key, path);
+        trap(&handler, "getOwnPropertyDescriptor", Box::new(move |_, property, _| {
+            let value = match &root {
+                Some(key) => {
+                    let value = resolve(store.get_state(key)?, &at)?;
+                    if !is_object(&value) || !js_sys::Object::has_own(value.unchecked_ref::<js_sys::Object>(), &property) {
+                        return Ok(JsValue::UNDEFINED);
+                    }
+                    js_sys::Reflect::get(&value, &property)?
+                }
+                None => match property.as_string() {
+                    Some(key) if store.has(&key)? => store.get_state(&key)?,
+                    _ => return Ok(JsValue::UNDEFINED),
+                },
+            };
+            descriptor(&value)
+        }))?;
+
+        Ok(js_sys::Proxy::new(&js_sys::Object::new(), &handler))
+    }
+}
@@ -0,0 +1,201 @@
+use crate::backends::spawn_write;
+use crate::error::WasmStorageError;
+use crate::error_handler::ErrorSource;
+use crate::rate_limit::{clear_timeout, set_timeout};
+use crate::WasmStorage;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::JsFuture;
+
+const DEFAULT_TIMEOUT_MS: f64 = 5000.0;
+
+pub(crate) enum LockedWrite {
+    Snapshot(Vec<(String, String)>),
+    Wal(Option<String>, Option<String>),
+}
+
+/// Persistence writes made while holding a Web Lock, so tabs sharing a
+/// backend never interleave a snapshot with each other's writes. Locks are
+/// granted asynchronously, so writes wait in a queue drained by a background
+/// task.
+pub(crate) struct WebLocks {
+    name: String,
+    timeout_ms: f64,
+    listeners: Vec<js_sys::Function>,
+    pending: Vec<LockedWrite>,
+    writing: bool,
+}
+
+fn lock_manager() -> Result<JsValue, JsValue> {
+    let navigator = js_sys::Reflect::get(&js_sys::global(), &JsValue::from_str("navigator"))?;
+    let locks = if navigator.is_undefined() {
+        JsValue::UNDEFINED
+    } else {
+        js_sys::Reflect::get(&navigator, &JsValue::from_str("locks"))?
+    };
+    if locks.is_undefined() || locks.is_null() {
+        return Err(WasmStorageError::unsupported("The Web Locks API is not available in this environment").into());
+    }
+    Ok(locks)
+}
+
+#[wasm_bindgen]
+impl WasmStorage {
+    /// Takes an exclusive `navigator.locks` lock around every snapshot and
+    /// write-ahead log write, so tabs flushing the same backend take turns.
+    /// Requires `enable_persistence`. Options: `name` (default
+    /// "wasm-storage:<storageKey>") and `timeoutMs` (how long a write waits
+    /// for the lock before failing with INVALID_STATE, default 5000). The
+    /// writes stay queued after a timeout and go out with the next attempt.
+    #[wasm_bindgen]
+    pub fn enable_web_locks(&mut self, options: Option<js_sys::Object>) -> Result<(), JsValue> {
+        lock_manager()?;
+        let (storage_key, _) = self
+            .persist_targets()?
+            .ok_or_else(|| WasmStorageError::invalid_state("Web Locks need persistence; call enable_persistence first"))?;
+        let options = options.map(JsValue::from).unwrap_or(JsValue::UNDEFINED);
+        let name = js_sys::Reflect::get(&options, &JsValue::from_str("name"))?
+            .as_string()
+            .unwrap_or_else(|| format!("wasm-storage:{}", storage_key));
+        let timeout_ms = js_sys::Reflect::get(&options, &JsValue::from_str("timeoutMs"))?
+            .as_f64()
+            .unwrap_or(DEFAULT_TIMEOUT_MS)
+            .max(0.0);
+
+        let mut locks = self.web_locks.lock().map_err(WasmStorageError::lock_poisoned)?;
+        let listeners = locks.take().map(|locks| locks.listeners).unwrap_or_default();
+        *locks = Some(WebLocks { name, timeout_ms, listeners, pending: Vec::new(), writing: false });
+        Ok(())
+    }
+
+    /// Calls `callback({ lock, queued })` when a write finds the lock held
+    /// by another tab and has to wait; `queued` is how many writes wait.
+    #[wasm_bindgen]
+    pub fn on_lock_contended(&mut self, callback: js_sys::Function) -> Result<(), JsValue> {
+        let mut locks = self.web_locks.lock().map_err(WasmStorageError::lock_poisoned)?;
+        let locks = locks
+            .as_mut()
+            .ok_or_else(|| WasmStorageError::invalid_state("Web Locks are not enabled"))?;
+        locks.listeners.push(callback);
+        Ok(())
+    }
+}
+
+impl WasmStorage {
+    /// Queues a write for the lock holder's turn; false when Web Locks are
+    /// off and the caller should write right away.
+    pub(crate) fn queue_locked(&self, write: LockedWrite) -> Result<bool, JsValue> {
+        {
+            let mut locks = self.web_locks.lock().map_err(WasmStorageError::lock_poisoned)?;
+            let Some(locks) = locks.as_mut() else {
+                return Ok(false);
+            };
+            // A snapshot holds everything queued before it
+            if matches!(write, LockedWrite::Snapshot(_)) {
+                locks.pending.clear();
+            }
+            locks.pending.push(write);
+            if locks.writing {
+                return Ok(true);
+            }
+            locks.writing = true;
+        }
+        let mut store = self.clone();
+        spawn_write(&self.backend_writes, async move {
+            if let Err(error) = store.drain_locked().await {
+                if let Ok(Some(locks)) = store.web_locks.lock().as_deref_mut() {
+                    locks.writing = false;
+                }
+                let source = ErrorSource { source: "locks", key: None, action: None, timestamp: js_sys::Date::now() };
+                let _ = store.report_error(&error, source);
+            }
+        });
+        Ok(true)
+    }
+
+    async fn drain_locked(&mut self) -> Result<(), JsValue> {
+        loop {
+            let (name, timeout_ms) = {
+                let mut locks = self.web_locks.lock().map_err(WasmStorageError::lock_poisoned)?;
+                let Some(locks) = locks.as_mut() else {
+                    return Ok(());
+                };
+                if locks.pending.is_empty() {
+                    locks.writing = false;
+                    return Ok(());
+                }
+                (locks.name.clone(), locks.timeout_ms)
+            };
+
+            let if_available = js_sys::Object::new();
+            js_sys::Reflect::set(&if_available, &JsValue::from_str("ifAvailable"), &JsValue::TRUE)?;
+            if self.request_lock(&name, &if_available).await?.is_truthy() {
+                continue;
+            }
+            self.notify_contended(&name)?;
+
+            let controller = web_sys::AbortController::new()?;
+            let options = js_sys::Object::new();
+            js_sys::Reflect::set(&options, &JsValue::from_str("signal"), &controller.signal())?;
+            let abort_controller = controller.clone();
+            let timer = set_timeout(&Closure::once_into_js(move || abort_controller.abort()), timeout_ms)?;
+            let granted = self.request_lock(&name, &options).await;
+            clear_timeout(&timer)?;
+            if granted.is_err() && controller.signal().aborted() {
+                return Err(WasmStorageError::invalid_state(format!("Timed out waiting {}ms for lock {}", timeout_ms, name))
+                    .with("lock", name.as_str())
+                    .with("timeoutMs", timeout_ms)
+                    .into());
+            }
+            granted?;
+        }
+    }
+
+    // Resolves to whether the lock was granted (and the queue written)
+    async fn request_lock(&self, name: &str, options: &js_sys::Object) -> Result<JsValue, JsValue> {
+        let locks = lock_manager()?;
+        let request = js_sys::Reflect::get(&locks, &JsValue::from_str("request"))?.dyn_into::<js_sys::Function>()?;
+        let mut store = self.clone();
+        // Writes are synchronous, so the lock is released as soon as this returns
+        let callback = Closure::once_into_js(move |lock: JsValue| -> Result<JsValue, JsValue> {
+            if lock.is_null() {
+                return Ok(JsValue::FALSE);
+            }
+            store.write_locked()?;
+            Ok(JsValue::TRUE)
+        });
+        let granted = request.call3(&locks, &JsValue::from_str(name), options, &callback)?;
+        JsFuture::from(js_sys::Promise::from(granted)).await
+    }
+
+    fn write_locked(&mut self) -> Result<(), JsValue> {
+        let pending = match self.web_locks.lock().map_err(WasmStorageError::lock_poisoned)?.as_mut() {
+            Some(locks) => std::mem::take(&mut locks.pending),
+            None => return Ok(()),
+        };
+        for write in pending {
+            match write {
+                LockedWrite::Snapshot(entries) => self.write_entries_now(entries)?,
+                LockedWrite::Wal(key, json) => self.append_wal_now(key.as_deref(), json.as_deref())?,
+            }
+        }
+        Ok(())
+    }
+
+    fn notify_contended(&self, name: &str) -> Result<(), JsValue> {
+        let (listeners, queued) = match self.web_locks.lock().map_err(WasmStorageError::lock_poisoned)?.as_ref() {
+            Some(locks) => (locks.listeners.clone(), locks.pending.len()),
+            None => return Ok(()),
+        };
+        if listeners.is_empty() {
+            return Ok(());
+        }
+        let event = js_sys::Object::new();
+        js_sys::Reflect::set(&event, &JsValue::from_str("lock"), &JsValue::from_str(name))?;
+        js_sys::Reflect::set(&event, &JsValue::from_str("queued"), &JsValue::from(queued as u32))?;
+        for listener in listeners {
+            listener.call1(&JsValue::NULL, &event)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,232 @@
+use crate::clock::monotonic_now;
+use crate::error::WasmStorageError;
+use crate::persistent_map::PersistentMap;
+use crate::{changed_keys, StorageEvent, WasmStorage};
+use std::cell::{Cell, RefCell};
+use wasm_bindgen::prelude::*;
+
+/// Severity of a store log message, least severe first.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub(crate) enum LogLevel {
+    Debug,
+    Info,
+    Warn,
+    Error,
+    Silent,
+}
+
+impl LogLevel {
+    fn as_str(self) -> &'static str {
+        match self {
+            LogLevel::Debug => "debug",
+            LogLevel::Info => "info",
+            LogLevel::Warn => "warn",
+            LogLevel::Error => "error",
+            LogLevel::Silent => "silent",
+        }
+    }
+
+    fn parse(level: &str) -> Result<LogLevel, JsValue> {
+        match level {
+            "debug" => Ok(LogLevel::Debug),
+            "info" => Ok(LogLevel::Info),
+            "warn" => Ok(LogLevel::Warn),
+            "error" => Ok(LogLevel::Error),
+            "silent" => Ok(LogLevel::Silent),
+            _ => Err(WasmStorageError::invalid_argument(format!(
+                "Unknown log level \"{}\" (expected debug, info, warn, error or silent)",
+                level
+            ))
+            .with("level", level)
+            .into()),
+        }
+    }
+}
+
+thread_local! {
+    // Shared by every store in the module
+    static THRESHOLD: Cell<LogLevel> = const { Cell::new(LogLevel::Info) };
+    static SINK: RefCell<Option<js_sys::Function>> = const { RefCell::new(None) };
+    // Console output in release builds, requested by `init_diagnostics`
+    static FORCED: Cell<bool> = const { Cell::new(false) };
+}
+
+pub(crate) fn enabled(level: LogLevel) -> bool {
+    let heard = SINK.with(|sink| sink.borrow().is_some()) || FORCED.with(Cell::get) || cfg!(debug_assertions);
+    heard && level != LogLevel::Silent && level >= THRESHOLD.with(Cell::get)
+}
+
+// Hands `{ level, message, data }` to the `set_logger` callback; false
+// when there is none and the console should be used
+fn to_sink(level: LogLevel, message: &str, data: Option<&JsValue>) -> bool {
+    let Some(sink) = SINK.with(|sink| sink.borrow().clone()) else {
+        return false;
+    };
+    let entry = js_sys::Object::new();
+    let _ = js_sys::Reflect::set(&entry, &JsValue::from_str("level"), &JsValue::from_str(level.as_str()));
+    let _ = js_sys::Reflect::set(&entry, &JsValue::from_str("message"), &JsValue::from_str(message));
+    if let Some(data) = data {
+        let _ = js_sys::Reflect::set(&entry, &JsValue::from_str("data"), data);
+    }
+    // A throwing sink has nowhere to report to
+    let _ = sink.call1(&JsValue::NULL, &entry);
+    true
+}
+
+/// Writes `message` to the `set_logger` sink (or the console) if `level`
+/// passes the threshold. Used through the `log_debug!`, `log_info!`,
+/// `log_warn!` and `log_error!` macros.
+pub(crate) fn emit(level: LogLevel, message: &str) {
+    if !enabled(level) || to_sink(level, message, None) {
+        return;
+    }
+    let message = JsValue::from_str(message);
+    match level {
+        LogLevel::Debug => web_sys::console::debug_1(&message),
+        LogLevel::Warn => web_sys::console::warn_1(&message),
+        LogLevel::Error => web_sys::console::error_1(&message),
+        _ => web_sys::console::log_1(&message),
+    }
+}
+
+/// Sets the least severe level the module logs: `"debug"`, `"info"` (the
+/// default), `"warn"`, `"error"` or `"silent"`. It applies to every store,
+/// including entries of `enable_logger`.
+#[wasm_bindgen]
+pub fn set_log_level(level: &str) -> Result<(), JsValue> {
+    let level = LogLevel::parse(level)?;
+    THRESHOLD.with(|threshold| threshold.set(level));
+    Ok(())
+}
+
+/// Logs at `level` and up even in release builds, where the console is
+/// otherwise silent.
+pub(crate) fn force_level(level: LogLevel) {
+    THRESHOLD.with(|threshold| threshold.set(level));
+    FORCED.with(|forced| forced.set(true));
+}
+
+/// Routes every log message of the module to `sink({ level, message,
+/// data })` instead of the console, e.g. into the host app's telemetry;
+/// `data` carries the details of `enable_logger` entries. A no-op sink
+/// silences logging. `None` restores the default: the console in debug
+/// builds and nothing in release builds.
+#[wasm_bindgen]
+pub fn set_logger(sink: Option<js_sys::Function>) {
+    SINK.with(|current| *current.borrow_mut() = sink);
+}
+
+/// Settings of the built-in action logger.
+pub(crate) struct ActionLogger {
+    level: LogLevel,
+    collapsed: bool,
+    diff: bool,
+}
+
+/// What the logger captures before an action is applied. The state map
+/// clones in O(1), so this costs nothing until something is written.
+pub(crate) struct LoggedDispatch {
+    before: PersistentMap<JsValue>,
+    started: f64,
+}
+
+#[wasm_bindgen]
+impl WasmStorage {
+    /// Logs every dispatched action as a console group (or one `set_logger`
+    /// entry): its type, payload (after middleware), the keys it changed
+    /// before and after, and how long it took. `options.level` (default
+    /// `"info"`) is the level the entries are logged at, `options.collapsed`
+    /// uses collapsed groups, and `options.diff` lists each changed key with
+    /// its old and new value instead of the two state slices.
+    #[wasm_bindgen]
+    pub fn enable_logger(&mut self, options: Option<js_sys::Object>) -> Result<(), JsValue> {
+        let options = options.map(JsValue::from).unwrap_or(JsValue::UNDEFINED);
+        let option = |name: &str| js_sys::Reflect::get(&options, &JsValue::from_str(name)).unwrap_or(JsValue::UNDEFINED);
+        let level = match option("level").as_string() {
+            Some(level) => LogLevel::parse(&level)?,
+            None => LogLevel::Info,
+        };
+        let logger = ActionLogger { level, collapsed: option("collapsed").is_truthy(), diff: option("diff").is_truthy() };
+        *self.logger.lock().map_err(WasmStorageError::lock_poisoned)? = Some(logger);
+        Ok(())
+    }
+
+    #[wasm_bindgen]
+    pub fn disable_logger(&mut self) -> Result<(), JsValue> {
+        *self.logger.lock().map_err(WasmStorageError::lock_poisoned)? = None;
+        Ok(())
+    }
+}
+
+impl WasmStorage {
+    /// Starts logging a dispatch, when the logger is on and would be heard.
+    pub(crate) fn begin_logged_dispatch(&self) -> Result<Option<LoggedDispatch>, JsValue> {
+        let logging = match &*self.logger.lock().map_err(WasmStorageError::lock_poisoned)? {
+            Some(logger) => enabled(logger.level),
+            None => false,
+        };
+        if !logging {
+            return Ok(None);
+        }
+        let before = self.state.lock().map_err(WasmStorageError::lock_poisoned)?.clone();
+        Ok(Some(LoggedDispatch { before, started: monotonic_now() }))
+    }
+
+    pub(crate) fn end_logged_dispatch(&self, dispatch: LoggedDispatch, event: &StorageEvent) -> Result<(), JsValue> {
+        let duration = monotonic_now() - dispatch.started;
+        let (level, collapsed, diff) = match &*self.logger.lock().map_err(WasmStorageError::lock_poisoned)? {
+            Some(logger) => (logger.level, logger.collapsed, logger.diff),
+            None => return Ok(()),
+        };
+        let after = self.state.lock().map_err(WasmStorageError::lock_poisoned)?.clone();
+
+        let changed = changed_keys(&dispatch.before, &after);
+        let (prev, next, changes) = (js_sys::Object::new(), js_sys::Object::new(), js_sys::Array::new());
+        for key in changed {
+            let old = dispatch.before.get(key).cloned().unwrap_or(JsValue::UNDEFINED);
+            let new = after.get(key).cloned().unwrap_or(JsValue::UNDEFINED);
+            let kind = match (old.is_undefined(), new.is_undefined()) {
+                (true, _) => "added",
+                (_, true) => "deleted",
+                _ => "changed",
+            };
+            js_sys::Reflect::set(&prev, &JsValue::from_str(key), &old)?;
+            js_sys::Reflect::set(&next, &JsValue::from_str(key), &new)?;
+            changes.push(&js_sys::Array::of4(&JsValue::from_str(kind), &JsValue::from_str(key), &old, &new));
+        }
+
+        let title = format!("action {} ({:.2} ms)", event.action_type, duration);
+        let data = js_sys::Object::new();
+        js_sys::Reflect::set(&data, &JsValue::from_str("type"), &JsValue::from_str(&event.action_type))?;
+        js_sys::Reflect::set(&data, &JsValue::from_str("payload"), &event.payload)?;
+        js_sys::Reflect::set(&data, &JsValue::from_str("prev"), &prev)?;
+        js_sys::Reflect::set(&data, &JsValue::from_str("next"), &next)?;
+        js_sys::Reflect::set(&data, &JsValue::from_str("duration"), &JsValue::from_f64(duration))?;
+        if diff {
+            js_sys::Reflect::set(&data, &JsValue::from_str("diff"), &changes)?;
+        }
+        if to_sink(level, &title, Some(&data)) {
+            return Ok(());
+        }
+
+        let title = JsValue::from_str(&title);
+        if collapsed {
+            web_sys::console::group_collapsed_1(&title);
+        } else {
+            web_sys::console::group_1(&title);
+        }
+        web_sys::console::log_2(&JsValue::from_str("payload"), &event.payload);
+        if diff {
+            for change in changes.iter() {
+                let change = js_sys::Array::from(&change);
+                let label = format!("{} {}", change.get(0).as_string().unwrap_or_default(), change.get(1).as_string().unwrap_or_default());
+                web_sys::console::log_4(&JsValue::from_str(&label), &change.get(2), &JsValue::from_str("→"), &change.get(3));
+            }
+        } else {
+            web_sys::console::log_2(&JsValue::from_str("prev state"), &prev);
+            web_sys::console::log_2(&JsValue::from_str("next state"), &next);
+        }
+        web_sys::console::group_end();
+        Ok(())
+    }
+}
@@ -0,0 +1,157 @@
+use crate::clock::monotonic_now;
+use crate::error::WasmStorageError;
+use crate::WasmStorage;
+use std::collections::HashMap;
+use wasm_bindgen::prelude::*;
+
+#[derive(Default, Clone, Copy)]
+struct ActionMetrics {
+    count: u32,
+    total_ms: f64,
+    max_ms: f64,
+    middleware_ms: f64,
+    listeners_notified: u32,
+}
+
+/// Dispatch timings and counters, only kept while metrics are enabled.
+#[derive(Default)]
+pub(crate) struct Metrics {
+    actions: HashMap<String, ActionMetrics>,
+    writes: HashMap<String, u32>,
+    // Every listener call so far; a dispatch's share is the difference
+    listeners_notified: u32,
+    on_event: Option<js_sys::Function>,
+}
+
+/// Taken when a dispatch starts, for `end_metered_dispatch`.
+pub(crate) struct MeteredDispatch {
+    started: f64,
+    notified_before: u32,
+    middleware_ms: f64,
+}
+
+impl MeteredDispatch {
+    pub(crate) fn add_middleware_time(&mut self, ms: f64) {
+        self.middleware_ms += ms;
+    }
+}
+
+#[wasm_bindgen]
+impl WasmStorage {
+    /// Starts collecting metrics for `get_metrics`: per action type the
+    /// dispatch count and duration, time spent in middleware and listeners
+    /// notified, plus writes per key. `on_event`, if given, receives
+    /// `{ type: "dispatch", action, duration, middleware, listenersNotified,
+    /// timestamp }` after every dispatch to stream them elsewhere. Enabling
+    /// again keeps the collected metrics and replaces the callback.
+    #[wasm_bindgen]
+    pub fn enable_metrics(&mut self, on_event: Option<js_sys::Function>) -> Result<(), JsValue> {
+        let mut metrics = self.metrics.lock().map_err(WasmStorageError::lock_poisoned)?;
+        metrics.get_or_insert_with(Metrics::default).on_event = on_event;
+        Ok(())
+    }
+
+    /// Stops collecting and discards the metrics.
+    #[wasm_bindgen]
+    pub fn disable_metrics(&mut self) -> Result<(), JsValue> {
+        *self.metrics.lock().map_err(WasmStorageError::lock_poisoned)? = None;
+        Ok(())
+    }
+
+    /// Clears the collected metrics, keeping collection on.
+    #[wasm_bindgen]
+    pub fn reset_metrics(&mut self) -> Result<(), JsValue> {
+        if let Some(metrics) = self.metrics.lock().map_err(WasmStorageError::lock_poisoned)?.as_mut() {
+            let on_event = metrics.on_event.take();
+            *metrics = Metrics { on_event, ..Metrics::default() };
+        }
+        Ok(())
+    }
+
+    /// `{ actions: { [type]: { count, totalMs, averageMs, maxMs,
+    /// middlewareMs, listenersNotified } }, writes: { [key]: count },
+    /// listenersNotified }`, with durations in milliseconds.
+    #[wasm_bindgen]
+    pub fn get_metrics(&self) -> Result<js_sys::Object, JsValue> {
+        let metrics = self.metrics.lock().map_err(WasmStorageError::lock_poisoned)?;
+        let metrics = metrics.as_ref().ok_or_else(|| WasmStorageError::invalid_state("Metrics are not enabled"))?;
+
+        let actions = js_sys::Object::new();
+        for (action_type, action) in metrics.actions.iter() {
+            let entry = js_sys::Object::new();
+            js_sys::Reflect::set(&entry, &JsValue::from_str("count"), &JsValue::from(action.count))?;
+            js_sys::Reflect::set(&entry, &JsValue::from_str("totalMs"), &JsValue::from_f64(action.total_ms))?;
+            js_sys::Reflect::set(&entry, &JsValue::from_str("averageMs"), &JsValue::from_f64(action.total_ms / action.count as f64))?;
+            js_sys::Reflect::set(&entry, &JsValue::from_str("maxMs"), &JsValue::from_f64(action.max_ms))?;
+            js_sys::Reflect::set(&entry, &JsValue::from_str("middlewareMs"), &JsValue::from_f64(action.middleware_ms))?;
+            js_sys::Reflect::set(&entry, &JsValue::from_str("listenersNotified"), &JsValue::from(action.listeners_notified))?;
+            js_sys::Reflect::set(&actions, &JsValue::from_str(action_type), &entry)?;
+        }
+        let writes = js_sys::Object::new();
+        for (key, count) in metrics.writes.iter() {
+            js_sys::Reflect::set(&writes, &JsValue::from_str(key), &JsValue::from(*count))?;
+        }
+
+        let result = js_sys::Object::new();
+        js_sys::Reflect::set(&result, &JsValue::from_str("actions"), &actions)?;
+        js_sys::Reflect::set(&result, &JsValue::from_str("writes"), &writes)?;
+        js_sys::Reflect::set(&result, &JsValue::from_str("listenersNotified"), &JsValue::from(metrics.listeners_notified))?;
+        Ok(result)
+    }
+}
+
+impl WasmStorage {
+    pub(crate) fn begin_metered_dispatch(&self) -> Result<Option<MeteredDispatch>, JsValue> {
+        let metrics = self.metrics.lock().map_err(WasmStorageError::lock_poisoned)?;
+        Ok(metrics.as_ref().map(|metrics| MeteredDispatch {
+            started: monotonic_now(),
+            notified_before: metrics.listeners_notified,
+            middleware_ms: 0.0,
+        }))
+    }
+
+    pub(crate) fn end_metered_dispatch(&self, dispatch: MeteredDispatch, action_type: &str) -> Result<(), JsValue> {
+        let duration = monotonic_now() - dispatch.started;
+        let (notified, on_event) = {
+            let mut metrics = self.metrics.lock().map_err(WasmStorageError::lock_poisoned)?;
+            // Disabled while dispatching
+            let Some(metrics) = metrics.as_mut() else {
+                return Ok(());
+            };
+            let notified = metrics.listeners_notified - dispatch.notified_before;
+            let action = metrics.actions.entry(action_type.to_string()).or_default();
+            action.count += 1;
+            action.total_ms += duration;
+            action.max_ms = action.max_ms.max(duration);
+            action.middleware_ms += dispatch.middleware_ms;
+            action.listeners_notified += notified;
+            (notified, metrics.on_event.clone())
+        };
+
+        if let Some(on_event) = on_event {
+            let event = js_sys::Object::new();
+            js_sys::Reflect::set(&event, &JsValue::from_str("type"), &JsValue::from_str("dispatch"))?;
+            js_sys::Reflect::set(&event, &JsValue::from_str("action"), &JsValue::from_str(action_type))?;
+            js_sys::Reflect::set(&event, &JsValue::from_str("duration"), &JsValue::from_f64(duration))?;
+            js_sys::Reflect::set(&event, &JsValue::from_str("middleware"), &JsValue::from_f64(dispatch.middleware_ms))?;
+            js_sys::Reflect::set(&event, &JsValue::from_str("listenersNotified"), &JsValue::from(notified))?;
+            js_sys::Reflect::set(&event, &JsValue::from_str("timestamp"), &JsValue::from_f64(self.now()?))?;
+            on_event.call1(&JsValue::NULL, &event)?;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn meter_write(&self, key: &str) -> Result<(), JsValue> {
+        if let Some(metrics) = self.metrics.lock().map_err(WasmStorageError::lock_poisoned)?.as_mut() {
+            *metrics.writes.entry(key.to_string()).or_default() += 1;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn meter_notified(&self, count: u32) -> Result<(), JsValue> {
+        if let Some(metrics) = self.metrics.lock().map_err(WasmStorageError::lock_poisoned)?.as_mut() {
+            metrics.listeners_notified += count;
+        }
+        Ok(())
+    }
+}
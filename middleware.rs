@@ -0,0 +1,131 @@
+use crate::error::WasmStorageError;
+use crate::subscriptions::priority;
+use crate::WasmStorage;
+use wasm_bindgen::prelude::*;
+
+struct Entry {
+    id: u32,
+    name: Option<String>,
+    priority: i32,
+    enabled: bool,
+    function: js_sys::Function,
+}
+
+/// Registered middleware in list order. They run by descending priority,
+/// and in list order within a priority.
+#[derive(Default)]
+pub(crate) struct MiddlewareChain {
+    next_id: u32,
+    entries: Vec<Entry>,
+}
+
+impl MiddlewareChain {
+    /// The enabled middleware, in the order they run.
+    pub(crate) fn snapshot(&self) -> Vec<js_sys::Function> {
+        let mut entries: Vec<&Entry> = self.entries.iter().filter(|entry| entry.enabled).collect();
+        // Stable, so equal priorities keep list order
+        entries.sort_by_key(|entry| std::cmp::Reverse(entry.priority));
+        entries.into_iter().map(|entry| entry.function.clone()).collect()
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    // A name already in use is replaced in place, keeping its position
+    fn insert(&mut self, position: Option<usize>, function: js_sys::Function, options: &JsValue) -> Result<u32, JsValue> {
+        let option = |name: &str| js_sys::Reflect::get(options, &JsValue::from_str(name)).unwrap_or(JsValue::UNDEFINED);
+        let name = option("name").as_string();
+        let priority = priority(&option("priority"))?;
+        let enabled = option("enabled").as_bool().unwrap_or(true);
+
+        self.next_id += 1;
+        let entry = Entry { id: self.next_id, name, priority, enabled, function };
+        let existing = entry.name.as_ref().and_then(|name| self.entries.iter().position(|other| other.name.as_ref() == Some(name)));
+        match (existing, position) {
+            (Some(index), _) => self.entries[index] = entry,
+            (None, Some(position)) => self.entries.insert(position.min(self.entries.len()), entry),
+            (None, None) => self.entries.push(entry),
+        }
+        Ok(self.next_id)
+    }
+
+    // `handle` is the ID returned when the middleware was added, or its name
+    fn find(&mut self, handle: &JsValue) -> Option<&mut Entry> {
+        match handle.as_string() {
+            Some(name) => self.entries.iter_mut().find(|entry| entry.name.as_deref() == Some(name.as_str())),
+            None => {
+                let id = handle.as_f64()?;
+                self.entries.iter_mut().find(|entry| entry.id as f64 == id)
+            }
+        }
+    }
+}
+
+#[wasm_bindgen]
+impl WasmStorage {
+    /// Appends middleware and returns its ID for `remove_middleware`.
+    /// `options.name` names it, replacing any middleware of the same name;
+    /// `options.priority` (an integer, default 0) runs it before middleware
+    /// of lower priority; `options.enabled: false` adds it switched off.
+    #[wasm_bindgen]
+    pub fn add_middleware(&mut self, middleware_fn: js_sys::Function, options: Option<js_sys::Object>) -> Result<u32, JsValue> {
+        let options = options.map(JsValue::from).unwrap_or(JsValue::UNDEFINED);
+        self.middleware.lock().map_err(WasmStorageError::lock_poisoned)?.insert(None, middleware_fn, &options)
+    }
+
+    /// Like `add_middleware`, but at `position` in the list (0 is first;
+    /// past the end appends). Priorities still take precedence.
+    #[wasm_bindgen]
+    pub fn insert_middleware(&mut self, position: u32, middleware_fn: js_sys::Function, options: Option<js_sys::Object>) -> Result<u32, JsValue> {
+        let options = options.map(JsValue::from).unwrap_or(JsValue::UNDEFINED);
+        self.middleware.lock().map_err(WasmStorageError::lock_poisoned)?.insert(Some(position as usize), middleware_fn, &options)
+    }
+
+    /// Removes middleware by ID or name; false when there was none.
+    #[wasm_bindgen]
+    pub fn remove_middleware(&mut self, handle: JsValue) -> Result<bool, JsValue> {
+        let mut chain = self.middleware.lock().map_err(WasmStorageError::lock_poisoned)?;
+        let Some(id) = chain.find(&handle).map(|entry| entry.id) else {
+            return Ok(false);
+        };
+        chain.entries.retain(|entry| entry.id != id);
+        Ok(true)
+    }
+
+    /// Switches middleware (by ID or name) off or back on without losing
+    /// its place, e.g. from a feature flag.
+    #[wasm_bindgen]
+    pub fn set_middleware_enabled(&mut self, handle: JsValue, enabled: bool) -> Result<(), JsValue> {
+        let mut chain = self.middleware.lock().map_err(WasmStorageError::lock_poisoned)?;
+        let entry = chain
+            .find(&handle)
+            .ok_or_else(|| WasmStorageError::invalid_argument(format!("No middleware matches {:?}", handle)).with("handle", handle.clone()))?;
+        entry.enabled = enabled;
+        Ok(())
+    }
+
+    /// `{ id, name, priority, enabled }` for every middleware, in the order
+    /// they run (disabled ones where they would).
+    #[wasm_bindgen]
+    pub fn list_middleware(&self) -> Result<js_sys::Array, JsValue> {
+        let chain = self.middleware.lock().map_err(WasmStorageError::lock_poisoned)?;
+        let mut entries: Vec<&Entry> = chain.entries.iter().collect();
+        entries.sort_by_key(|entry| std::cmp::Reverse(entry.priority));
+        let list = js_sys::Array::new();
+        for entry in entries {
+            let item = js_sys::Object::new();
+            js_sys::Reflect::set(&item, &JsValue::from_str("id"), &JsValue::from(entry.id))?;
+            js_sys::Reflect::set(&item, &JsValue::from_str("name"), &entry.name.as_deref().map(JsValue::from_str).unwrap_or(JsValue::NULL))?;
+            js_sys::Reflect::set(&item, &JsValue::from_str("priority"), &JsValue::from(entry.priority))?;
+            js_sys::Reflect::set(&item, &JsValue::from_str("enabled"), &JsValue::from_bool(entry.enabled))?;
+            list.push(&item);
+        }
+        Ok(list)
+    }
+}
+
@@ -0,0 +1,90 @@
+use crate::WasmStorage;
+use crate::error::WasmStorageError;
+use std::collections::HashMap;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+
+const LOG_PREFIX: &str = "[wasm-storage middleware]";
+
+/// State behind the `context` argument handed to middleware. The JS object
+/// is built once and reused, since its closures only reach the store through
+/// shared handles.
+#[derive(Default)]
+pub(crate) struct MiddlewareContext {
+    context: Option<js_sys::Object>,
+    metrics: HashMap<String, f64>,
+}
+
+#[wasm_bindgen]
+impl WasmStorage {
+    /// Counters recorded by middleware through `context.metrics.increment(name, by)`.
+    #[wasm_bindgen]
+    pub fn middleware_metrics(&self) -> Result<js_sys::Object, JsValue> {
+        let ctx = self.middleware_context.lock().map_err(WasmStorageError::lock_poisoned)?;
+        let metrics = js_sys::Object::new();
+        for (name, value) in ctx.metrics.iter() {
+            js_sys::Reflect::set(&metrics, &JsValue::from_str(name), &JsValue::from_f64(*value))?;
+        }
+        Ok(metrics)
+    }
+}
+
+impl WasmStorage {
+    /// The `{ getState, dispatch, metrics, logger }` object passed to every
+    /// middleware as its second argument. `dispatch` queues behind the
+    /// dispatch in progress, or runs right away when nothing is running,
+    /// e.g. when called once a token refresh resolves.
+    pub(crate) fn middleware_context_object(&self) -> Result<js_sys::Object, JsValue> {
+        if let Some(context) = self.middleware_context.lock().map_err(WasmStorageError::lock_poisoned)?.context.clone() {
+            return Ok(context);
+        }
+
+        let store = self.clone();
+        let get_state = Closure::wrap(Box::new(move |key: JsValue| -> Result<JsValue, JsValue> {
+            match key.as_string() {
+                Some(key) => store.get_state(&key),
+                None => store.get_all_state(),
+            }
+        }) as Box<dyn FnMut(JsValue) -> Result<JsValue, JsValue>>);
+
+        // Queued while a dispatch runs, see `dispatch_queue`
+        let mut dispatcher = self.clone();
+        let dispatch = Closure::wrap(Box::new(move |action_type: String, payload: JsValue| -> Result<(), JsValue> {
+            dispatcher.dispatch(&action_type, payload)?;
+            Ok(())
+        }) as Box<dyn FnMut(String, JsValue) -> Result<(), JsValue>>);
+
+        let counters = self.middleware_context.clone();
+        let increment = Closure::wrap(Box::new(move |name: String, by: JsValue| -> Result<(), JsValue> {
+            let mut ctx = counters.lock().map_err(WasmStorageError::lock_poisoned)?;
+            *ctx.metrics.entry(name).or_insert(0.0) += by.as_f64().unwrap_or(1.0);
+            Ok(())
+        }) as Box<dyn FnMut(String, JsValue) -> Result<(), JsValue>>);
+        let metrics = js_sys::Object::new();
+        js_sys::Reflect::set(&metrics, &JsValue::from_str("increment"), &increment.into_js_value())?;
+
+        // Console methods pre-bound with a prefix, so call sites keep their location
+        let console = js_sys::Reflect::get(&js_sys::global(), &JsValue::from_str("console"))?;
+        let logger = js_sys::Object::new();
+        for level in ["debug", "info", "log", "warn", "error"] {
+            if let Ok(method) = js_sys::Reflect::get(&console, &JsValue::from_str(level))?.dyn_into::<js_sys::Function>() {
+                js_sys::Reflect::set(&logger, &JsValue::from_str(level), &method.bind1(&console, &JsValue::from_str(LOG_PREFIX)))?;
+            }
+        }
+
+        let context = js_sys::Object::new();
+        js_sys::Reflect::set(&context, &JsValue::from_str("getState"), &get_state.into_js_value())?;
+        js_sys::Reflect::set(&context, &JsValue::from_str("dispatch"), &dispatch.into_js_value())?;
+        js_sys::Reflect::set(&context, &JsValue::from_str("metrics"), &metrics)?;
+        js_sys::Reflect::set(&context, &JsValue::from_str("logger"), &logger)?;
+
+        self.middleware_context.lock().map_err(WasmStorageError::lock_poisoned)?.context = Some(context.clone());
+        Ok(context)
+    }
+
+    /// Drops the cached context, whose closures hold the store.
+    pub(crate) fn clear_middleware_context(&self) -> Result<(), JsValue> {
+        self.middleware_context.lock().map_err(WasmStorageError::lock_poisoned)?.context = None;
+        Ok(())
+    }
+}
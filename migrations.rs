@@ -0,0 +1,104 @@
+use crate::error::WasmStorageError;
+use crate::WasmStorage;
+use wasm_bindgen::prelude::*;
+
+struct Migration {
+    from: u32,
+    to: u32,
+    migrate: js_sys::Function,
+}
+
+/// The current state shape version and the steps that upgrade older
+/// persisted snapshots to it.
+#[derive(Default)]
+pub(crate) struct Migrations {
+    version: u32,
+    steps: Vec<Migration>,
+}
+
+#[wasm_bindgen]
+impl WasmStorage {
+    /// Declares the version of the state shape this build writes. Persisted
+    /// snapshots record it, and older ones are migrated up to it when they
+    /// are hydrated. Defaults to 0.
+    #[wasm_bindgen]
+    pub fn set_schema_version(&mut self, version: u32) -> Result<(), JsValue> {
+        self.migrations.lock().map_err(WasmStorageError::lock_poisoned)?.version = version;
+        Ok(())
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn schema_version(&self) -> Result<u32, JsValue> {
+        Ok(self.migrations.lock().map_err(WasmStorageError::lock_poisoned)?.version)
+    }
+
+    /// Registers `migrate(data, from, to)`, which turns persisted data (a
+    /// key -> value object) of version `from` into version `to`. It may
+    /// return the new data or modify `data` in place. Registering the same
+    /// `from` and `to` again replaces the step.
+    #[wasm_bindgen]
+    pub fn register_migration(&mut self, from: u32, to: u32, migrate: js_sys::Function) -> Result<(), JsValue> {
+        if to <= from {
+            return Err(WasmStorageError::invalid_argument(format!(
+                "A migration must move to a later version (got {} -> {})",
+                from, to
+            ))
+            .into());
+        }
+        let mut migrations = self.migrations.lock().map_err(WasmStorageError::lock_poisoned)?;
+        migrations.steps.retain(|step| step.from != from || step.to != to);
+        migrations.steps.push(Migration { from, to, migrate });
+        Ok(())
+    }
+}
+
+impl WasmStorage {
+    /// Runs the migrations that take `data` from `version` to the schema
+    /// version, preferring the step that skips furthest ahead at each point.
+    /// Called by persistence backends before hydrating.
+    pub(crate) fn migrate(&self, data: JsValue, version: u32) -> Result<JsValue, JsValue> {
+        let (target, path) = {
+            let migrations = self.migrations.lock().map_err(WasmStorageError::lock_poisoned)?;
+            let target = migrations.version;
+            if version > target {
+                return Err(WasmStorageError::invalid_state(format!(
+                    "Persisted state has version {}, newer than schema version {}",
+                    version, target
+                ))
+                .with("from", version)
+                .with("to", target)
+                .into());
+            }
+
+            let mut path = Vec::new();
+            let mut current = version;
+            while current < target {
+                let step = migrations
+                    .steps
+                    .iter()
+                    .filter(|step| step.from == current && step.to <= target)
+                    .max_by_key(|step| step.to)
+                    .ok_or_else(|| {
+                        WasmStorageError::invalid_state(format!("No migration from version {} towards {}", current, target))
+                            .with("from", current)
+                            .with("to", target)
+                    })?;
+                path.push((step.from, step.to, step.migrate.clone()));
+                current = step.to;
+            }
+            (target, path)
+        };
+
+        let mut data = data;
+        for (from, to, migrate) in path {
+            let migrated = migrate.call3(&JsValue::NULL, &data, &JsValue::from(from), &JsValue::from(to))?;
+            if !migrated.is_undefined() {
+                data = migrated;
+            }
+        }
+        if version < target {
+            log_info!("Migrated persisted state from version {} to {}", version, target);
+        }
+        Ok(data)
+    }
+}
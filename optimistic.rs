@@ -0,0 +1,171 @@
+use crate::error::WasmStorageError;
+use crate::error_handler::ErrorSource;
+use crate::persistent_map::PersistentMap;
+use crate::WasmStorage;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen_futures::{future_to_promise, JsFuture};
+
+/// A key an unconfirmed optimistic action changed; None means absent.
+struct KeyChange {
+    key: String,
+    before: Option<JsValue>,
+    after: Option<JsValue>,
+}
+
+struct PendingUpdate {
+    id: u32,
+    changes: Vec<KeyChange>,
+}
+
+/// Optimistic actions waiting for their confirmation, oldest first.
+#[derive(Default)]
+pub(crate) struct Optimistic {
+    next_id: u32,
+    pending: Vec<PendingUpdate>,
+}
+
+fn same(a: &Option<JsValue>, b: &Option<JsValue>) -> bool {
+    match (a, b) {
+        (Some(a), Some(b)) => js_sys::Object::is(a, b),
+        (None, None) => true,
+        _ => false,
+    }
+}
+
+#[wasm_bindgen]
+impl WasmStorage {
+    /// Dispatches the action right away, then waits for `confirm` (usually
+    /// the server request). If it rejects, every key the action changed is
+    /// put back to its previous value, notifying listeners, and the returned
+    /// promise rejects with the same reason; otherwise it resolves with the
+    /// confirmed value. Keys written again since keep the newer value, and
+    /// rolling back an action that later optimistic actions build on makes
+    /// their rollback restore the value from before it. The changes must
+    /// apply right away to be rolled back, so it fails with INVALID_STATE,
+    /// changing nothing, inside another dispatch (where it would be
+    /// queued) or when a key policy defers one of the action's writes.
+    #[wasm_bindgen]
+    pub fn dispatch_optimistic(&mut self, action_type: &str, payload: JsValue, confirm: js_sys::Promise) -> Result<js_sys::Promise, JsValue> {
+        if self.dispatching()? {
+            return Err(WasmStorageError::invalid_state("dispatch_optimistic can't run inside another dispatch, where it would be queued")
+                .with("action", action_type)
+                .into());
+        }
+        let deferred_before = self.deferred_writes()?;
+        let before: PersistentMap<JsValue> = self.state.lock().map_err(WasmStorageError::lock_poisoned)?.clone();
+        self.dispatch(action_type, payload)?;
+        let changes: Vec<KeyChange> = {
+            let state = self.state.lock().map_err(WasmStorageError::lock_poisoned)?;
+            let removed = before.keys().filter(|key| !state.contains_key(key));
+            let mut keys: Vec<&String> = state.keys().chain(removed).collect();
+            keys.sort();
+            keys.into_iter()
+                .map(|key| KeyChange { key: key.clone(), before: before.get(key).cloned(), after: state.get(key).cloned() })
+                .filter(|change| !same(&change.before, &change.after))
+                .collect()
+        };
+        let deferred: Vec<String> = self
+            .deferred_writes()?
+            .into_iter()
+            .filter(|(key, pending)| {
+                !deferred_before.iter().any(|(before_key, before)| before_key == key && same(before, pending))
+            })
+            .map(|(key, _)| key)
+            .collect();
+        if let Some(key) = deferred.first() {
+            self.undo_deferred_dispatch(&deferred, deferred_before, changes)?;
+            return Err(WasmStorageError::invalid_state(format!("dispatch_optimistic can't roll back \"{}\", whose write a key policy deferred", key))
+                .with("action", action_type)
+                .with("key", key.as_str())
+                .into());
+        }
+        let id = {
+            let mut optimistic = self.optimistic.lock().map_err(WasmStorageError::lock_poisoned)?;
+            optimistic.next_id += 1;
+            let id = optimistic.next_id;
+            optimistic.pending.push(PendingUpdate { id, changes });
+            id
+        };
+
+        let mut store = self.clone();
+        let action_type = action_type.to_string();
+        Ok(future_to_promise(async move {
+            let confirmed = JsFuture::from(confirm).await;
+            let settled = match &confirmed {
+                Ok(_) => store.settle_optimistic(id, false),
+                Err(_) => store.settle_optimistic(id, true),
+            };
+            if let Err(error) = settled {
+                let source = ErrorSource { source: "optimistic", key: None, action: Some(&action_type), timestamp: js_sys::Date::now() };
+                store.report_error(&error, source)?;
+            }
+            if confirmed.is_err() {
+                log_info!("Rolled back optimistic {}", action_type);
+            }
+            confirmed
+        }))
+    }
+
+    /// How many optimistic actions are still waiting for confirmation.
+    #[wasm_bindgen]
+    pub fn pending_optimistic(&self) -> Result<u32, JsValue> {
+        Ok(self.optimistic.lock().map_err(WasmStorageError::lock_poisoned)?.pending.len() as u32)
+    }
+}
+
+impl WasmStorage {
+    // Takes back an optimistic dispatch whose writes were partly deferred:
+    // cancels the deferred writes and restores what was applied
+    fn undo_deferred_dispatch(&mut self, deferred: &[String], deferred_before: Vec<(String, Option<JsValue>)>, changes: Vec<KeyChange>) -> Result<(), JsValue> {
+        for key in deferred {
+            let pending = deferred_before.iter().find(|(before_key, _)| before_key == key).map(|(_, pending)| pending.clone());
+            self.restore_deferred(key, pending)?;
+        }
+        for change in changes {
+            match change.before {
+                Some(value) => self.set_state(&change.key, value)?,
+                None => self.remove_state(&change.key)?,
+            }
+        }
+        Ok(())
+    }
+
+    // Forgets a settled action, first undoing its changes when rejected
+    fn settle_optimistic(&mut self, id: u32, roll_back: bool) -> Result<(), JsValue> {
+        let restore = {
+            let mut optimistic = self.optimistic.lock().map_err(WasmStorageError::lock_poisoned)?;
+            let Some(index) = optimistic.pending.iter().position(|update| update.id == id) else {
+                return Ok(());
+            };
+            let update = optimistic.pending.remove(index);
+            if !roll_back {
+                return Ok(());
+            }
+            let mut restore = Vec::new();
+            for change in update.changes {
+                // A later optimistic action on the key now rolls back past this one
+                let later = optimistic.pending[index..]
+                    .iter_mut()
+                    .flat_map(|update| update.changes.iter_mut())
+                    .find(|later| later.key == change.key);
+                match later {
+                    Some(later) => later.before = change.before,
+                    None => restore.push(change),
+                }
+            }
+            restore
+        };
+
+        for change in restore {
+            let current = self.state.lock().map_err(WasmStorageError::lock_poisoned)?.get(&change.key).cloned();
+            if !same(&current, &change.after) {
+                continue;
+            }
+            match change.before {
+                Some(value) => self.set_state(&change.key, value)?,
+                None => self.remove_state(&change.key)?,
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,99 @@
+use crate::WasmStorage;
+use crate::error::WasmStorageError;
+use std::collections::BTreeSet;
+use std::ops::{Bound, RangeBounds};
+use wasm_bindgen::prelude::*;
+
+#[wasm_bindgen]
+impl WasmStorage {
+    /// Maintains a sorted key index so `range` doesn't sort on every call.
+    #[wasm_bindgen]
+    pub fn enable_ordered_index(&mut self) -> Result<(), JsValue> {
+        let keys: BTreeSet<String> = {
+            let state = self.state.lock().map_err(WasmStorageError::lock_poisoned)?;
+            state.keys().cloned().collect()
+        };
+        *self.ordered_index.lock().map_err(WasmStorageError::lock_poisoned)? = Some(keys);
+        Ok(())
+    }
+
+    #[wasm_bindgen]
+    pub fn disable_ordered_index(&mut self) -> Result<(), JsValue> {
+        *self.ordered_index.lock().map_err(WasmStorageError::lock_poisoned)? = None;
+        Ok(())
+    }
+
+    /// Returns `{ entries, nextCursor }` with entries in lexicographic key
+    /// order from `start_key` (inclusive) to `end_key` (exclusive). Pass
+    /// `nextCursor` back as `start_key` to fetch the following page.
+    #[wasm_bindgen]
+    pub fn range(&self, start_key: Option<String>, end_key: Option<String>, limit: Option<u32>) -> Result<JsValue, JsValue> {
+        let bounds = (
+            start_key.map_or(Bound::Unbounded, Bound::Included),
+            end_key.map_or(Bound::Unbounded, Bound::Excluded),
+        );
+        let limit = limit.map_or(usize::MAX, |limit| limit as usize);
+        // Denied keys are skipped, so the page can only be cut after filtering
+        let restricted = self.restricts_reads()?;
+        let wanted = if restricted { usize::MAX } else { limit.saturating_add(1) };
+
+        let page: Vec<String> = {
+            let index = self.ordered_index.lock().map_err(WasmStorageError::lock_poisoned)?;
+            match index.as_ref() {
+                Some(keys) => keys.range(bounds).take(wanted).cloned().collect(),
+                None => {
+                    // No index: sort on demand
+                    let state = self.state.lock().map_err(WasmStorageError::lock_poisoned)?;
+                    let keys: BTreeSet<&String> = state.keys().collect();
+                    keys.into_iter()
+                        .filter(|key| bounds.contains(*key))
+                        .take(wanted)
+                        .cloned()
+                        .collect()
+                }
+            }
+        };
+        let page: Vec<String> = match restricted {
+            true => self.retain_readable(page, |key| key)?.into_iter().take(limit.saturating_add(1)).collect(),
+            false => page,
+        };
+
+        let entries = js_sys::Array::new();
+        {
+            let state = self.state.lock().map_err(WasmStorageError::lock_poisoned)?;
+            for key in page.iter().take(limit) {
+                let value = state.get(key).cloned().unwrap_or(JsValue::NULL);
+                entries.push(&js_sys::Array::of2(&JsValue::from_str(key), &value));
+            }
+        }
+        let entries = self.read_result(entries.into(), None)?;
+        let next_cursor = page.get(limit).map_or(JsValue::NULL, |key| JsValue::from_str(key));
+
+        let result = js_sys::Object::new();
+        js_sys::Reflect::set(&result, &JsValue::from_str("entries"), &entries)?;
+        js_sys::Reflect::set(&result, &JsValue::from_str("nextCursor"), &next_cursor)?;
+        Ok(result.into())
+    }
+}
+
+impl WasmStorage {
+    pub(crate) fn index_key(&self, key: &str, present: bool) -> Result<(), JsValue> {
+        let mut index = self.ordered_index.lock().map_err(WasmStorageError::lock_poisoned)?;
+        if let Some(keys) = index.as_mut() {
+            if present {
+                keys.insert(key.to_string());
+            } else {
+                keys.remove(key);
+            }
+        }
+        Ok(())
+    }
+
+    pub(crate) fn clear_index(&self) -> Result<(), JsValue> {
+        let mut index = self.ordered_index.lock().map_err(WasmStorageError::lock_poisoned)?;
+        if let Some(keys) = index.as_mut() {
+            keys.clear();
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,69 @@
+use crate::WasmStorage;
+use crate::error::WasmStorageError;
+use wasm_bindgen::prelude::*;
+
+/// Where a change came from. Every change event carries one as `origin`,
+/// and subscriptions can filter on it.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum Origin {
+    Local,
+    Sync,
+    Hydration,
+    Replay,
+    System,
+}
+
+impl Origin {
+    pub(crate) fn parse(origin: &str) -> Result<Origin, JsValue> {
+        match origin {
+            "local" => Ok(Origin::Local),
+            "sync" => Ok(Origin::Sync),
+            "hydration" => Ok(Origin::Hydration),
+            "replay" => Ok(Origin::Replay),
+            "system" => Ok(Origin::System),
+            _ => Err(WasmStorageError::invalid_argument(format!(
+                "Unknown origin \"{}\" (expected local, sync, hydration, replay or system)",
+                origin
+            )).into()),
+        }
+    }
+
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            Origin::Local => "local",
+            Origin::Sync => "sync",
+            Origin::Hydration => "hydration",
+            Origin::Replay => "replay",
+            Origin::System => "system",
+        }
+    }
+}
+
+#[wasm_bindgen]
+impl WasmStorage {
+    /// Runs `callback` synchronously with changes tagged as `origin`, e.g. so
+    /// a JS sync adapter can mark the writes it applies as `"sync"`.
+    #[wasm_bindgen]
+    pub fn run_with_origin(&mut self, origin: &str, callback: &js_sys::Function) -> Result<JsValue, JsValue> {
+        let origin = Origin::parse(origin)?;
+        self.with_origin(origin, |_| callback.call0(&JsValue::NULL))
+    }
+}
+
+impl WasmStorage {
+    /// Tags changes made inside `f` with `origin`, restoring the previous
+    /// origin afterwards so nested calls compose.
+    pub(crate) fn with_origin<T>(&mut self, origin: Origin, f: impl FnOnce(&mut WasmStorage) -> Result<T, JsValue>) -> Result<T, JsValue> {
+        let previous = std::mem::replace(
+            &mut *self.origin.lock().map_err(WasmStorageError::lock_poisoned)?,
+            origin,
+        );
+        let result = f(self);
+        *self.origin.lock().map_err(WasmStorageError::lock_poisoned)? = previous;
+        result
+    }
+
+    pub(crate) fn current_origin(&self) -> Result<Origin, JsValue> {
+        Ok(*self.origin.lock().map_err(WasmStorageError::lock_poisoned)?)
+    }
+}
@@ -0,0 +1,231 @@
+use crate::error::WasmStorageError;
+use crate::subscriptions::Subscription;
+use crate::WasmStorage;
+use std::collections::{HashMap, HashSet};
+use crate::sync::{Lock, Shared};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+
+struct PeerShared {
+    peer_id: String,
+    keys: HashSet<String>,
+    channel: web_sys::RtcDataChannel,
+    // Last value of each key sent to or received from the peer. Every entry
+    // sent carries it as `base`, so the receiver can tell a write made
+    // after seeing its own latest value from one that crossed it
+    known: Lock<HashMap<String, JsValue>>,
+}
+
+/// Synchronizes a set of keys with a remote peer over an already negotiated
+/// `RTCDataChannel`. Signaling is left to the application. Incoming writes
+/// merge like `connect_remote` mutations: by CRDT stamp with `enable_crdt`,
+/// otherwise through the `set_conflict_resolver` callback when both peers
+/// changed a key, the peer's value winning without one. Enable CRDT mode
+/// or register a resolver so crossing writes settle on the same value.
+#[wasm_bindgen]
+pub struct PeerSync {
+    shared: Shared<PeerShared>,
+    subscription: Option<Subscription>,
+    _listener: Closure<dyn FnMut(JsValue)>,
+    _on_message: Closure<dyn FnMut(web_sys::MessageEvent)>,
+    _on_open: Closure<dyn FnMut(JsValue)>,
+}
+
+#[wasm_bindgen]
+impl WasmStorage {
+    #[wasm_bindgen]
+    pub fn sync_with_peer(&mut self, channel: web_sys::RtcDataChannel, keys: js_sys::Array) -> Result<PeerSync, JsValue> {
+        let shared = Shared::new(PeerShared {
+            peer_id: self.generate_id("sync")?,
+            keys: keys.iter().filter_map(|k| k.as_string()).collect(),
+            channel: channel.clone(),
+            known: Lock::new(HashMap::new()),
+        });
+
+        // Forward local changes of synced keys to the peer
+        let listener_shared = shared.clone();
+        let listener_store = self.clone();
+        let listener = Closure::wrap(Box::new(move |event: JsValue| {
+            let _ = listener_shared.send_local_change(&listener_store, &event);
+        }) as Box<dyn FnMut(JsValue)>);
+
+        // Apply changes coming from the peer
+        let message_shared = shared.clone();
+        let mut message_store = self.clone();
+        let on_message = Closure::wrap(Box::new(move |event: web_sys::MessageEvent| {
+            if let Some(data) = event.data().as_string() {
+                if let Err(e) = message_shared.receive(&mut message_store, &data) {
+                    log_warn!("Peer sync message rejected: {:?}", e);
+                }
+            }
+        }) as Box<dyn FnMut(web_sys::MessageEvent)>);
+
+        // Exchange a snapshot of synced keys once the channel opens
+        let open_shared = shared.clone();
+        let open_store = self.clone();
+        let on_open = Closure::wrap(Box::new(move |_: JsValue| {
+            let _ = open_shared.send_snapshot(&open_store);
+        }) as Box<dyn FnMut(JsValue)>);
+
+        channel.set_onmessage(Some(on_message.as_ref().unchecked_ref()));
+        channel.set_onopen(Some(on_open.as_ref().unchecked_ref()));
+
+        // destroy() leaves the channel open but stops handling its messages
+        let teardown_channel = channel.clone();
+        self.register_teardown(Box::new(move || {
+            teardown_channel.set_onmessage(None);
+            teardown_channel.set_onopen(None);
+        }))?;
+
+        let subscription = self.subscribe(listener.as_ref().unchecked_ref::<js_sys::Function>().clone(), None)?;
+
+        if channel.ready_state() == web_sys::RtcDataChannelState::Open {
+            shared.send_snapshot(self)?;
+        }
+
+        log_info!("Peer sync started as {}", shared.peer_id);
+        Ok(PeerSync {
+            shared,
+            subscription: Some(subscription),
+            _listener: listener,
+            _on_message: on_message,
+            _on_open: on_open,
+        })
+    }
+}
+
+#[wasm_bindgen]
+impl PeerSync {
+    #[wasm_bindgen(getter)]
+    pub fn peer_id(&self) -> String {
+        self.shared.peer_id.clone()
+    }
+
+    /// Stops syncing; the data channel itself is left open for the application.
+    #[wasm_bindgen]
+    pub fn close(&mut self) -> Result<(), JsValue> {
+        self.shared.channel.set_onmessage(None);
+        self.shared.channel.set_onopen(None);
+        if let Some(subscription) = self.subscription.take() {
+            subscription.unsubscribe()?;
+        }
+        Ok(())
+    }
+}
+
+impl PeerShared {
+    fn send_local_change(&self, store: &WasmStorage, event: &JsValue) -> Result<(), JsValue> {
+        // Writes from the peer (and restored values) aren't sent back
+        let origin = js_sys::Reflect::get(event, &JsValue::from_str("origin"))?.as_string();
+        if matches!(origin.as_deref(), Some("sync" | "hydration")) {
+            return Ok(());
+        }
+        let key = js_sys::Reflect::get(event, &JsValue::from_str("key"))?.as_string().unwrap_or_default();
+        if !self.keys.contains(&key) {
+            return Ok(());
+        }
+        let value = js_sys::Reflect::get(event, &JsValue::from_str("value"))?;
+        // Removals arrive as undefined, which JSON would drop
+        let value = if value.is_undefined() { JsValue::NULL } else { value };
+
+        let message = js_sys::Object::new();
+        js_sys::Reflect::set(&message, &JsValue::from_str("kind"), &JsValue::from_str("set"))?;
+        let entry = self.entry_object(store, &key, &value)?;
+        js_sys::Reflect::set(&message, &JsValue::from_str("entry"), &entry)?;
+        if self.send(&message)? {
+            self.remember(key, value)?;
+        }
+        Ok(())
+    }
+
+    fn send_snapshot(&self, store: &WasmStorage) -> Result<(), JsValue> {
+        let mut sent = Vec::new();
+        let entries = js_sys::Array::new();
+        for key in self.keys.iter() {
+            let value = store.get_state(key)?;
+            if value.is_null() {
+                continue;
+            }
+            let entry = self.entry_object(store, key, &value)?;
+            entries.push(&entry);
+            sent.push((key.clone(), value));
+        }
+
+        let message = js_sys::Object::new();
+        js_sys::Reflect::set(&message, &JsValue::from_str("kind"), &JsValue::from_str("snapshot"))?;
+        js_sys::Reflect::set(&message, &JsValue::from_str("entries"), &entries)?;
+        if self.send(&message)? {
+            for (key, value) in sent {
+                self.remember(key, value)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn remember(&self, key: String, value: JsValue) -> Result<(), JsValue> {
+        self.known.lock().map_err(WasmStorageError::lock_poisoned)?.insert(key, value);
+        Ok(())
+    }
+
+    fn receive(&self, store: &mut WasmStorage, data: &str) -> Result<(), JsValue> {
+        let message = js_sys::JSON::parse(data)?;
+        let kind = js_sys::Reflect::get(&message, &JsValue::from_str("kind"))?.as_string().unwrap_or_default();
+        match kind.as_str() {
+            "set" => {
+                let entry = js_sys::Reflect::get(&message, &JsValue::from_str("entry"))?;
+                self.apply_remote(store, &entry)
+            }
+            "snapshot" => {
+                let entries = js_sys::Array::from(&js_sys::Reflect::get(&message, &JsValue::from_str("entries"))?);
+                for entry in entries.iter() {
+                    self.apply_remote(store, &entry)?;
+                }
+                Ok(())
+            }
+            _ => Err(WasmStorageError::invalid_argument(format!("Unknown peer sync message kind: {}", kind)).into()),
+        }
+    }
+
+    // Merged the way `connect_remote` merges pushed mutations
+    fn apply_remote(&self, store: &mut WasmStorage, entry: &JsValue) -> Result<(), JsValue> {
+        let key = js_sys::Reflect::get(entry, &JsValue::from_str("key"))?.as_string().unwrap_or_default();
+        if !self.keys.contains(&key) {
+            return Ok(());
+        }
+        let value = js_sys::Reflect::get(entry, &JsValue::from_str("value"))?;
+        let value = if value.is_undefined() { JsValue::NULL } else { value };
+        let clock = js_sys::Reflect::get(entry, &JsValue::from_str("clock"))?;
+        if !clock.is_undefined() {
+            return store.accept_stamped(key, value, clock);
+        }
+        let base = js_sys::Reflect::get(entry, &JsValue::from_str("base"))?;
+        let base = if base.is_undefined() { JsValue::NULL } else { base };
+        self.remember(key.clone(), value.clone())?;
+        store.merge_pushed(key, value, &base).map(|_| ())
+    }
+
+    // `{ key, value, base, peer }`, plus the key's stamp as `clock` in CRDT
+    // mode
+    fn entry_object(&self, store: &WasmStorage, key: &str, value: &JsValue) -> Result<js_sys::Object, JsValue> {
+        let entry = js_sys::Object::new();
+        js_sys::Reflect::set(&entry, &JsValue::from_str("key"), &JsValue::from_str(key))?;
+        js_sys::Reflect::set(&entry, &JsValue::from_str("value"), value)?;
+        let base = self.known.lock().map_err(WasmStorageError::lock_poisoned)?.get(key).cloned().unwrap_or(JsValue::NULL);
+        js_sys::Reflect::set(&entry, &JsValue::from_str("base"), &base)?;
+        js_sys::Reflect::set(&entry, &JsValue::from_str("peer"), &JsValue::from_str(&self.peer_id))?;
+        if let Some(clock) = store.stamp_of(key)? {
+            js_sys::Reflect::set(&entry, &JsValue::from_str("clock"), &clock)?;
+        }
+        Ok(entry)
+    }
+
+    // Whether the message went out; nothing is sent before the channel opens
+    fn send(&self, message: &js_sys::Object) -> Result<bool, JsValue> {
+        if self.channel.ready_state() != web_sys::RtcDataChannelState::Open {
+            return Ok(false);
+        }
+        let json = js_sys::JSON::stringify(message)?;
+        self.channel.send_with_str(&String::from(json))?;
+        Ok(true)
+    }
+}
@@ -0,0 +1,84 @@
+use crate::error::WasmStorageError;
+use crate::WasmStorage;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+
+const PREFIX: &str = "wasm-storage";
+
+/// Whether phases are measured, and the actions being dispatched (nested
+/// when middleware or effects dispatch) so notifications can be named after
+/// the action that caused them.
+#[derive(Default)]
+pub(crate) struct PerfMarks {
+    enabled: bool,
+    actions: Vec<String>,
+}
+
+/// A started phase, ended by `end_measure`.
+pub(crate) struct Measure {
+    name: String,
+    // Dispatches pop their action when they end
+    dispatch: bool,
+}
+
+// `performance.<method>(...args)`, ignoring environments without it
+fn call_performance(method: &str, args: &[&str]) {
+    let Ok(performance) = js_sys::Reflect::get(&js_sys::global(), &JsValue::from_str("performance")) else {
+        return;
+    };
+    let Some(function) = js_sys::Reflect::get(&performance, &JsValue::from_str(method)).ok().and_then(|f| f.dyn_into::<js_sys::Function>().ok()) else {
+        return;
+    };
+    let args: js_sys::Array = args.iter().map(|arg| JsValue::from_str(arg)).collect();
+    let _ = function.apply(&performance, &args);
+}
+
+#[wasm_bindgen]
+impl WasmStorage {
+    /// Emits `performance.mark`/`performance.measure` entries for every
+    /// dispatch, its middleware and the listener notifications it causes,
+    /// named `wasm-storage:<phase>:<action type>` (phases `dispatch`,
+    /// `middleware`, `notify`; direct writes notify as
+    /// `wasm-storage:notify`), so store overhead shows up in the browser's
+    /// Performance panel. The start and end marks are cleared once measured.
+    #[wasm_bindgen]
+    pub fn set_performance_marks(&mut self, enabled: bool) -> Result<(), JsValue> {
+        self.perf_marks.lock().map_err(WasmStorageError::lock_poisoned)?.enabled = enabled;
+        Ok(())
+    }
+}
+
+impl WasmStorage {
+    /// Starts measuring `phase`. Dispatches pass their action type, which
+    /// names the phases nested in them until the dispatch ends.
+    pub(crate) fn begin_measure(&self, phase: &str, dispatching: Option<&str>) -> Result<Option<Measure>, JsValue> {
+        let mut marks = self.perf_marks.lock().map_err(WasmStorageError::lock_poisoned)?;
+        if !marks.enabled {
+            return Ok(None);
+        }
+        if let Some(action_type) = dispatching {
+            marks.actions.push(action_type.to_string());
+        }
+        let name = match marks.actions.last() {
+            Some(action_type) => format!("{}:{}:{}", PREFIX, phase, action_type),
+            None => format!("{}:{}", PREFIX, phase),
+        };
+        call_performance("mark", &[&format!("{}:start", name)]);
+        Ok(Some(Measure { name, dispatch: dispatching.is_some() }))
+    }
+
+    pub(crate) fn end_measure(&self, measure: Option<Measure>) -> Result<(), JsValue> {
+        let Some(Measure { name, dispatch }) = measure else {
+            return Ok(());
+        };
+        if dispatch {
+            self.perf_marks.lock().map_err(WasmStorageError::lock_poisoned)?.actions.pop();
+        }
+        let (start, end) = (format!("{}:start", name), format!("{}:end", name));
+        call_performance("mark", &[&end]);
+        call_performance("measure", &[&name, &start, &end]);
+        call_performance("clearMarks", &[&start]);
+        call_performance("clearMarks", &[&end]);
+        Ok(())
+    }
+}
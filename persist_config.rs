@@ -0,0 +1,62 @@
+use crate::error::WasmStorageError;
+use crate::{glob_match, WasmStorage};
+use wasm_bindgen::prelude::*;
+
+/// Which keys may be written to a persistence backend. Shared by every
+/// backend, so it is configured on the store rather than per backend.
+#[derive(Default)]
+pub(crate) struct PersistFilter {
+    include: Vec<String>,
+    exclude: Vec<String>,
+    key_prefixes: Vec<String>,
+}
+
+impl PersistFilter {
+    // Exclusions win; with no include rules every other key is persisted
+    fn allows(&self, key: &str) -> bool {
+        if self.exclude.iter().any(|pattern| glob_match(pattern, key)) {
+            return false;
+        }
+        if self.include.is_empty() && self.key_prefixes.is_empty() {
+            return true;
+        }
+        self.include.iter().any(|pattern| glob_match(pattern, key))
+            || self.key_prefixes.iter().any(|prefix| key.starts_with(prefix.as_str()))
+    }
+}
+
+fn strings(config: &JsValue, name: &str) -> Result<Vec<String>, JsValue> {
+    let list = js_sys::Reflect::get(config, &JsValue::from_str(name))?;
+    if list.is_undefined() || list.is_null() {
+        return Ok(Vec::new());
+    }
+    if !js_sys::Array::is_array(&list) {
+        return Err(WasmStorageError::invalid_argument(format!("persist_config {} must be an array of strings", name)).into());
+    }
+    Ok(js_sys::Array::from(&list).iter().filter_map(|item| item.as_string()).collect())
+}
+
+#[wasm_bindgen]
+impl WasmStorage {
+    /// Limits which keys reach disk: `include` (keys or globs), `keyPrefixes`
+    /// and `exclude` (keys or globs, checked first). Without include rules
+    /// every key that isn't excluded is persisted. Applies to all backends,
+    /// both when writing and when hydrating, and rewrites the current
+    /// snapshot so newly excluded keys leave disk right away.
+    #[wasm_bindgen]
+    pub fn persist_config(&mut self, config: js_sys::Object) -> Result<(), JsValue> {
+        let filter = PersistFilter {
+            include: strings(&config, "include")?,
+            exclude: strings(&config, "exclude")?,
+            key_prefixes: strings(&config, "keyPrefixes")?,
+        };
+        *self.persist_filter.lock().map_err(WasmStorageError::lock_poisoned)? = filter;
+        self.write_snapshot()
+    }
+}
+
+impl WasmStorage {
+    pub(crate) fn persistable(&self, key: &str) -> Result<bool, JsValue> {
+        Ok(self.persist_filter.lock().map_err(WasmStorageError::lock_poisoned)?.allows(key))
+    }
+}
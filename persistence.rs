@@ -0,0 +1,639 @@
+use crate::backends::Backend;
+use crate::encryption::contains_sealed;
+use crate::error::WasmStorageError;
+use crate::error_handler::ErrorSource;
+use crate::locks::LockedWrite;
+use crate::origin::Origin;
+use crate::quota::is_quota_error;
+use crate::rate_limit::{clear_timeout, set_timeout};
+use crate::wal::Wal;
+use crate::WasmStorage;
+use std::collections::HashMap;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+
+const DEFAULT_THRESHOLD: f64 = 0.9;
+
+/// How important it is for a key to survive quota pressure. Lower priorities
+/// are dropped from the persisted snapshot first; `Must` and `Normal` never are.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub(crate) enum PersistPriority {
+    Cache,
+    Low,
+    Normal,
+    Must,
+}
+
+impl PersistPriority {
+    fn parse(priority: &str) -> Result<PersistPriority, JsValue> {
+        match priority {
+            "cache" => Ok(PersistPriority::Cache),
+            "low" => Ok(PersistPriority::Low),
+            "normal" => Ok(PersistPriority::Normal),
+            "must" => Ok(PersistPriority::Must),
+            _ => Err(WasmStorageError::invalid_argument(format!(
+                "Unknown persist priority \"{}\" (expected must, normal, low or cache)",
+                priority
+            )).into()),
+        }
+    }
+
+    fn droppable(self) -> bool {
+        self <= PersistPriority::Low
+    }
+}
+
+/// The storage key and the per-key priorities of enabled persistence.
+pub(crate) type PersistTargets = (String, HashMap<String, PersistPriority>);
+
+pub(crate) struct Persistence {
+    backend: Backend,
+    storage_key: String,
+    priorities: HashMap<String, PersistPriority>,
+    quota_bytes: f64,
+    threshold: f64,
+    evict: bool,
+    on_report: Option<js_sys::Function>,
+    // Keys left out of the last snapshot because of quota pressure
+    dropped: Vec<String>,
+    hydrating: bool,
+    write_behind: Option<WriteBehind>,
+    wal: Option<Wal>,
+}
+
+/// Coalesces snapshot writes: `debounce_ms` waits for a quiet period,
+/// `interval_ms` bounds how long a change can stay unwritten.
+struct WriteBehind {
+    debounce_ms: Option<f64>,
+    interval_ms: Option<f64>,
+    timer: Option<JsValue>,
+    // When the oldest unwritten change was made
+    pending_since: Option<f64>,
+    // Writes since the last snapshot, for `get_stats`
+    pending_writes: u32,
+}
+
+struct SerializedEntry {
+    key: String,
+    json: String,
+    priority: PersistPriority,
+}
+
+impl SerializedEntry {
+    // localStorage stores UTF-16, so every character costs two bytes
+    fn bytes(&self) -> f64 {
+        ((self.key.len() + self.json.len() + 4) * 2) as f64
+    }
+}
+
+#[wasm_bindgen]
+impl WasmStorage {
+    /// Persists state under `storage_key` in localStorage, or in the backend
+    /// registered as `backend` (see `register_backend`), restoring any
+    /// previously saved state first (migrated to the schema version).
+    /// Options: `priorities` (key -> "must" | "normal" | "low" | "cache"),
+    /// `quotaBytes`, `threshold` (fraction of the quota at which low-priority
+    /// keys stop being persisted), `evict` (also remove dropped keys from
+    /// memory), `onReport(report)`, and `debounceMs` / `flushIntervalMs` to
+    /// write behind: changes are then written once writes pause for
+    /// `debounceMs`, and at least every `flushIntervalMs`, rather than on
+    /// every write. Pending changes are also flushed before the page unloads.
+    /// `wal: true` (or `{ compactAfter }`) appends each change to a
+    /// write-ahead log instead, so it survives the page being killed before
+    /// the next snapshot; the log is replayed on startup and compacted into a
+    /// snapshot every `compactAfter` entries (default 200) or on flush.
+    #[wasm_bindgen]
+    pub fn enable_persistence(&mut self, storage_key: &str, options: Option<js_sys::Object>) -> Result<(), JsValue> {
+        let options = options.map(JsValue::from).unwrap_or(JsValue::UNDEFINED);
+        let backend = self.backend(&option(&options, "backend").as_string().unwrap_or_else(|| "localStorage".to_string()))?;
+
+        let mut priorities = HashMap::new();
+        let configured = option(&options, "priorities");
+        if let Some(configured) = configured.dyn_ref::<js_sys::Object>() {
+            for entry in js_sys::Object::entries(configured).iter() {
+                let pair = js_sys::Array::from(&entry);
+                let priority = PersistPriority::parse(&pair.get(1).as_string().unwrap_or_default())?;
+                priorities.insert(pair.get(0).as_string().unwrap_or_default(), priority);
+            }
+        }
+
+        let debounce_ms = option(&options, "debounceMs").as_f64().map(|ms| ms.max(0.0));
+        let interval_ms = option(&options, "flushIntervalMs").as_f64().map(|ms| ms.max(0.0));
+        let write_behind = (debounce_ms.is_some() || interval_ms.is_some())
+            .then_some(WriteBehind { debounce_ms, interval_ms, timer: None, pending_since: None, pending_writes: 0 });
+        if write_behind.is_some() {
+            self.install_unload_flush()?;
+        }
+
+        let wal = option(&options, "wal");
+        let wal = wal
+            .is_truthy()
+            .then(|| Wal::new(storage_key, option(&wal, "compactAfter").as_f64().map(|n| n as u32)));
+
+        let mut persistence = Persistence {
+            quota_bytes: option(&options, "quotaBytes").as_f64().unwrap_or_else(|| backend.default_quota_bytes()),
+            backend,
+            storage_key: storage_key.to_string(),
+            priorities,
+            threshold: option(&options, "threshold").as_f64().unwrap_or(DEFAULT_THRESHOLD),
+            evict: option(&options, "evict").is_truthy(),
+            on_report: option(&options, "onReport").dyn_into::<js_sys::Function>().ok(),
+            dropped: Vec::new(),
+            hydrating: true,
+            write_behind,
+            wal,
+        };
+        let signing = self.signing()?;
+        let saved = persistence.backend.get_item(storage_key)?;
+        let found = saved.is_some();
+        let saved = match (saved, signing.as_ref()) {
+            (Some(saved), Some(signing)) => js_sys::JSON::parse(&signing.verify_object(&saved, "Persisted snapshot")?)?,
+            (Some(saved), None) => js_sys::JSON::parse(&saved)?,
+            (None, _) => JsValue::UNDEFINED,
+        };
+        let data = option(&saved, "data").dyn_into::<js_sys::Object>().unwrap_or_default();
+        // Changes logged after the snapshot was written
+        let recovered = match persistence.wal.as_mut() {
+            Some(wal) => {
+                let snapshot_seq = option(&saved, "walSeq").as_f64().unwrap_or(0.0) as u64;
+                wal.replay(&persistence.backend, signing.as_ref(), &data, snapshot_seq)?
+            }
+            None => 0,
+        };
+        *self.persistence.lock().map_err(WasmStorageError::lock_poisoned)? = Some(persistence);
+        log_info!("Persistence enabled for {}", storage_key);
+
+        // Snapshots written before versioning existed count as version 0
+        let version = option(&saved, "version").as_f64().unwrap_or(0.0) as u32;
+        let restore = found || recovered > 0;
+        if restore && self.encrypting()? {
+            let mut store = self.clone();
+            wasm_bindgen_futures::spawn_local(async move {
+                let hydrated = match store.unseal_entries(&data).await {
+                    Ok(()) => store.hydrate(Some(data), version, recovered),
+                    Err(error) => Err(error),
+                };
+                if let Err(error) = hydrated {
+                    let source = ErrorSource { source: "persistence", key: None, action: None, timestamp: js_sys::Date::now() };
+                    let _ = store.report_error(&error, source);
+                }
+            });
+            return Ok(());
+        }
+        if restore && contains_sealed(&data) {
+            return Err(WasmStorageError::invalid_state("Persisted state is encrypted; call enable_encryption first").into());
+        }
+        self.hydrate(restore.then_some(data), version, recovered)
+    }
+
+    #[wasm_bindgen]
+    pub fn set_persist_priority(&mut self, key: &str, priority: &str) -> Result<(), JsValue> {
+        let priority = PersistPriority::parse(priority)?;
+        let mut persistence = self.persistence.lock().map_err(WasmStorageError::lock_poisoned)?;
+        let persistence = persistence
+            .as_mut()
+            .ok_or_else(|| WasmStorageError::invalid_state("Persistence is not enabled"))?;
+        persistence.priorities.insert(key.to_string(), priority);
+        Ok(())
+    }
+
+    /// Writes pending changes now instead of waiting for the write-behind
+    /// timer. A no-op when nothing is pending.
+    #[wasm_bindgen]
+    pub fn flush(&mut self) -> Result<(), JsValue> {
+        {
+            let mut persistence = self.persistence.lock().map_err(WasmStorageError::lock_poisoned)?;
+            if let Some(write_behind) = persistence.as_mut().and_then(|persistence| persistence.write_behind.as_mut()) {
+                if let Some(timer) = write_behind.timer.take() {
+                    clear_timeout(&timer)?;
+                }
+                write_behind.pending_writes = 0;
+                if write_behind.pending_since.take().is_none() {
+                    return Ok(());
+                }
+            }
+        }
+        self.write_snapshot()
+    }
+
+    /// `flush`, then resolves once backends that write in the background
+    /// (encryption, Web Locks, Cache Storage) have finished, e.g. before a
+    /// Service Worker may be stopped.
+    #[wasm_bindgen]
+    pub fn flush_async(&mut self) -> Result<js_sys::Promise, JsValue> {
+        self.flush()?;
+        self.backend_writes_settled()
+    }
+
+    /// Keys currently left out of the persisted snapshot because of quota pressure.
+    #[wasm_bindgen]
+    pub fn dropped_persist_keys(&self) -> Result<js_sys::Array, JsValue> {
+        let persistence = self.persistence.lock().map_err(WasmStorageError::lock_poisoned)?;
+        Ok(persistence
+            .iter()
+            .flat_map(|persistence| persistence.dropped.iter())
+            .map(|key| JsValue::from_str(key))
+            .collect())
+    }
+}
+
+impl WasmStorage {
+    /// Whether persistence is still restoring, and how many writes the
+    /// write-behind timer has yet to flush; None without persistence.
+    pub(crate) fn persistence_status(&self) -> Result<Option<(bool, u32)>, JsValue> {
+        let persistence = self.persistence.lock().map_err(WasmStorageError::lock_poisoned)?;
+        Ok(persistence.as_ref().map(|persistence| {
+            let pending = persistence.write_behind.as_ref().map_or(0, |write_behind| write_behind.pending_writes);
+            (persistence.hydrating, pending)
+        }))
+    }
+
+    // Applies the restored data (if anything was saved), then starts persisting
+    fn hydrate(&mut self, data: Option<js_sys::Object>, version: u32, recovered: u32) -> Result<(), JsValue> {
+        if let Some(data) = data {
+            self.decompress_entries(&data)?;
+            let data = self.migrate(data.into(), version)?;
+            if let Some(data) = data.dyn_ref::<js_sys::Object>() {
+                self.with_origin(Origin::Hydration, |store| {
+                    for entry in js_sys::Object::entries(data).iter() {
+                        let pair = js_sys::Array::from(&entry);
+                        let key = pair.get(0).as_string().unwrap_or_default();
+                        if !store.persistable(&key)? {
+                            continue;
+                        }
+                        let value = store.decode_value(&key, &pair.get(1))?;
+                        store.set_state(&key, value)?;
+                    }
+                    Ok(())
+                })?;
+            }
+        }
+
+        if let Some(persistence) = self.persistence.lock().map_err(WasmStorageError::lock_poisoned)?.as_mut() {
+            persistence.hydrating = false;
+        }
+        if recovered > 0 {
+            log_info!("Recovered {} logged changes", recovered);
+            self.write_snapshot()?;
+        } else {
+            self.persist()?;
+        }
+        self.finish_hydration("persistence")
+    }
+
+    /// Called after every change: writes the snapshot now, or schedules the
+    /// write when write-behind is configured.
+    pub(crate) fn persist(&mut self) -> Result<(), JsValue> {
+        if self.schedule_flush()? {
+            return Ok(());
+        }
+        self.write_snapshot()
+    }
+
+    // Returns false when the snapshot should be written right away
+    fn schedule_flush(&self) -> Result<bool, JsValue> {
+        let mut persistence = self.persistence.lock().map_err(WasmStorageError::lock_poisoned)?;
+        let Some(persistence) = persistence.as_mut().filter(|persistence| !persistence.hydrating) else {
+            return Ok(false);
+        };
+        // Logged changes are already durable; the snapshot only has to be
+        // written once the log grows long
+        let logged = persistence.wal.as_ref().map(|wal| !wal.needs_compaction());
+        if logged == Some(false) {
+            return Ok(false);
+        }
+        let Some(write_behind) = persistence.write_behind.as_mut() else {
+            return Ok(logged.unwrap_or(false));
+        };
+
+        let now = js_sys::Date::now();
+        let pending_since = *write_behind.pending_since.get_or_insert(now);
+        write_behind.pending_writes += 1;
+        let delay = match (write_behind.debounce_ms, write_behind.interval_ms) {
+            (Some(debounce), interval) => {
+                if let Some(timer) = write_behind.timer.take() {
+                    clear_timeout(&timer)?;
+                }
+                // The interval caps how long debouncing can postpone a write
+                interval.map_or(debounce, |interval| debounce.min(pending_since + interval - now))
+            }
+            (None, Some(_)) if write_behind.timer.is_some() => return Ok(true),
+            (None, Some(interval)) => interval,
+            (None, None) => return Ok(false),
+        };
+
+        let mut store = self.clone();
+        let flush = Closure::once_into_js(move || {
+            if let Err(e) = store.flush() {
+                log_warn!("Write-behind flush failed: {:?}", e);
+            }
+        });
+        write_behind.timer = Some(set_timeout(&flush, delay.max(0.0))?);
+        Ok(true)
+    }
+
+    // beforeunload is skipped by some mobile browsers, which fire pagehide instead
+    fn install_unload_flush(&self) -> Result<(), JsValue> {
+        let Some(window) = web_sys::window() else {
+            return Ok(());
+        };
+        let mut store = self.clone();
+        let on_unload = Closure::wrap(Box::new(move |_: web_sys::Event| {
+            if let Err(e) = store.flush() {
+                log_warn!("Flush before unload failed: {:?}", e);
+            }
+        }) as Box<dyn FnMut(web_sys::Event)>)
+        .into_js_value()
+        .unchecked_into::<js_sys::Function>();
+
+        for event in ["beforeunload", "pagehide"] {
+            window.add_event_listener_with_callback(event, &on_unload)?;
+        }
+        self.register_teardown(Box::new(move || {
+            for event in ["beforeunload", "pagehide"] {
+                let _ = window.remove_event_listener_with_callback(event, &on_unload);
+            }
+        }))
+    }
+
+    /// Appends a change to the write-ahead log, if there is one; `key` is
+    /// None for a clear.
+    pub(crate) fn log_to_wal(&self, key: Option<&str>, value: Option<&JsValue>) -> Result<(), JsValue> {
+        let enabled = self
+            .persistence
+            .lock()
+            .map_err(WasmStorageError::lock_poisoned)?
+            .as_ref()
+            .is_some_and(|persistence| !persistence.hydrating && persistence.wal.is_some());
+        if !enabled || !self.writes_backends()? {
+            return Ok(());
+        }
+        let value_json = match (key, value) {
+            (Some(key), _) if !self.persistable(key)? => return Ok(()),
+            // Values JSON can't represent are left out of snapshots, so log them as removals
+            (Some(_), Some(value)) => JsValue::from(js_sys::JSON::stringify(value)?).as_string(),
+            _ => None,
+        };
+        let value_json = value_json.map(|json| self.compress_json(json)).transpose()?;
+        if self.encrypting()? {
+            return self.queue_sealed_wal(key, value_json);
+        }
+        self.append_wal(key, value_json.as_deref())
+    }
+
+    /// Appends an already serialized (and, with encryption, sealed) change,
+    /// once the Web Lock is held if locking is enabled.
+    pub(crate) fn append_wal(&self, key: Option<&str>, value_json: Option<&str>) -> Result<(), JsValue> {
+        if self.queue_locked(LockedWrite::Wal(key.map(str::to_string), value_json.map(str::to_string)))? {
+            return Ok(());
+        }
+        self.append_wal_now(key, value_json)
+    }
+
+    pub(crate) fn append_wal_now(&self, key: Option<&str>, value_json: Option<&str>) -> Result<(), JsValue> {
+        let mut persistence = self.persistence.lock().map_err(WasmStorageError::lock_poisoned)?;
+        if let Some(persistence) = persistence.as_mut() {
+            if let Some(wal) = persistence.wal.as_mut() {
+                wal.append(&persistence.backend, self.signing()?.as_ref(), key, value_json)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// The storage key and the configured priorities, while persistence is
+    /// enabled.
+    pub(crate) fn persist_targets(&self) -> Result<Option<PersistTargets>, JsValue> {
+        let persistence = self.persistence.lock().map_err(WasmStorageError::lock_poisoned)?;
+        Ok(persistence
+            .as_ref()
+            .map(|persistence| (persistence.storage_key.clone(), persistence.priorities.clone())))
+    }
+
+    /// Writes the current snapshot to the backend, dropping low-priority keys
+    /// when the snapshot gets close to the quota. A successful write compacts
+    /// the write-ahead log.
+    pub(crate) fn write_snapshot(&mut self) -> Result<(), JsValue> {
+        let active = self
+            .persistence
+            .lock()
+            .map_err(WasmStorageError::lock_poisoned)?
+            .as_ref()
+            .is_some_and(|persistence| !persistence.hydrating);
+        if !active || !self.writes_backends()? {
+            return Ok(());
+        }
+        let entries = self.serialized_entries()?;
+        if self.encrypting()? {
+            return self.queue_sealed_snapshot(entries);
+        }
+        self.write_entries(entries)
+    }
+
+    /// Writes serialized (and, with encryption, sealed) entries as the
+    /// snapshot, running the eviction policy if they don't fit the backend.
+    /// With Web Locks the write waits for the lock.
+    pub(crate) fn write_entries(&mut self, entries: Vec<(String, String)>) -> Result<(), JsValue> {
+        if self.queue_locked(LockedWrite::Snapshot(entries.clone()))? {
+            return Ok(());
+        }
+        self.write_entries_now(entries)
+    }
+
+    pub(crate) fn write_entries_now(&mut self, entries: Vec<(String, String)>) -> Result<(), JsValue> {
+        match self.store_entries(entries.clone())? {
+            Some(quota_error) => self.evict_for_quota(entries, quota_error),
+            None => Ok(()),
+        }
+    }
+
+    /// One attempt at writing the snapshot. Returns the backend's
+    /// `QuotaExceededError` if even the entries that can't be dropped by
+    /// priority don't fit.
+    pub(crate) fn store_entries(&mut self, entries: Vec<(String, String)>) -> Result<Option<JsValue>, JsValue> {
+        let version = self.schema_version()?;
+        let signing = self.signing()?;
+        let (dropped, evict, report) = {
+            let mut guard = self.persistence.lock().map_err(WasmStorageError::lock_poisoned)?;
+            let Some(persistence) = guard.as_mut() else {
+                return Ok(None);
+            };
+
+            let mut entries: Vec<SerializedEntry> = entries
+                .into_iter()
+                .map(|(key, json)| {
+                    let priority = persistence.priorities.get(&key).copied().unwrap_or(PersistPriority::Normal);
+                    SerializedEntry { key, json, priority }
+                })
+                .collect();
+
+            let wal_seq = persistence.wal.as_ref().map(Wal::seq);
+            let snapshot = |entries: &[SerializedEntry]| {
+                let json = snapshot_json(version, wal_seq, entries);
+                match &signing {
+                    Some(signing) => signing.sign_object(&json),
+                    None => json,
+                }
+            };
+            let budget = persistence.quota_bytes * persistence.threshold;
+            let mut dropped = drop_to_budget(&mut entries, budget);
+            let used_bytes = match persistence.backend.set_item(&persistence.storage_key, &snapshot(&entries)) {
+                Ok(()) => entries.iter().map(SerializedEntry::bytes).sum::<f64>(),
+                Err(error) if !is_quota_error(&error) => return Err(error),
+                Err(_) => {
+                    // The real quota was lower than configured: keep only what must survive
+                    dropped.extend(drop_to_budget(&mut entries, 0.0));
+                    match persistence.backend.set_item(&persistence.storage_key, &snapshot(&entries)) {
+                        Ok(()) => entries.iter().map(SerializedEntry::bytes).sum::<f64>(),
+                        Err(error) if is_quota_error(&error) => return Ok(Some(error)),
+                        Err(error) => return Err(error),
+                    }
+                }
+            };
+            if let Some(wal) = persistence.wal.as_mut() {
+                wal.truncate(&persistence.backend)?;
+            }
+
+            dropped.sort();
+            if dropped == persistence.dropped {
+                return Ok(None);
+            }
+            // Evicted keys leave the state entirely, so nothing stays dropped
+            persistence.dropped = if persistence.evict { Vec::new() } else { dropped.clone() };
+            let report = persistence
+                .on_report
+                .clone()
+                .map(|callback| (callback, used_bytes, persistence.quota_bytes));
+            (dropped, persistence.evict, report)
+        };
+
+        if let Some((callback, used_bytes, quota_bytes)) = report {
+            let report = js_sys::Object::new();
+            let dropped_keys: js_sys::Array = dropped.iter().map(|key| JsValue::from_str(key)).collect();
+            js_sys::Reflect::set(&report, &JsValue::from_str("dropped"), &dropped_keys)?;
+            js_sys::Reflect::set(&report, &JsValue::from_str("evicted"), &JsValue::from_bool(evict))?;
+            js_sys::Reflect::set(&report, &JsValue::from_str("usedBytes"), &JsValue::from_f64(used_bytes))?;
+            js_sys::Reflect::set(&report, &JsValue::from_str("quotaBytes"), &JsValue::from_f64(quota_bytes))?;
+            callback.call1(&JsValue::NULL, &report)?;
+        }
+
+        if evict && !dropped.is_empty() {
+            self.with_origin(Origin::System, |store| {
+                for key in dropped.iter() {
+                    store.remove_state(key)?;
+                }
+                Ok(())
+            })?;
+        }
+        Ok(None)
+    }
+
+    // JSON of every persistable entry; transient and filtered-out keys never
+    // reach the backend
+    fn serialized_entries(&self) -> Result<Vec<(String, String)>, JsValue> {
+        let live: Vec<(String, JsValue)> = {
+            let transient = self.transient.lock().map_err(WasmStorageError::lock_poisoned)?;
+            let state = self.state.lock().map_err(WasmStorageError::lock_poisoned)?;
+            state
+                .iter()
+                .filter(|(key, _)| !transient.contains(key))
+                .map(|(key, value)| (key.clone(), value.clone()))
+                .collect()
+        };
+        let mut entries = Vec::with_capacity(live.len());
+        for (key, value) in live {
+            if !self.persistable(&key)? {
+                continue;
+            }
+            // Values JSON can't represent (undefined, functions) are skipped
+            if let Some(json) = JsValue::from(js_sys::JSON::stringify(&value)?).as_string() {
+                entries.push((key, self.compress_json(json)?));
+            }
+        }
+        Ok(entries)
+    }
+}
+
+// Removes droppable entries (cache-only first, then low, largest first)
+// until the snapshot fits the budget; returns the removed keys.
+fn drop_to_budget(entries: &mut Vec<SerializedEntry>, budget: f64) -> Vec<String> {
+    let mut used: f64 = entries.iter().map(SerializedEntry::bytes).sum();
+    let mut dropped = Vec::new();
+    if used <= budget {
+        return dropped;
+    }
+
+    entries.sort_by(|a, b| {
+        a.priority
+            .cmp(&b.priority)
+            .then(b.bytes().partial_cmp(&a.bytes()).unwrap_or(std::cmp::Ordering::Equal))
+    });
+    // The droppable entries come first, so dropping is cutting a prefix
+    let mut count = 0;
+    for entry in entries.iter() {
+        if used <= budget || !entry.priority.droppable() {
+            break;
+        }
+        used -= entry.bytes();
+        count += 1;
+    }
+    dropped.extend(entries.drain(..count).map(|entry| entry.key));
+    dropped
+}
+
+fn snapshot_json(version: u32, wal_seq: Option<u64>, entries: &[SerializedEntry]) -> String {
+    let body: Vec<String> = entries
+        .iter()
+        .map(|entry| format!("{}:{}", js_string(&entry.key), entry.json))
+        .collect();
+    let wal_seq = wal_seq.map(|seq| format!("\"walSeq\":{},", seq)).unwrap_or_default();
+    format!("{{\"version\":{},{}\"data\":{{{}}}}}", version, wal_seq, body.join(","))
+}
+
+pub(crate) fn js_string(value: &str) -> String {
+    js_sys::JSON::stringify(&JsValue::from_str(value))
+        .map(String::from)
+        .unwrap_or_else(|_| format!("\"{}\"", value))
+}
+
+fn option(options: &JsValue, name: &str) -> JsValue {
+    js_sys::Reflect::get(options, &JsValue::from_str(name)).unwrap_or(JsValue::UNDEFINED)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(key: &str, size: usize, priority: PersistPriority) -> SerializedEntry {
+        SerializedEntry { key: key.to_string(), json: "x".repeat(size), priority }
+    }
+
+    #[test]
+    fn drops_low_priority_entries_largest_first() {
+        let mut entries = vec![
+            entry("must", 100, PersistPriority::Must),
+            entry("small-cache", 10, PersistPriority::Cache),
+            entry("low", 50, PersistPriority::Low),
+            entry("big-cache", 40, PersistPriority::Cache),
+            entry("normal", 100, PersistPriority::Normal),
+        ];
+        let total: f64 = entries.iter().map(SerializedEntry::bytes).sum();
+        let cut = entries[3].bytes() + 1.0;
+
+        assert_eq!(drop_to_budget(&mut entries, total - cut), vec!["big-cache", "small-cache"]);
+        let kept: Vec<&str> = entries.iter().map(|entry| entry.key.as_str()).collect();
+        assert_eq!(kept, vec!["low", "normal", "must"]);
+
+        // Must and normal keys stay even over budget
+        assert_eq!(drop_to_budget(&mut entries, 0.0), vec!["low"]);
+        assert_eq!(entries.len(), 2);
+    }
+
+    #[test]
+    fn keeps_everything_within_budget() {
+        let mut entries: Vec<SerializedEntry> = (0..10_000).map(|index| entry(&format!("{:05}", index), 8, PersistPriority::Cache)).collect();
+        let total: f64 = entries.iter().map(SerializedEntry::bytes).sum();
+        assert!(drop_to_budget(&mut entries, total).is_empty());
+        assert_eq!(drop_to_budget(&mut entries, total / 2.0).len(), 5_000);
+        assert_eq!(entries.len(), 5_000);
+    }
+}
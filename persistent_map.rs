@@ -0,0 +1,365 @@
+use crate::sync::Shared;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+const BITS: u32 = 5;
+const MASK: u64 = (1 << BITS) - 1;
+
+/// A hash array mapped trie: a `HashMap<String, V>` whose `clone` is O(1).
+/// Writes copy only the path to the changed entry and share every other
+/// node with earlier clones, so snapshots (dirty baselines, optimistic
+/// checkpoints) cost nothing until the state diverges from them.
+pub(crate) struct PersistentMap<V> {
+    root: Option<Shared<Node<V>>>,
+    len: usize,
+}
+
+enum Node<V> {
+    // One child per set bit, ordered by the 5-bit hash chunk at this depth
+    Branch { bitmap: u32, children: Vec<Shared<Node<V>>> },
+    // Keys whose full hashes are equal
+    Leaf { hash: u64, entries: Vec<(String, V)> },
+}
+
+fn hash_key(key: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    key.hash(&mut hasher);
+    hasher.finish()
+}
+
+fn chunk(hash: u64, shift: u32) -> u32 {
+    ((hash >> shift) & MASK) as u32
+}
+
+// Position of `bit` among the set bits of `bitmap`
+fn slot(bitmap: u32, bit: u32) -> usize {
+    (bitmap & (bit - 1)).count_ones() as usize
+}
+
+impl<V: Clone> Node<V> {
+    fn get(&self, hash: u64, shift: u32, key: &str) -> Option<&V> {
+        match self {
+            Node::Leaf { hash: leaf_hash, entries } => {
+                if *leaf_hash != hash {
+                    return None;
+                }
+                entries.iter().find(|(existing, _)| existing == key).map(|(_, value)| value)
+            }
+            Node::Branch { bitmap, children } => {
+                let bit = 1 << chunk(hash, shift);
+                if bitmap & bit == 0 {
+                    return None;
+                }
+                children[slot(*bitmap, bit)].get(hash, shift + BITS, key)
+            }
+        }
+    }
+
+    fn insert(&self, hash: u64, shift: u32, key: String, value: V) -> (Node<V>, Option<V>) {
+        match self {
+            Node::Leaf { hash: leaf_hash, entries } if *leaf_hash == hash => {
+                let mut entries = entries.clone();
+                let previous = match entries.iter_mut().find(|(existing, _)| *existing == key) {
+                    Some(entry) => Some(std::mem::replace(&mut entry.1, value)),
+                    None => {
+                        entries.push((key, value));
+                        None
+                    }
+                };
+                (Node::Leaf { hash, entries }, previous)
+            }
+            Node::Leaf { hash: leaf_hash, entries } => {
+                let existing = Node::Leaf { hash: *leaf_hash, entries: entries.clone() };
+                let added = Node::Leaf { hash, entries: vec![(key, value)] };
+                (Node::split(existing, *leaf_hash, added, hash, shift), None)
+            }
+            Node::Branch { bitmap, children } => {
+                let bit = 1 << chunk(hash, shift);
+                let index = slot(*bitmap, bit);
+                let mut children = children.clone();
+                if bitmap & bit == 0 {
+                    children.insert(index, Shared::new(Node::Leaf { hash, entries: vec![(key, value)] }));
+                    return (Node::Branch { bitmap: bitmap | bit, children }, None);
+                }
+                let (child, previous) = children[index].insert(hash, shift + BITS, key, value);
+                children[index] = Shared::new(child);
+                (Node::Branch { bitmap: *bitmap, children }, previous)
+            }
+        }
+    }
+
+    // A branch holding two leaves whose hashes differ somewhere past `shift`
+    fn split(a: Node<V>, a_hash: u64, b: Node<V>, b_hash: u64, shift: u32) -> Node<V> {
+        let (a_chunk, b_chunk) = (chunk(a_hash, shift), chunk(b_hash, shift));
+        if a_chunk == b_chunk {
+            let child = Node::split(a, a_hash, b, b_hash, shift + BITS);
+            return Node::Branch { bitmap: 1 << a_chunk, children: vec![Shared::new(child)] };
+        }
+        let children = if a_chunk < b_chunk { vec![Shared::new(a), Shared::new(b)] } else { vec![Shared::new(b), Shared::new(a)] };
+        Node::Branch { bitmap: (1 << a_chunk) | (1 << b_chunk), children }
+    }
+
+    // None when `key` isn't present; Some((None, _)) when the node became empty
+    fn remove(&self, hash: u64, shift: u32, key: &str) -> Option<(Option<Shared<Node<V>>>, V)> {
+        match self {
+            Node::Leaf { hash: leaf_hash, entries } => {
+                if *leaf_hash != hash {
+                    return None;
+                }
+                let index = entries.iter().position(|(existing, _)| existing == key)?;
+                let mut entries = entries.clone();
+                let (_, removed) = entries.remove(index);
+                let node = (!entries.is_empty()).then(|| Shared::new(Node::Leaf { hash, entries }));
+                Some((node, removed))
+            }
+            Node::Branch { bitmap, children } => {
+                let bit = 1 << chunk(hash, shift);
+                if bitmap & bit == 0 {
+                    return None;
+                }
+                let index = slot(*bitmap, bit);
+                let (child, removed) = children[index].remove(hash, shift + BITS, key)?;
+                let mut children = children.clone();
+                let mut bitmap = *bitmap;
+                match child {
+                    Some(child) => children[index] = child,
+                    None => {
+                        children.remove(index);
+                        bitmap &= !bit;
+                    }
+                }
+                let node = match children.as_slice() {
+                    [] => None,
+                    // A lone leaf moves up, keeping paths as short as the keys allow
+                    [only] if matches!(**only, Node::Leaf { .. }) => Some(only.clone()),
+                    _ => Some(Shared::new(Node::Branch { bitmap, children })),
+                };
+                Some((node, removed))
+            }
+        }
+    }
+}
+
+impl<V: Clone> PersistentMap<V> {
+    pub(crate) fn new() -> PersistentMap<V> {
+        PersistentMap { root: None, len: 0 }
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.len
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub(crate) fn get(&self, key: &str) -> Option<&V> {
+        self.get_hashed(hash_key(key), key)
+    }
+
+    pub(crate) fn contains_key(&self, key: &str) -> bool {
+        self.get(key).is_some()
+    }
+
+    pub(crate) fn insert(&mut self, key: String, value: V) -> Option<V> {
+        self.insert_hashed(hash_key(&key), key, value)
+    }
+
+    pub(crate) fn remove(&mut self, key: &str) -> Option<V> {
+        self.remove_hashed(hash_key(key), key)
+    }
+
+    // The hashed forms let tests pick colliding hashes
+    fn get_hashed(&self, hash: u64, key: &str) -> Option<&V> {
+        self.root.as_ref()?.get(hash, 0, key)
+    }
+
+    fn insert_hashed(&mut self, hash: u64, key: String, value: V) -> Option<V> {
+        let (root, previous) = match &self.root {
+            Some(root) => root.insert(hash, 0, key, value),
+            None => (Node::Leaf { hash, entries: vec![(key, value)] }, None),
+        };
+        self.root = Some(Shared::new(root));
+        if previous.is_none() {
+            self.len += 1;
+        }
+        previous
+    }
+
+    fn remove_hashed(&mut self, hash: u64, key: &str) -> Option<V> {
+        let (root, removed) = self.root.as_ref()?.remove(hash, 0, key)?;
+        self.root = root;
+        self.len -= 1;
+        Some(removed)
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.root = None;
+        self.len = 0;
+    }
+
+    pub(crate) fn iter(&self) -> Iter<'_, V> {
+        Iter { stack: self.root.iter().map(|root| &**root).collect(), leaf: [].iter() }
+    }
+
+    pub(crate) fn keys(&self) -> impl Iterator<Item = &String> {
+        self.iter().map(|(key, _)| key)
+    }
+
+    pub(crate) fn values(&self) -> impl Iterator<Item = &V> {
+        self.iter().map(|(_, value)| value)
+    }
+}
+
+impl<V> Clone for PersistentMap<V> {
+    fn clone(&self) -> PersistentMap<V> {
+        PersistentMap { root: self.root.clone(), len: self.len }
+    }
+}
+
+impl<V: Clone> Default for PersistentMap<V> {
+    fn default() -> PersistentMap<V> {
+        PersistentMap::new()
+    }
+}
+
+/// Entries in trie order, which like `HashMap`'s is arbitrary.
+pub(crate) struct Iter<'a, V> {
+    stack: Vec<&'a Node<V>>,
+    leaf: std::slice::Iter<'a, (String, V)>,
+}
+
+impl<'a, V> Iterator for Iter<'a, V> {
+    type Item = (&'a String, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some((key, value)) = self.leaf.next() {
+                return Some((key, value));
+            }
+            match self.stack.pop()? {
+                Node::Leaf { entries, .. } => self.leaf = entries.iter(),
+                Node::Branch { children, .. } => self.stack.extend(children.iter().rev().map(|child| &**child)),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn sorted(map: &PersistentMap<u32>) -> Vec<(String, u32)> {
+        let mut entries: Vec<(String, u32)> = map.iter().map(|(key, value)| (key.clone(), *value)).collect();
+        entries.sort();
+        entries
+    }
+
+    #[test]
+    fn inserts_overwrites_and_removes() {
+        let mut map = PersistentMap::new();
+        assert!(map.is_empty());
+        assert_eq!(map.insert("a".to_string(), 1), None);
+        assert_eq!(map.insert("b".to_string(), 2), None);
+        assert_eq!(map.insert("a".to_string(), 3), Some(1));
+        assert_eq!(map.len(), 2);
+        assert_eq!(map.get("a"), Some(&3));
+        assert!(map.contains_key("b"));
+
+        assert_eq!(map.remove("a"), Some(3));
+        assert_eq!(map.remove("a"), None);
+        assert_eq!(map.remove("missing"), None);
+        assert_eq!(map.len(), 1);
+        assert_eq!(sorted(&map), vec![("b".to_string(), 2)]);
+
+        map.clear();
+        assert!(map.is_empty());
+        assert_eq!(map.get("b"), None);
+    }
+
+    #[test]
+    fn keeps_colliding_keys_in_one_leaf() {
+        let mut map = PersistentMap::new();
+        for (index, key) in ["x", "y", "z"].iter().enumerate() {
+            assert_eq!(map.insert_hashed(42, key.to_string(), index as u32), None);
+        }
+        assert_eq!(map.insert_hashed(42, "y".to_string(), 10), Some(1));
+        assert!(matches!(map.root.as_deref(), Some(Node::Leaf { entries, .. }) if entries.len() == 3));
+        assert_eq!(map.get_hashed(42, "y"), Some(&10));
+        assert_eq!(map.get_hashed(43, "y"), None);
+
+        assert_eq!(map.remove_hashed(42, "x"), Some(0));
+        assert_eq!(map.remove_hashed(42, "x"), None);
+        assert_eq!(map.get_hashed(42, "z"), Some(&2));
+        assert_eq!(map.remove_hashed(42, "y"), Some(10));
+        assert_eq!(map.remove_hashed(42, "z"), Some(2));
+        assert!(map.root.is_none());
+        assert_eq!(map.len(), 0);
+    }
+
+    #[test]
+    fn collapses_branches_when_removing() {
+        // Equal in the low 40 bits, so the split goes eight levels deep
+        let (a, b) = (0x1f, 0x1f | (1 << 40));
+        let mut map = PersistentMap::new();
+        map.insert_hashed(a, "a".to_string(), 1);
+        map.insert_hashed(b, "b".to_string(), 2);
+        let mut depth = 0;
+        let mut node = map.root.as_deref();
+        while let Some(Node::Branch { children, .. }) = node {
+            depth += 1;
+            node = children.first().map(|child| &**child);
+        }
+        assert_eq!(depth, 9);
+
+        assert_eq!(map.remove_hashed(b, "b"), Some(2));
+        assert!(matches!(map.root.as_deref(), Some(Node::Leaf { hash, .. }) if *hash == a));
+        assert_eq!(map.get_hashed(a, "a"), Some(&1));
+    }
+
+    #[test]
+    fn clones_are_unaffected_by_later_writes() {
+        let mut map = PersistentMap::new();
+        for index in 0..100 {
+            map.insert(format!("key{}", index), index);
+        }
+        let snapshot = map.clone();
+        let before = sorted(&snapshot);
+
+        map.insert("key1".to_string(), 1000);
+        map.insert("new".to_string(), 1);
+        for index in 50..100 {
+            map.remove(&format!("key{}", index));
+        }
+
+        assert_eq!(sorted(&snapshot), before);
+        assert_eq!(snapshot.len(), 100);
+        assert_eq!(snapshot.get("key1"), Some(&1));
+        assert_eq!(map.len(), 51);
+        assert_eq!(map.get("key1"), Some(&1000));
+    }
+
+    #[test]
+    fn matches_a_hash_map() {
+        let mut map = PersistentMap::new();
+        let mut model = HashMap::new();
+        // A fixed linear congruential sequence, so failures reproduce
+        let mut seed: u64 = 0x2545_f491;
+        for step in 0..5000 {
+            seed = seed.wrapping_mul(6_364_136_223_846_793_005).wrapping_add(1_442_695_040_888_963_407);
+            let key = format!("k{}", (seed >> 33) % 300);
+            if (seed >> 20).is_multiple_of(3) {
+                assert_eq!(map.remove(&key), model.remove(&key));
+            } else {
+                assert_eq!(map.insert(key.clone(), step), model.insert(key, step));
+            }
+            assert_eq!(map.len(), model.len());
+        }
+
+        let mut expected: Vec<(String, u32)> = model.into_iter().collect();
+        expected.sort();
+        assert_eq!(sorted(&map), expected);
+        assert_eq!(map.iter().count(), map.len());
+    }
+}
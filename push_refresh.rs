@@ -0,0 +1,86 @@
+use crate::WasmStorage;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::{future_to_promise, JsFuture};
+
+/// Dispatches a designated refetch action whenever the hosting Service Worker
+/// receives a push message.
+#[wasm_bindgen]
+pub struct PushRefresh {
+    target: web_sys::EventTarget,
+    _on_push: Closure<dyn FnMut(web_sys::PushEvent)>,
+}
+
+#[wasm_bindgen]
+impl WasmStorage {
+    /// Must be called from a Service Worker. When `topic` is given, only push
+    /// messages whose JSON body carries a matching `topic` field trigger the refetch.
+    /// The dispatch and the persistence flush after it run inside the push
+    /// event's `waitUntil`, so the worker stays alive until async backends
+    /// have written the refreshed state too.
+    #[wasm_bindgen]
+    pub fn enable_push_refresh(&mut self, action_type: &str, topic: Option<String>) -> Result<PushRefresh, JsValue> {
+        let target = js_sys::global().unchecked_into::<web_sys::EventTarget>();
+        let action_type = action_type.to_string();
+        let store = self.clone();
+
+        let on_push = Closure::wrap(Box::new(move |event: web_sys::PushEvent| {
+            let data = push_data(&event);
+            if let Some(topic) = &topic {
+                let pushed_topic = js_sys::Reflect::get(&data, &JsValue::from_str("topic"))
+                    .ok()
+                    .and_then(|t| t.as_string());
+                if pushed_topic.as_deref() != Some(topic.as_str()) {
+                    return;
+                }
+            }
+
+            let payload = js_sys::Object::new();
+            let _ = js_sys::Reflect::set(&payload, &JsValue::from_str("data"), &data);
+            let _ = js_sys::Reflect::set(&payload, &JsValue::from_str("receivedAt"), &JsValue::from_f64(js_sys::Date::now()));
+            let mut store = store.clone();
+            let action_type = action_type.clone();
+            let work = future_to_promise(async move {
+                let written = store.dispatch(&action_type, payload.into()).and_then(|_| store.flush_async());
+                let refreshed = match written {
+                    Ok(written) => JsFuture::from(written).await,
+                    Err(e) => Err(e),
+                };
+                if let Err(e) = &refreshed {
+                    log_warn!("Push refresh dispatch failed: {:?}", e);
+                }
+                refreshed.map(|_| JsValue::UNDEFINED)
+            });
+            if let Err(e) = event.wait_until(&work) {
+                log_warn!("Push refresh could not extend the push event: {:?}", e);
+            }
+        }) as Box<dyn FnMut(web_sys::PushEvent)>);
+
+        target.add_event_listener_with_callback("push", on_push.as_ref().unchecked_ref())?;
+        let teardown_target = target.clone();
+        let teardown_callback = on_push.as_ref().unchecked_ref::<js_sys::Function>().clone();
+        self.register_teardown(Box::new(move || {
+            let _ = teardown_target.remove_event_listener_with_callback("push", &teardown_callback);
+        }))?;
+        log_info!("Push refresh enabled");
+
+        Ok(PushRefresh { target, _on_push: on_push })
+    }
+}
+
+#[wasm_bindgen]
+impl PushRefresh {
+    #[wasm_bindgen]
+    pub fn disable(&self) -> Result<(), JsValue> {
+        self.target
+            .remove_event_listener_with_callback("push", self._on_push.as_ref().unchecked_ref())
+    }
+}
+
+// Push bodies are usually JSON; fall back to the raw text otherwise
+fn push_data(event: &web_sys::PushEvent) -> JsValue {
+    match event.data() {
+        Some(data) => data.json().unwrap_or_else(|_| JsValue::from_str(&data.text())),
+        None => JsValue::NULL,
+    }
+}
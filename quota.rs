@@ -0,0 +1,191 @@
+use crate::error::{ErrorCode, WasmStorageError};
+use crate::error_handler::ErrorSource;
+use crate::origin::Origin;
+use crate::persistence::PersistPriority;
+use crate::WasmStorage;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+
+/// Which persisted keys to give up, in order, when a snapshot doesn't fit
+/// the backend. Evicted keys are removed from the store as well.
+#[derive(Default)]
+pub(crate) enum EvictionPolicy {
+    #[default]
+    None,
+    OldestFirst,
+    LargestFirst,
+    // callback({ storageKey, candidates: [{ key, bytes, lastWrite }] }) -> keys
+    Custom(js_sys::Function),
+}
+
+#[derive(Default)]
+pub(crate) struct QuotaHandling {
+    policy: EvictionPolicy,
+    listeners: Vec<js_sys::Function>,
+}
+
+/// Whether a backend error is the browser's quota error (or the legacy
+/// Firefox one), as opposed to any other write failure.
+pub(crate) fn is_quota_error(error: &JsValue) -> bool {
+    if let Some(exception) = error.dyn_ref::<web_sys::DomException>() {
+        return matches!(exception.code(), 22 | 1014)
+            || matches!(exception.name().as_str(), "QuotaExceededError" | "NS_ERROR_DOM_QUOTA_REACHED");
+    }
+    js_sys::Reflect::get(error, &JsValue::from_str("name"))
+        .ok()
+        .and_then(|name| name.as_string())
+        .is_some_and(|name| name == "QuotaExceededError")
+}
+
+struct Candidate {
+    key: String,
+    bytes: usize,
+    last_write: u64,
+}
+
+#[wasm_bindgen]
+impl WasmStorage {
+    /// What to evict when a snapshot exceeds the backend's quota:
+    /// "none" (the default; the write fails with `QUOTA_EXCEEDED`),
+    /// "oldestFirst" (least recently written keys), "largestFirst", or a
+    /// function receiving `{ storageKey, candidates: [{ key, bytes,
+    /// lastWrite }] }` and returning the keys to evict in order. Keys with
+    /// the "must" persist priority are never candidates. Keys are evicted
+    /// one at a time until the snapshot fits, then removed from the store.
+    #[wasm_bindgen]
+    pub fn set_eviction_policy(&mut self, policy: JsValue) -> Result<(), JsValue> {
+        let policy = match (policy.as_string().as_deref(), policy.dyn_into::<js_sys::Function>()) {
+            (Some("none"), _) => EvictionPolicy::None,
+            (Some("oldestFirst"), _) => EvictionPolicy::OldestFirst,
+            (Some("largestFirst"), _) => EvictionPolicy::LargestFirst,
+            (None, Ok(callback)) => EvictionPolicy::Custom(callback),
+            _ => {
+                return Err(WasmStorageError::invalid_argument(
+                    "Eviction policy must be \"none\", \"oldestFirst\", \"largestFirst\" or a function",
+                ).into())
+            }
+        };
+        self.quota.lock().map_err(WasmStorageError::lock_poisoned)?.policy = policy;
+        Ok(())
+    }
+
+    /// Calls `callback({ storageKey, error, evicted, recovered })` whenever a
+    /// backend rejects a snapshot with `QuotaExceededError`, after the
+    /// eviction policy ran; `recovered` is whether the retried write fit.
+    #[wasm_bindgen]
+    pub fn on_quota_exceeded(&mut self, callback: js_sys::Function) -> Result<(), JsValue> {
+        self.quota.lock().map_err(WasmStorageError::lock_poisoned)?.listeners.push(callback);
+        Ok(())
+    }
+}
+
+impl WasmStorage {
+    /// Evicts keys per the policy, retrying the snapshot after each one,
+    /// then notifies `on_quota_exceeded` listeners. Fails with
+    /// `QUOTA_EXCEEDED` if nothing the policy offers makes it fit.
+    pub(crate) fn evict_for_quota(&mut self, entries: Vec<(String, String)>, quota_error: JsValue) -> Result<(), JsValue> {
+        let Some((storage_key, priorities)) = self.persist_targets()? else {
+            return Ok(());
+        };
+        let candidates = {
+            let changelog = self.changelog.lock().map_err(WasmStorageError::lock_poisoned)?;
+            entries
+                .iter()
+                .filter(|(key, _)| priorities.get(key) != Some(&PersistPriority::Must))
+                .map(|(key, json)| Candidate { key: key.clone(), bytes: key.len() + json.len(), last_write: changelog.last_write(key) })
+                .collect()
+        };
+
+        let mut remaining = entries;
+        let mut evicted = Vec::new();
+        let rejection = quota_error.clone();
+        let mut quota_error = Some(quota_error);
+        for key in self.eviction_order(&storage_key, candidates)? {
+            remaining.retain(|(candidate, _)| *candidate != key);
+            evicted.push(key);
+            quota_error = self.store_entries(remaining.clone())?;
+            if quota_error.is_none() {
+                break;
+            }
+        }
+
+        if quota_error.is_none() {
+            self.with_origin(Origin::System, |store| {
+                for key in evicted.iter() {
+                    store.remove_state(key)?;
+                }
+                Ok(())
+            })?;
+        }
+        self.notify_quota_exceeded(&storage_key, &rejection, &evicted, quota_error.is_none())?;
+
+        match quota_error {
+            Some(cause) => Err(WasmStorageError::new(ErrorCode::QuotaExceeded, "Persisted state exceeds the storage quota")
+                .with("storageKey", storage_key.as_str())
+                .with("cause", cause)
+                .into()),
+            None => Ok(()),
+        }
+    }
+
+    fn eviction_order(&self, storage_key: &str, mut candidates: Vec<Candidate>) -> Result<Vec<String>, JsValue> {
+        let callback = {
+            let quota = self.quota.lock().map_err(WasmStorageError::lock_poisoned)?;
+            match &quota.policy {
+                EvictionPolicy::None => return Ok(Vec::new()),
+                EvictionPolicy::OldestFirst => {
+                    candidates.sort_by_key(|candidate| candidate.last_write);
+                    return Ok(candidates.into_iter().map(|candidate| candidate.key).collect());
+                }
+                EvictionPolicy::LargestFirst => {
+                    candidates.sort_by_key(|candidate| std::cmp::Reverse(candidate.bytes));
+                    return Ok(candidates.into_iter().map(|candidate| candidate.key).collect());
+                }
+                EvictionPolicy::Custom(callback) => callback.clone(),
+            }
+        };
+
+        let offered = js_sys::Array::new();
+        for candidate in candidates.iter() {
+            let entry = js_sys::Object::new();
+            js_sys::Reflect::set(&entry, &JsValue::from_str("key"), &JsValue::from_str(&candidate.key))?;
+            js_sys::Reflect::set(&entry, &JsValue::from_str("bytes"), &JsValue::from_f64(candidate.bytes as f64))?;
+            js_sys::Reflect::set(&entry, &JsValue::from_str("lastWrite"), &JsValue::from_f64(candidate.last_write as f64))?;
+            offered.push(&entry);
+        }
+        let request = js_sys::Object::new();
+        js_sys::Reflect::set(&request, &JsValue::from_str("storageKey"), &JsValue::from_str(storage_key))?;
+        js_sys::Reflect::set(&request, &JsValue::from_str("candidates"), &offered)?;
+
+        let chosen = callback.call1(&JsValue::NULL, &request)?;
+        if !js_sys::Array::is_array(&chosen) {
+            return Err(WasmStorageError::invalid_argument("Eviction policy must return an array of keys").into());
+        }
+        // Only offered keys may go, so a policy can't evict "must" keys
+        Ok(js_sys::Array::from(&chosen)
+            .iter()
+            .filter_map(|key| key.as_string())
+            .filter(|key| candidates.iter().any(|candidate| candidate.key == *key))
+            .collect())
+    }
+
+    fn notify_quota_exceeded(&self, storage_key: &str, error: &JsValue, evicted: &[String], recovered: bool) -> Result<(), JsValue> {
+        let listeners = self.quota.lock().map_err(WasmStorageError::lock_poisoned)?.listeners.clone();
+        if listeners.is_empty() {
+            return Ok(());
+        }
+        let event = js_sys::Object::new();
+        let evicted_keys: js_sys::Array = evicted.iter().map(|key| JsValue::from_str(key)).collect();
+        js_sys::Reflect::set(&event, &JsValue::from_str("storageKey"), &JsValue::from_str(storage_key))?;
+        js_sys::Reflect::set(&event, &JsValue::from_str("error"), error)?;
+        js_sys::Reflect::set(&event, &JsValue::from_str("evicted"), &evicted_keys)?;
+        js_sys::Reflect::set(&event, &JsValue::from_str("recovered"), &JsValue::from_bool(recovered))?;
+        for listener in listeners {
+            if let Err(error) = listener.call1(&JsValue::NULL, &event) {
+                let source = ErrorSource { source: "quota", key: None, action: None, timestamp: js_sys::Date::now() };
+                self.report_error(&error, source)?;
+            }
+        }
+        Ok(())
+    }
+}
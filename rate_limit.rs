@@ -0,0 +1,171 @@
+use crate::WasmStorage;
+use crate::error::WasmStorageError;
+use std::collections::HashMap;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+
+#[derive(Clone, Copy, Debug)]
+enum KeyPolicy {
+    Debounce(f64),
+    Throttle(f64),
+}
+
+/// Rate-limiting state for one key. A deferred write only reaches the
+/// state (and with it listeners and persistence) when it is committed.
+pub(crate) struct RateLimit {
+    policy: KeyPolicy,
+    // Latest deferred write; `Some(None)` is a pending removal
+    pending: Option<Option<JsValue>>,
+    timer: Option<JsValue>,
+    // End of the current throttle window
+    window_until: f64,
+    committing: bool,
+}
+
+pub(crate) type KeyPolicies = HashMap<String, RateLimit>;
+
+#[wasm_bindgen]
+impl WasmStorage {
+    /// Rate-limits writes to `key`: `{ debounceMs }` commits the last write
+    /// after that much quiet, `{ throttleMs }` commits at most once per window
+    /// (leading write immediately, latest write at the end). Until a deferred
+    /// write commits, reads return the previous value. Pass null to remove the
+    /// policy; any pending write is committed first.
+    #[wasm_bindgen]
+    pub fn set_key_policy(&mut self, key: &str, policy: JsValue) -> Result<(), JsValue> {
+        if policy.is_null() || policy.is_undefined() {
+            self.flush_key(key)?;
+            self.key_policies.lock().map_err(WasmStorageError::lock_poisoned)?.remove(key);
+            return Ok(());
+        }
+
+        let debounce = js_sys::Reflect::get(&policy, &JsValue::from_str("debounceMs"))?.as_f64();
+        let throttle = js_sys::Reflect::get(&policy, &JsValue::from_str("throttleMs"))?.as_f64();
+        let policy = match (debounce, throttle) {
+            (Some(ms), None) => KeyPolicy::Debounce(ms.max(0.0)),
+            (None, Some(ms)) => KeyPolicy::Throttle(ms.max(0.0)),
+            _ => return Err(WasmStorageError::invalid_argument("Key policy needs exactly one of debounceMs or throttleMs").into()),
+        };
+
+        self.flush_key(key)?;
+        let mut policies = self.key_policies.lock().map_err(WasmStorageError::lock_poisoned)?;
+        policies.insert(
+            key.to_string(),
+            RateLimit { policy, pending: None, timer: None, window_until: 0.0, committing: false },
+        );
+        Ok(())
+    }
+
+    /// Commits a deferred write to `key` right away, e.g. before unload.
+    #[wasm_bindgen]
+    pub fn flush_key(&mut self, key: &str) -> Result<(), JsValue> {
+        let pending = {
+            let mut policies = self.key_policies.lock().map_err(WasmStorageError::lock_poisoned)?;
+            let Some(limit) = policies.get_mut(key) else {
+                return Ok(());
+            };
+            if let Some(timer) = limit.timer.take() {
+                clear_timeout(&timer)?;
+            }
+            let Some(pending) = limit.pending.take() else {
+                return Ok(());
+            };
+            limit.committing = true;
+            if let KeyPolicy::Throttle(ms) = limit.policy {
+                limit.window_until = js_sys::Date::now() + ms;
+            }
+            pending
+        };
+
+        let result = match pending {
+            Some(value) => self.set_state(key, value),
+            None => self.remove_state(key),
+        };
+        if let Some(limit) = self.key_policies.lock().map_err(WasmStorageError::lock_poisoned)?.get_mut(key) {
+            limit.committing = false;
+        }
+        result
+    }
+}
+
+impl WasmStorage {
+    /// The deferred write waiting on each rate-limited key that has one.
+    pub(crate) fn deferred_writes(&self) -> Result<Vec<(String, Option<JsValue>)>, JsValue> {
+        let policies = self.key_policies.lock().map_err(WasmStorageError::lock_poisoned)?;
+        Ok(policies
+            .iter()
+            .filter_map(|(key, limit)| limit.pending.clone().map(|pending| (key.clone(), pending)))
+            .collect())
+    }
+
+    /// Puts `key`'s deferred write back to `pending`; None cancels it.
+    pub(crate) fn restore_deferred(&mut self, key: &str, pending: Option<Option<JsValue>>) -> Result<(), JsValue> {
+        let mut policies = self.key_policies.lock().map_err(WasmStorageError::lock_poisoned)?;
+        let Some(limit) = policies.get_mut(key) else {
+            return Ok(());
+        };
+        if pending.is_none() {
+            if let Some(timer) = limit.timer.take() {
+                clear_timeout(&timer)?;
+            }
+        }
+        limit.pending = pending;
+        Ok(())
+    }
+
+    /// Called at the start of every write; returns true when the write was
+    /// deferred by the key's policy and must not be applied now.
+    pub(crate) fn rate_limit(&mut self, key: &str, value: Option<&JsValue>) -> Result<bool, JsValue> {
+        let mut policies = self.key_policies.lock().map_err(WasmStorageError::lock_poisoned)?;
+        let Some(limit) = policies.get_mut(key) else {
+            return Ok(false);
+        };
+        if limit.committing {
+            return Ok(false);
+        }
+
+        let now = js_sys::Date::now();
+        let delay = match limit.policy {
+            KeyPolicy::Debounce(ms) => {
+                if let Some(timer) = limit.timer.take() {
+                    clear_timeout(&timer)?;
+                }
+                ms
+            }
+            KeyPolicy::Throttle(ms) => {
+                if limit.timer.is_none() && now >= limit.window_until {
+                    // Leading edge: write through and open a new window
+                    limit.window_until = now + ms;
+                    return Ok(false);
+                }
+                limit.window_until - now
+            }
+        };
+
+        limit.pending = Some(value.cloned());
+        if limit.timer.is_none() {
+            let mut store = self.clone();
+            let commit_key = key.to_string();
+            let commit = Closure::once_into_js(move || {
+                if let Err(e) = store.flush_key(&commit_key) {
+                    log_warn!("Deferred write to {} failed: {:?}", commit_key, e);
+                }
+            });
+            limit.timer = Some(set_timeout(&commit, delay.max(0.0))?);
+        }
+        Ok(true)
+    }
+}
+
+pub(crate) fn set_timeout(callback: &JsValue, delay_ms: f64) -> Result<JsValue, JsValue> {
+    let set_timeout = js_sys::Reflect::get(&js_sys::global(), &JsValue::from_str("setTimeout"))?
+        .dyn_into::<js_sys::Function>()?;
+    set_timeout.call2(&JsValue::NULL, callback, &JsValue::from_f64(delay_ms))
+}
+
+pub(crate) fn clear_timeout(timer: &JsValue) -> Result<(), JsValue> {
+    let clear_timeout = js_sys::Reflect::get(&js_sys::global(), &JsValue::from_str("clearTimeout"))?
+        .dyn_into::<js_sys::Function>()?;
+    clear_timeout.call1(&JsValue::NULL, timer)?;
+    Ok(())
+}
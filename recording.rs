@@ -0,0 +1,170 @@
+use crate::error::WasmStorageError;
+use crate::origin::Origin;
+use crate::{StorageEvent, WasmStorage};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+
+const FIXTURE_VERSION: u32 = 1;
+
+/// A session being recorded: the state when it started and every action
+/// dispatched since, both as JSON so later mutations can't leak into it.
+pub(crate) struct Recording {
+    initial_state: Vec<(String, String)>,
+    actions: Vec<RecordedAction>,
+    started_at: f64,
+}
+
+struct RecordedAction {
+    action_type: String,
+    payload_json: String,
+    timestamp: f64,
+}
+
+fn field(value: &JsValue, name: &str) -> JsValue {
+    js_sys::Reflect::get(value, &JsValue::from_str(name)).unwrap_or(JsValue::UNDEFINED)
+}
+
+fn to_json(value: &JsValue) -> Option<String> {
+    if value.is_undefined() {
+        return Some("null".to_string());
+    }
+    js_sys::JSON::stringify(value).ok().and_then(|json| json.as_string())
+}
+
+fn invalid_fixture(message: &str) -> WasmStorageError {
+    WasmStorageError::invalid_argument(format!("Invalid fixture: {}", message))
+}
+
+#[wasm_bindgen]
+impl WasmStorage {
+    /// Starts capturing a reproducible session: the current state, then
+    /// every dispatched action (after middleware) until `stop_recording`.
+    /// Restarts the recording if one is running. Keys whose values JSON
+    /// can't represent are left out.
+    #[wasm_bindgen]
+    pub fn start_recording(&mut self) -> Result<(), JsValue> {
+        let initial_state = {
+            let state = self.state.lock().map_err(WasmStorageError::lock_poisoned)?;
+            let mut entries: Vec<(String, String)> = state
+                .iter()
+                .filter_map(|(key, value)| to_json(value).map(|json| (key.clone(), json)))
+                .collect();
+            entries.sort_by(|a, b| a.0.cmp(&b.0));
+            entries
+        };
+        let initial_state = self.retain_readable(initial_state, |(key, _)| key)?;
+        let started_at = self.now()?;
+        *self.recording.lock().map_err(WasmStorageError::lock_poisoned)? =
+            Some(Recording { initial_state, actions: Vec::new(), started_at });
+        log_info!("Recording started");
+        Ok(())
+    }
+
+    /// Stops recording and returns the fixture as a JSON-serializable
+    /// `{ version, recordedAt, initialState, actions: [{ type, payload, timestamp }] }`.
+    #[wasm_bindgen]
+    pub fn stop_recording(&mut self) -> Result<js_sys::Object, JsValue> {
+        let recording = self
+            .recording
+            .lock()
+            .map_err(WasmStorageError::lock_poisoned)?
+            .take()
+            .ok_or_else(|| WasmStorageError::invalid_state("No recording is running; call start_recording first"))?;
+
+        let initial_state = js_sys::Object::new();
+        for (key, json) in &recording.initial_state {
+            js_sys::Reflect::set(&initial_state, &JsValue::from_str(key), &js_sys::JSON::parse(json)?)?;
+        }
+        let actions = js_sys::Array::new();
+        for action in &recording.actions {
+            let entry = js_sys::Object::new();
+            js_sys::Reflect::set(&entry, &JsValue::from_str("type"), &JsValue::from_str(&action.action_type))?;
+            js_sys::Reflect::set(&entry, &JsValue::from_str("payload"), &js_sys::JSON::parse(&action.payload_json)?)?;
+            js_sys::Reflect::set(&entry, &JsValue::from_str("timestamp"), &JsValue::from_f64(action.timestamp))?;
+            actions.push(&entry);
+        }
+        let fixture = js_sys::Object::new();
+        js_sys::Reflect::set(&fixture, &JsValue::from_str("version"), &JsValue::from(FIXTURE_VERSION))?;
+        js_sys::Reflect::set(&fixture, &JsValue::from_str("recordedAt"), &JsValue::from_f64(recording.started_at))?;
+        js_sys::Reflect::set(&fixture, &JsValue::from_str("initialState"), &initial_state)?;
+        js_sys::Reflect::set(&fixture, &JsValue::from_str("actions"), &actions)?;
+        log_info!("Recording stopped with {} actions", recording.actions.len());
+        Ok(fixture)
+    }
+
+    #[wasm_bindgen]
+    pub fn is_recording(&self) -> Result<bool, JsValue> {
+        Ok(self.recording.lock().map_err(WasmStorageError::lock_poisoned)?.is_some())
+    }
+
+    /// Replaces the state with a fixture from `stop_recording` (the object or
+    /// its JSON text) and replays its actions in order, each with its
+    /// recorded timestamp and without running middleware again. Changes
+    /// carry the "replay" origin. Returns the number of actions replayed.
+    #[wasm_bindgen]
+    pub fn load_fixture(&mut self, fixture: JsValue) -> Result<u32, JsValue> {
+        self.ensure_alive()?;
+        let fixture = match fixture.as_string() {
+            Some(json) => js_sys::JSON::parse(&json).map_err(|_| invalid_fixture("not valid JSON"))?,
+            None => fixture,
+        };
+        let version = field(&fixture, "version").as_f64().ok_or_else(|| invalid_fixture("missing version"))?;
+        if version as u32 > FIXTURE_VERSION {
+            return Err(invalid_fixture(&format!("version {} is newer than this build supports ({})", version, FIXTURE_VERSION))
+                .with("version", version)
+                .into());
+        }
+        let initial_state = field(&fixture, "initialState")
+            .dyn_into::<js_sys::Object>()
+            .map_err(|_| invalid_fixture("initialState must be an object"))?;
+        let actions = field(&fixture, "actions");
+        if !js_sys::Array::is_array(&actions) {
+            return Err(invalid_fixture("actions must be an array").into());
+        }
+        let mut events = Vec::new();
+        for action in js_sys::Array::from(&actions).iter() {
+            let action_type = field(&action, "type").as_string().ok_or_else(|| invalid_fixture("every action needs a type"))?;
+            events.push(StorageEvent {
+                action_type,
+                payload: field(&action, "payload"),
+                timestamp: field(&action, "timestamp").as_f64().unwrap_or(0.0),
+                changes: None,
+            });
+        }
+
+        let replayed = events.len() as u32;
+        self.with_origin(Origin::Replay, |store| {
+            store.clear_state()?;
+            for entry in js_sys::Object::entries(&initial_state).iter() {
+                let pair = js_sys::Array::from(&entry);
+                store.set_state(&pair.get(0).as_string().unwrap_or_default(), pair.get(1))?;
+            }
+            for event in &events {
+                store.at_time(event.timestamp, |store| store.handle_action(event))?;
+            }
+            Ok(())
+        })?;
+        log_info!("Loaded fixture with {} actions", replayed);
+        Ok(replayed)
+    }
+}
+
+impl WasmStorage {
+    /// Adds a handled action to the running recording, if any.
+    pub(crate) fn record_action(&self, event: &StorageEvent) -> Result<(), JsValue> {
+        let mut recording = self.recording.lock().map_err(WasmStorageError::lock_poisoned)?;
+        let Some(recording) = recording.as_mut() else {
+            return Ok(());
+        };
+        let payload_json = to_json(&event.payload).unwrap_or_else(|| {
+            log_warn!("Recording {} with a null payload; JSON can't represent it", event.action_type);
+            "null".to_string()
+        });
+        recording.actions.push(RecordedAction {
+            action_type: event.action_type.clone(),
+            payload_json,
+            timestamp: event.timestamp,
+        });
+        Ok(())
+    }
+}
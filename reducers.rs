@@ -0,0 +1,289 @@
+use crate::WasmStorage;
+use crate::error::{ErrorCode, WasmStorageError};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+
+// Declarative reducers: `;`-separated statements evaluated in Rust.
+//
+//   set <path> = <value>        merge <path> = <value>
+//   increment <path> [by <value>]
+//   remove <path>
+//
+// A path is a state key followed by nested properties (`user.profile.name`);
+// quote segments containing dots (`"user.id"`). A value is `payload`,
+// `payload.<path>` or a JSON literal.
+
+#[derive(Clone)]
+enum Value {
+    Payload(Vec<String>),
+    Literal(JsValue),
+}
+
+#[derive(Clone)]
+enum Update {
+    Set(Value),
+    Merge(Value),
+    Increment(Value),
+    Remove,
+}
+
+#[derive(Clone)]
+pub(crate) struct Statement {
+    path: Vec<String>,
+    update: Update,
+}
+
+#[wasm_bindgen]
+impl WasmStorage {
+    /// Handles `action_type` with a reducer expression, so it no longer goes
+    /// to `on_unhandled_action`. The expression is parsed here, so
+    /// syntax errors surface at registration rather than at dispatch.
+    #[wasm_bindgen]
+    pub fn register_reducer(&mut self, action_type: &str, expression: &str) -> Result<(), JsValue> {
+        let statements = parse(expression)
+            .map_err(|e| WasmStorageError::invalid_argument(format!("Invalid reducer for {}: {}", action_type, e)))?;
+        let mut reducers = self.reducers.lock().map_err(WasmStorageError::lock_poisoned)?;
+        reducers.insert(action_type.to_string(), statements);
+        Ok(())
+    }
+
+    #[wasm_bindgen]
+    pub fn unregister_reducer(&mut self, action_type: &str) -> Result<(), JsValue> {
+        let mut reducers = self.reducers.lock().map_err(WasmStorageError::lock_poisoned)?;
+        reducers.remove(action_type);
+        Ok(())
+    }
+}
+
+impl WasmStorage {
+    /// Runs the reducer registered for `action_type`; returns false if there is none.
+    pub(crate) fn apply_reducer(&mut self, action_type: &str, payload: &JsValue) -> Result<bool, JsValue> {
+        let statements = {
+            let reducers = self.reducers.lock().map_err(WasmStorageError::lock_poisoned)?;
+            match reducers.get(action_type) {
+                Some(statements) => statements.clone(),
+                None => return Ok(false),
+            }
+        };
+
+        // Compute every statement before writing, so a rejected value leaves
+        // the state untouched
+        let mut staged: Vec<(String, Option<JsValue>)> = Vec::new();
+        for statement in statements.iter() {
+            let key = &statement.path[0];
+            let current = match staged.iter().rev().find(|(staged_key, _)| staged_key == key) {
+                Some((_, value)) => value.clone(),
+                None if self.has(key)? => Some(self.get_state(key)?),
+                None => None,
+            };
+            let updated = update_path(current, &statement.path[1..], &statement.update, payload)?;
+            if let Some(value) = &updated {
+                self.validate(key, value)?;
+            }
+            staged.push((key.clone(), updated));
+        }
+
+        for (key, value) in staged {
+            match value {
+                Some(value) => self.set_state(&key, value)?,
+                None => self.remove_state(&key)?,
+            }
+        }
+        Ok(true)
+    }
+}
+
+// Copy-on-write update of `path` inside `current`, so a changed nested field
+// always yields a new top-level object. `None` means "delete".
+fn update_path(current: Option<JsValue>, path: &[String], update: &Update, payload: &JsValue) -> Result<Option<JsValue>, JsValue> {
+    let Some((segment, rest)) = path.split_first() else {
+        return apply_update(current, update, payload);
+    };
+
+    let container: js_sys::Object = match current {
+        Some(value) if js_sys::Array::is_array(&value) => js_sys::Array::from(&value).into(),
+        Some(value) if value.is_object() => js_sys::Object::assign(&js_sys::Object::new(), value.unchecked_ref()),
+        _ => js_sys::Object::new(),
+    };
+    let segment = JsValue::from_str(segment);
+    let child = js_sys::Reflect::get(&container, &segment)?;
+    let child = if child.is_undefined() { None } else { Some(child) };
+
+    match update_path(child, rest, update, payload)? {
+        Some(value) => js_sys::Reflect::set(&container, &segment, &value)?,
+        None => js_sys::Reflect::delete_property(&container, &segment)?,
+    };
+    Ok(Some(container.into()))
+}
+
+fn apply_update(current: Option<JsValue>, update: &Update, payload: &JsValue) -> Result<Option<JsValue>, JsValue> {
+    match update {
+        Update::Set(value) => Ok(Some(resolve(value, payload)?)),
+        Update::Merge(value) => {
+            let value = resolve(value, payload)?;
+            if !value.is_object() {
+                return Err(WasmStorageError::new(ErrorCode::TypeMismatch, "merge needs an object value").into());
+            }
+            let base = current.filter(JsValue::is_object).unwrap_or_else(|| js_sys::Object::new().into());
+            let merged = js_sys::Object::assign(&js_sys::Object::new(), base.unchecked_ref());
+            Ok(Some(js_sys::Object::assign(&merged, value.unchecked_ref()).into()))
+        }
+        Update::Increment(by) => {
+            let by = resolve(by, payload)?
+                .as_f64()
+                .ok_or_else(|| WasmStorageError::invalid_argument("increment needs a numeric amount"))?;
+            let base = match current {
+                Some(value) if !value.is_null() => value
+                    .as_f64()
+                    .ok_or_else(|| WasmStorageError::new(ErrorCode::TypeMismatch, "increment target is not a number"))?,
+                _ => 0.0,
+            };
+            Ok(Some(JsValue::from_f64(base + by)))
+        }
+        Update::Remove => Ok(None),
+    }
+}
+
+fn resolve(value: &Value, payload: &JsValue) -> Result<JsValue, JsValue> {
+    match value {
+        Value::Literal(literal) => Ok(literal.clone()),
+        Value::Payload(path) => {
+            let mut current = payload.clone();
+            for segment in path {
+                if !current.is_object() {
+                    return Ok(JsValue::UNDEFINED);
+                }
+                current = js_sys::Reflect::get(&current, &JsValue::from_str(segment))?;
+            }
+            Ok(current)
+        }
+    }
+}
+
+fn parse(expression: &str) -> Result<Vec<Statement>, String> {
+    let statements: Vec<Statement> = split_statements(expression)
+        .iter()
+        .map(|statement| parse_statement(statement))
+        .collect::<Result<_, _>>()?;
+    if statements.is_empty() {
+        return Err("expression has no statements".to_string());
+    }
+    Ok(statements)
+}
+
+// Splits on `;` outside of string literals
+fn split_statements(expression: &str) -> Vec<String> {
+    let mut statements = Vec::new();
+    let mut current = String::new();
+    let mut in_string = false;
+    let mut escaped = false;
+    for c in expression.chars() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if in_string => escaped = true,
+            '"' => in_string = !in_string,
+            ';' if !in_string => {
+                statements.push(std::mem::take(&mut current));
+                continue;
+            }
+            _ => {}
+        }
+        current.push(c);
+    }
+    statements.push(current);
+    statements
+        .into_iter()
+        .map(|statement| statement.trim().to_string())
+        .filter(|statement| !statement.is_empty())
+        .collect()
+}
+
+fn parse_statement(statement: &str) -> Result<Statement, String> {
+    let (verb, rest) = statement.split_once(char::is_whitespace).unwrap_or((statement, ""));
+    let (path, rest) = parse_path(rest)?;
+    let rest = rest.trim();
+
+    let update = match verb {
+        "set" | "merge" => {
+            let value = rest
+                .strip_prefix('=')
+                .ok_or_else(|| format!("expected `=` after the path in `{}`", statement))?;
+            let value = parse_value(value)?;
+            if verb == "set" {
+                Update::Set(value)
+            } else {
+                Update::Merge(value)
+            }
+        }
+        "increment" if rest.is_empty() => Update::Increment(Value::Literal(JsValue::from_f64(1.0))),
+        "increment" => {
+            let amount = rest
+                .strip_prefix("by")
+                .filter(|amount| amount.starts_with(char::is_whitespace))
+                .ok_or_else(|| format!("expected `by <value>` in `{}`", statement))?;
+            Update::Increment(parse_value(amount)?)
+        }
+        "remove" if rest.is_empty() => Update::Remove,
+        "remove" => return Err(format!("unexpected `{}` after remove path", rest)),
+        _ => return Err(format!("unknown operation `{}` (expected set, merge, increment or remove)", verb)),
+    };
+    Ok(Statement { path, update })
+}
+
+// Parses `segment(.segment)*`, returning the segments and the unparsed rest
+fn parse_path(input: &str) -> Result<(Vec<String>, &str), String> {
+    let mut rest = input.trim_start();
+    let mut segments = Vec::new();
+    loop {
+        let (segment, after) = if let Some(quoted) = rest.strip_prefix('"') {
+            parse_quoted(quoted)?
+        } else {
+            let end = rest
+                .find(|c: char| !(c.is_alphanumeric() || c == '_' || c == '$' || c == '-'))
+                .unwrap_or(rest.len());
+            (rest[..end].to_string(), &rest[end..])
+        };
+        if segment.is_empty() {
+            return Err(format!("expected a path at `{}`", input.trim()));
+        }
+        segments.push(segment);
+        match after.strip_prefix('.') {
+            Some(next) => rest = next,
+            None => return Ok((segments, after)),
+        }
+    }
+}
+
+fn parse_quoted(input: &str) -> Result<(String, &str), String> {
+    let mut segment = String::new();
+    let mut escaped = false;
+    for (i, c) in input.char_indices() {
+        match c {
+            _ if escaped => {
+                segment.push(c);
+                escaped = false;
+            }
+            '\\' => escaped = true,
+            '"' => return Ok((segment, &input[i + 1..])),
+            _ => segment.push(c),
+        }
+    }
+    Err("unterminated quoted path segment".to_string())
+}
+
+fn parse_value(input: &str) -> Result<Value, String> {
+    let input = input.trim();
+    if input == "payload" {
+        return Ok(Value::Payload(Vec::new()));
+    }
+    if let Some(path) = input.strip_prefix("payload.") {
+        let (path, rest) = parse_path(path)?;
+        if !rest.trim().is_empty() {
+            return Err(format!("unexpected `{}` after payload path", rest.trim()));
+        }
+        return Ok(Value::Payload(path));
+    }
+    js_sys::JSON::parse(input)
+        .map(Value::Literal)
+        .map_err(|_| format!("`{}` is neither payload nor a JSON literal", input))
+}
@@ -0,0 +1,58 @@
+use crate::error::WasmStorageError;
+use crate::WasmStorage;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use wasm_bindgen::prelude::*;
+
+thread_local! {
+    // Named stores of this module instance, by name
+    static REGISTRY: RefCell<BTreeMap<String, WasmStorage>> = const { RefCell::new(BTreeMap::new()) };
+}
+
+#[wasm_bindgen]
+impl WasmStorage {
+    /// The store registered as `name`, created with `options` (as for the
+    /// constructor) the first time, so every bundle sharing this module
+    /// works on one store instead of each creating its own. A destroyed
+    /// store is replaced by a new one.
+    #[wasm_bindgen]
+    pub fn get_or_create(name: &str, options: Option<js_sys::Object>) -> Result<WasmStorage, JsValue> {
+        if name.is_empty() {
+            return Err(WasmStorageError::invalid_argument("Store names must not be empty").into());
+        }
+        let existing = REGISTRY.with(|registry| registry.borrow().get(name).cloned());
+        if let Some(store) = existing {
+            if !store.destroyed()? {
+                return Ok(store);
+            }
+        }
+
+        let store = WasmStorage::new(options);
+        *store.name.lock().map_err(WasmStorageError::lock_poisoned)? = Some(name.to_string());
+        REGISTRY.with(|registry| registry.borrow_mut().insert(name.to_string(), store.clone()));
+        log_debug!("Created named store {}", name);
+        Ok(store)
+    }
+
+    /// Names of the live stores created by `get_or_create`, sorted.
+    #[wasm_bindgen]
+    pub fn list_instances() -> Result<js_sys::Array, JsValue> {
+        let stores: Vec<(String, WasmStorage)> = REGISTRY.with(|registry| {
+            registry.borrow().iter().map(|(name, store)| (name.clone(), store.clone())).collect()
+        });
+        let names = js_sys::Array::new();
+        for (name, store) in stores {
+            if !store.destroyed()? {
+                names.push(&JsValue::from_str(&name));
+            }
+        }
+        Ok(names)
+    }
+
+    /// The name the store was registered under by `get_or_create`, if any;
+    /// devtools integrations use it as the store's label.
+    #[wasm_bindgen(getter)]
+    pub fn name(&self) -> Result<Option<String>, JsValue> {
+        Ok(self.name.lock().map_err(WasmStorageError::lock_poisoned)?.clone())
+    }
+}
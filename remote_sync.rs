@@ -0,0 +1,380 @@
+use crate::conflicts::Merge;
+use crate::error::WasmStorageError;
+use crate::rate_limit::{clear_timeout, set_timeout};
+use crate::subscriptions::Subscription;
+use crate::{glob_match, WasmStorage};
+use std::collections::{HashMap, VecDeque};
+use crate::sync::{Lock, Shared};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+
+const DEFAULT_MIN_BACKOFF_MS: f64 = 500.0;
+const DEFAULT_MAX_BACKOFF_MS: f64 = 30_000.0;
+const DEFAULT_MAX_QUEUED: usize = 1000;
+
+// A socket and the handlers attached to it; replaced on every reconnect
+struct Socket {
+    socket: web_sys::WebSocket,
+    _on_open: Closure<dyn FnMut(JsValue)>,
+    _on_message: Closure<dyn FnMut(web_sys::MessageEvent)>,
+    _on_close: Closure<dyn FnMut(JsValue)>,
+}
+
+struct Connection {
+    socket: Option<Socket>,
+    status: &'static str,
+    // Mutations (key, value, message) waiting for an open socket, oldest
+    // first, at most one per key
+    outbox: VecDeque<(String, JsValue, String)>,
+    attempts: u32,
+    reconnect_timer: Option<JsValue>,
+    closed: bool,
+}
+
+struct RemoteShared {
+    url: String,
+    client_id: String,
+    // Keys or globs to sync; empty syncs every key
+    keys: Vec<String>,
+    min_backoff_ms: f64,
+    max_backoff_ms: f64,
+    max_queued: usize,
+    on_status: Option<js_sys::Function>,
+    connection: Lock<Connection>,
+    // Last value of each key sent to or received from the server: the
+    // common ancestor when a pushed mutation meets an unsent local one
+    known: Lock<HashMap<String, JsValue>>,
+}
+
+/// Client half of a realtime sync pipeline: streams local mutations to a
+/// WebSocket server and applies the mutations it pushes, reconnecting with
+/// exponential backoff and queueing outbound mutations while offline.
+#[wasm_bindgen]
+pub struct RemoteSync {
+    shared: Shared<RemoteShared>,
+    subscription: Option<Subscription>,
+    _listener: Closure<dyn FnMut(JsValue)>,
+}
+
+#[wasm_bindgen]
+impl WasmStorage {
+    /// Connects to `ws_url`. Both directions exchange JSON messages
+    /// `{ kind: "mutation", key, value, client, ts }` (a null value removes
+    /// the key); the server may also push `{ kind: "snapshot", entries }`
+    /// with a key -> value object. Options: `keys` (keys or globs, default
+    /// all), `minBackoffMs` / `maxBackoffMs` (reconnect delay bounds,
+    /// default 500 and 30000), `maxQueued` (mutations kept while offline,
+    /// default 1000; the oldest go first) and `onStatus(status)` with
+    /// "connecting", "open", "reconnecting" or "closed". Pushed mutations
+    /// are applied with the "sync" origin and aren't sent back. With
+    /// `enable_crdt`, mutations also carry the key's stamp as `clock`, and
+    /// pushed mutations with a `clock` only apply if they win. Otherwise a
+    /// pushed mutation that meets an unsent local change goes to the
+    /// `set_conflict_resolver` callback when one is registered.
+    #[wasm_bindgen]
+    pub fn connect_remote(&mut self, ws_url: &str, options: Option<js_sys::Object>) -> Result<RemoteSync, JsValue> {
+        let options = options.map(JsValue::from).unwrap_or(JsValue::UNDEFINED);
+        let option = |name: &str| js_sys::Reflect::get(&options, &JsValue::from_str(name)).unwrap_or(JsValue::UNDEFINED);
+        let keys = option("keys");
+        let keys = if js_sys::Array::is_array(&keys) {
+            js_sys::Array::from(&keys).iter().filter_map(|key| key.as_string()).collect()
+        } else {
+            Vec::new()
+        };
+
+        let shared = Shared::new(RemoteShared {
+            url: ws_url.to_string(),
+            client_id: self.generate_id("remote")?,
+            keys,
+            min_backoff_ms: option("minBackoffMs").as_f64().unwrap_or(DEFAULT_MIN_BACKOFF_MS).max(0.0),
+            max_backoff_ms: option("maxBackoffMs").as_f64().unwrap_or(DEFAULT_MAX_BACKOFF_MS).max(0.0),
+            max_queued: option("maxQueued").as_f64().map_or(DEFAULT_MAX_QUEUED, |max| max.max(1.0) as usize),
+            on_status: option("onStatus").dyn_into::<js_sys::Function>().ok(),
+            connection: Lock::new(Connection {
+                socket: None,
+                status: "connecting",
+                outbox: VecDeque::new(),
+                attempts: 0,
+                reconnect_timer: None,
+                closed: false,
+            }),
+            known: Lock::new(HashMap::new()),
+        });
+        shared.open(self.clone())?;
+
+        // Forward local changes of synced keys to the server
+        let listener_shared = shared.clone();
+        let listener_store = self.clone();
+        let listener = Closure::wrap(Box::new(move |event: JsValue| {
+            if let Err(e) = listener_shared.send_local_change(&listener_store, &event) {
+                log_warn!("Remote sync failed to send a change: {:?}", e);
+            }
+        }) as Box<dyn FnMut(JsValue)>);
+        let subscription = self.subscribe(listener.as_ref().unchecked_ref::<js_sys::Function>().clone(), None)?;
+
+        let teardown_shared = shared.clone();
+        self.register_teardown(Box::new(move || {
+            let _ = teardown_shared.shut_down();
+        }))?;
+
+        log_info!("Remote sync connecting to {} as {}", ws_url, shared.client_id);
+        Ok(RemoteSync { shared, subscription: Some(subscription), _listener: listener })
+    }
+}
+
+#[wasm_bindgen]
+impl RemoteSync {
+    #[wasm_bindgen(getter)]
+    pub fn client_id(&self) -> String {
+        self.shared.client_id.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn status(&self) -> Result<String, JsValue> {
+        Ok(self.shared.connection.lock().map_err(WasmStorageError::lock_poisoned)?.status.to_string())
+    }
+
+    /// Mutations waiting to be sent.
+    #[wasm_bindgen(getter)]
+    pub fn queued(&self) -> Result<u32, JsValue> {
+        Ok(self.shared.connection.lock().map_err(WasmStorageError::lock_poisoned)?.outbox.len() as u32)
+    }
+
+    /// Closes the socket and stops syncing; queued mutations are discarded.
+    #[wasm_bindgen]
+    pub fn close(&mut self) -> Result<(), JsValue> {
+        if let Some(subscription) = self.subscription.take() {
+            subscription.unsubscribe()?;
+        }
+        self.shared.shut_down()
+    }
+}
+
+impl RemoteShared {
+    fn syncs(&self, key: &str) -> bool {
+        self.keys.is_empty() || self.keys.iter().any(|pattern| glob_match(pattern, key))
+    }
+
+    fn open(self: &Shared<Self>, store: WasmStorage) -> Result<(), JsValue> {
+        let socket = web_sys::WebSocket::new(&self.url)?;
+
+        let open_shared = self.clone();
+        let on_open = Closure::wrap(Box::new(move |_: JsValue| {
+            if let Err(e) = open_shared.flush_outbox() {
+                log_warn!("Remote sync failed to flush queued changes: {:?}", e);
+            }
+        }) as Box<dyn FnMut(JsValue)>);
+
+        let message_shared = self.clone();
+        let mut message_store = store.clone();
+        let on_message = Closure::wrap(Box::new(move |event: web_sys::MessageEvent| {
+            if let Some(data) = event.data().as_string() {
+                if let Err(e) = message_shared.receive(&mut message_store, &data) {
+                    log_warn!("Remote sync message rejected: {:?}", e);
+                }
+            }
+        }) as Box<dyn FnMut(web_sys::MessageEvent)>);
+
+        // Errors are always followed by close, so reconnecting here covers both
+        let close_shared = self.clone();
+        let on_close = Closure::wrap(Box::new(move |_: JsValue| {
+            if let Err(e) = close_shared.schedule_reconnect(store.clone()) {
+                log_warn!("Remote sync failed to reconnect: {:?}", e);
+            }
+        }) as Box<dyn FnMut(JsValue)>);
+
+        socket.set_onopen(Some(on_open.as_ref().unchecked_ref()));
+        socket.set_onmessage(Some(on_message.as_ref().unchecked_ref()));
+        socket.set_onclose(Some(on_close.as_ref().unchecked_ref()));
+
+        let mut connection = self.connection.lock().map_err(WasmStorageError::lock_poisoned)?;
+        if connection.closed {
+            detach(&socket);
+            return socket.close();
+        }
+        let replaced = connection.socket.replace(Socket { socket, _on_open: on_open, _on_message: on_message, _on_close: on_close });
+        if let Some(replaced) = replaced {
+            detach(&replaced.socket);
+        }
+        Ok(())
+    }
+
+    fn send_local_change(&self, store: &WasmStorage, event: &JsValue) -> Result<(), JsValue> {
+        let field = |name: &str| js_sys::Reflect::get(event, &JsValue::from_str(name));
+        if matches!(field("origin")?.as_string().as_deref(), Some("sync" | "hydration")) {
+            return Ok(());
+        }
+        let key = field("key")?.as_string().unwrap_or_default();
+        if !self.syncs(&key) || !store.writes_backends()? {
+            return Ok(());
+        }
+        let value = field("value")?;
+        // Removals arrive as undefined, which JSON would drop
+        let value = if value.is_undefined() { JsValue::NULL } else { value };
+        let message = js_sys::Object::new();
+        js_sys::Reflect::set(&message, &JsValue::from_str("kind"), &JsValue::from_str("mutation"))?;
+        js_sys::Reflect::set(&message, &JsValue::from_str("key"), &JsValue::from_str(&key))?;
+        js_sys::Reflect::set(&message, &JsValue::from_str("value"), &value)?;
+        js_sys::Reflect::set(&message, &JsValue::from_str("client"), &JsValue::from_str(&self.client_id))?;
+        js_sys::Reflect::set(&message, &JsValue::from_str("ts"), &field("timestamp")?)?;
+        if let Some(clock) = store.stamp_of(&key)? {
+            js_sys::Reflect::set(&message, &JsValue::from_str("clock"), &clock)?;
+        }
+        let json = String::from(js_sys::JSON::stringify(&message)?);
+
+        let mut connection = self.connection.lock().map_err(WasmStorageError::lock_poisoned)?;
+        if connection.outbox.is_empty() {
+            if let Some(socket) = connection.socket.as_ref().filter(|socket| is_open(&socket.socket)) {
+                if socket.socket.send_with_str(&json).is_ok() {
+                    return self.remember(key, value);
+                }
+            }
+        }
+        // Only the latest value of a key matters once it is finally sent
+        connection.outbox.retain(|(queued, _, _)| *queued != key);
+        connection.outbox.push_back((key, value, json));
+        while connection.outbox.len() > self.max_queued {
+            connection.outbox.pop_front();
+        }
+        Ok(())
+    }
+
+    fn flush_outbox(&self) -> Result<(), JsValue> {
+        let mut sent = Vec::new();
+        {
+            let mut connection = self.connection.lock().map_err(WasmStorageError::lock_poisoned)?;
+            connection.attempts = 0;
+            connection.status = "open";
+            let Connection { socket, outbox, .. } = &mut *connection;
+            if let Some(socket) = socket.as_ref() {
+                while let Some((_, _, json)) = outbox.front() {
+                    if socket.socket.send_with_str(json).is_err() {
+                        break;
+                    }
+                    sent.extend(outbox.pop_front());
+                }
+            }
+        }
+        for (key, value, _) in sent {
+            self.remember(key, value)?;
+        }
+        self.notify_status("open")
+    }
+
+    fn remember(&self, key: String, value: JsValue) -> Result<(), JsValue> {
+        self.known.lock().map_err(WasmStorageError::lock_poisoned)?.insert(key, value);
+        Ok(())
+    }
+
+    fn receive(&self, store: &mut WasmStorage, data: &str) -> Result<(), JsValue> {
+        let message = js_sys::JSON::parse(data)?;
+        let kind = js_sys::Reflect::get(&message, &JsValue::from_str("kind"))?.as_string().unwrap_or_default();
+        match kind.as_str() {
+            "mutation" => {
+                let key = js_sys::Reflect::get(&message, &JsValue::from_str("key"))?.as_string().unwrap_or_default();
+                let value = js_sys::Reflect::get(&message, &JsValue::from_str("value"))?;
+                let clock = js_sys::Reflect::get(&message, &JsValue::from_str("clock"))?;
+                if !clock.is_undefined() && self.syncs(&key) {
+                    return store.accept_stamped(key, value, clock);
+                }
+                self.apply_remote(store, key, value)
+            }
+            "snapshot" => {
+                let entries = js_sys::Reflect::get(&message, &JsValue::from_str("entries"))?;
+                let entries = entries
+                    .dyn_into::<js_sys::Object>()
+                    .map_err(|_| WasmStorageError::invalid_argument("Remote snapshot entries must be an object"))?;
+                for entry in js_sys::Object::entries(&entries).iter() {
+                    let pair = js_sys::Array::from(&entry);
+                    self.apply_remote(store, pair.get(0).as_string().unwrap_or_default(), pair.get(1))?;
+                }
+                Ok(())
+            }
+            _ => Err(WasmStorageError::invalid_argument(format!("Unknown remote sync message kind: {}", kind)).into()),
+        }
+    }
+
+    fn apply_remote(&self, store: &mut WasmStorage, key: String, value: JsValue) -> Result<(), JsValue> {
+        if !self.syncs(&key) {
+            return Ok(());
+        }
+        let value = if value.is_undefined() { JsValue::NULL } else { value };
+        let base = self.known.lock().map_err(WasmStorageError::lock_poisoned)?.insert(key.clone(), value.clone());
+        let base = base.unwrap_or(JsValue::NULL);
+        // A resolved value written locally replaces the unsent local one
+        // in the outbox; a kept remote value just supersedes it
+        if store.merge_pushed(key.clone(), value, &base)? == Merge::ResolvedRemote {
+            self.connection
+                .lock()
+                .map_err(WasmStorageError::lock_poisoned)?
+                .outbox
+                .retain(|(queued, _, _)| *queued != key);
+        }
+        Ok(())
+    }
+
+    fn schedule_reconnect(self: &Shared<Self>, store: WasmStorage) -> Result<(), JsValue> {
+        let delay_ms = {
+            let mut connection = self.connection.lock().map_err(WasmStorageError::lock_poisoned)?;
+            if connection.closed {
+                return Ok(());
+            }
+            // Full backoff with jitter, so clients dropped together don't return together
+            let backoff = (self.min_backoff_ms * 2f64.powi(connection.attempts.min(30) as i32)).min(self.max_backoff_ms);
+            connection.attempts += 1;
+            connection.status = "reconnecting";
+            backoff * (0.5 + js_sys::Math::random() / 2.0)
+        };
+
+        let shared = self.clone();
+        let reconnect = Closure::once_into_js(move || {
+            if let Ok(mut connection) = shared.connection.lock() {
+                connection.reconnect_timer = None;
+            }
+            if let Err(e) = shared.open(store.clone()) {
+                log_warn!("Remote sync failed to open {}: {:?}", shared.url, e);
+                let _ = shared.schedule_reconnect(store);
+            }
+        });
+        let timer = set_timeout(&reconnect, delay_ms)?;
+        self.connection.lock().map_err(WasmStorageError::lock_poisoned)?.reconnect_timer = Some(timer);
+        self.notify_status("reconnecting")
+    }
+
+    fn shut_down(&self) -> Result<(), JsValue> {
+        let socket = {
+            let mut connection = self.connection.lock().map_err(WasmStorageError::lock_poisoned)?;
+            if std::mem::replace(&mut connection.closed, true) {
+                return Ok(());
+            }
+            if let Some(timer) = connection.reconnect_timer.take() {
+                clear_timeout(&timer)?;
+            }
+            connection.outbox.clear();
+            connection.status = "closed";
+            connection.socket.take()
+        };
+        if let Some(socket) = socket {
+            detach(&socket.socket);
+            socket.socket.close()?;
+        }
+        self.notify_status("closed")
+    }
+
+    fn notify_status(&self, status: &str) -> Result<(), JsValue> {
+        if let Some(callback) = &self.on_status {
+            callback.call1(&JsValue::NULL, &JsValue::from_str(status))?;
+        }
+        Ok(())
+    }
+}
+
+fn is_open(socket: &web_sys::WebSocket) -> bool {
+    socket.ready_state() == web_sys::WebSocket::OPEN
+}
+
+fn detach(socket: &web_sys::WebSocket) {
+    socket.set_onopen(None);
+    socket.set_onmessage(None);
+    socket.set_onclose(None);
+}
@@ -0,0 +1,228 @@
+use crate::error::WasmStorageError;
+use crate::origin::Origin;
+use crate::{values_equal, WasmStorage};
+use std::collections::HashMap;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::{future_to_promise, JsFuture};
+
+/// What the store and a REST backend last agreed on.
+#[derive(Default)]
+pub(crate) struct RestSync {
+    // Changelog seq of the last successful push
+    pushed_seq: u64,
+    // Server value of each key as of the last pull or push: the common
+    // ancestor when local and remote both changed
+    base: HashMap<String, JsValue>,
+}
+
+#[wasm_bindgen]
+impl WasmStorage {
+    /// Fetches `url` (a JSON object of key -> value, null removing a key) and
+    /// merges it into the state with the "sync" origin. Keys changed only
+    /// remotely take the remote value and keys changed only locally keep
+    /// theirs; when both changed, `onConflict({ key, local, remote, base })`
+    /// returns the value to keep (falling back to `set_conflict_resolver`,
+    /// then to the remote value). Options:
+    /// `headers` and `onConflict`. Resolves to `{ applied, conflicts }`.
+    #[wasm_bindgen]
+    pub fn sync_pull(&self, url: &str, options: Option<js_sys::Object>) -> js_sys::Promise {
+        let mut store = self.clone();
+        let url = url.to_string();
+        let options = options.map(JsValue::from).unwrap_or(JsValue::UNDEFINED);
+
+        future_to_promise(async move {
+            let init = request_init("GET", &options, None)?;
+            let (status, body) = fetch_json(&url, &init).await?;
+            if !(200..300).contains(&status) {
+                return Err(sync_failed("sync_pull", &url, status).into());
+            }
+            let remote = body
+                .dyn_into::<js_sys::Object>()
+                .map_err(|_| WasmStorageError::invalid_argument("sync_pull expects a JSON object of key/value pairs"))?;
+
+            let on_conflict = on_conflict(&options);
+            let (applied, conflicts) = store.merge_remote(&remote, on_conflict.as_ref())?;
+            log_info!("Pulled {} keys from {} ({} conflicts)", applied, url, conflicts);
+            sync_result(&[("applied", applied), ("conflicts", conflicts)])
+        })
+    }
+
+    /// Sends the keys changed since the last sync to `url` as a JSON object
+    /// of key -> value (null for removed keys). A 409 response carrying the
+    /// server's current values is merged like a pull, so resolved values go
+    /// out with the next push. Options: `method` (default "PATCH"),
+    /// `headers` and `onConflict`. Resolves to `{ pushed, conflicts }`.
+    #[wasm_bindgen]
+    pub fn sync_push(&self, url: &str, options: Option<js_sys::Object>) -> js_sys::Promise {
+        let mut store = self.clone();
+        let url = url.to_string();
+        let options = options.map(JsValue::from).unwrap_or(JsValue::UNDEFINED);
+
+        future_to_promise(async move {
+            let (seq, diff) = store.push_diff()?;
+            let pushed = js_sys::Object::keys(&diff).length();
+            if pushed == 0 {
+                return sync_result(&[("pushed", 0), ("conflicts", 0)]);
+            }
+
+            let method = js_sys::Reflect::get(&options, &JsValue::from_str("method"))?
+                .as_string()
+                .unwrap_or_else(|| "PATCH".to_string());
+            let body = String::from(js_sys::JSON::stringify(&diff)?);
+            let init = request_init(&method, &options, Some(&body))?;
+            let (status, response) = fetch_json(&url, &init).await?;
+
+            if status == 409 {
+                let remote = response.dyn_into::<js_sys::Object>().unwrap_or_default();
+                let (_, conflicts) = store.merge_remote(&remote, on_conflict(&options).as_ref())?;
+                return sync_result(&[("pushed", 0), ("conflicts", conflicts)]);
+            }
+            if !(200..300).contains(&status) {
+                return Err(sync_failed("sync_push", &url, status).into());
+            }
+
+            let mut sync = store.rest_sync.lock().map_err(WasmStorageError::lock_poisoned)?;
+            sync.pushed_seq = seq;
+            for entry in js_sys::Object::entries(&diff).iter() {
+                let pair = js_sys::Array::from(&entry);
+                sync.base.insert(pair.get(0).as_string().unwrap_or_default(), pair.get(1));
+            }
+            drop(sync);
+            log_info!("Pushed {} keys to {}", pushed, url);
+            sync_result(&[("pushed", pushed), ("conflicts", 0)])
+        })
+    }
+}
+
+impl WasmStorage {
+    /// Three-way merges server values into the state; returns how many keys
+    /// changed and how many were conflicts.
+    fn merge_remote(&mut self, remote: &js_sys::Object, on_conflict: Option<&js_sys::Function>) -> Result<(u32, u32), JsValue> {
+        let (mut applied, mut conflicts) = (0, 0);
+        for entry in js_sys::Object::entries(remote).iter() {
+            let pair = js_sys::Array::from(&entry);
+            let key = pair.get(0).as_string().unwrap_or_default();
+            let remote = pair.get(1);
+            let local = self.state.lock().map_err(WasmStorageError::lock_poisoned)?.get(&key).cloned().unwrap_or(JsValue::NULL);
+            let base = self
+                .rest_sync
+                .lock()
+                .map_err(WasmStorageError::lock_poisoned)?
+                .base
+                .get(&key)
+                .cloned()
+                .unwrap_or(JsValue::NULL);
+
+            let value = if values_equal(&local, &base) || values_equal(&local, &remote) {
+                remote.clone()
+            } else if values_equal(&remote, &base) {
+                local.clone()
+            } else {
+                conflicts += 1;
+                match on_conflict {
+                    Some(resolve) => {
+                        let conflict = js_sys::Object::new();
+                        js_sys::Reflect::set(&conflict, &JsValue::from_str("key"), &JsValue::from_str(&key))?;
+                        js_sys::Reflect::set(&conflict, &JsValue::from_str("local"), &local)?;
+                        js_sys::Reflect::set(&conflict, &JsValue::from_str("remote"), &remote)?;
+                        js_sys::Reflect::set(&conflict, &JsValue::from_str("base"), &base)?;
+                        resolve.call1(&JsValue::NULL, &conflict)?
+                    }
+                    None => self.resolve_conflict(&key, &local, &remote, &base)?.unwrap_or_else(|| remote.clone()),
+                }
+            };
+
+            if !values_equal(&value, &local) {
+                self.with_origin(Origin::Sync, |store| {
+                    if value.is_null() || value.is_undefined() {
+                        store.remove_state(&key)
+                    } else {
+                        store.set_state(&key, value)
+                    }
+                })?;
+                applied += 1;
+            }
+            self.rest_sync.lock().map_err(WasmStorageError::lock_poisoned)?.base.insert(key, remote);
+        }
+        Ok((applied, conflicts))
+    }
+
+    // Changelog seq and key -> value (null when removed) of every key that
+    // changed since the last push and differs from what the server has
+    fn push_diff(&self) -> Result<(u64, js_sys::Object), JsValue> {
+        let sync = self.rest_sync.lock().map_err(WasmStorageError::lock_poisoned)?;
+        let (seq, mut keys, cleared) = {
+            let changelog = self.changelog.lock().map_err(WasmStorageError::lock_poisoned)?;
+            let (seq, keys) = changelog.changed_since(sync.pushed_seq);
+            (seq, keys, changelog.cleared_since(sync.pushed_seq))
+        };
+        // A clear since the last push removed every key the server has
+        if cleared {
+            keys.extend(sync.base.keys().cloned());
+        }
+
+        let state = self.state.lock().map_err(WasmStorageError::lock_poisoned)?;
+        let diff = js_sys::Object::new();
+        for key in keys {
+            let value = state.get(&key).cloned().unwrap_or(JsValue::NULL);
+            let known = sync.base.get(&key).cloned().unwrap_or(JsValue::NULL);
+            if !values_equal(&value, &known) {
+                js_sys::Reflect::set(&diff, &JsValue::from_str(&key), &value)?;
+            }
+        }
+        Ok((seq, diff))
+    }
+}
+
+fn on_conflict(options: &JsValue) -> Option<js_sys::Function> {
+    js_sys::Reflect::get(options, &JsValue::from_str("onConflict"))
+        .ok()
+        .and_then(|callback| callback.dyn_into::<js_sys::Function>().ok())
+}
+
+fn request_init(method: &str, options: &JsValue, body: Option<&str>) -> Result<js_sys::Object, JsValue> {
+    let headers = js_sys::Object::new();
+    js_sys::Reflect::set(&headers, &JsValue::from_str("Accept"), &JsValue::from_str("application/json"))?;
+    if body.is_some() {
+        js_sys::Reflect::set(&headers, &JsValue::from_str("Content-Type"), &JsValue::from_str("application/json"))?;
+    }
+    if let Ok(extra) = js_sys::Reflect::get(options, &JsValue::from_str("headers"))?.dyn_into::<js_sys::Object>() {
+        js_sys::Object::assign(&headers, &extra);
+    }
+
+    let init = js_sys::Object::new();
+    js_sys::Reflect::set(&init, &JsValue::from_str("method"), &JsValue::from_str(method))?;
+    js_sys::Reflect::set(&init, &JsValue::from_str("headers"), &headers)?;
+    if let Some(body) = body {
+        js_sys::Reflect::set(&init, &JsValue::from_str("body"), &JsValue::from_str(body))?;
+    }
+    Ok(init)
+}
+
+// Global fetch, so pulls and pushes also work inside workers
+async fn fetch_json(url: &str, init: &js_sys::Object) -> Result<(u16, JsValue), JsValue> {
+    let fetch = js_sys::Reflect::get(&js_sys::global(), &JsValue::from_str("fetch"))?
+        .dyn_into::<js_sys::Function>()
+        .map_err(|_| WasmStorageError::unsupported("fetch is not available in this environment"))?;
+    let response = JsFuture::from(js_sys::Promise::from(fetch.call2(&JsValue::NULL, &JsValue::from_str(url), init)?)).await?;
+    let status = js_sys::Reflect::get(&response, &JsValue::from_str("status"))?.as_f64().unwrap_or(0.0) as u16;
+    let json = js_sys::Reflect::get(&response, &JsValue::from_str("json"))?.dyn_into::<js_sys::Function>()?;
+    // Empty bodies (204, most PATCH responses) read as null
+    let body = JsFuture::from(js_sys::Promise::from(json.call0(&response)?)).await.unwrap_or(JsValue::NULL);
+    Ok((status, body))
+}
+
+fn sync_failed(operation: &str, url: &str, status: u16) -> WasmStorageError {
+    WasmStorageError::invalid_state(format!("{} to {} failed with HTTP {}", operation, url, status))
+        .with("url", url)
+        .with("status", status)
+}
+
+fn sync_result(counts: &[(&str, u32)]) -> Result<JsValue, JsValue> {
+    let result = js_sys::Object::new();
+    for (name, count) in counts {
+        js_sys::Reflect::set(&result, &JsValue::from_str(name), &JsValue::from(*count))?;
+    }
+    Ok(result.into())
+}
@@ -0,0 +1,113 @@
+use crate::error::WasmStorageError;
+use crate::error_handler::ErrorSource;
+use crate::rate_limit::{clear_timeout, set_timeout};
+use crate::sync::{Lock, Shared};
+use crate::WasmStorage;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+
+fn global_function(name: &str) -> Result<js_sys::Function, JsValue> {
+    js_sys::Reflect::get(&js_sys::global(), &JsValue::from_str(name))?
+        .dyn_into::<js_sys::Function>()
+        .map_err(|_| WasmStorageError::unsupported(format!("{} is not available", name)).into())
+}
+
+// Stops a timer of either kind
+fn clear_timer(timer: &JsValue, repeating: bool) -> Result<(), JsValue> {
+    if repeating {
+        global_function("clearInterval")?.call1(&JsValue::NULL, timer)?;
+        Ok(())
+    } else {
+        clear_timeout(timer)
+    }
+}
+
+/// A dispatch scheduled by `dispatch_after` or `dispatch_interval`.
+#[wasm_bindgen]
+pub struct ScheduledDispatch {
+    timer: Shared<Lock<JsValue>>,
+    repeating: bool,
+    // Cleared once a one-off dispatch ran or the handle was cancelled
+    active: Shared<Lock<bool>>,
+}
+
+#[wasm_bindgen]
+impl ScheduledDispatch {
+    /// Stops the timer; dispatches that already ran stay applied. Calling
+    /// it again does nothing.
+    #[wasm_bindgen]
+    pub fn cancel(&self) -> Result<(), JsValue> {
+        let mut active = self.active.lock().map_err(WasmStorageError::lock_poisoned)?;
+        if !std::mem::replace(&mut *active, false) {
+            return Ok(());
+        }
+        clear_timer(&*self.timer.lock().map_err(WasmStorageError::lock_poisoned)?, self.repeating)
+    }
+
+    /// Whether the dispatch is still to come (or, for intervals, still
+    /// repeating).
+    #[wasm_bindgen(getter)]
+    pub fn active(&self) -> Result<bool, JsValue> {
+        Ok(*self.active.lock().map_err(WasmStorageError::lock_poisoned)?)
+    }
+}
+
+#[wasm_bindgen]
+impl WasmStorage {
+    /// Dispatches `action_type` with `payload` once, `ms` milliseconds from
+    /// now, e.g. to expire a session. Errors go to the error handler (source
+    /// `"schedule"`); nothing runs once the store is destroyed.
+    #[wasm_bindgen]
+    pub fn dispatch_after(&mut self, ms: f64, action_type: &str, payload: JsValue) -> Result<ScheduledDispatch, JsValue> {
+        self.schedule_dispatch(ms, action_type, payload, false)
+    }
+
+    /// Dispatches `action_type` with `payload` every `ms` milliseconds, e.g.
+    /// a countdown tick or a polling flag, until the handle is cancelled or
+    /// the store is destroyed.
+    #[wasm_bindgen]
+    pub fn dispatch_interval(&mut self, ms: f64, action_type: &str, payload: JsValue) -> Result<ScheduledDispatch, JsValue> {
+        if ms <= 0.0 {
+            return Err(WasmStorageError::invalid_argument("dispatch_interval needs a positive interval").with("ms", ms).into());
+        }
+        self.schedule_dispatch(ms, action_type, payload, true)
+    }
+}
+
+impl WasmStorage {
+    fn schedule_dispatch(&mut self, ms: f64, action_type: &str, payload: JsValue, repeating: bool) -> Result<ScheduledDispatch, JsValue> {
+        self.ensure_alive()?;
+        if !ms.is_finite() || ms < 0.0 {
+            return Err(WasmStorageError::invalid_argument("Delays must be finite and not negative").with("ms", ms).into());
+        }
+        let (active, timer) = (Shared::new(Lock::new(true)), Shared::new(Lock::new(JsValue::UNDEFINED)));
+        let (still_active, own_timer) = (active.clone(), timer.clone());
+        let mut store = self.clone();
+        let action_type = action_type.to_string();
+        let tick = Closure::wrap(Box::new(move || {
+            let destroyed = store.destroyed().unwrap_or(true);
+            match still_active.lock() {
+                Ok(mut active) if *active => *active = repeating && !destroyed,
+                _ => return,
+            }
+            if destroyed {
+                if let Ok(timer) = own_timer.lock() {
+                    let _ = clear_timer(&timer, repeating);
+                }
+                return;
+            }
+            if let Err(error) = store.dispatch(&action_type, payload.clone()) {
+                let source = ErrorSource { source: "schedule", key: None, action: Some(action_type.as_str()), timestamp: js_sys::Date::now() };
+                let _ = store.report_error(&error, source);
+            }
+        }) as Box<dyn FnMut()>)
+        .into_js_value();
+
+        *timer.lock().map_err(WasmStorageError::lock_poisoned)? = if repeating {
+            global_function("setInterval")?.call2(&JsValue::NULL, &tick, &JsValue::from_f64(ms))?
+        } else {
+            set_timeout(&tick, ms)?
+        };
+        Ok(ScheduledDispatch { timer, repeating, active })
+    }
+}
@@ -0,0 +1,78 @@
+use crate::error::WasmStorageError;
+use crate::subscriptions::{immediate, Subscription};
+use crate::sync::Lock;
+use crate::WasmStorage;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+
+#[wasm_bindgen]
+impl WasmStorage {
+    /// Calls `listener(selected, previous)` when `selector(state)` returns
+    /// something new after a change, where `state` is the `get_all_state`
+    /// snapshot. Selections are compared with `Object.is`, or with
+    /// `options.equalityFn(previous, selected)`. `options.immediate` calls
+    /// `listener(selected, undefined)` with the current selection at once;
+    /// the other options are those of `subscribe`.
+    #[wasm_bindgen]
+    pub fn subscribe_with_selector(
+        &mut self,
+        selector: js_sys::Function,
+        listener: js_sys::Function,
+        options: Option<js_sys::Object>,
+    ) -> Result<Subscription, JsValue> {
+        let equals = match &options {
+            Some(options) => js_sys::Reflect::get(options, &JsValue::from_str("equalityFn"))?.dyn_into::<js_sys::Function>().ok(),
+            None => None,
+        };
+        let initial = selector.call1(&JsValue::NULL, &self.get_all_state()?)?;
+        let last_selected = Lock::new(initial.clone());
+        let immediate = immediate(&options)?;
+        let first_call = immediate.then(|| listener.clone());
+
+        let store = self.clone();
+        let callback = Closure::wrap(Box::new(move |_event: JsValue| -> Result<(), JsValue> {
+            let selected = selector.call1(&JsValue::NULL, &store.get_all_state()?)?;
+            let previous = last_selected.lock().map_err(WasmStorageError::lock_poisoned)?.clone();
+            let same = match &equals {
+                Some(equals) => equals.call2(&JsValue::NULL, &previous, &selected)?.is_truthy(),
+                None => js_sys::Object::is(&previous, &selected),
+            };
+            if same {
+                return Ok(());
+            }
+            *last_selected.lock().map_err(WasmStorageError::lock_poisoned)? = selected.clone();
+            listener.call2(&JsValue::NULL, &selected, &previous)?;
+            Ok(())
+        }) as Box<dyn FnMut(JsValue) -> Result<(), JsValue>>);
+
+        // The selection was just taken, so replaying the state to it is moot
+        let options = match options {
+            Some(options) if immediate => {
+                let copy = js_sys::Object::assign(&js_sys::Object::new(), &options);
+                js_sys::Reflect::set(&copy, &JsValue::from_str("immediate"), &JsValue::FALSE)?;
+                Some(copy)
+            }
+            options => options,
+        };
+        let subscription = self.subscribe(callback.into_js_value().unchecked_into(), options)?;
+        if let Some(listener) = first_call {
+            listener.call2(&JsValue::NULL, &initial, &JsValue::UNDEFINED)?;
+        }
+        Ok(subscription)
+    }
+
+    /// The state to hydrate against, for `useSyncExternalStore`'s
+    /// `getServerSnapshot`: the `get_all_state` snapshot at the first call,
+    /// returned unchanged from then on so it matches what the server
+    /// rendered. Call it once the server's state has been loaded.
+    #[wasm_bindgen]
+    pub fn get_server_snapshot(&self) -> Result<JsValue, JsValue> {
+        let mut server_snapshot = self.server_snapshot.lock().map_err(WasmStorageError::lock_poisoned)?;
+        if let Some(snapshot) = &*server_snapshot {
+            return Ok(snapshot.clone());
+        }
+        let snapshot = self.get_all_state()?;
+        *server_snapshot = Some(snapshot.clone());
+        Ok(snapshot)
+    }
+}
@@ -0,0 +1,266 @@
+use crate::error::WasmStorageError;
+use crate::subscriptions::Subscription;
+use crate::{glob_match, WasmStorage};
+use crate::sync::{Lock, Shared};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::JsFuture;
+
+const PROTOCOL: &str = "__wasmStorageServiceWorker";
+
+/// One end of a state slice shared between a Service Worker's store and the
+/// stores of the pages it serves.
+#[wasm_bindgen]
+pub struct ServiceWorkerShare {
+    target: web_sys::EventTarget,
+    subscription: Option<Subscription>,
+    _on_message: Closure<dyn FnMut(JsValue)>,
+    _listener: Closure<dyn FnMut(JsValue)>,
+}
+
+fn field(value: &JsValue, name: &str) -> JsValue {
+    js_sys::Reflect::get(value, &JsValue::from_str(name)).unwrap_or(JsValue::UNDEFINED)
+}
+
+fn covers(keys: &[String], key: &str) -> bool {
+    keys.is_empty() || keys.iter().any(|pattern| glob_match(pattern, key))
+}
+
+fn share_message(kind: &str) -> Result<js_sys::Object, JsValue> {
+    let message = js_sys::Object::new();
+    js_sys::Reflect::set(&message, &JsValue::from_str(PROTOCOL), &JsValue::from_str(kind))?;
+    Ok(message)
+}
+
+fn change_message(kind: &str, event: &JsValue) -> Result<js_sys::Object, JsValue> {
+    let value = field(event, "value");
+    let value = if value.is_undefined() { JsValue::NULL } else { value };
+    let message = share_message(kind)?;
+    js_sys::Reflect::set(&message, &JsValue::from_str("key"), &field(event, "key"))?;
+    js_sys::Reflect::set(&message, &JsValue::from_str("value"), &value)?;
+    Ok(message)
+}
+
+fn post(target: &JsValue, message: &JsValue) -> Result<(), JsValue> {
+    let post_message = js_sys::Reflect::get(target, &JsValue::from_str("postMessage"))?.dyn_into::<js_sys::Function>()?;
+    post_message.call1(target, message)?;
+    Ok(())
+}
+
+fn apply_shared(store: &mut WasmStorage, key: String, value: JsValue) -> Result<(), JsValue> {
+    let target = key.clone();
+    store.accept_remote(&target, None, Box::new(move |store| {
+        if value.is_null() || value.is_undefined() {
+            store.remove_state(&key)
+        } else {
+            store.set_state(&key, value)
+        }
+    }))
+}
+
+// Posts to every window client, including ones this worker doesn't control yet
+fn post_to_clients(clients: JsValue, message: JsValue) {
+    wasm_bindgen_futures::spawn_local(async move {
+        let posted: Result<(), JsValue> = async {
+            let options = js_sys::Object::new();
+            js_sys::Reflect::set(&options, &JsValue::from_str("includeUncontrolled"), &JsValue::TRUE)?;
+            js_sys::Reflect::set(&options, &JsValue::from_str("type"), &JsValue::from_str("window"))?;
+            let match_all = js_sys::Reflect::get(&clients, &JsValue::from_str("matchAll"))?.dyn_into::<js_sys::Function>()?;
+            let matched = JsFuture::from(js_sys::Promise::from(match_all.call1(&clients, &options)?)).await?;
+            for client in js_sys::Array::from(&matched).iter() {
+                post(&client, &message)?;
+            }
+            Ok(())
+        }
+        .await;
+        if let Err(e) = posted {
+            log_warn!("Service Worker share failed to post to clients: {:?}", e);
+        }
+    });
+}
+
+#[wasm_bindgen]
+impl WasmStorage {
+    /// Must be called from a Service Worker. Shares the keys matching
+    /// `keys` (keys or globs, default all) with every page that calls
+    /// `connect_to_service_worker`: pages get a snapshot when they connect
+    /// and every later change through `clients.postMessage`, and their
+    /// writes to shared keys apply here with the "sync" origin.
+    #[wasm_bindgen]
+    pub fn share_with_clients(&mut self, keys: Option<js_sys::Array>) -> Result<ServiceWorkerShare, JsValue> {
+        let global = js_sys::global();
+        let clients = field(&global, "clients");
+        if !clients.is_object() || !field(&global, "registration").is_object() {
+            return Err(WasmStorageError::unsupported("share_with_clients must be called from a Service Worker").into());
+        }
+        let keys: Vec<String> = keys.map(|keys| keys.iter().filter_map(|key| key.as_string()).collect()).unwrap_or_default();
+        let target = global.unchecked_into::<web_sys::EventTarget>();
+
+        let mut store = self.clone();
+        let message_keys = keys.clone();
+        let on_message = Closure::wrap(Box::new(move |event: JsValue| {
+            if let Err(e) = store.serve_clients(&message_keys, &event) {
+                log_warn!("Service Worker share failed to handle a client message: {:?}", e);
+            }
+        }) as Box<dyn FnMut(JsValue)>);
+        target.add_event_listener_with_callback("message", on_message.as_ref().unchecked_ref())?;
+
+        // Every change of a shared key, including those a page made, so
+        // the other pages see it too
+        let listener_keys = keys.clone();
+        let listener = Closure::wrap(Box::new(move |event: JsValue| {
+            let key = field(&event, "key").as_string().unwrap_or_default();
+            if !covers(&listener_keys, &key) {
+                return;
+            }
+            match change_message("change", &event) {
+                Ok(message) => post_to_clients(clients.clone(), message.into()),
+                Err(e) => log_warn!("Service Worker share failed to post a change: {:?}", e),
+            }
+        }) as Box<dyn FnMut(JsValue)>);
+        let subscription = self.subscribe(listener.as_ref().unchecked_ref::<js_sys::Function>().clone(), None)?;
+
+        log_info!("Sharing {} with Service Worker clients", if keys.is_empty() { "all keys".to_string() } else { keys.join(", ") });
+        Ok(ServiceWorkerShare { target, subscription: Some(subscription), _on_message: on_message, _listener: listener })
+    }
+
+    /// Mirrors the slice this page's Service Worker shares with
+    /// `share_with_clients`. Values from the worker apply with the "sync"
+    /// origin; local writes to shared keys are sent to the worker, which
+    /// passes them on to the other pages.
+    #[wasm_bindgen]
+    pub fn connect_to_service_worker(&mut self) -> Result<ServiceWorkerShare, JsValue> {
+        let navigator = field(&js_sys::global(), "navigator");
+        let container = field(&navigator, "serviceWorker");
+        if !container.is_object() {
+            return Err(WasmStorageError::unsupported("Service Workers are not available in this environment").into());
+        }
+        // Learned from the worker's snapshot; nothing is forwarded before it
+        let keys: Shared<Lock<Option<Vec<String>>>> = Shared::new(Lock::new(None));
+        let worker: Shared<Lock<Option<JsValue>>> = Shared::new(Lock::new(None));
+
+        let mut store = self.clone();
+        let message_keys = keys.clone();
+        let message_worker = worker.clone();
+        let on_message = Closure::wrap(Box::new(move |event: JsValue| {
+            let source = field(&event, "source");
+            if let Err(e) = store.mirror_worker(&message_keys, &message_worker, source, &field(&event, "data")) {
+                log_warn!("Service Worker share failed to handle a worker message: {:?}", e);
+            }
+        }) as Box<dyn FnMut(JsValue)>);
+        let target = container.clone().unchecked_into::<web_sys::EventTarget>();
+        target.add_event_listener_with_callback("message", on_message.as_ref().unchecked_ref())?;
+
+        let listener = Closure::wrap(Box::new(move |event: JsValue| {
+            if field(&event, "origin").as_string().as_deref() != Some("local") {
+                return;
+            }
+            let key = field(&event, "key").as_string().unwrap_or_default();
+            let shared = keys.lock().ok().and_then(|keys| keys.as_ref().map(|keys| covers(keys, &key))).unwrap_or(false);
+            let target = worker.lock().ok().and_then(|worker| worker.clone());
+            let (true, Some(target)) = (shared, target) else {
+                return;
+            };
+            if let Err(e) = change_message("set", &event).and_then(|message| post(&target, &message)) {
+                log_warn!("Service Worker share failed to forward a change: {:?}", e);
+            }
+        }) as Box<dyn FnMut(JsValue)>);
+        let subscription = self.subscribe(listener.as_ref().unchecked_ref::<js_sys::Function>().clone(), None)?;
+
+        // Listeners added with addEventListener only get messages once started
+        if let Ok(start) = js_sys::Reflect::get(&container, &JsValue::from_str("startMessages"))?.dyn_into::<js_sys::Function>() {
+            start.call0(&container)?;
+        }
+        let ready = js_sys::Promise::from(field(&container, "ready"));
+        wasm_bindgen_futures::spawn_local(async move {
+            let hello: Result<(), JsValue> = async {
+                let registration = JsFuture::from(ready).await?;
+                post(&field(&registration, "active"), &share_message("hello")?.into())
+            }
+            .await;
+            if let Err(e) = hello {
+                log_warn!("Service Worker share failed to reach the worker: {:?}", e);
+            }
+        });
+        Ok(ServiceWorkerShare { target, subscription: Some(subscription), _on_message: on_message, _listener: listener })
+    }
+}
+
+#[wasm_bindgen]
+impl ServiceWorkerShare {
+    /// Stops sharing; both stores keep their last values.
+    #[wasm_bindgen]
+    pub fn close(&mut self) -> Result<(), JsValue> {
+        self.target
+            .remove_event_listener_with_callback("message", self._on_message.as_ref().unchecked_ref())?;
+        if let Some(subscription) = self.subscription.take() {
+            subscription.unsubscribe()?;
+        }
+        Ok(())
+    }
+}
+
+impl WasmStorage {
+    // Worker side: answers a page's hello and applies its writes
+    fn serve_clients(&mut self, keys: &[String], event: &JsValue) -> Result<(), JsValue> {
+        let data = field(event, "data");
+        match field(&data, PROTOCOL).as_string().as_deref() {
+            Some("hello") => {
+                let shared: Vec<(String, JsValue)> = {
+                    let state = self.state.lock().map_err(WasmStorageError::lock_poisoned)?;
+                    state.iter().filter(|(key, _)| covers(keys, key)).map(|(key, value)| (key.clone(), value.clone())).collect()
+                };
+                let entries = js_sys::Object::new();
+                for (key, value) in self.retain_readable(shared, |(key, _)| key)? {
+                    js_sys::Reflect::set(&entries, &JsValue::from_str(&key), &value)?;
+                }
+                let message = share_message("snapshot")?;
+                js_sys::Reflect::set(&message, &JsValue::from_str("entries"), &entries)?;
+                let keys: js_sys::Array = keys.iter().map(|key| JsValue::from_str(key)).collect();
+                js_sys::Reflect::set(&message, &JsValue::from_str("keys"), &keys)?;
+                post(&field(event, "source"), &message)
+            }
+            Some("set") => {
+                let key = field(&data, "key").as_string().unwrap_or_default();
+                if !covers(keys, &key) {
+                    return Err(WasmStorageError::invalid_argument(format!("A page wrote unshared key \"{}\"", key)).with("key", key.as_str()).into());
+                }
+                apply_shared(self, key, field(&data, "value"))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    // Page side: applies the worker's snapshot and changes
+    fn mirror_worker(
+        &mut self,
+        keys: &Lock<Option<Vec<String>>>,
+        worker: &Lock<Option<JsValue>>,
+        source: JsValue,
+        data: &JsValue,
+    ) -> Result<(), JsValue> {
+        match field(data, PROTOCOL).as_string().as_deref() {
+            Some("snapshot") => {
+                let shared = field(data, "keys");
+                let shared = if js_sys::Array::is_array(&shared) {
+                    js_sys::Array::from(&shared).iter().filter_map(|key| key.as_string()).collect()
+                } else {
+                    Vec::new()
+                };
+                *keys.lock().map_err(WasmStorageError::lock_poisoned)? = Some(shared);
+                *worker.lock().map_err(WasmStorageError::lock_poisoned)? = Some(source);
+                let entries = field(data, "entries").dyn_into::<js_sys::Object>().unwrap_or_default();
+                for entry in js_sys::Object::entries(&entries).iter() {
+                    let pair = js_sys::Array::from(&entry);
+                    apply_shared(self, pair.get(0).as_string().unwrap_or_default(), pair.get(1))?;
+                }
+                Ok(())
+            }
+            Some("change") => {
+                let key = field(data, "key").as_string().unwrap_or_default();
+                apply_shared(self, key, field(data, "value"))
+            }
+            _ => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,286 @@
+use crate::error::WasmStorageError;
+use crate::WasmStorage;
+use std::collections::HashMap;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+
+// Header words (Int32): magic, version, slot count, slot bytes, slots offset
+const MAGIC: i32 = 0x5753_4d31;
+const VERSION_WORD: u32 = 1;
+const HEADER_BYTES: u32 = 20;
+// Per slot: seq, type, length and padding words ahead of the data
+const SLOT_HEADER_BYTES: u32 = 16;
+const DEFAULT_SLOT_BYTES: u32 = 256;
+const MAX_READ_ATTEMPTS: u32 = 1000;
+
+const TYPE_ABSENT: i32 = 0;
+const TYPE_NULL: i32 = 1;
+const TYPE_BOOL: i32 = 2;
+const TYPE_NUMBER: i32 = 3;
+const TYPE_STRING: i32 = 4;
+const TYPE_UINT8: i32 = 5;
+const TYPE_INT32: i32 = 6;
+const TYPE_FLOAT32: i32 = 7;
+const TYPE_FLOAT64: i32 = 8;
+
+/// A SharedArrayBuffer mirroring a fixed set of keys. Each key owns a slot
+/// guarded by a sequence lock: the writer makes the sequence odd while it
+/// writes, and readers retry when it was odd or moved during their read, so
+/// they never see a half-written value. Only the store that created the
+/// buffer writes it.
+pub(crate) struct SharedRegion {
+    words: js_sys::Int32Array,
+    bytes: js_sys::Uint8Array,
+    slots: HashMap<String, u32>,
+    slot_bytes: u32,
+    slots_offset: u32,
+    writer: bool,
+}
+
+#[wasm_bindgen]
+impl WasmStorage {
+    /// Mirrors `keys` into a new SharedArrayBuffer, returned for posting to
+    /// workers once (`attach_shared_memory` there). Shared keys hold null,
+    /// booleans, numbers, strings or Uint8Array / Int32Array / Float32Array /
+    /// Float64Array values of at most `slotBytes` bytes (option, default
+    /// 256); other writes fail with TYPE_MISMATCH or INVALID_ARGUMENT. Every
+    /// write bumps the Int32 at index 1 and notifies it, so a worker can
+    /// `Atomics.wait` on it for changes.
+    #[wasm_bindgen]
+    pub fn enable_shared_memory(&mut self, keys: js_sys::Array, options: Option<js_sys::Object>) -> Result<js_sys::SharedArrayBuffer, JsValue> {
+        if !js_sys::Reflect::has(&js_sys::global(), &JsValue::from_str("SharedArrayBuffer"))? {
+            return Err(WasmStorageError::unsupported("SharedArrayBuffer needs a cross-origin isolated page").into());
+        }
+        let keys: Vec<String> = keys.iter().filter_map(|key| key.as_string()).collect();
+        let options = options.map(JsValue::from).unwrap_or(JsValue::UNDEFINED);
+        let slot_bytes = js_sys::Reflect::get(&options, &JsValue::from_str("slotBytes"))?
+            .as_f64()
+            .map_or(DEFAULT_SLOT_BYTES, |bytes| bytes.max(8.0) as u32);
+        // Slot data starts 8-byte aligned, so JS can view numbers in place
+        let slot_bytes = slot_bytes.div_ceil(8) * 8;
+
+        let mut key_table = Vec::new();
+        for key in keys.iter() {
+            let len = u16::try_from(key.len())
+                .map_err(|_| WasmStorageError::invalid_argument("Shared keys must be shorter than 64 KiB").with("key", key.as_str()))?;
+            key_table.extend_from_slice(&len.to_le_bytes());
+            key_table.extend_from_slice(key.as_bytes());
+        }
+        let slots_offset = (HEADER_BYTES + key_table.len() as u32).div_ceil(8) * 8;
+        let total = slots_offset + keys.len() as u32 * (SLOT_HEADER_BYTES + slot_bytes);
+
+        let buffer = js_sys::SharedArrayBuffer::new(total as _);
+        let region = SharedRegion::view(&buffer, true)?;
+        region.bytes.subarray(HEADER_BYTES, HEADER_BYTES + key_table.len() as u32).copy_from(&key_table);
+        for (index, word) in [MAGIC, 0, keys.len() as i32, slot_bytes as i32, slots_offset as i32].into_iter().enumerate() {
+            js_sys::Atomics::store(&region.words, index as u32, word)?;
+        }
+        let region = SharedRegion { slots: slot_index(&keys), slot_bytes, slots_offset, ..region };
+
+        {
+            let state = self.state.lock().map_err(WasmStorageError::lock_poisoned)?;
+            for key in keys.iter() {
+                if let Some(value) = state.get(key) {
+                    region.write(key, Some(value))?;
+                }
+            }
+        }
+        *self.shared_memory.lock().map_err(WasmStorageError::lock_poisoned)? = Some(region);
+        log_info!("Sharing {} keys through {} bytes of shared memory", keys.len(), total);
+        Ok(buffer)
+    }
+
+    /// Reads the keys of a buffer from `enable_shared_memory` in another
+    /// thread through `shared_value`, without copying the state over.
+    #[wasm_bindgen]
+    pub fn attach_shared_memory(&mut self, buffer: js_sys::SharedArrayBuffer) -> Result<(), JsValue> {
+        let region = SharedRegion::view(&buffer, false)?;
+        if js_sys::Atomics::load(&region.words, 0)? != MAGIC {
+            return Err(WasmStorageError::invalid_argument("Buffer was not created by enable_shared_memory").into());
+        }
+        let slot_count = js_sys::Atomics::load(&region.words, 2)? as u32;
+        let slot_bytes = js_sys::Atomics::load(&region.words, 3)? as u32;
+        let slots_offset = js_sys::Atomics::load(&region.words, 4)? as u32;
+
+        let table = region.bytes.subarray(HEADER_BYTES, slots_offset).to_vec();
+        let mut keys = Vec::with_capacity(slot_count as usize);
+        let mut at = 0;
+        while keys.len() < slot_count as usize && at + 2 <= table.len() {
+            let len = u16::from_le_bytes([table[at], table[at + 1]]) as usize;
+            let key = table.get(at + 2..at + 2 + len).ok_or_else(|| WasmStorageError::invalid_argument("Shared key table is truncated"))?;
+            keys.push(String::from_utf8_lossy(key).into_owned());
+            at += 2 + len;
+        }
+        let region = SharedRegion { slots: slot_index(&keys), slot_bytes, slots_offset, ..region };
+        *self.shared_memory.lock().map_err(WasmStorageError::lock_poisoned)? = Some(region);
+        Ok(())
+    }
+
+    /// Current value of a shared key straight from shared memory (undefined
+    /// once removed); binary values come back as copies.
+    #[wasm_bindgen]
+    pub fn shared_value(&self, key: &str) -> Result<JsValue, JsValue> {
+        let region = self.shared_memory.lock().map_err(WasmStorageError::lock_poisoned)?;
+        let region = region
+            .as_ref()
+            .ok_or_else(|| WasmStorageError::invalid_state("Shared memory is not enabled or attached"))?;
+        region.read(key)
+    }
+
+    /// How many writes the shared buffer has seen; changes whenever any
+    /// shared key does.
+    #[wasm_bindgen]
+    pub fn shared_version(&self) -> Result<u32, JsValue> {
+        let region = self.shared_memory.lock().map_err(WasmStorageError::lock_poisoned)?;
+        match region.as_ref() {
+            Some(region) => Ok(js_sys::Atomics::load(&region.words, VERSION_WORD)? as u32),
+            None => Err(WasmStorageError::invalid_state("Shared memory is not enabled or attached").into()),
+        }
+    }
+}
+
+impl WasmStorage {
+    /// Rejects values a shared key's slot can't hold, before they are written.
+    pub(crate) fn check_shareable(&self, key: &str, value: &JsValue) -> Result<(), JsValue> {
+        let region = self.shared_memory.lock().map_err(WasmStorageError::lock_poisoned)?;
+        match region.as_ref().filter(|region| region.writer && region.slots.contains_key(key)) {
+            Some(region) => region.encode(key, value).map(|_| ()),
+            None => Ok(()),
+        }
+    }
+
+    /// Mirrors a write (None for a removal) of a shared key.
+    pub(crate) fn share_write(&self, key: &str, value: Option<&JsValue>) -> Result<(), JsValue> {
+        let region = self.shared_memory.lock().map_err(WasmStorageError::lock_poisoned)?;
+        match region.as_ref().filter(|region| region.writer) {
+            Some(region) if region.slots.contains_key(key) => region.write(key, value),
+            _ => Ok(()),
+        }
+    }
+
+    pub(crate) fn share_clear(&self) -> Result<(), JsValue> {
+        let region = self.shared_memory.lock().map_err(WasmStorageError::lock_poisoned)?;
+        if let Some(region) = region.as_ref().filter(|region| region.writer) {
+            for key in region.slots.keys() {
+                region.write(key, None)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl SharedRegion {
+    fn view(buffer: &js_sys::SharedArrayBuffer, writer: bool) -> Result<SharedRegion, JsValue> {
+        Ok(SharedRegion {
+            words: js_sys::Int32Array::new(buffer),
+            bytes: js_sys::Uint8Array::new(buffer),
+            slots: HashMap::new(),
+            slot_bytes: 0,
+            slots_offset: 0,
+            writer,
+        })
+    }
+
+    fn slot(&self, key: &str) -> Result<u32, JsValue> {
+        let index = self.slots.get(key).ok_or_else(|| WasmStorageError::key_missing(key))?;
+        Ok(self.slots_offset + index * (SLOT_HEADER_BYTES + self.slot_bytes))
+    }
+
+    // Type tag and bytes of a value, if its slot can hold it
+    fn encode(&self, key: &str, value: &JsValue) -> Result<(i32, Vec<u8>), JsValue> {
+        let encoded = if value.is_null() {
+            (TYPE_NULL, Vec::new())
+        } else if let Some(flag) = value.as_bool() {
+            (TYPE_BOOL, vec![flag as u8])
+        } else if let Some(number) = value.as_f64() {
+            (TYPE_NUMBER, number.to_le_bytes().to_vec())
+        } else if let Some(text) = value.as_string() {
+            (TYPE_STRING, text.into_bytes())
+        } else if let Some(array) = value.dyn_ref::<js_sys::Uint8Array>() {
+            (TYPE_UINT8, array.to_vec())
+        } else if let Some(array) = value.dyn_ref::<js_sys::Int32Array>() {
+            (TYPE_INT32, array.to_vec().iter().flat_map(|n| n.to_le_bytes()).collect())
+        } else if let Some(array) = value.dyn_ref::<js_sys::Float32Array>() {
+            (TYPE_FLOAT32, array.to_vec().iter().flat_map(|n| n.to_le_bytes()).collect())
+        } else if let Some(array) = value.dyn_ref::<js_sys::Float64Array>() {
+            (TYPE_FLOAT64, array.to_vec().iter().flat_map(|n| n.to_le_bytes()).collect())
+        } else {
+            return Err(WasmStorageError::type_mismatch(key, "a primitive or typed array (shared key)", value).into());
+        };
+        if encoded.1.len() as u32 > self.slot_bytes {
+            return Err(WasmStorageError::invalid_argument(format!("Value of shared key \"{}\" exceeds its {}-byte slot", key, self.slot_bytes))
+                .with("key", key)
+                .with("slotBytes", self.slot_bytes)
+                .into());
+        }
+        Ok(encoded)
+    }
+
+    fn write(&self, key: &str, value: Option<&JsValue>) -> Result<(), JsValue> {
+        let (kind, data) = match value {
+            Some(value) if !value.is_undefined() => self.encode(key, value)?,
+            _ => (TYPE_ABSENT, Vec::new()),
+        };
+        let offset = self.slot(key)?;
+        let seq = offset / 4;
+        js_sys::Atomics::add(&self.words, seq, 1)?;
+        js_sys::Atomics::store(&self.words, seq + 1, kind)?;
+        js_sys::Atomics::store(&self.words, seq + 2, data.len() as i32)?;
+        let start = offset + SLOT_HEADER_BYTES;
+        self.bytes.subarray(start, start + data.len() as u32).copy_from(&data);
+        js_sys::Atomics::add(&self.words, seq, 1)?;
+
+        js_sys::Atomics::add(&self.words, VERSION_WORD, 1)?;
+        js_sys::Atomics::notify(&self.words, VERSION_WORD)?;
+        Ok(())
+    }
+
+    fn read(&self, key: &str) -> Result<JsValue, JsValue> {
+        let offset = self.slot(key)?;
+        let seq = offset / 4;
+        let start = offset + SLOT_HEADER_BYTES;
+        for _ in 0..MAX_READ_ATTEMPTS {
+            let before = js_sys::Atomics::load(&self.words, seq)?;
+            if before % 2 != 0 {
+                continue;
+            }
+            let kind = js_sys::Atomics::load(&self.words, seq + 1)?;
+            let len = (js_sys::Atomics::load(&self.words, seq + 2)? as u32).min(self.slot_bytes);
+            let data = self.bytes.subarray(start, start + len).to_vec();
+            if js_sys::Atomics::load(&self.words, seq)? == before {
+                return decode(kind, &data);
+            }
+        }
+        Err(WasmStorageError::invalid_state(format!("Shared key \"{}\" kept changing while being read", key))
+            .with("key", key)
+            .into())
+    }
+}
+
+fn slot_index(keys: &[String]) -> HashMap<String, u32> {
+    keys.iter().enumerate().map(|(index, key)| (key.clone(), index as u32)).collect()
+}
+
+fn decode(kind: i32, data: &[u8]) -> Result<JsValue, JsValue> {
+    let words = |size: usize| data.chunks_exact(size);
+    Ok(match kind {
+        TYPE_NULL => JsValue::NULL,
+        TYPE_BOOL => JsValue::from_bool(data.first().is_some_and(|flag| *flag != 0)),
+        TYPE_NUMBER => JsValue::from_f64(f64::from_le_bytes(data.try_into().unwrap_or_default())),
+        TYPE_STRING => JsValue::from_str(&String::from_utf8_lossy(data)),
+        TYPE_UINT8 => js_sys::Uint8Array::from(data).into(),
+        TYPE_INT32 => {
+            let values: Vec<i32> = words(4).map(|n| i32::from_le_bytes([n[0], n[1], n[2], n[3]])).collect();
+            js_sys::Int32Array::from(values.as_slice()).into()
+        }
+        TYPE_FLOAT32 => {
+            let values: Vec<f32> = words(4).map(|n| f32::from_le_bytes([n[0], n[1], n[2], n[3]])).collect();
+            js_sys::Float32Array::from(values.as_slice()).into()
+        }
+        TYPE_FLOAT64 => {
+            let values: Vec<f64> = words(8).map(|n| f64::from_le_bytes(n.try_into().unwrap_or_default())).collect();
+            js_sys::Float64Array::from(values.as_slice()).into()
+        }
+        _ => JsValue::UNDEFINED,
+    })
+}
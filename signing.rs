@@ -0,0 +1,92 @@
+use crate::digest::{hmac_sha256, to_hex};
+use crate::error::{ErrorCode, WasmStorageError};
+use crate::WasmStorage;
+use wasm_bindgen::prelude::*;
+
+const SIGNATURE_FIELD: &str = "{\"signature\":\"";
+const SIGNATURE_LENGTH: usize = 64;
+
+/// App-provided HMAC key. Signed JSON objects carry a leading `signature`
+/// field over the rest of the object's exact text, so verification doesn't
+/// depend on how a JSON round trip orders keys.
+#[derive(Clone)]
+pub(crate) struct Signing {
+    key: Vec<u8>,
+    // Lets snapshots written before signing was enabled load once
+    accept_unsigned: bool,
+}
+
+impl Signing {
+    pub(crate) fn signature(&self, message: &str) -> String {
+        to_hex(&hmac_sha256(&self.key, message.as_bytes()))
+    }
+
+    pub(crate) fn accepts_unsigned(&self) -> bool {
+        self.accept_unsigned
+    }
+
+    /// Checks `signature` without leaking how much of it matched.
+    pub(crate) fn verify(&self, message: &str, signature: &str) -> bool {
+        let expected = self.signature(message);
+        expected.len() == signature.len()
+            && expected.bytes().zip(signature.bytes()).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+    }
+
+    /// Prepends the signature of `json`, which must be an object.
+    pub(crate) fn sign_object(&self, json: &str) -> String {
+        let rest = json.strip_prefix('{').unwrap_or(json);
+        let separator = if rest.starts_with('}') { "" } else { "," };
+        format!("{}{}\"{}{}", SIGNATURE_FIELD, self.signature(json), separator, rest)
+    }
+
+    /// The object `sign_object` was given, or an error if `text` is unsigned
+    /// (and unsigned data isn't accepted) or was modified.
+    pub(crate) fn verify_object(&self, text: &str, what: &str) -> Result<String, JsValue> {
+        let Some(signed) = text.strip_prefix(SIGNATURE_FIELD) else {
+            if self.accept_unsigned {
+                return Ok(text.to_string());
+            }
+            return Err(tampered(format!("{} is not signed", what)).into());
+        };
+        let unsigned = signed
+            .get(..SIGNATURE_LENGTH)
+            .zip(signed.get(SIGNATURE_LENGTH..).and_then(|rest| rest.strip_prefix('"')))
+            .map(|(signature, rest)| (signature, format!("{{{}", rest.strip_prefix(',').unwrap_or(rest))))
+            .filter(|(signature, unsigned)| self.verify(unsigned, signature));
+        match unsigned {
+            Some((_, unsigned)) => Ok(unsigned),
+            None => Err(tampered(format!("{} failed signature verification", what)).into()),
+        }
+    }
+}
+
+pub(crate) fn tampered(message: impl Into<String>) -> WasmStorageError {
+    WasmStorageError::new(ErrorCode::SignatureInvalid, message)
+}
+
+#[wasm_bindgen]
+impl WasmStorage {
+    /// Signs persisted snapshots, write-ahead log entries and incremental
+    /// exports with HMAC-SHA256 under `key`, and verifies them on hydration
+    /// and import: anything modified or unsigned is rejected with
+    /// `SIGNATURE_INVALID`. Set `acceptUnsigned` while existing unsigned
+    /// snapshots are being migrated. Call before `enable_persistence`.
+    #[wasm_bindgen]
+    pub fn enable_signing(&mut self, key: &str, options: Option<js_sys::Object>) -> Result<(), JsValue> {
+        if key.is_empty() {
+            return Err(WasmStorageError::invalid_argument("Signing key must not be empty").into());
+        }
+        let accept_unsigned = options
+            .and_then(|options| js_sys::Reflect::get(&options, &JsValue::from_str("acceptUnsigned")).ok())
+            .is_some_and(|accept| accept.is_truthy());
+        *self.signing.lock().map_err(WasmStorageError::lock_poisoned)? =
+            Some(Signing { key: key.as_bytes().to_vec(), accept_unsigned });
+        self.write_snapshot()
+    }
+}
+
+impl WasmStorage {
+    pub(crate) fn signing(&self) -> Result<Option<Signing>, JsValue> {
+        Ok(self.signing.lock().map_err(WasmStorageError::lock_poisoned)?.clone())
+    }
+}
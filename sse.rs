@@ -0,0 +1,105 @@
+use crate::error_handler::ErrorSource;
+use crate::WasmStorage;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+
+/// An `EventSource` whose events are mapped to actions and dispatched into
+/// the store.
+#[wasm_bindgen]
+pub struct SseSubscription {
+    source: web_sys::EventSource,
+    events: Vec<String>,
+    _on_event: Closure<dyn FnMut(web_sys::MessageEvent)>,
+}
+
+#[wasm_bindgen]
+impl WasmStorage {
+    /// Connects an `EventSource` to `url` and passes every event to
+    /// `mapper({ event, data, lastEventId })`, where `data` is parsed JSON
+    /// when possible. The mapper returns `{ type, payload }`, an array of
+    /// them, or null to ignore the event; each action goes through the
+    /// normal middleware and reducer pipeline. Options: `events` (event
+    /// names to listen for, default ["message"]) and `withCredentials`.
+    /// Mapper and dispatch errors go to the error handler.
+    #[wasm_bindgen]
+    pub fn subscribe_sse(&mut self, url: &str, mapper: js_sys::Function, options: Option<js_sys::Object>) -> Result<SseSubscription, JsValue> {
+        let options = options.map(JsValue::from).unwrap_or(JsValue::UNDEFINED);
+        let events = js_sys::Reflect::get(&options, &JsValue::from_str("events"))?;
+        let events: Vec<String> = if js_sys::Array::is_array(&events) {
+            js_sys::Array::from(&events).iter().filter_map(|name| name.as_string()).collect()
+        } else {
+            vec!["message".to_string()]
+        };
+
+        let init = web_sys::EventSourceInit::new();
+        init.set_with_credentials(js_sys::Reflect::get(&options, &JsValue::from_str("withCredentials"))?.is_truthy());
+        let source = web_sys::EventSource::new_with_event_source_init_dict(url, &init)?;
+
+        let mut store = self.clone();
+        let on_event = Closure::wrap(Box::new(move |event: web_sys::MessageEvent| {
+            let name = event.type_();
+            if let Err(error) = store.dispatch_sse_event(&mapper, &event) {
+                let source = ErrorSource { source: "sse", key: None, action: Some(name.as_str()), timestamp: js_sys::Date::now() };
+                let _ = store.report_error(&error, source);
+            }
+        }) as Box<dyn FnMut(web_sys::MessageEvent)>);
+
+        for name in events.iter() {
+            source.add_event_listener_with_callback(name, on_event.as_ref().unchecked_ref())?;
+        }
+        let teardown_source = source.clone();
+        self.register_teardown(Box::new(move || teardown_source.close()))?;
+        log_info!("Subscribed to server-sent events from {}", url);
+
+        Ok(SseSubscription { source, events, _on_event: on_event })
+    }
+}
+
+#[wasm_bindgen]
+impl SseSubscription {
+    #[wasm_bindgen(getter)]
+    pub fn ready_state(&self) -> u16 {
+        self.source.ready_state()
+    }
+
+    /// Closes the connection; the browser no longer reconnects.
+    #[wasm_bindgen]
+    pub fn close(&self) -> Result<(), JsValue> {
+        for name in self.events.iter() {
+            self.source
+                .remove_event_listener_with_callback(name, self._on_event.as_ref().unchecked_ref())?;
+        }
+        self.source.close();
+        Ok(())
+    }
+}
+
+impl WasmStorage {
+    fn dispatch_sse_event(&mut self, mapper: &js_sys::Function, event: &web_sys::MessageEvent) -> Result<(), JsValue> {
+        let raw = event.data();
+        // Server-sent data is text; most feeds send JSON
+        let data = raw.as_string().and_then(|text| js_sys::JSON::parse(&text).ok()).unwrap_or(raw);
+        let message = js_sys::Object::new();
+        js_sys::Reflect::set(&message, &JsValue::from_str("event"), &JsValue::from_str(&event.type_()))?;
+        js_sys::Reflect::set(&message, &JsValue::from_str("data"), &data)?;
+        js_sys::Reflect::set(&message, &JsValue::from_str("lastEventId"), &JsValue::from_str(&event.last_event_id()))?;
+
+        let mapped = mapper.call1(&JsValue::NULL, &message)?;
+        let actions = if js_sys::Array::is_array(&mapped) {
+            js_sys::Array::from(&mapped)
+        } else {
+            js_sys::Array::of1(&mapped)
+        };
+        for action in actions.iter() {
+            if action.is_null() || action.is_undefined() {
+                continue;
+            }
+            let Some(action_type) = js_sys::Reflect::get(&action, &JsValue::from_str("type"))?.as_string() else {
+                continue;
+            };
+            let payload = js_sys::Reflect::get(&action, &JsValue::from_str("payload"))?;
+            self.dispatch(&action_type, payload)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,163 @@
+use crate::error::{ErrorCode, WasmStorageError};
+use crate::WasmStorage;
+use std::collections::{HashMap, HashSet};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+
+const ANY_STATE: &str = "*";
+
+/// The legal values of a key and the transitions between them. Writing
+/// the current value again is always allowed.
+pub(crate) struct StateMachine {
+    states: Vec<String>,
+    initial: Option<String>,
+    // From state (or "*") -> reachable states
+    transitions: HashMap<String, HashSet<String>>,
+}
+
+impl StateMachine {
+    fn from_js(key: &str, definition: &JsValue) -> Result<StateMachine, JsValue> {
+        let invalid = |message: String| -> JsValue { WasmStorageError::invalid_argument(message).with("key", key).into() };
+        let states = js_sys::Reflect::get(definition, &JsValue::from_str("states"))?;
+        if !js_sys::Array::is_array(&states) {
+            return Err(invalid(format!("State machine for \"{}\" needs a states array", key)));
+        }
+        let states: Vec<String> = js_sys::Array::from(&states).iter().filter_map(|state| state.as_string()).collect();
+        if states.is_empty() {
+            return Err(invalid(format!("State machine for \"{}\" has no states", key)));
+        }
+        let known = |state: &str| state == ANY_STATE || states.iter().any(|known| known == state);
+
+        let mut transitions: HashMap<String, HashSet<String>> = HashMap::new();
+        let defined = js_sys::Reflect::get(definition, &JsValue::from_str("transitions"))?;
+        if let Some(defined) = defined.dyn_ref::<js_sys::Object>() {
+            for entry in js_sys::Object::entries(defined).iter() {
+                let pair = js_sys::Array::from(&entry);
+                let from = pair.get(0).as_string().unwrap_or_default();
+                let targets = pair.get(1);
+                let targets: Vec<String> = if js_sys::Array::is_array(&targets) {
+                    js_sys::Array::from(&targets).iter().filter_map(|to| to.as_string()).collect()
+                } else {
+                    targets.as_string().into_iter().collect()
+                };
+                for state in std::iter::once(&from).chain(targets.iter()) {
+                    if !known(state) || (state == ANY_STATE && state != &from) {
+                        return Err(invalid(format!("Transition {} -> {:?} of \"{}\" uses unknown state \"{}\"", from, targets, key, state)));
+                    }
+                }
+                transitions.entry(from).or_default().extend(targets);
+            }
+        }
+
+        let initial = js_sys::Reflect::get(definition, &JsValue::from_str("initial"))?.as_string();
+        if let Some(initial) = &initial {
+            if !states.contains(initial) {
+                return Err(invalid(format!("Initial state \"{}\" of \"{}\" is not one of its states", initial, key)));
+            }
+        }
+        Ok(StateMachine { states, initial, transitions })
+    }
+
+    fn allowed_from(&self, from: &str) -> Vec<String> {
+        let mut allowed: Vec<String> = self
+            .transitions
+            .get(from)
+            .into_iter()
+            .chain(self.transitions.get(ANY_STATE))
+            .flatten()
+            .cloned()
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .collect();
+        allowed.sort();
+        allowed
+    }
+}
+
+#[wasm_bindgen]
+impl WasmStorage {
+    /// Restricts `key` to `definition.states` and its writes to
+    /// `definition.transitions` (state -> reachable states; `"*"` as the
+    /// source applies from any state). A first write may use any state, or
+    /// only `definition.initial` when given. Illegal writes fail with
+    /// VALIDATION_FAILED and the attempted `from`/`to` in the context. The
+    /// key's current value must already be a legal state.
+    #[wasm_bindgen]
+    pub fn define_state_machine(&mut self, key: &str, definition: js_sys::Object) -> Result<(), JsValue> {
+        let machine = StateMachine::from_js(key, &definition)?;
+        if let Some(current) = self.state.lock().map_err(WasmStorageError::lock_poisoned)?.get(key) {
+            let legal = current.as_string().is_some_and(|state| machine.states.contains(&state));
+            if !legal {
+                return Err(WasmStorageError::invalid_state(format!("\"{}\" currently holds {:?}, which is not one of its states", key, current))
+                    .with("key", key)
+                    .into());
+            }
+        }
+        self.state_machines.lock().map_err(WasmStorageError::lock_poisoned)?.insert(key.to_string(), machine);
+        Ok(())
+    }
+
+    #[wasm_bindgen]
+    pub fn remove_state_machine(&mut self, key: &str) -> Result<(), JsValue> {
+        self.state_machines.lock().map_err(WasmStorageError::lock_poisoned)?.remove(key);
+        Ok(())
+    }
+
+    /// The states `key` may move to from its current value, for enabling
+    /// the matching UI actions.
+    #[wasm_bindgen]
+    pub fn allowed_transitions(&self, key: &str) -> Result<js_sys::Array, JsValue> {
+        let machines = self.state_machines.lock().map_err(WasmStorageError::lock_poisoned)?;
+        let machine = machines
+            .get(key)
+            .ok_or_else(|| WasmStorageError::invalid_state(format!("\"{}\" has no state machine", key)).with("key", key))?;
+        let current = self.state.lock().map_err(WasmStorageError::lock_poisoned)?.get(key).and_then(|value| value.as_string());
+        let allowed = match current {
+            Some(current) => machine.allowed_from(&current),
+            None => match &machine.initial {
+                Some(initial) => vec![initial.clone()],
+                None => machine.states.clone(),
+            },
+        };
+        Ok(allowed.iter().map(|state| JsValue::from_str(state)).collect())
+    }
+}
+
+impl WasmStorage {
+    /// Rejects a write to a key with a state machine unless it is a legal
+    /// transition from the key's current value.
+    pub(crate) fn check_transition(&self, key: &str, value: &JsValue) -> Result<(), JsValue> {
+        let machines = self.state_machines.lock().map_err(WasmStorageError::lock_poisoned)?;
+        let Some(machine) = machines.get(key) else {
+            return Ok(());
+        };
+        let current = self.state.lock().map_err(WasmStorageError::lock_poisoned)?.get(key).and_then(|value| value.as_string());
+        let to = value.as_string();
+        let failure = match (&current, &to) {
+            (_, None) => format!("Invalid value for \"{}\": {:?} is not a state", key, value),
+            (_, Some(to)) if !machine.states.contains(to) => format!("Invalid value for \"{}\": \"{}\" is not a state", key, to),
+            (Some(from), Some(to)) if from == to => return Ok(()),
+            (None, Some(to)) => match &machine.initial {
+                Some(initial) if initial != to => format!("Invalid value for \"{}\": it must start as \"{}\", not \"{}\"", key, initial, to),
+                _ => return Ok(()),
+            },
+            (Some(from), Some(to)) => {
+                let allowed = machine.allowed_from(from);
+                if allowed.contains(to) {
+                    return Ok(());
+                }
+                format!("Illegal transition for \"{}\": \"{}\" -> \"{}\" (allowed: {})", key, from, to, allowed.join(", "))
+            }
+        };
+        let allowed: js_sys::Array = match &current {
+            Some(from) => machine.allowed_from(from).iter().map(|state| JsValue::from_str(state)).collect(),
+            None => machine.states.iter().map(|state| JsValue::from_str(state)).collect(),
+        };
+        Err(WasmStorageError::new(ErrorCode::ValidationFailed, failure)
+            .with("key", key)
+            .with("from", current.map(JsValue::from).unwrap_or(JsValue::NULL))
+            .with("to", value.clone())
+            .with("allowed", allowed)
+            .into())
+    }
+}
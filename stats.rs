@@ -0,0 +1,48 @@
+use crate::error::WasmStorageError;
+use crate::WasmStorage;
+use wasm_bindgen::prelude::*;
+
+#[wasm_bindgen]
+impl WasmStorage {
+    /// A snapshot of the store's internals, for debugging overlays:
+    /// `{ entries, estimatedBytes, listeners, middleware, historyDepth,
+    /// hydration, pendingFlushes }`. `estimatedBytes` counts keys and JSON
+    /// values as UTF-16, like persistence does (values JSON can't represent
+    /// count as nothing); `historyDepth` is the number of actions in the
+    /// event log, or null without event sourcing; `hydration` is `"none"`,
+    /// `"pending"` or `"complete"`; `pendingFlushes` counts writes the
+    /// write-behind timer has yet to persist.
+    #[wasm_bindgen]
+    pub fn get_stats(&self) -> Result<js_sys::Object, JsValue> {
+        let (entries, estimated_bytes) = {
+            let state = self.state.lock().map_err(WasmStorageError::lock_poisoned)?;
+            let bytes: usize = state
+                .iter()
+                .map(|(key, value)| {
+                    let json = js_sys::JSON::stringify(value).ok().and_then(|json| json.as_string()).unwrap_or_default();
+                    (key.encode_utf16().count() + json.encode_utf16().count()) * 2
+                })
+                .sum();
+            (state.len(), bytes)
+        };
+        let listeners = self.listeners.lock().map_err(WasmStorageError::lock_poisoned)?.len();
+        let middleware = self.middleware.lock().map_err(WasmStorageError::lock_poisoned)?.len();
+        let history_depth = self.event_log.lock().map_err(WasmStorageError::lock_poisoned)?.as_ref().map(|log| log.len());
+        let persistence = self.persistence_status()?;
+        let hydration = match (persistence, self.is_hydrated()?) {
+            (Some((true, _)), _) => "pending",
+            (Some(_), _) | (None, true) => "complete",
+            (None, false) => "none",
+        };
+
+        let stats = js_sys::Object::new();
+        js_sys::Reflect::set(&stats, &JsValue::from_str("entries"), &JsValue::from_f64(entries as f64))?;
+        js_sys::Reflect::set(&stats, &JsValue::from_str("estimatedBytes"), &JsValue::from_f64(estimated_bytes as f64))?;
+        js_sys::Reflect::set(&stats, &JsValue::from_str("listeners"), &JsValue::from_f64(listeners as f64))?;
+        js_sys::Reflect::set(&stats, &JsValue::from_str("middleware"), &JsValue::from_f64(middleware as f64))?;
+        js_sys::Reflect::set(&stats, &JsValue::from_str("historyDepth"), &history_depth.map_or(JsValue::NULL, |depth| JsValue::from_f64(depth as f64)))?;
+        js_sys::Reflect::set(&stats, &JsValue::from_str("hydration"), &JsValue::from_str(hydration))?;
+        js_sys::Reflect::set(&stats, &JsValue::from_str("pendingFlushes"), &JsValue::from(persistence.map_or(0, |(_, pending)| pending)))?;
+        Ok(stats)
+    }
+}
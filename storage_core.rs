@@ -0,0 +1,171 @@
+//! The store's state, change notification and reducer dispatch in plain
+//! Rust, with no wasm-bindgen in sight. It builds for native targets, so
+//! reducers and store logic can be unit-tested with `cargo test` and reused
+//! for server-side rendering; build without the `wasm` feature
+//! (`cargo test --no-default-features`) to leave the browser layer out.
+
+use crate::persistent_map::PersistentMap;
+use crate::sync::Shared;
+use std::collections::HashMap;
+
+/// A change to one key, as passed to listeners; None means absent.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Change<V> {
+    pub key: String,
+    pub value: Option<V>,
+    pub old_value: Option<V>,
+}
+
+type Listener<V> = Box<dyn FnMut(&Change<V>)>;
+type Reducer<V> = Shared<dyn Fn(&mut StorageCore<V>, &V)>;
+
+/// Key/value state of any value type. Writes of an equal value notify no
+/// one, like `set_change_detection("deep")` in the browser store.
+pub struct StorageCore<V> {
+    state: PersistentMap<V>,
+    next_listener: u32,
+    listeners: Vec<(u32, Listener<V>)>,
+    reducers: HashMap<String, Reducer<V>>,
+}
+
+impl<V: Clone + PartialEq + 'static> StorageCore<V> {
+    pub fn new() -> StorageCore<V> {
+        StorageCore { state: PersistentMap::new(), next_listener: 0, listeners: Vec::new(), reducers: HashMap::new() }
+    }
+
+    pub fn get(&self, key: &str) -> Option<&V> {
+        self.state.get(key)
+    }
+
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.state.contains_key(key)
+    }
+
+    pub fn len(&self) -> usize {
+        self.state.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.state.is_empty()
+    }
+
+    /// Keys and values in arbitrary order.
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &V)> {
+        self.state.iter()
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &String> {
+        self.state.keys()
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &V> {
+        self.state.values()
+    }
+
+    pub fn set(&mut self, key: impl Into<String>, value: V) {
+        let key = key.into();
+        if self.state.get(&key) == Some(&value) {
+            return;
+        }
+        let old_value = self.state.insert(key.clone(), value.clone());
+        self.notify(Change { key, value: Some(value), old_value });
+    }
+
+    pub fn remove(&mut self, key: &str) -> Option<V> {
+        let old_value = self.state.remove(key)?;
+        self.notify(Change { key: key.to_string(), value: None, old_value: Some(old_value.clone()) });
+        Some(old_value)
+    }
+
+    /// Empties the state. Like `clear_state`, this notifies no one.
+    pub fn clear(&mut self) {
+        self.state.clear();
+    }
+
+    /// Calls `listener` after every change; returns an ID for `unsubscribe`.
+    pub fn subscribe(&mut self, listener: impl FnMut(&Change<V>) + 'static) -> u32 {
+        self.next_listener += 1;
+        self.listeners.push((self.next_listener, Box::new(listener)));
+        self.next_listener
+    }
+
+    /// False when the listener was already gone.
+    pub fn unsubscribe(&mut self, id: u32) -> bool {
+        let before = self.listeners.len();
+        self.listeners.retain(|(listener, _)| *listener != id);
+        self.listeners.len() != before
+    }
+
+    /// Handles `action_type` with `reducer(store, payload)`, replacing an
+    /// earlier reducer for the same type.
+    pub fn register_reducer(&mut self, action_type: impl Into<String>, reducer: impl Fn(&mut StorageCore<V>, &V) + 'static) {
+        let reducer: Reducer<V> = Shared::new(reducer);
+        self.reducers.insert(action_type.into(), reducer);
+    }
+
+    /// Runs the reducer for `action_type`. Without one, the payload is kept
+    /// under `__actions_<type>`, as the browser store's legacy mode does for
+    /// unhandled actions. The JS built-ins (`SET_STATE` and friends) rely on
+    /// JS payload shapes and are the `set`/`remove`/`clear` methods here.
+    pub fn dispatch(&mut self, action_type: &str, payload: V) {
+        match self.reducers.get(action_type).cloned() {
+            Some(reducer) => reducer(self, &payload),
+            None => self.set(format!("__actions_{}", action_type), payload),
+        }
+    }
+
+    fn notify(&mut self, change: Change<V>) {
+        for (_, listener) in self.listeners.iter_mut() {
+            listener(&change);
+        }
+    }
+}
+
+impl<V: Clone + PartialEq + 'static> Default for StorageCore<V> {
+    fn default() -> StorageCore<V> {
+        StorageCore::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    #[test]
+    fn notifies_changes_but_not_equal_writes() {
+        let mut store = StorageCore::new();
+        let changes = Rc::new(RefCell::new(Vec::new()));
+        let seen = changes.clone();
+        let id = store.subscribe(move |change: &Change<i32>| seen.borrow_mut().push(change.clone()));
+
+        store.set("count", 1);
+        store.set("count", 1);
+        store.set("count", 2);
+        assert_eq!(store.remove("count"), Some(2));
+        assert_eq!(store.remove("count"), None);
+        assert!(store.unsubscribe(id));
+        store.set("count", 3);
+
+        let changes: Vec<_> = changes.borrow().iter().map(|change| (change.value, change.old_value)).collect();
+        assert_eq!(changes, vec![(Some(1), None), (Some(2), Some(1)), (None, Some(2))]);
+    }
+
+    #[test]
+    fn dispatches_to_reducers() {
+        let mut store: StorageCore<i64> = StorageCore::new();
+        store.register_reducer("INCREMENT", |store, by| {
+            let count = store.get("count").copied().unwrap_or(0);
+            store.set("count", count + by);
+        });
+
+        store.dispatch("INCREMENT", 2);
+        store.dispatch("INCREMENT", 3);
+        store.dispatch("PING", 7);
+
+        assert_eq!(store.get("count"), Some(&5));
+        assert_eq!(store.get("__actions_PING"), Some(&7));
+        assert_eq!(store.len(), 2);
+    }
+}
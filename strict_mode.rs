@@ -0,0 +1,64 @@
+use crate::error::WasmStorageError;
+use crate::WasmStorage;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+
+/// Freezes `value` and everything reachable through its own enumerable
+/// properties. Typed arrays and buffers can't be frozen and are left as
+/// they are; already frozen objects are assumed to be frozen deeply, which
+/// also stops cycles.
+pub(crate) fn deep_freeze(value: &JsValue) {
+    let Some(object) = value.dyn_ref::<js_sys::Object>() else {
+        return;
+    };
+    if object.is_instance_of::<js_sys::Function>()
+        || js_sys::Object::is_frozen(object)
+        || js_sys::ArrayBuffer::is_view(value)
+        || object.is_instance_of::<js_sys::ArrayBuffer>()
+    {
+        return;
+    }
+    js_sys::Object::freeze(object);
+    for nested in js_sys::Object::values(object).iter() {
+        deep_freeze(&nested);
+    }
+}
+
+#[wasm_bindgen]
+impl WasmStorage {
+    /// Deep-freezes every stored value, now and on each later write, so
+    /// objects returned by `get_state` and `get_all_state` can't be mutated
+    /// in place: in strict-mode code (ES modules, classes) the mutation
+    /// throws a TypeError at the offending line. Values passed to
+    /// `set_state` are frozen too, since the store keeps them by reference.
+    #[wasm_bindgen]
+    pub fn enable_strict_mode(&mut self) -> Result<(), JsValue> {
+        *self.strict.lock().map_err(WasmStorageError::lock_poisoned)? = true;
+        let state = self.state.lock().map_err(WasmStorageError::lock_poisoned)?;
+        for value in state.values() {
+            deep_freeze(value);
+        }
+        Ok(())
+    }
+
+    /// Stops freezing new writes; values frozen so far stay frozen.
+    #[wasm_bindgen]
+    pub fn disable_strict_mode(&mut self) -> Result<(), JsValue> {
+        *self.strict.lock().map_err(WasmStorageError::lock_poisoned)? = false;
+        Ok(())
+    }
+}
+
+impl WasmStorage {
+    pub(crate) fn strict(&self) -> Result<bool, JsValue> {
+        Ok(*self.strict.lock().map_err(WasmStorageError::lock_poisoned)?)
+    }
+
+    /// Freezes a value about to be stored, in strict mode.
+    pub(crate) fn harden(&self, value: &JsValue) -> Result<(), JsValue> {
+        if self.strict()? {
+            deep_freeze(value);
+        }
+        Ok(())
+    }
+}
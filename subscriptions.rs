@@ -0,0 +1,318 @@
+use crate::delivery::{DeferredQueue, Delivery};
+use crate::error::WasmStorageError;
+use crate::error_handler::ErrorSource;
+use crate::origin::Origin;
+use crate::{values_equal, WasmStorage};
+use std::collections::BTreeMap;
+use crate::sync::{Lock, Shared};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+
+/// How a write is compared with the previous value to decide whether
+/// listeners hear about it at all.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum ChangeDetection {
+    Always,
+    Reference,
+    Deep,
+    Hash,
+}
+
+impl ChangeDetection {
+    fn parse(mode: &str) -> Result<ChangeDetection, JsValue> {
+        match mode {
+            "always" => Ok(ChangeDetection::Always),
+            "reference" => Ok(ChangeDetection::Reference),
+            "deep" => Ok(ChangeDetection::Deep),
+            "hash" => Ok(ChangeDetection::Hash),
+            _ => Err(WasmStorageError::invalid_argument(format!(
+                "Unknown change detection mode \"{}\" (expected always, reference, deep or hash)",
+                mode
+            )).into()),
+        }
+    }
+
+    pub(crate) fn unchanged(self, old_value: &JsValue, value: &JsValue) -> bool {
+        match self {
+            ChangeDetection::Always => false,
+            ChangeDetection::Reference => js_sys::Object::is(old_value, value),
+            // Identical content was already dropped by `same_content`, so an
+            // object written again was mutated in place
+            ChangeDetection::Hash => !value.is_object() && js_sys::Object::is(old_value, value),
+            ChangeDetection::Deep => values_equal(old_value, value),
+        }
+    }
+}
+
+/// Per-subscription settings parsed from `subscribe`'s options.
+pub(crate) struct ListenerOptions {
+    // None hears changes of every origin
+    origins: Option<Vec<Origin>>,
+    // Receives `{ changes, timestamp }` instead of one event per change
+    pub(crate) aggregate: bool,
+    delivery: Delivery,
+    // Deliver only the latest change per key since the last delivery
+    coalesce: bool,
+    // Hear the current state right away, see `WasmStorage::deliver_current`
+    pub(crate) immediate: bool,
+    priority: i32,
+}
+
+impl ListenerOptions {
+    pub(crate) fn from_js(options: &JsValue) -> Result<ListenerOptions, JsValue> {
+        let option = |name: &str| js_sys::Reflect::get(options, &JsValue::from_str(name)).unwrap_or(JsValue::UNDEFINED);
+        let origins = option("origins")
+            .dyn_into::<js_sys::Array>()
+            .ok()
+            .map(|origins| {
+                origins
+                    .iter()
+                    .map(|origin| Origin::parse(&origin.as_string().unwrap_or_default()))
+                    .collect::<Result<Vec<Origin>, JsValue>>()
+            })
+            .transpose()?;
+        let delivery = match option("delivery").as_string() {
+            Some(delivery) => Delivery::parse(&delivery)?,
+            None => Delivery::Sync,
+        };
+        // `throttle: "frame"`: at most one coalesced delivery per animation frame
+        let coalesce = match option("throttle").as_string().as_deref() {
+            None => false,
+            Some("frame") if matches!(delivery, Delivery::Sync | Delivery::Frame) => true,
+            Some("frame") => return Err(WasmStorageError::invalid_argument("throttle: \"frame\" can't be combined with another delivery mode").into()),
+            Some(other) => return Err(WasmStorageError::invalid_argument(format!("Unknown throttle \"{}\" (expected frame)", other)).into()),
+        };
+        let delivery = if coalesce { Delivery::Frame } else { delivery };
+        let priority = priority(&option("priority"))?;
+        Ok(ListenerOptions {
+            origins,
+            aggregate: option("aggregate").is_truthy(),
+            delivery,
+            coalesce,
+            immediate: option("immediate").is_truthy(),
+            priority,
+        })
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct Listener {
+    pub(crate) id: u32,
+    handle: String,
+    pub(crate) callback: js_sys::Function,
+    origins: Option<Vec<Origin>>,
+    pub(crate) aggregate: bool,
+    pub(crate) delivery: Delivery,
+    pub(crate) coalesce: bool,
+    priority: i32,
+    // Changes waiting for a non-synchronous delivery
+    pub(crate) queue: Option<DeferredQueue>,
+}
+
+impl Listener {
+    pub(crate) fn accepts(&self, origin: Origin) -> bool {
+        self.origins.as_ref().is_none_or(|origins| origins.contains(&origin))
+    }
+}
+
+/// A single key change, before it is turned into a JS change event.
+#[derive(Clone)]
+pub(crate) struct Change {
+    pub(crate) key: String,
+    pub(crate) value: JsValue,
+    pub(crate) old_value: JsValue,
+    pub(crate) origin: Origin,
+    pub(crate) timestamp: f64,
+}
+
+impl Change {
+    pub(crate) fn to_event(&self) -> Result<js_sys::Object, JsValue> {
+        let event = js_sys::Object::new();
+        js_sys::Reflect::set(&event, &JsValue::from_str("key"), &JsValue::from_str(&self.key))?;
+        js_sys::Reflect::set(&event, &JsValue::from_str("value"), &self.value)?;
+        js_sys::Reflect::set(&event, &JsValue::from_str("oldValue"), &self.old_value)?;
+        js_sys::Reflect::set(&event, &JsValue::from_str("origin"), &JsValue::from_str(self.origin.as_str()))?;
+        js_sys::Reflect::set(&event, &JsValue::from_str("timestamp"), &JsValue::from_f64(self.timestamp))?;
+        Ok(event)
+    }
+}
+
+/// An integer `priority` option, 0 when undefined.
+pub(crate) fn priority(value: &JsValue) -> Result<i32, JsValue> {
+    if value.is_undefined() {
+        return Ok(0);
+    }
+    match value.as_f64().filter(|priority| priority.fract() == 0.0 && priority.abs() <= i32::MAX as f64) {
+        Some(priority) => Ok(priority as i32),
+        None => Err(WasmStorageError::invalid_argument(format!("priority must be an integer, got {:?}", value)).into()),
+    }
+}
+
+/// Whether `options.immediate` asks for a first call with the current value.
+pub(crate) fn immediate(options: &Option<js_sys::Object>) -> Result<bool, JsValue> {
+    match options {
+        Some(options) => Ok(js_sys::Reflect::get(options, &JsValue::from_str("immediate"))?.is_truthy()),
+        None => Ok(false),
+    }
+}
+
+/// Listeners keyed by a monotonically increasing ID, so removing one never
+/// changes the ID of another. They are called by descending priority, and
+/// in subscription order (BTreeMap iteration) within a priority. Callers
+/// know a listener by its handle, from the store's ID generator.
+#[derive(Default)]
+pub(crate) struct Listeners {
+    next_id: u32,
+    entries: BTreeMap<u32, Listener>,
+}
+
+impl Listeners {
+    pub(crate) fn insert(&mut self, callback: js_sys::Function, options: ListenerOptions, handle: String) -> u32 {
+        let id = self.next_id;
+        self.next_id += 1;
+        let queue = (options.delivery != Delivery::Sync).then(DeferredQueue::default);
+        let listener = Listener {
+            id,
+            handle,
+            callback,
+            origins: options.origins,
+            aggregate: options.aggregate,
+            delivery: options.delivery,
+            coalesce: options.coalesce,
+            priority: options.priority,
+            queue,
+        };
+        self.entries.insert(id, listener);
+        id
+    }
+
+    pub(crate) fn contains(&self, id: u32) -> bool {
+        self.entries.contains_key(&id)
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub(crate) fn remove(&mut self, id: u32) -> bool {
+        self.entries.remove(&id).is_some()
+    }
+
+    pub(crate) fn remove_handle(&mut self, handle: &str) -> bool {
+        match self.entries.values().find(|listener| listener.handle == handle) {
+            Some(listener) => {
+                let id = listener.id;
+                self.remove(id)
+            }
+            None => false,
+        }
+    }
+
+    /// Every listener, in invocation order.
+    pub(crate) fn snapshot(&self) -> Vec<Listener> {
+        let mut listeners: Vec<Listener> = self.entries.values().cloned().collect();
+        // Stable, so equal priorities keep subscription order
+        listeners.sort_by_key(|listener| std::cmp::Reverse(listener.priority));
+        listeners
+    }
+}
+
+#[wasm_bindgen]
+impl WasmStorage {
+    /// Writes that leave a key unchanged notify no one. `"reference"` (the
+    /// default) compares with `Object.is`, `"deep"` also treats structurally
+    /// equal objects as unchanged, and `"always"` notifies on every write.
+    /// `"hash"` compares content hashes kept per key and drops a write of
+    /// identical content entirely: the existing value stays, and nothing is
+    /// persisted or notified.
+    #[wasm_bindgen]
+    pub fn set_change_detection(&mut self, mode: &str) -> Result<(), JsValue> {
+        let mode = ChangeDetection::parse(mode)?;
+        *self.change_detection.lock().map_err(WasmStorageError::lock_poisoned)? = mode;
+        if mode != ChangeDetection::Hash {
+            self.forget_fingerprint(None)?;
+        }
+        Ok(())
+    }
+}
+
+impl WasmStorage {
+    /// The current value of `key` as a `"system"` change event with a null
+    /// `oldValue`, as `immediate` subscriptions first hear it.
+    pub(crate) fn current_change(&self, key: &str) -> Result<Change, JsValue> {
+        let value = self.state.lock().map_err(WasmStorageError::lock_poisoned)?.get(key).cloned();
+        Ok(Change { key: key.to_string(), value: value.unwrap_or(JsValue::NULL), old_value: JsValue::NULL, origin: Origin::System, timestamp: self.now()? })
+    }
+
+    /// Calls a new `immediate` listener with one change event per stored
+    /// key, in key order, or once with all of them when it aggregates. This
+    /// happens synchronously whatever the listener's delivery mode or
+    /// origins, and its errors are reported like any listener's.
+    pub(crate) fn deliver_current(&self, callback: &js_sys::Function, aggregate: bool) -> Result<(), JsValue> {
+        let keys: Vec<String> = self.state.lock().map_err(WasmStorageError::lock_poisoned)?.keys().cloned().collect();
+        let mut keys = self.retain_readable(keys, |key| key)?;
+        keys.sort();
+        let changes = keys.iter().map(|key| self.current_change(key)).collect::<Result<Vec<_>, _>>()?;
+        let timestamp = self.now()?;
+
+        if aggregate {
+            let events: js_sys::Array = changes.iter().map(|change| change.to_event().map(JsValue::from)).collect::<Result<_, _>>()?;
+            let batch_event = js_sys::Object::new();
+            js_sys::Reflect::set(&batch_event, &JsValue::from_str("changes"), &events)?;
+            js_sys::Reflect::set(&batch_event, &JsValue::from_str("timestamp"), &JsValue::from_f64(timestamp))?;
+            if let Err(error) = callback.call1(&JsValue::NULL, &batch_event) {
+                self.report_error(&error, ErrorSource { source: "listener", key: None, action: None, timestamp })?;
+            }
+            return Ok(());
+        }
+        for change in changes.iter() {
+            if let Err(error) = callback.call1(&JsValue::NULL, &change.to_event()?.into()) {
+                self.report_error(&error, ErrorSource { source: "listener", key: Some(&change.key), action: None, timestamp })?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Returned by `subscribe`. Unsubscribing is idempotent and only ever
+/// detaches the listener this handle was created for.
+#[wasm_bindgen]
+pub struct Subscription {
+    id: u32,
+    handle: String,
+    listeners: Shared<Lock<Listeners>>,
+}
+
+impl Subscription {
+    pub(crate) fn new(id: u32, handle: String, listeners: Shared<Lock<Listeners>>) -> Subscription {
+        Subscription { id, handle, listeners }
+    }
+
+    // Handle for a subscription that was never registered (already-aborted signal)
+    pub(crate) fn inactive(handle: String, listeners: Shared<Lock<Listeners>>) -> Subscription {
+        Subscription { id: u32::MAX, handle, listeners }
+    }
+}
+
+#[wasm_bindgen]
+impl Subscription {
+    /// The handle `unsubscribe(id)` takes, from the store's ID generator
+    /// (scope `"subscription"`).
+    #[wasm_bindgen(getter)]
+    pub fn id(&self) -> String {
+        self.handle.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn active(&self) -> Result<bool, JsValue> {
+        let listeners = self.listeners.lock().map_err(WasmStorageError::lock_poisoned)?;
+        Ok(listeners.contains(self.id))
+    }
+
+    #[wasm_bindgen]
+    pub fn unsubscribe(&self) -> Result<(), JsValue> {
+        let mut listeners = self.listeners.lock().map_err(WasmStorageError::lock_poisoned)?;
+        listeners.remove(self.id);
+        Ok(())
+    }
+}
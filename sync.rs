@@ -0,0 +1,41 @@
+//! The shared, mutable state behind every store handle. Browsers run wasm
+//! on a single thread, so by default this is `Rc` plus a `RefCell`-backed
+//! `Lock`: no atomics, and a re-entrant access fails with LOCK_POISONED
+//! instead of deadlocking. The `threads` feature switches to `Arc`/`Mutex`
+//! for wasm-threads builds; the store's API is the same either way.
+
+// Only the wasm layer locks anything; `storage_core` just shares
+#![cfg_attr(not(feature = "wasm"), allow(dead_code, unused_imports))]
+
+#[cfg(not(feature = "threads"))]
+pub(crate) use single::{Lock, Shared};
+#[cfg(feature = "threads")]
+pub(crate) use threaded::{Lock, Shared};
+
+#[cfg(not(feature = "threads"))]
+mod single {
+    use std::cell::{BorrowMutError, RefCell, RefMut};
+
+    pub(crate) type Shared<T> = std::rc::Rc<T>;
+
+    #[derive(Default)]
+    pub(crate) struct Lock<T>(RefCell<T>);
+
+    impl<T> Lock<T> {
+        pub(crate) const fn new(value: T) -> Lock<T> {
+            Lock(RefCell::new(value))
+        }
+
+        /// Mirrors `Mutex::lock`; fails while the value is already in use
+        /// further up the stack.
+        pub(crate) fn lock(&self) -> Result<RefMut<'_, T>, BorrowMutError> {
+            self.0.try_borrow_mut()
+        }
+    }
+}
+
+#[cfg(feature = "threads")]
+mod threaded {
+    pub(crate) type Shared<T> = std::sync::Arc<T>;
+    pub(crate) type Lock<T> = std::sync::Mutex<T>;
+}
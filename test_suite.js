@@ -1,6 +1,6 @@
 // tests/wasm-storage.test.js
-import { describe, it, expect, beforeEach, afterEach } from 'vitest';
-import { createWasmStorage } from '../src/wasm-storage.js';
+import { describe, it, expect, beforeEach, afterEach, vi } from 'vitest';
+import { createWasmStorage, createWorkerStoreClient } from '../src/wasm-storage.js';
 
 describe('WASM Storage Core', () => {
     let storage;
@@ -32,6 +32,58 @@ describe('WASM Storage Core', () => {
             expect(state).toEqual({ a: 1, b: 2 });
         });
 
+        it('should start from the initial state and fill in defaults', async () => {
+            const { storage: seeded } = await createWasmStorage('../pkg/wasm_storage.js', {
+                initialState: { theme: 'dark' },
+                defaults: { theme: 'light', locale: 'en' }
+            });
+            expect(seeded.getAllState()).toEqual({ theme: 'dark', locale: 'en' });
+
+            storage.setState('count', 5);
+            const origins = [];
+            storage.subscribe((event) => origins.push([event.key, event.origin]));
+            storage.setDefaults({ count: 0, page: 1 });
+            expect(storage.getState('count')).toBe(5);
+            expect(storage.getState('page')).toBe(1);
+            expect(origins).toEqual([['page', 'system']]);
+            seeded.destroy();
+        });
+
+        it('should initialize unset keys on first read', () => {
+            const changes = [];
+            storage.subscribe((event) => changes.push(event.key));
+            expect(storage.getOrInsert('theme', 'dark')).toBe('dark');
+            expect(storage.getOrInsert('theme', 'light')).toBe('dark');
+
+            const factory = vi.fn(() => ({ items: [] }));
+            expect(storage.getOrInsertWith('cart', factory)).toEqual({ items: [] });
+            expect(storage.getOrInsertWith('cart', factory)).toEqual({ items: [] });
+            expect(factory).toHaveBeenCalledTimes(1);
+            expect(factory).toHaveBeenCalledWith('cart');
+            expect(changes).toEqual(['theme', 'cart']);
+        });
+
+        it('should read many keys at once and report the missing ones', () => {
+            storage.setState('a', 1);
+            storage.setState('b', { nested: true });
+
+            expect(storage.getMany(['a', 'x', 'b', 'y'])).toEqual({ values: { a: 1, b: { nested: true } }, missing: ['x', 'y'] });
+            expect(storage.getMany([])).toEqual({ values: {}, missing: [] });
+            expect(() => storage.getMany([1])).toThrow(expect.objectContaining({ code: 'INVALID_ARGUMENT' }));
+        });
+
+        it('should reuse the state snapshot until the next write', () => {
+            storage.setState('a', 1);
+            const snapshot = storage.getAllState();
+            expect(Object.isFrozen(snapshot)).toBe(true);
+            expect(storage.getAllState()).toBe(snapshot);
+
+            storage.setState('b', 2);
+            expect(storage.getAllState()).not.toBe(snapshot);
+            expect(storage.getAllState()).toEqual({ a: 1, b: 2 });
+            expect(snapshot).toEqual({ a: 1 });
+        });
+
         it('should clear all state', () => {
             storage.setState('a', 1);
             storage.setState('b', 2);
@@ -47,6 +99,516 @@ describe('WASM Storage Core', () => {
             
             expect(storage.getAllState()).toEqual({ b: 2 });
         });
+
+        it('should enumerate keys, values and entries', () => {
+            storage.setState('cart:1', 'apple');
+            storage.setState('cart:2', 'pear');
+            storage.setState('user', 'john');
+
+            expect(storage.keys().sort()).toEqual(['cart:1', 'cart:2', 'user']);
+            expect(storage.values().sort()).toEqual(['apple', 'john', 'pear']);
+            expect(storage.len()).toBe(3);
+            expect(storage.has('user')).toBe(true);
+            expect(storage.has('missing')).toBe(false);
+            expect(storage.entries('cart:').sort()).toEqual([['cart:1', 'apple'], ['cart:2', 'pear']]);
+        });
+
+        it('should deep-freeze returned values in strict mode', () => {
+            storage.setState('user', { name: 'Ada', tags: ['admin'] });
+            storage.enableStrictMode();
+            expect(() => { storage.getState('user').name = 'Eve'; }).toThrow(TypeError);
+            expect(() => storage.getState('user').tags.push('root')).toThrow(TypeError);
+
+            storage.setState('bytes', new Uint8Array([1, 2]));
+            storage.setState('cart', { items: [] });
+            expect(() => storage.getAllState().cart.items.push(1)).toThrow(TypeError);
+            expect(storage.getState('user')).toEqual({ name: 'Ada', tags: ['admin'] });
+
+            storage.disableStrictMode();
+            const draft = { items: [] };
+            storage.setState('draft', draft);
+            draft.items.push(1);
+            expect(storage.getState('draft').items).toEqual([1]);
+        });
+
+        it('should return copies when cloning on read', () => {
+            storage.setState('user', { name: 'John', tags: ['admin'] });
+
+            const copy = storage.getStateCloned('user');
+            copy.tags.push('owner');
+            expect(storage.getState('user').tags).toEqual(['admin']);
+
+            storage.setCloneOnRead(true);
+            storage.getState('user').name = 'Eve';
+            storage.getAllState().user.tags.length = 0;
+            expect(storage.getState('user')).toEqual({ name: 'John', tags: ['admin'] });
+
+            storage.setState('callback', { run: () => {} });
+            expect(() => storage.getState('callback')).toThrow(expect.objectContaining({ code: 'INVALID_STATE' }));
+            storage.setCloneOnRead(false);
+        });
+
+        it('should clone the values every read returns when cloning on read', () => {
+            storage.setState('user', { name: 'John', tags: ['admin'] });
+            storage.setCloneOnRead(true);
+
+            storage.values()[0].name = 'Eve';
+            storage.entries()[0][1].tags.push('values');
+            storage.query((key) => key === 'user').user.name = 'Eve';
+            storage.query((key) => key === 'user', true)[0][1].name = 'Eve';
+            storage.find((key) => key === 'user')[1].tags.push('find');
+            storage.range().entries[0][1].tags.push('range');
+            storage.getMany(['user']).values.user.name = 'Eve';
+
+            storage.setCloneOnRead(false);
+            expect(storage.getState('user')).toEqual({ name: 'John', tags: ['admin'] });
+            expect(storage.values()[0]).toBe(storage.getState('user'));
+        });
+
+        it('should read and write through the live proxy', () => {
+            const original = { name: 'John', address: { city: 'Oslo' } };
+            storage.setState('user', original);
+            const changes = [];
+            storage.subscribe((event) => changes.push(event.key));
+
+            const state = storage.state;
+            state.user.address.city = 'Bergen';
+            state.theme = 'dark';
+            delete state.theme;
+
+            expect(state.user.address.city).toBe('Bergen');
+            expect(original.address.city).toBe('Oslo');
+            expect('user' in state).toBe(true);
+            expect(Object.keys(state)).toEqual(['user']);
+            expect(changes).toEqual(['user', 'theme', 'theme']);
+        });
+    });
+
+    describe('Key Queries', () => {
+        beforeEach(() => {
+            storage.setState('cart:1', { qty: 1 });
+            storage.setState('cart:2', { qty: 3 });
+            storage.setState('user:7:settings', { theme: 'dark' });
+            storage.setState('user:7:profile', { name: 'Ann' });
+        });
+
+        it('should get entries by prefix', () => {
+            expect(storage.getByPrefix('cart:')).toEqual({
+                'cart:1': { qty: 1 },
+                'cart:2': { qty: 3 }
+            });
+            expect(storage.getByPrefix('cart:', true)).toHaveLength(2);
+        });
+
+        it('should query keys with glob patterns', () => {
+            expect(storage.queryKeys('user:*:settings')).toEqual({
+                'user:7:settings': { theme: 'dark' }
+            });
+            expect(storage.queryKeys('cart:?')).toEqual({
+                'cart:1': { qty: 1 },
+                'cart:2': { qty: 3 }
+            });
+            expect(storage.queryKeys('order:*')).toEqual({});
+        });
+
+        it('should page through sorted key ranges', () => {
+            storage.enableOrderedIndex();
+            storage.setState('post:003', 'c');
+            storage.setState('post:001', 'a');
+            storage.setState('post:002', 'b');
+
+            const first = storage.range('post:', 'post;', 2);
+            expect(first.entries).toEqual([['post:001', 'a'], ['post:002', 'b']]);
+            expect(first.nextCursor).toBe('post:003');
+
+            const second = storage.range(first.nextCursor, 'post;', 2);
+            expect(second.entries).toEqual([['post:003', 'c']]);
+            expect(second.nextCursor).toBeNull();
+        });
+
+        it('should query entries with a filter function', () => {
+            expect(storage.query((key, value) => key.startsWith('cart:') && value.qty > 1)).toEqual({
+                'cart:2': { qty: 3 }
+            });
+            expect(storage.find((key, value) => value.theme === 'dark')).toEqual([
+                'user:7:settings', { theme: 'dark' }
+            ]);
+            expect(storage.find(() => false)).toBeNull();
+        });
+    });
+
+    describe('Bulk Import', () => {
+        it('should import JSON in chunks and report progress', async () => {
+            const doc = {};
+            for (let i = 0; i < 25; i++) {
+                doc[`item_${i}`] = i;
+            }
+
+            const progress = [];
+            const imported = await storage.importJson(JSON.stringify(doc), {
+                chunkSize: 10,
+                onProgress: (p) => progress.push(p)
+            });
+
+            expect(imported).toBe(25);
+            expect(storage.getState('item_24')).toBe(24);
+            expect(progress.map(p => p.processed)).toEqual([10, 20, 25]);
+            expect(progress[2].percentage).toBe(100);
+        });
+
+        it('should restore a snapshot object', async () => {
+            await storage.restore({ a: 1, b: { nested: true } });
+            expect(storage.getAllState()).toEqual({ a: 1, b: { nested: true } });
+        });
+
+        it('should reject non-object documents', () => {
+            expect(() => storage.importJson('42')).toThrow();
+        });
+    });
+
+    describe('Incremental Export', () => {
+        let backup;
+
+        beforeEach(async () => {
+            const { storage: backupStorage } = await createWasmStorage('../pkg/wasm_storage.js');
+            backup = backupStorage;
+        });
+
+        it('should export only changes since a sequence number', () => {
+            storage.setState('a', 1);
+            storage.setState('b', 2);
+            const first = JSON.parse(storage.exportIncremental(0));
+            expect(first.ops.map(op => op.key)).toEqual(['a', 'b']);
+
+            storage.setState('b', 3);
+            storage.remove('a');
+            const second = JSON.parse(storage.exportIncremental(first.to));
+            expect(second.ops).toEqual([
+                { seq: expect.any(Number), key: 'b', op: 'set', value: 3 },
+                { seq: expect.any(Number), key: 'a', op: 'remove' }
+            ]);
+        });
+
+        it('should apply streams in order on another store', () => {
+            storage.setState('a', 1);
+            const first = storage.exportIncremental(0);
+            storage.setState('b', 2);
+            const second = storage.exportIncremental(JSON.parse(first).to);
+
+            expect(() => backup.importIncremental(second)).toThrow();
+            backup.importIncremental(first);
+            backup.importIncremental(second);
+
+            expect(backup.getAllState()).toEqual({ a: 1, b: 2 });
+        });
+
+        it('should reject tampered streams', () => {
+            storage.setState('role', 'user');
+            const stream = JSON.parse(storage.exportIncremental(0));
+            stream.ops[0].value = 'admin';
+
+            expect(() => backup.importIncremental(JSON.stringify(stream))).toThrow();
+        });
+    });
+
+    describe('Transient State', () => {
+        it('should drop transient keys when their scope ends', () => {
+            storage.setTransient('toast', 'Saved!', 'navigation');
+            storage.setTransient('scrollY', 420, 'visibility');
+            expect(storage.isTransient('toast')).toBe(true);
+
+            storage.endTransientScope('navigation');
+
+            expect(storage.getState('toast')).toBeNull();
+            expect(storage.getState('scrollY')).toBe(420);
+        });
+
+        it('should make a key durable again when set normally', () => {
+            storage.setTransient('banner', 'hello', 'session');
+            storage.setState('banner', 'pinned');
+
+            storage.endTransientScope('session');
+
+            expect(storage.isTransient('banner')).toBe(false);
+            expect(storage.getState('banner')).toBe('pinned');
+        });
+
+        it('should reject unknown scopes', () => {
+            expect(() => storage.setTransient('x', 1, 'forever')).toThrow();
+        });
+    });
+
+    describe('Dirty Tracking', () => {
+        it('should report keys changed since the baseline', () => {
+            storage.setState('title', 'Draft');
+            storage.trackDirty();
+            expect(storage.isDirty()).toBe(false);
+
+            storage.setState('title', 'Final');
+            storage.setState('body', 'text');
+            expect(storage.dirtyKeys().sort()).toEqual(['body', 'title']);
+
+            storage.setState('title', 'Draft');
+            storage.remove('body');
+            expect(storage.isDirty()).toBe(false);
+        });
+
+        it('should reset the baseline with markClean', () => {
+            storage.trackDirty({ count: 0 });
+            storage.setState('count', 1);
+            expect(storage.dirtyKeys()).toEqual(['count']);
+
+            storage.markClean();
+            expect(storage.isDirty()).toBe(false);
+        });
+    });
+
+    describe('Capabilities', () => {
+        it('should report environment support and feature status', () => {
+            const { environment, features } = storage.capabilities();
+
+            expect(typeof environment.localStorage).toBe('boolean');
+            expect(typeof environment.cryptoSubtle).toBe('boolean');
+            expect(environment.queueMicrotask).toBe(true);
+            expect(features.batching).toBe('enabled');
+            expect(['enabled', 'degraded', 'unavailable']).toContain(features.frameDelivery);
+        });
+    });
+
+    describe('Watchers', () => {
+        it('should only notify when the comparator reports a significant change', () => {
+            const crossings = [];
+            storage.setState('temperature', 18);
+            storage.watch('temperature', (previous, value) => (previous >= 25) !== (value >= 25),
+                (value, previous) => crossings.push([previous, value]));
+
+            storage.setState('temperature', 22);
+            storage.setState('temperature', 26);
+            storage.setState('temperature', 27);
+            storage.setState('temperature', 20);
+            storage.setState('humidity', 90);
+
+            expect(crossings).toEqual([[18, 26], [26, 20]]);
+        });
+
+        it('should notify on every change without a comparator until unsubscribed', () => {
+            const values = [];
+            const watcher = storage.watch('count', null, (value) => values.push(value));
+
+            storage.setState('count', 1);
+            watcher.unsubscribe();
+            storage.setState('count', 2);
+
+            expect(values).toEqual([1]);
+        });
+
+        it('should resolve once and waitFor promises, then unsubscribe', async () => {
+            const next = storage.once('count');
+            const large = storage.once('count', (value) => value > 10);
+            const token = storage.waitFor('token');
+            storage.setState('ready', true);
+            const ready = storage.waitFor('ready');
+
+            storage.setState('count', 5);
+            storage.setState('count', 12);
+            storage.setState('count', 20);
+            storage.setState('token', 'abc');
+
+            await expect(next).resolves.toBe(5);
+            await expect(large).resolves.toBe(12);
+            await expect(token).resolves.toBe('abc');
+            await expect(ready).resolves.toBe(true);
+
+            const failing = storage.once('count', () => { throw new Error('bad predicate'); });
+            storage.setState('count', 21);
+            await expect(failing).rejects.toThrow('bad predicate');
+        });
+
+        it('should call immediate subscribers with the current value first', () => {
+            storage.setState('b', 2);
+            storage.setState('a', 1);
+            const events = [];
+            const watched = [];
+            const selected = [];
+
+            storage.subscribe((event) => events.push([event.key, event.value, event.origin]), { immediate: true });
+            storage.watch('a', null, (value, previous) => watched.push([value, previous]), { immediate: true });
+            storage.subscribeWithSelector((state) => state.a, (value, previous) => selected.push([value, previous]), { immediate: true });
+            storage.setState('a', 3);
+
+            expect(events).toEqual([['a', 1, 'system'], ['b', 2, 'system'], ['a', 3, 'local']]);
+            expect(watched).toEqual([[1, null], [3, 1]]);
+            expect(selected).toEqual([[1, undefined], [3, 1]]);
+        });
+    });
+
+    describe('Keyspace Stats', () => {
+        it('should count reads and writes per key, hottest first', () => {
+            storage.trackKeyspace(true);
+            storage.setState('hot', 1);
+            storage.setState('hot', 2);
+            storage.getState('hot');
+            storage.setState('cold', 0);
+
+            const stats = storage.keyspaceStats();
+
+            expect(stats.map(s => s.key)).toEqual(['hot', 'cold']);
+            expect(stats[0]).toMatchObject({ reads: 1, writes: 2, present: true });
+            expect(stats[0].lastAccessAt).toBeGreaterThan(0);
+            expect(stats[1].lastReadAt).toBeNull();
+        });
+
+        it('should throw when tracking is not enabled', () => {
+            expect(() => storage.keyspaceStats()).toThrow();
+        });
+    });
+
+    describe('Store Statistics', () => {
+        it('should report the store internals', () => {
+            storage.setState('a', 'xy');
+            storage.setState('bb', { n: 1 });
+            storage.subscribe(() => {});
+            storage.addMiddleware((action) => action);
+
+            expect(storage.getStats()).toEqual({
+                entries: 2,
+                estimatedBytes: (1 + 4 + 2 + 7) * 2,
+                listeners: 1,
+                middleware: 1,
+                historyDepth: null,
+                hydration: 'none',
+                pendingFlushes: 0,
+            });
+        });
+    });
+
+    describe('Performance Metrics', () => {
+        it('should time dispatches and count writes and notified listeners', () => {
+            const events = [];
+            storage.enableMetrics((event) => events.push(event));
+            storage.addMiddleware((action) => action);
+            storage.subscribe(() => {});
+            storage.subscribe(() => {});
+
+            storage.dispatch('SET_STATE', { a: 1, b: 2 });
+            storage.dispatch('SET_STATE', { a: 3 });
+            storage.setState('b', 4);
+
+            const metrics = storage.getMetrics();
+            expect(metrics.actions.SET_STATE).toEqual(expect.objectContaining({ count: 2, listenersNotified: 6 }));
+            expect(metrics.actions.SET_STATE.maxMs).toBeGreaterThanOrEqual(metrics.actions.SET_STATE.averageMs);
+            expect(metrics.writes).toEqual({ a: 2, b: 2 });
+            expect(metrics.listenersNotified).toBe(8);
+            expect(events.map(({ type, action, listenersNotified }) => [type, action, listenersNotified]))
+                .toEqual([['dispatch', 'SET_STATE', 4], ['dispatch', 'SET_STATE', 2]]);
+
+            storage.resetMetrics();
+            expect(storage.getMetrics().actions).toEqual({});
+            storage.disableMetrics();
+            expect(() => storage.getMetrics()).toThrow(expect.objectContaining({ code: 'INVALID_STATE' }));
+        });
+
+        it('should add performance measures named by action type', () => {
+            performance.clearMeasures();
+            storage.setPerformanceMarks(true);
+            storage.subscribe(() => {});
+
+            storage.dispatch('SET_STATE', { a: 1 });
+            storage.setState('b', 2);
+            storage.setPerformanceMarks(false);
+            storage.dispatch('SET_STATE', { a: 2 });
+
+            const names = performance.getEntriesByType('measure').map(({ name }) => name).filter((name) => name.startsWith('wasm-storage:'));
+            expect(names).toEqual([
+                'wasm-storage:middleware:SET_STATE',
+                'wasm-storage:notify:SET_STATE',
+                'wasm-storage:dispatch:SET_STATE',
+                'wasm-storage:notify',
+            ]);
+            expect(performance.getEntriesByType('mark').filter(({ name }) => name.startsWith('wasm-storage:'))).toEqual([]);
+        });
+    });
+
+    describe('Hot Module Replacement', () => {
+        it('should resume preserved state in a new store', async () => {
+            storage.setState('form.name', 'Ada');
+            storage.preserveForHmr({ slot: 'hmr-test', history: true });
+            const seq = storage.currentSeq();
+
+            const { storage: resumed } = await createWasmStorage('../pkg/wasm_storage.js', { hmr: 'hmr-test' });
+
+            expect(resumed.getState('form.name')).toBe('Ada');
+            expect(resumed.currentSeq()).toBe(seq);
+        });
+
+        it('should start empty when nothing was preserved', async () => {
+            const { storage: fresh } = await createWasmStorage('../pkg/wasm_storage.js', { hmr: 'missing-slot' });
+            expect(fresh.getAllState()).toEqual({});
+        });
+    });
+
+    describe('Computed Keys', () => {
+        it('should recompute derived values when a dependency changes', () => {
+            storage.setState('cart.items', [{ price: 2 }, { price: 3 }]);
+            storage.setState('cart.discount', 1);
+            storage.defineComputed('cart.total', ['cart.items', 'cart.discount'],
+                (items, discount) => items.reduce((sum, item) => sum + item.price, 0) - (discount || 0));
+            expect(storage.getState('cart.total')).toBe(4);
+
+            const events = [];
+            storage.subscribe((event) => events.push(event));
+            storage.setState('cart.discount', 0);
+
+            expect(storage.getState('cart.total')).toBe(5);
+            expect(events.filter(e => e.key === 'cart.total').map(e => e.value)).toEqual([5]);
+        });
+
+        it('should chain computed keys and reject cycles', () => {
+            storage.setState('n', 2);
+            storage.defineComputed('double', ['n'], (n) => n * 2);
+            storage.defineComputed('quadruple', ['double'], (d) => d * 2);
+
+            storage.setState('n', 3);
+            expect(storage.getState('quadruple')).toBe(12);
+            expect(() => storage.defineComputed('double', ['quadruple'], (q) => q)).toThrow();
+        });
+    });
+
+    describe('Key Dependencies', () => {
+        it('should clear dependents transitively when the parent changes', () => {
+            storage.dependsOn('user.id', ['user.permissions']);
+            storage.dependsOn('user.permissions', ['user.menu']);
+            storage.setState('user.permissions', ['read']);
+            storage.setState('user.menu', ['home']);
+
+            storage.setState('user.id', 42);
+
+            expect(storage.has('user.permissions')).toBe(false);
+            expect(storage.has('user.menu')).toBe(false);
+        });
+
+        it('should refetch or invalidate dependents', async () => {
+            storage.dependsOn('user.id', ['user.avatarUrl'], {
+                refetch: async (key, id) => `/avatars/${id}.png`
+            });
+            storage.dependsOn('user.id', ['user.profile'], { strategy: 'invalidate' });
+            storage.setState('user.profile', { name: 'Ada' });
+
+            storage.setState('user.id', 7);
+            await new Promise(resolve => setTimeout(resolve, 0));
+
+            expect(storage.getState('user.avatarUrl')).toBe('/avatars/7.png');
+            expect(storage.isInvalidated('user.profile')).toBe(true);
+            expect(storage.getState('user.profile')).toEqual({ name: 'Ada' });
+        });
+
+        it('should reject dependency cycles', () => {
+            storage.dependsOn('a', ['b']);
+            storage.dependsOn('b', ['c']);
+
+            expect(() => storage.dependsOn('c', ['a'])).toThrow();
+            expect(() => storage.dependsOn('a', ['a'])).toThrow();
+        });
     });
 
     describe('Action Dispatch', () => {
@@ -61,43 +623,1188 @@ describe('WASM Storage Core', () => {
             storage.dispatch('TEST_ACTION', { data: 'test' });
             
             expect(receivedAction).toEqual({
+                id: expect.any(String),
                 type: 'TEST_ACTION',
                 payload: { data: 'test' },
                 timestamp: expect.any(Number)
             });
         });
 
-        it('should handle built-in actions', () => {
-            storage.dispatch('SET_STATE', { count: 5, name: 'test' });
-            
-            expect(storage.getState('count')).toBe(5);
-            expect(storage.getState('name')).toBe('test');
+        it('should queue dispatches made while another dispatch runs', () => {
+            const heard = [];
+            let nested, greetingDuringListener;
+            storage.subscribe((event) => {
+                heard.push(`a:${event.key}`);
+                if (event.key === 'user') {
+                    nested = storage.dispatch('SET_STATE', { greeting: `hi ${event.value}` });
+                    greetingDuringListener = storage.getState('greeting');
+                }
+            });
+            storage.subscribe((event) => heard.push(`b:${event.key}`));
+
+            storage.dispatch('SET_STATE', { user: 'ada' });
+            expect(nested.payload).toEqual({ greeting: 'hi ada' });
+            expect(greetingDuringListener).toBeNull();
+            expect(heard).toEqual(['a:user', 'b:user', 'a:greeting', 'b:greeting']);
+            expect(storage.getState('greeting')).toBe('hi ada');
+        });
+
+        it('should hand unhandled actions to the hook instead of writing state', () => {
+            const unhandled = [];
+            storage.onUnhandledAction((action) => unhandled.push(action));
+            storage.dispatch('TYPO_ACTION', { id: 1 });
+            expect(unhandled).toEqual([{ type: 'TYPO_ACTION', payload: { id: 1 }, timestamp: expect.any(Number) }]);
+            expect(storage.keys()).toEqual([]);
+
+            storage.setUnhandledActions('error');
+            expect(() => storage.dispatch('TYPO_ACTION', {})).toThrow(expect.objectContaining({ code: 'INVALID_ARGUMENT' }));
+
+            storage.setUnhandledActions('legacy');
+            storage.dispatch('PING', 7);
+            expect(storage.getState('__actions_PING')).toBe(7);
+        });
+
+        it('should reject unregistered action types in strict mode', () => {
+            const types = storage.registerActionTypes(['ADD_TODO']);
+            expect(types.ADD_TODO).toBe('ADD_TODO');
+            expect(storage.actionTypes).toEqual({ SET_STATE: 'SET_STATE', REMOVE_STATE: 'REMOVE_STATE', CLEAR_STATE: 'CLEAR_STATE', ADD_TODO: 'ADD_TODO' });
+            expect(Object.isFrozen(types)).toBe(true);
+
+            storage.dispatch('ADD_TODOO', {});
+            storage.setStrictActionTypes(true);
+            expect(() => storage.dispatch('ADD_TODOO', {})).toThrow(expect.objectContaining({ code: 'INVALID_ARGUMENT' }));
+            storage.dispatch(types.ADD_TODO, { title: 'x' });
+            storage.dispatch(types.SET_STATE, { count: 1 });
+            expect(storage.getState('count')).toBe(1);
+        });
+
+        it('should report the keys a dispatch changed', () => {
+            storage.setState('user', 'ada');
+            storage.setState('theme', 'dark');
+
+            const event = storage.dispatchWithChanges('SET_STATE', { theme: 'light', count: 1 });
+            expect(event.changes).toEqual({ count: 1, theme: 'light' });
+            expect(event.toJSON().changes).toEqual({ count: 1, theme: 'light' });
+            expect(storage.dispatchWithChanges('REMOVE_STATE', 'user').changes).toEqual({ user: null });
+            expect(storage.dispatch('SET_STATE', { count: 2 }).changes).toBeUndefined();
+        });
+
+        it('should drop superseded async dispatches', async () => {
+            const signals = [];
+            const search = (query, ms) => storage.dispatchAsync('SET_STATE', (signal) => {
+                signals.push(signal);
+                return new Promise(resolve => setTimeout(() => resolve({ results: query }), ms));
+            }, { key: 'search' });
+
+            const stale = search('wa', 20);
+            const fresh = search('wasm', 5);
+            expect(signals[0].aborted).toBe(true);
+            expect(storage.pendingAsync()).toBe(1);
+            expect((await fresh).payload).toEqual({ results: 'wasm' });
+            expect(await stale).toBeNull();
+            await new Promise(resolve => setTimeout(resolve, 30));
+            expect(storage.getState('results')).toBe('wasm');
+
+            const cancelled = search('again', 5);
+            expect(storage.cancelAsync('search')).toBe(1);
+            expect(await cancelled).toBeNull();
+            expect(storage.getState('results')).toBe('wasm');
+        });
+
+        it('should follow an external signal until the dispatch settles', async () => {
+            const external = new AbortController();
+            // A producer that only settles by rejecting once it's aborted
+            const pending = signal => new Promise((_, reject) => signal.addEventListener('abort', () => reject(signal.reason)));
+            let removed = vi.spyOn(external.signal, 'removeEventListener');
+            expect((await storage.dispatchAsync('SET_STATE', () => ({ loaded: 1 }), { signal: external.signal })).payload).toEqual({ loaded: 1 });
+            expect(removed).toHaveBeenCalledWith('abort', expect.any(Function));
+
+            // Superseded dispatches stop listening as well
+            removed.mockRestore();
+            removed = vi.spyOn(external.signal, 'removeEventListener');
+            const stale = storage.dispatchAsync('SET_STATE', pending, { key: 'load', signal: external.signal });
+            const fresh = storage.dispatchAsync('SET_STATE', () => ({ loaded: 2 }), { key: 'load' });
+            expect(removed).toHaveBeenCalledTimes(1);
+            expect(await stale).toBeNull();
+            await fresh;
+
+            const aborted = storage.dispatchAsync('SET_STATE', pending, { signal: external.signal });
+            external.abort();
+            expect(await aborted).toBeNull();
+            expect(removed).toHaveBeenCalledTimes(2);
+            expect(storage.getState('loaded')).toBe(2);
+            removed.mockRestore();
+        });
+
+        it('should dispatch after a delay and on an interval until cancelled', async () => {
+            const wait = (ms) => new Promise(resolve => setTimeout(resolve, ms));
+            let ticks = 0;
+            storage.onUnhandledAction(() => ticks++);
+
+            const once = storage.dispatchAfter(10, 'SET_STATE', { done: true });
+            const cancelled = storage.dispatchAfter(10, 'SET_STATE', { cancelled: true });
+            cancelled.cancel();
+            expect(once.active).toBe(true);
+            await wait(30);
+            expect(storage.getState('done')).toBe(true);
+            expect(storage.getState('cancelled')).toBeNull();
+            expect(once.active).toBe(false);
+
+            const ticker = storage.dispatchInterval(10, 'tick', 1);
+            await wait(55);
+            ticker.cancel();
+            const counted = ticks;
+            expect(counted).toBeGreaterThanOrEqual(2);
+            await wait(30);
+            expect(ticks).toBe(counted);
+            expect(() => storage.dispatchInterval(0, 'tick', 1)).toThrow(expect.objectContaining({ code: 'INVALID_ARGUMENT' }));
+        });
+
+        it('should return the dispatched event with the processed payload', () => {
+            storage.addMiddleware((action) => ({ ...action, payload: { ...action.payload, seen: true } }));
+
+            const event = storage.dispatch('TEST_ACTION', { data: 'test' });
+            expect(event.action_type).toBe('TEST_ACTION');
+            expect(event.payload).toEqual({ data: 'test', seen: true });
+            expect(event.timestamp).toEqual(expect.any(Number));
+            expect(JSON.parse(JSON.stringify(event))).toEqual({
+                type: 'TEST_ACTION',
+                payload: { data: 'test', seen: true },
+                timestamp: event.timestamp
+            });
+        });
+
+        it('should log actions with the changed keys, gated by the log level', () => {
+            const entries = [];
+            const log = vi.spyOn(console, 'log');
+            storage.setLogger((entry) => entries.push(entry));
+            storage.setState('count', 1);
+            storage.enableLogger({ collapsed: true, diff: true });
+
+            storage.dispatch('SET_STATE', { count: 2, label: 'two' });
+            const [entry] = entries.filter(({ data }) => data);
+            expect(entry.level).toBe('info');
+            expect(entry.message).toMatch(/^action SET_STATE \([\d.]+ ms\)$/);
+            expect(entry.data.diff).toEqual([['added', 'label', undefined, 'two'], ['changed', 'count', 1, 2]]);
+            expect(entry.data.prev).toEqual({ count: 1, label: undefined });
+
+            entries.length = 0;
+            storage.setLogLevel('warn');
+            storage.dispatch('SET_STATE', { count: 3 });
+            expect(entries).toEqual([]);
+            expect(log).not.toHaveBeenCalled();
+
+            storage.setLogLevel('info');
+            storage.setLogger(null);
+            storage.disableLogger();
+            vi.restoreAllMocks();
+        });
+
+        it('should enforce access policies on reads and writes', () => {
+            storage.setState('secrets.apiKey', 'k');
+            storage.setState('profile.name', 'Ada');
+            storage.setAccessPolicy('secrets.', { read: false, write: false });
+            storage.setAccessPolicy('profile.', { write: (key, value) => value !== 'root' });
+
+            expect(() => storage.getState('secrets.apiKey')).toThrow(expect.objectContaining({ code: 'ACCESS_DENIED' }));
+            expect(storage.getAllState()).toEqual({ 'profile.name': 'Ada' });
+            expect(() => storage.dispatch('SET_STATE', { 'secrets.apiKey': 'x' })).toThrow(expect.objectContaining({ code: 'ACCESS_DENIED' }));
+            expect(() => storage.setState('profile.name', 'root')).toThrow(expect.objectContaining({ code: 'ACCESS_DENIED' }));
+            expect(() => storage.clear()).toThrow(expect.objectContaining({ code: 'ACCESS_DENIED' }));
+            storage.setState('profile.name', 'Grace');
+
+            storage.setAccessPolicy('secrets.', null);
+            expect(storage.getState('secrets.apiKey')).toBe('k');
+        });
+
+        it('should hide denied keys from every enumeration, query and stream', () => {
+            storage.setState('secrets.apiKey', 'k');
+            storage.setState('profile.name', 'Ada');
+            storage.setAccessPolicy('secrets.', { read: false });
+            const visible = [['profile.name', 'Ada']];
+
+            expect(storage.keys()).toEqual(['profile.name']);
+            expect(storage.values()).toEqual(['Ada']);
+            expect(storage.entries()).toEqual(visible);
+            expect(storage.entries('secrets.')).toEqual([]);
+            expect(storage.has('secrets.apiKey')).toBe(false);
+            expect(storage.len()).toBe(1);
+            expect(storage.getByPrefix('')).toEqual({ 'profile.name': 'Ada' });
+            expect(storage.queryKeys('*')).toEqual({ 'profile.name': 'Ada' });
+            expect(storage.query(() => true, true)).toEqual(visible);
+            expect(storage.find((key) => key.startsWith('secrets.'))).toBeNull();
+            expect(storage.range().entries).toEqual(visible);
+            expect(storage.createView('secrets.').keys()).toEqual([]);
+            expect(JSON.parse(storage.exportIncremental(0)).ops.map(op => op.key)).toEqual(['profile.name']);
+            expect(() => storage.getStateCloned('secrets.apiKey')).toThrow(expect.objectContaining({ code: 'ACCESS_DENIED' }));
+            expect(() => storage.getString('secrets.apiKey')).toThrow(expect.objectContaining({ code: 'ACCESS_DENIED' }));
+            expect(() => storage.watch('secrets.apiKey', null, () => {})).toThrow(expect.objectContaining({ code: 'ACCESS_DENIED' }));
+            storage.startRecording();
+            expect(JSON.parse(JSON.stringify(storage.stopRecording())).initialState).toEqual({ 'profile.name': 'Ada' });
+
+            const heard = [];
+            const batches = [];
+            storage.subscribe((event) => heard.push(event.key), { immediate: true });
+            storage.subscribe((event) => batches.push(event.changes.map(change => change.key)), { aggregate: true });
+            storage.setState('secrets.apiKey', 'rotated');
+            storage.setState('profile.name', 'Grace');
+            expect(heard).toEqual(['profile.name', 'profile.name']);
+            expect(batches).toEqual([['profile.name']]);
+        });
+
+        it('should scope read-only views to their keys', () => {
+            storage.setState('theme.mode', 'dark');
+            storage.setState('secret', 'token');
+            const view = storage.createView('theme.');
+            expect(view.getAll()).toEqual({ 'theme.mode': 'dark' });
+            expect(view.has('secret')).toBe(false);
+            expect(() => view.get('secret')).toThrow(expect.objectContaining({ code: 'INVALID_ARGUMENT' }));
+            expect(view.setState).toBeUndefined();
+
+            const events = [];
+            view.subscribe((event) => events.push(event.key));
+            storage.setState('secret', 'other');
+            storage.setState('theme.accent', '#08f');
+            expect(events).toEqual(['theme.accent']);
+
+            const listed = storage.createView(['secret']);
+            expect(listed.keys()).toEqual(['secret']);
+        });
+
+        it('should fork a scratch store and merge its changes back', () => {
+            storage.setState('theme', 'light');
+            storage.setState('fontSize', 12);
+            const draft = storage.fork();
+            draft.setState('theme', 'dark');
+            draft.remove('fontSize');
+            expect(storage.getState('theme')).toBe('light');
+
+            storage.setState('theme', 'system');
+            expect(() => storage.mergeFrom(draft, 'fail')).toThrow(expect.objectContaining({ code: 'INVALID_STATE' }));
+            expect(storage.mergeFrom(draft, 'ours')).toEqual(['fontSize']);
+            expect(storage.getState('theme')).toBe('system');
+            expect(storage.getState('fontSize')).toBeNull();
+
+            draft.setState('theme', 'dark2');
+            expect(storage.mergeFrom(draft)).toEqual(['theme']);
+            expect(storage.getState('theme')).toBe('dark2');
+        });
+
+        it('should share named stores across callers', async () => {
+            const { storage: first } = await createWasmStorage('../pkg/wasm_storage.js', { name: 'auth' });
+            const { storage: second } = await createWasmStorage('../pkg/wasm_storage.js', { name: 'auth' });
+            first.setState('token', 'abc');
+            expect(second.getState('token')).toBe('abc');
+            expect(second.name).toBe('auth');
+            expect(first.listInstances()).toContain('auth');
+            expect(storage.name).toBeNull();
+
+            first.destroy();
+            expect(first.listInstances()).not.toContain('auth');
+            const { storage: third } = await createWasmStorage('../pkg/wasm_storage.js', { name: 'auth' });
+            expect(third.getState('token')).toBeNull();
+            third.destroy();
+        });
+
+        it('should tag errors with the store ID once diagnostics are on', () => {
+            const entries = [];
+            storage.setLogger((entry) => entries.push(entry));
+            storage.initDiagnostics({ verbose: true });
+            storage.clearState();
+            expect(entries).toContainEqual({ level: 'debug', message: 'State cleared' });
+
+            storage.freeze();
+            let thrown;
+            try {
+                storage.setState('count', 1);
+            } catch (error) {
+                thrown = error;
+            }
+            expect(thrown.context.storeId).toBe(storage.instanceId);
+            expect(storage.instanceId).toBe(storage.instanceId);
+
+            storage.setLogLevel('info');
+            storage.setLogger(null);
+        });
+
+        it('should order, toggle and remove middleware', () => {
+            const calls = [];
+            const track = (label) => (action) => { calls.push(label); return action; };
+            const first = storage.addMiddleware(track('first'));
+            storage.addMiddleware(track('logger'), { name: 'logger' });
+            storage.insertMiddleware(0, track('inserted'));
+            storage.addMiddleware(track('auth'), { priority: 10 });
+
+            storage.dispatch('PING', null);
+            expect(calls).toEqual(['auth', 'inserted', 'first', 'logger']);
+
+            calls.length = 0;
+            storage.setMiddlewareEnabled('logger', false);
+            expect(storage.removeMiddleware(first)).toBe(true);
+            expect(storage.removeMiddleware(first)).toBe(false);
+            storage.dispatch('PING', null);
+            expect(calls).toEqual(['auth', 'inserted']);
+
+            calls.length = 0;
+            storage.addMiddleware(track('new logger'), { name: 'logger' });
+            storage.dispatch('PING', null);
+            expect(calls).toEqual(['auth', 'inserted', 'new logger']);
+            expect(storage.listMiddleware().map(({ name, priority }) => [name, priority])).toEqual([[null, 10], [null, 0], ['logger', 0]]);
+        });
+
+        it('should assign action IDs from the configured generator', () => {
+            let counter = 0;
+            storage.setIdGenerator((scope) => `${scope}-${++counter}`);
+
+            const ids = [];
+            storage.addMiddleware((action) => {
+                ids.push(action.id);
+                return action;
+            });
+
+            storage.dispatch('FIRST', null);
+            storage.dispatch('SECOND', null);
+
+            expect(ids).toEqual(['action-1', 'action-2']);
+        });
+
+        it('should issue subscription and entity IDs from the configured generator', () => {
+            let counter = 0;
+            storage.setIdGenerator((scope) => `${scope}-${++counter}`);
+
+            const heard = [];
+            const subscription = storage.subscribe((event) => heard.push(event.key));
+            expect(subscription.id).toBe('subscription-1');
+
+            const id = storage.addEntity('todos', { title: 'Write tests' });
+            expect(id).toBe('entity-2');
+            expect(storage.getState('todos:entity-2')).toEqual({ title: 'Write tests', id: 'entity-2' });
+            expect(storage.getEntities('todos')).toEqual({ 'entity-2': { title: 'Write tests', id: 'entity-2' } });
+
+            storage.unsubscribe(subscription.id);
+            storage.removeEntity('todos', id);
+            expect(heard).toEqual(['todos:entity-2']);
+            expect(storage.getEntities('todos')).toEqual({});
+        });
+
+        it('should read timestamps from the configured time provider', () => {
+            let now = 1000;
+            storage.setTimeProvider(() => now);
+            const timestamps = [];
+            storage.addMiddleware((action) => {
+                timestamps.push(action.timestamp);
+                return action;
+            });
+            storage.subscribe(event => timestamps.push(event.timestamp));
+
+            storage.dispatch('SET_STATE', { count: 1 });
+            now = 2000;
+            storage.setState('count', 2);
+            expect(timestamps).toEqual([1000, 1000, 2000]);
+            expect(storage.now()).toBe(2000);
+
+            storage.setTimeProvider(() => 'soon');
+            expect(() => storage.dispatch('TICK', null)).toThrow(expect.objectContaining({ code: 'INVALID_ARGUMENT' }));
+            storage.setTimeProvider(null);
+            expect(storage.now()).toBeGreaterThan(2000);
+        });
+
+        it('should record a session as a fixture and replay it', async () => {
+            storage.registerReducer('ADD_TODO', 'merge todos = payload');
+            storage.setState('filter', 'all');
+            storage.startRecording();
+            storage.dispatch('ADD_TODO', { a: 'Write report' });
+            storage.dispatch('SET_STATE', { filter: 'open' });
+            const fixture = JSON.parse(JSON.stringify(storage.stopRecording()));
+            expect(fixture.initialState).toEqual({ filter: 'all' });
+            expect(fixture.actions.map(action => action.type)).toEqual(['ADD_TODO', 'SET_STATE']);
+            expect(storage.isRecording()).toBe(false);
+
+            const { storage: replica } = await createWasmStorage('../pkg/wasm_storage.js');
+            replica.registerReducer('ADD_TODO', 'merge todos = payload');
+            replica.setState('stale', true);
+            expect(replica.loadFixture(JSON.stringify(fixture))).toBe(2);
+            expect(replica.getAllState()).toEqual(storage.getAllState());
+            expect(() => replica.loadFixture({ version: 1, initialState: {}, actions: [{}] }))
+                .toThrow(expect.objectContaining({ code: 'INVALID_ARGUMENT' }));
+        });
+
+        it('should generate UUID, ULID and nanoid IDs', () => {
+            expect(storage.generateId('test')).toMatch(/^[0-9a-f]{8}-[0-9a-f]{4}-4[0-9a-f]{3}-[89ab][0-9a-f]{3}-[0-9a-f]{12}$/);
+
+            storage.setIdGenerator('ulid');
+            expect(storage.generateId('test')).toMatch(/^[0-9A-HJKMNP-TV-Z]{26}$/);
+
+            storage.setIdGenerator('nanoid');
+            expect(storage.generateId('test')).toMatch(/^[A-Za-z0-9_-]{21}$/);
+        });
+
+        it('should roll back a rejected optimistic action', async () => {
+            storage.setState('likes', 10);
+            const changes = [];
+            storage.subscribe(event => changes.push([event.key, event.value]));
+
+            let reject;
+            const request = new Promise((_, rejectRequest) => { reject = rejectRequest; });
+            const result = storage.dispatchOptimistic('SET_STATE', { likes: 11, liked: true }, request);
+            expect(storage.getState('likes')).toBe(11);
+            expect(storage.pendingOptimistic()).toBe(1);
+
+            reject(new Error('offline'));
+            await expect(result).rejects.toThrow('offline');
+            expect(storage.getState('likes')).toBe(10);
+            expect(storage.has('liked')).toBe(false);
+            expect(changes).toContainEqual(['likes', 10]);
+            expect(storage.pendingOptimistic()).toBe(0);
+
+            await expect(storage.dispatchOptimistic('SET_STATE', { likes: 12 }, Promise.resolve('ok'))).resolves.toBe('ok');
+            expect(storage.getState('likes')).toBe(12);
+        });
+
+        it('should reject optimistic actions that would be queued', () => {
+            let nested;
+            storage.addMiddleware((action) => {
+                if (action.type === 'outer') {
+                    try {
+                        storage.dispatchOptimistic('SET_STATE', { likes: 11 }, new Promise(() => {}));
+                    } catch (error) {
+                        nested = error;
+                    }
+                }
+                return action;
+            });
+
+            storage.dispatch('outer', null);
+            expect(nested).toEqual(expect.objectContaining({ code: 'INVALID_STATE' }));
+            expect(storage.has('likes')).toBe(false);
+            expect(storage.pendingOptimistic()).toBe(0);
+        });
+
+        it('should reject optimistic actions whose writes a key policy defers', async () => {
+            storage.setState('likes', 10);
+            storage.setKeyPolicy('likes', { debounceMs: 20 });
+
+            expect(() => storage.dispatchOptimistic('SET_STATE', { liked: true, likes: 11 }, new Promise(() => {})))
+                .toThrow(expect.objectContaining({ code: 'INVALID_STATE', context: expect.objectContaining({ key: 'likes' }) }));
+            expect(storage.has('liked')).toBe(false);
+            expect(storage.pendingOptimistic()).toBe(0);
+
+            await new Promise(resolve => setTimeout(resolve, 40));
+            expect(storage.getState('likes')).toBe(10);
+        });
+
+        it('should run effects with takeLatest cancellation', async () => {
+            const responses = [];
+            const signals = [];
+            storage.registerEffect('search/*', async (action, dispatch, getState, signal) => {
+                signals.push(signal);
+                const results = await new Promise(resolve => responses.push(resolve));
+                dispatch('SET_STATE', { results, query: action.payload });
+            });
+
+            storage.dispatch('search/run', 'wa');
+            storage.dispatch('search/run', 'wasm');
+            expect(signals[0].aborted).toBe(true);
+            expect(storage.runningEffects()).toBe(1);
+
+            responses[1](['wasm-storage']);
+            responses[0](['stale']);
+            await new Promise(resolve => setTimeout(resolve, 0));
+            expect(storage.getState('results')).toEqual(['wasm-storage']);
+            expect(storage.getState('query')).toBe('wasm');
+            expect(storage.runningEffects()).toBe(0);
+        });
+
+        it('should drop effects and abort their runs on destroy', async () => {
+            const { storage: store } = await createWasmStorage('../pkg/wasm_storage.js');
+            const signals = [];
+            store.registerEffect('load', (action, dispatch, getState, signal) => {
+                signals.push(signal);
+                return new Promise(() => {});
+            });
+            store.dispatch('load', null);
+            expect(store.runningEffects()).toBe(1);
+
+            store.destroy();
+            expect(signals[0].aborted).toBe(true);
+            expect(store.runningEffects()).toBe(0);
+        });
+
+        it('should handle built-in actions', () => {
+            storage.dispatch('SET_STATE', { count: 5, name: 'test' });
+            
+            expect(storage.getState('count')).toBe(5);
+            expect(storage.getState('name')).toBe('test');
+        });
+    });
+
+    describe('Key Policies', () => {
+        const wait = (ms) => new Promise(resolve => setTimeout(resolve, ms));
+
+        it('should debounce writes to a key', async () => {
+            const values = [];
+            storage.subscribe((event) => values.push(event.value));
+            storage.setKeyPolicy('draft', { debounceMs: 20 });
+
+            storage.setState('draft', 'h');
+            storage.setState('draft', 'he');
+            storage.setState('draft', 'hello');
+            expect(storage.getState('draft')).toBeNull();
+
+            await wait(40);
+            expect(values).toEqual(['hello']);
+            expect(storage.getState('draft')).toBe('hello');
+        });
+
+        it('should throttle writes with leading and trailing commits', async () => {
+            const values = [];
+            storage.subscribe((event) => values.push(event.value));
+            storage.setKeyPolicy('slider', { throttleMs: 20 });
+
+            storage.setState('slider', 1);
+            storage.setState('slider', 2);
+            storage.setState('slider', 3);
+            expect(values).toEqual([1]);
+
+            await wait(40);
+            expect(values).toEqual([1, 3]);
+        });
+
+        it('should commit pending writes with flushKey', () => {
+            storage.setKeyPolicy('draft', { debounceMs: 1000 });
+            storage.setState('draft', 'saved');
+
+            storage.flushKey('draft');
+
+            expect(storage.getState('draft')).toBe('saved');
+        });
+    });
+
+    describe('Lifecycle', () => {
+        it('should commit pending writes and notify destroy hooks on destroy', () => {
+            let destroyCalls = 0;
+            storage.onDestroy(() => destroyCalls++);
+            storage.setKeyPolicy('draft', { debounceMs: 1000 });
+            storage.setState('draft', 'unsaved');
+
+            storage.destroy();
+
+            expect(storage.getState('draft')).toBe('unsaved');
+            expect(destroyCalls).toBe(1);
+            expect(storage.isDestroyed()).toBe(true);
+        });
+
+        it('should reject writes and stop notifying after destroy', () => {
+            const events = [];
+            storage.subscribe((event) => events.push(event));
+
+            storage.destroy();
+
+            expect(() => storage.setState('key', 'value')).toThrow();
+            expect(() => storage.dispatch('ACTION', {})).toThrow();
+            expect(events).toEqual([]);
+        });
+
+        it('should run init hooks once a restore completes', async () => {
+            const sources = [];
+            storage.onInit((event) => sources.push(event.source));
+
+            await storage.restore({ a: 1 });
+            storage.onInit((event) => sources.push(event.phase));
+
+            expect(sources).toEqual(['restore', 'init']);
+        });
+
+        it('should reject local writes while frozen', () => {
+            storage.setState('count', 1);
+            storage.freeze();
+            expect(storage.isFrozen()).toBe(true);
+            expect(() => storage.setState('count', 2)).toThrow(expect.objectContaining({ code: 'FROZEN' }));
+            expect(() => storage.dispatch('SET_STATE', { count: 3 })).toThrow(expect.objectContaining({ code: 'FROZEN' }));
+            storage.runWithOrigin('hydration', () => storage.setState('count', 4));
+            storage.unfreeze();
+            storage.setState('count', 5);
+
+            const view = storage.readOnlyView();
+            expect(view.getState('count')).toBe(5);
+            expect(view.remove).toBeUndefined();
+            expect(view.unfreeze).toBeUndefined();
+            storage.setState('count', 6);
+            expect(view.getState('count')).toBe(6);
+        });
+
+        it('should give read-only views no way to change or destroy the store', () => {
+            const view = storage.readOnlyView();
+            const raw = view.view;
+            for (const method of ['add_middleware', 'destroy', 'set_state', 'dispatch', 'set_clone_on_read', 'register_validator', 'unfreeze']) {
+                expect(raw[method]).toBeUndefined();
+            }
+            expect(view.addMiddleware).toBeUndefined();
+            expect(view.destroy).toBeUndefined();
+
+            const heard = [];
+            view.subscribe((event) => heard.push(event.key));
+            storage.setState('count', 1);
+            expect(view.getState('count')).toBe(1);
+            expect(heard).toEqual(['count']);
+            expect(storage.isDestroyed()).toBe(false);
+        });
+    });
+
+    describe('Remote Inbox', () => {
+        it('should apply remote actions by priority when flushed', () => {
+            const applied = [];
+            storage.addMiddleware((action) => { applied.push(action.type); return action; });
+            storage.enableInbox({ priorities: { 'TELEMETRY_*': 'low', 'MESSAGE_*': 'high' } });
+
+            storage.receiveRemote('TELEMETRY_PING', {});
+            storage.receiveRemote('PROFILE_UPDATED', {});
+            storage.receiveRemote('MESSAGE_RECEIVED', {});
+            expect(applied).toEqual([]);
+            expect(storage.inboxMetrics()).toMatchObject({ queued: 3, high: 1, normal: 1, low: 1 });
+
+            storage.flushInbox();
+            expect(applied).toEqual(['MESSAGE_RECEIVED', 'PROFILE_UPDATED', 'TELEMETRY_PING']);
+            expect(storage.inboxMetrics()).toMatchObject({ queued: 0, processed: 3 });
+        });
+
+        it('should drop the oldest low-priority items when full', () => {
+            storage.enableInbox({ maxQueued: 2, priorities: { 'TELEMETRY_*': 'low' } });
+
+            storage.receiveRemote('TELEMETRY_A', {});
+            storage.receiveRemote('TELEMETRY_B', {});
+            storage.receiveRemote('ORDER_PLACED', {});
+
+            expect(storage.inboxMetrics()).toMatchObject({ queued: 2, dropped: 1, low: 1, normal: 1 });
+        });
+
+        it('should apply batches on animation frames', async () => {
+            storage.enableInbox({ batchSize: 1 });
+            storage.receiveRemote('A', {});
+            storage.receiveRemote('B', {});
+
+            await new Promise(resolve => setTimeout(resolve, 100));
+            expect(storage.inboxMetrics()).toMatchObject({ queued: 0, processed: 2, batches: 2 });
+        });
+    });
+
+    describe('Typed Accessors', () => {
+        it('should return values of the expected type', () => {
+            storage.setState('name', 'Ada');
+            storage.setState('age', 36);
+            storage.setState('admin', false);
+            storage.setState('prefs', { theme: 'dark' });
+
+            expect(storage.getString('name')).toBe('Ada');
+            expect(storage.getNumber('age')).toBe(36);
+            expect(storage.getBool('admin')).toBe(false);
+            expect(storage.getObject('prefs')).toEqual({ theme: 'dark' });
+        });
+
+        it('should throw a structured error naming the key and types', () => {
+            storage.setState('age', '36');
+
+            let error;
+            try {
+                storage.getNumber('age');
+            } catch (e) {
+                error = e;
+            }
+
+            expect(error.code).toBe('TYPE_MISMATCH');
+            expect(error.context).toEqual({ key: 'age', expected: 'number', actual: 'string' });
+            expect(() => storage.getObject('missing')).toThrow();
+        });
+    });
+
+    describe('Errors', () => {
+        const thrown = (fn) => {
+            try {
+                fn();
+            } catch (e) {
+                return e;
+            }
+        };
+
+        it('should throw errors with a stable code and context', () => {
+            storage.registerValidator('age', (value) => value >= 0 || 'must not be negative');
+
+            const invalid = thrown(() => storage.setState('age', -1));
+            expect(invalid.code).toBe('VALIDATION_FAILED');
+            expect(invalid.context).toEqual({ key: 'age', reason: 'must not be negative' });
+
+            const unknown = thrown(() => storage.setTransient('x', 1, 'forever'));
+            expect(unknown.code).toBe('INVALID_ARGUMENT');
+
+            storage.destroy();
+            expect(thrown(() => storage.setState('age', 1)).code).toBe('DESTROYED');
+        });
+    });
+
+    describe('Codecs', () => {
+        it('should revive dates and bytes on incremental import', async () => {
+            const { storage: source } = await createWasmStorage('../pkg/wasm_storage.js');
+            for (const store of [source, storage]) {
+                store.registerCodec('session.started', 'date');
+                store.registerCodec('avatar', 'base64');
+            }
+            source.setState('session.started', new Date('2024-05-01T12:00:00.000Z'));
+            source.setState('avatar', new Uint8Array([1, 2, 255]));
+
+            const stream = source.exportIncremental(0);
+            expect(JSON.parse(stream).ops.find(op => op.key === 'avatar').value).toBe('AQL/');
+
+            storage.importIncremental(stream);
+            expect(storage.getState('session.started')).toBeInstanceOf(Date);
+            expect(storage.getState('session.started').toISOString()).toBe('2024-05-01T12:00:00.000Z');
+            expect(Array.from(storage.getState('avatar'))).toEqual([1, 2, 255]);
+        });
+
+        it('should apply custom encode/decode functions', async () => {
+            const { storage: source } = await createWasmStorage('../pkg/wasm_storage.js');
+            const codec = { encode: (set) => [...set], decode: (items) => new Set(items) };
+            source.registerCodec('tags', codec);
+            storage.registerCodec('tags', codec);
+
+            source.setState('tags', new Set(['a', 'b']));
+            storage.importIncremental(source.exportIncremental(0));
+
+            expect(storage.getState('tags')).toEqual(new Set(['a', 'b']));
+        });
+
+        it('should encode on set and decode on get', () => {
+            const encode = vi.fn((value) => value instanceof Set ? [...value] : value);
+            const decode = vi.fn((value) => Array.isArray(value) ? new Set(value) : value);
+            storage.registerCodec('tags', { encode, decode });
+
+            storage.setState('tags', new Set(['a']));
+            expect(encode).toHaveBeenCalledTimes(1);
+            expect(storage.getAllState().tags).toEqual(['a']);
+            expect(storage.getState('tags')).toEqual(new Set(['a']));
+            expect(storage.getMany(['tags']).values.tags).toEqual(new Set(['a']));
+
+            storage.unregisterCodec('tags');
+            expect(storage.getState('tags')).toEqual(['a']);
+        });
+
+        it('should keep deflated values compressed in the store', async () => {
+            storage.registerCodec('report', 'deflate');
+            const report = { rows: Array.from({ length: 200 }, (_, i) => ({ id: i, status: 'ok' })) };
+            storage.setState('report', report);
+
+            const stored = storage.getAllState().report;
+            expect(typeof stored.__deflate).toBe('string');
+            expect(stored.__deflate.length).toBeLessThan(JSON.stringify(report).length);
+            expect(storage.getState('report')).toEqual(report);
+
+            const { storage: copy } = await createWasmStorage('../pkg/wasm_storage.js');
+            copy.registerCodec('report', 'deflate');
+            copy.importIncremental(storage.exportIncremental(0));
+            expect(copy.getState('report')).toEqual(report);
+            expect(() => storage.registerCodec('x', 'gzip')).toThrow(expect.objectContaining({ code: 'INVALID_ARGUMENT' }));
+        });
+    });
+
+    describe('Validators', () => {
+        it('should reject writes that fail a validator function', () => {
+            storage.registerValidator('user.*', (value) => typeof value === 'string' || 'must be a string');
+
+            storage.setState('user.name', 'Ada');
+            expect(() => storage.setState('user.age', 36)).toThrow();
+            expect(storage.getState('user.age')).toBeNull();
+            expect(storage.getState('user.name')).toBe('Ada');
+        });
+
+        it('should validate against a JSON schema', () => {
+            storage.registerValidator('profile', {
+                type: 'object',
+                required: ['email'],
+                properties: {
+                    email: { type: 'string', pattern: '@' },
+                    age: { type: 'integer', minimum: 0 }
+                }
+            });
+
+            storage.setState('profile', { email: 'ada@example.com', age: 36 });
+            expect(() => storage.setState('profile', { email: 'ada@example.com', age: -1 })).toThrow();
+            expect(() => storage.setState('profile', { age: 3 })).toThrow();
+            expect(storage.getState('profile').age).toBe(36);
+        });
+
+        it('should reject reducer output without applying any of it', () => {
+            storage.registerValidator('stock', { type: 'integer', minimum: 0 });
+            storage.setState('stock', 1);
+            storage.setState('sold', 0);
+            storage.registerReducer('SELL', 'increment sold by payload.quantity; set stock = payload.remaining');
+
+            expect(() => storage.dispatch('SELL', { quantity: 2, remaining: -1 })).toThrow();
+            expect(storage.getState('stock')).toBe(1);
+            expect(storage.getState('sold')).toBe(0);
+        });
+
+        it('should only allow the transitions of a state machine', () => {
+            storage.defineStateMachine('order.status', {
+                states: ['pending', 'paid', 'shipped', 'cancelled'],
+                initial: 'pending',
+                transitions: { pending: ['paid', 'cancelled'], paid: ['shipped'], '*': ['cancelled'] }
+            });
+
+            expect(() => storage.setState('order.status', 'paid')).toThrow(expect.objectContaining({ code: 'VALIDATION_FAILED' }));
+            storage.setState('order.status', 'pending');
+            storage.setState('order.status', 'paid');
+            expect(storage.allowedTransitions('order.status')).toEqual(['cancelled', 'shipped']);
+            expect(() => storage.setState('order.status', 'pending')).toThrow(expect.objectContaining({
+                code: 'VALIDATION_FAILED',
+                context: expect.objectContaining({ from: 'paid', to: 'pending' })
+            }));
+            expect(() => storage.setState('order.status', 'lost')).toThrow();
+            expect(storage.getState('order.status')).toBe('paid');
+        });
+
+        it('should generate TypeScript definitions from key schemas', () => {
+            storage.defineSchema({
+                cart: {
+                    type: 'object',
+                    required: ['items'],
+                    properties: { items: { type: 'array', items: { type: 'string' } }, coupon: { type: ['string', 'null'] } },
+                    additionalProperties: false
+                },
+                'ui.theme': { enum: ['light', 'dark'] }
+            });
+            storage.registerValidator('user.*', { type: 'object' });
+
+            const definitions = storage.typeDefinitions();
+            expect(definitions).toContain('    cart: {\n        items: Array<string>;\n        coupon?: string | null;\n    };');
+            expect(definitions).toContain('    "ui.theme": "light" | "dark";');
+            expect(definitions).toContain('getState<K extends keyof StorageState>(key: K): StorageState[K] | null;');
+            expect(definitions).not.toContain('user.');
+            expect(() => storage.setState('cart', { items: [1] })).toThrow(expect.objectContaining({ code: 'VALIDATION_FAILED' }));
+        });
+    });
+
+    describe('Expression Reducers', () => {
+        it('should apply set, merge, increment and remove statements', () => {
+            storage.setState('cart', { count: 1, items: { a: 1 } });
+            storage.setState('banner', 'Welcome');
+            storage.registerReducer('ADD_ITEM', `
+                increment cart.count by payload.qty;
+                merge cart.items = payload.items;
+                set cart."last.added" = payload.id;
+                set status = "dirty";
+                remove banner
+            `);
+
+            const before = storage.getState('cart');
+            storage.dispatch('ADD_ITEM', { id: 'b', qty: 2, items: { b: 2 } });
+
+            expect(storage.getState('cart')).toEqual({ count: 3, items: { a: 1, b: 2 }, 'last.added': 'b' });
+            expect(before).toEqual({ count: 1, items: { a: 1 } });
+            expect(storage.getState('status')).toBe('dirty');
+            expect(storage.has('banner')).toBe(false);
+            expect(storage.has('__actions_ADD_ITEM')).toBe(false);
+        });
+
+        it('should reject invalid expressions at registration', () => {
+            expect(() => storage.registerReducer('BAD', 'multiply count by 2')).toThrow();
+            expect(() => storage.registerReducer('BAD', 'set count')).toThrow();
+            expect(() => storage.registerReducer('BAD', 'set count = nope')).toThrow();
+        });
+    });
+
+    describe('Subscriptions', () => {
+        it('should subscribe to state changes', (done) => {
+            const callback = (event) => {
+                expect(event.key).toBe('count');
+                expect(event.value).toBe(10);
+                done();
+            };
+            
+            storage.subscribe(callback);
+            storage.setState('count', 10);
+        });
+
+        it('should unsubscribe from state changes', () => {
+            let callCount = 0;
+            const callback = () => callCount++;
+            
+            const id = storage.subscribe(callback);
+            storage.setState('count', 1);
+            storage.unsubscribe(id);
+            storage.setState('count', 2);
+            
+            expect(callCount).toBe(1);
+        });
+
+        it('should call listeners by priority, then in subscription order', () => {
+            const calls = [];
+            storage.subscribe(() => calls.push('ui'));
+            const removed = storage.subscribe(() => calls.push('removed'), { priority: 10 });
+            storage.subscribe(() => calls.push('persistence'), { priority: 10 });
+            storage.subscribe(() => calls.push('late ui'));
+            storage.subscribe(() => calls.push('validation mirror'), { priority: 5 });
+            removed.unsubscribe();
+
+            storage.setState('count', 1);
+
+            expect(calls).toEqual(['persistence', 'validation mirror', 'ui', 'late ui']);
+            expect(() => storage.subscribe(() => {}, { priority: 1.5 })).toThrow(expect.objectContaining({ code: 'INVALID_ARGUMENT' }));
+        });
+
+        it('should include the previous value in change events', () => {
+            const events = [];
+            storage.setState('count', 1);
+            storage.subscribe((event) => events.push(event));
+
+            storage.setState('count', 2);
+            storage.remove('count');
+
+            expect(events.map(({ value, oldValue }) => [value, oldValue])).toEqual([[2, 1], [null, 2]]);
+        });
+
+        it('should skip notifications for unchanged values', () => {
+            let callCount = 0;
+            storage.subscribe(() => callCount++);
+
+            storage.setState('count', 1);
+            storage.setState('count', 1);
+            storage.setState('user', { name: 'Ada' });
+            storage.setState('user', { name: 'Ada' });
+            expect(callCount).toBe(3);
+
+            storage.setChangeDetection('deep');
+            storage.setState('user', { name: 'Ada' });
+            expect(callCount).toBe(3);
+
+            storage.setChangeDetection('always');
+            storage.setState('count', 1);
+            expect(callCount).toBe(4);
+        });
+
+        it('should emit change events like an EventTarget', () => {
+            const all = [];
+            const user = [];
+            const onUser = (event) => user.push(event.detail.value);
+            storage.addEventListener('change', (event) => all.push(event.detail.key));
+            storage.addEventListener('change:user', onUser);
+            storage.addEventListener('change:user', (event) => user.push(`once:${event.detail.value}`), { once: true });
+
+            storage.setState('user', 'ada');
+            storage.setState('theme', 'dark');
+            storage.removeEventListener('change:user', onUser);
+            storage.setState('user', 'grace');
+
+            expect(all).toEqual(['user', 'theme', 'user']);
+            expect(user).toEqual(['ada', 'once:ada']);
+            expect(storage.dispatchEvent(new Event('ping'))).toBe(true);
+        });
+
+        it('should notify selector subscribers only when the selection changes', () => {
+            const calls = [];
+            storage.setState('cart', { items: ['apple'] });
+            storage.subscribeWithSelector((state) => state.cart.items.length, (count, previous) => calls.push([count, previous]));
+            storage.subscribeWithSelector(
+                (state) => ({ first: state.cart.items[0] }),
+                (selected) => calls.push(selected.first),
+                { equalityFn: (a, b) => a.first === b.first }
+            );
+
+            storage.setState('theme', 'dark');
+            storage.setState('cart', { items: ['apple', 'pear'] });
+            storage.setState('cart', { items: ['kiwi', 'pear'] });
+            expect(calls).toEqual([[2, 1], 'kiwi']);
+
+            const serverSnapshot = storage.getServerSnapshot();
+            storage.setState('theme', 'light');
+            expect(storage.getServerSnapshot()).toBe(serverSnapshot);
+            expect(serverSnapshot.theme).toBe('dark');
+        });
+
+        it('should drop writes of identical content in hash mode', () => {
+            const events = [];
+            storage.setChangeDetection('hash');
+            storage.setState('user', { name: 'Ada', roles: ['admin'] });
+            const stored = storage.getState('user');
+            storage.subscribe((event) => events.push(event.key));
+
+            storage.setState('user', { name: 'Ada', roles: ['admin'] });
+            expect(events).toEqual([]);
+            expect(storage.getState('user')).toBe(stored);
+
+            stored.roles.push('owner');
+            storage.setState('user', stored);
+            storage.setState('user', { name: 'Grace', roles: [] });
+            expect(events).toEqual(['user', 'user']);
+            storage.setChangeDetection('reference');
+        });
+
+        it('should tag changes with their origin and filter on it', async () => {
+            const all = [];
+            const local = [];
+            storage.subscribe((event) => all.push(event.origin));
+            storage.subscribe((event) => local.push(event.key), { origins: ['local'] });
+
+            storage.setState('draft', 'typed');
+            storage.runWithOrigin('sync', () => storage.setState('remote', 'echo'));
+
+            const { storage: source } = await createWasmStorage('../pkg/wasm_storage.js');
+            source.setState('restored', true);
+            storage.importIncremental(source.exportIncremental(0));
+
+            expect(all).toEqual(['local', 'sync', 'replay']);
+            expect(local).toEqual(['draft']);
+        });
+
+        it('should coalesce batched writes into one event per key', async () => {
+            const events = [];
+            const batches = [];
+            storage.setState('count', 0);
+            storage.setBatching(true);
+            storage.subscribe((event) => events.push([event.key, event.value, event.oldValue]));
+            storage.subscribe((batch) => batches.push(batch.changes.length), { aggregate: true });
+
+            storage.setState('count', 1);
+            storage.setState('count', 2);
+            storage.setState('name', 'Ada');
+            expect(events).toEqual([]);
+
+            await Promise.resolve();
+
+            expect(events).toEqual([['count', 2, 0], ['name', 'Ada', null]]);
+            expect(batches).toEqual([2]);
+        });
+
+        it('should deliver batched changes immediately with flushSync', () => {
+            const events = [];
+            storage.setBatching(true);
+            storage.subscribe((event) => events.push(event.key));
+
+            storage.setState('a', 1);
+            storage.setState('b', 1);
+            storage.remove('b');
+            storage.flushSync();
+
+            expect(events).toEqual(['a']);
+        });
+
+        it('should defer listeners according to their delivery mode', async () => {
+            const calls = [];
+            storage.subscribe(() => calls.push('sync'));
+            storage.subscribe(() => calls.push('microtask'), { delivery: 'microtask' });
+            storage.subscribe(() => calls.push('idle'), { delivery: 'idle' });
+
+            storage.setState('count', 1);
+            expect(calls).toEqual(['sync']);
+
+            await Promise.resolve();
+            expect(calls).toEqual(['sync', 'microtask']);
+
+            await new Promise(resolve => setTimeout(resolve, 50));
+            expect(calls).toEqual(['sync', 'microtask', 'idle']);
+        });
+
+        it('should deliver one coalesced change per frame when throttled', async () => {
+            const events = [];
+            storage.setState('pointer', { x: 0, y: 0 });
+            storage.subscribe((event) => events.push([event.value, event.oldValue]), { throttle: 'frame' });
+
+            for (let x = 1; x <= 10; x++) {
+                storage.setState('pointer', { x, y: 0 });
+            }
+            await new Promise(resolve => setTimeout(resolve, 50));
+
+            expect(events).toEqual([[{ x: 10, y: 0 }, { x: 0, y: 0 }]]);
+        });
+
+        it('should not deliver deferred changes after unsubscribing', async () => {
+            let called = false;
+            const subscription = storage.subscribe(() => { called = true; }, { delivery: 'microtask' });
+
+            storage.setState('count', 1);
+            subscription.unsubscribe();
+            await Promise.resolve();
+
+            expect(called).toBe(false);
         });
-    });
 
-    describe('Subscriptions', () => {
-        it('should subscribe to state changes', (done) => {
-            const callback = (event) => {
-                expect(event.key).toBe('count');
-                expect(event.value).toBe(10);
-                done();
-            };
-            
-            storage.subscribe(callback);
-            storage.setState('count', 10);
+        it('should keep other subscriptions attached when one unsubscribes', () => {
+            const calls = [];
+            const first = storage.subscribe(() => calls.push('first'));
+            const second = storage.subscribe(() => calls.push('second'));
+            const third = storage.subscribe(() => calls.push('third'));
+
+            first.unsubscribe();
+            first.unsubscribe();
+            storage.unsubscribe(third.id);
+            storage.setState('count', 1);
+
+            expect(calls).toEqual(['second']);
+            expect(second.active).toBe(true);
+            expect(third.active).toBe(false);
         });
 
-        it('should unsubscribe from state changes', () => {
+        it('should keep notifying listeners after one throws', () => {
+            const errors = [];
+            let called = false;
+            storage.setErrorHandler((error, context) => errors.push({ message: error.message, ...context }));
+            storage.subscribe(() => { throw new Error('boom'); });
+            storage.subscribe(() => { called = true; });
+
+            storage.setState('count', 1);
+
+            expect(called).toBe(true);
+            expect(errors).toEqual([
+                { message: 'boom', source: 'listener', key: 'count', action: null, timestamp: expect.any(Number) }
+            ]);
+        });
+
+        it('should unsubscribe when the AbortSignal aborts', () => {
+            const controller = new AbortController();
             let callCount = 0;
-            const callback = () => callCount++;
-            
-            const id = storage.subscribe(callback);
+
+            storage.subscribe(() => callCount++, { signal: controller.signal });
             storage.setState('count', 1);
-            storage.unsubscribe(id);
+            controller.abort();
             storage.setState('count', 2);
-            
+
             expect(callCount).toBe(1);
         });
+
+        it('should not subscribe with an already aborted signal', () => {
+            let called = false;
+            storage.subscribe(() => { called = true; }, { signal: AbortSignal.abort() });
+            storage.setState('count', 1);
+
+            expect(called).toBe(false);
+        });
+    });
+
+    describe('Change Streams', () => {
+        it('should iterate change events with for await', async () => {
+            const changes = storage.changes();
+            storage.setState('a', 1);
+            storage.setState('b', 2);
+
+            const seen = [];
+            for await (const change of changes) {
+                seen.push([change.key, change.value]);
+                if (seen.length === 2) break;
+            }
+
+            expect(seen).toEqual([['a', 1], ['b', 2]]);
+        });
+
+        it('should resolve pending next() calls when a change arrives', async () => {
+            const changes = storage.changes();
+            const pending = changes.next();
+            storage.setState('late', true);
+
+            const { value, done } = await pending;
+            expect(done).toBe(false);
+            expect(value.key).toBe('late');
+
+            await changes.return();
+            expect((await changes.next()).done).toBe(true);
+        });
     });
 
     describe('Middleware', () => {
@@ -121,6 +1828,7 @@ describe('WASM Storage Core', () => {
         });
 
         it('should allow middleware to transform payloads', () => {
+            storage.setUnhandledActions('legacy');
             storage.addMiddleware((action) => {
                 if (action.type === 'DOUBLE') {
                     return { ...action, payload: action.payload * 2 };
@@ -134,6 +1842,636 @@ describe('WASM Storage Core', () => {
             // The DOUBLE action should be transformed but stored as custom action
             expect(storage.getState('__actions_DOUBLE')).toBe(20);
         });
+
+        it('should pass a context with state access and metrics', () => {
+            storage.setUnhandledActions('legacy');
+            storage.setState('token', 'abc');
+            storage.addMiddleware((action, context) => {
+                context.metrics.increment(`actions.${action.type}`);
+                return { ...action, payload: { ...action.payload, token: context.getState('token') } };
+            });
+
+            storage.dispatch('FETCH', { url: '/me' });
+            storage.dispatch('FETCH', { url: '/feed' });
+
+            expect(storage.getState('__actions_FETCH')).toEqual({ url: '/feed', token: 'abc' });
+            expect(storage.middlewareMetrics()).toEqual({ 'actions.FETCH': 2 });
+        });
+
+        it('should skip a throwing middleware when an error handler is set', () => {
+            storage.setUnhandledActions('legacy');
+            const errors = [];
+            storage.setErrorHandler((error, context) => errors.push(context));
+            storage.addMiddleware(() => { throw new Error('broken'); });
+            storage.addMiddleware((action) => ({ ...action, payload: action.payload + 1 }));
+
+            storage.dispatch('BUMP', 1);
+
+            expect(storage.getState('__actions_BUMP')).toBe(2);
+            expect(errors[0]).toMatchObject({ source: 'middleware', action: 'BUMP', key: null });
+        });
+
+        it('should run context dispatches after the current action', () => {
+            storage.setUnhandledActions('legacy');
+            const order = [];
+            storage.addMiddleware((action, context) => {
+                order.push(action.type);
+                if (action.type === 'LOGIN') {
+                    context.dispatch('REFRESH_TOKEN', null);
+                    expect(storage.getState('__actions_LOGIN')).toBeNull();
+                }
+                return action;
+            });
+
+            storage.dispatch('LOGIN', 'ada');
+
+            expect(order).toEqual(['LOGIN', 'REFRESH_TOKEN']);
+            expect(storage.getState('__actions_REFRESH_TOKEN')).toBeNull();
+            expect(storage.has('__actions_REFRESH_TOKEN')).toBe(true);
+        });
+
+        it('should run context dispatches made after the action settled', async () => {
+            let refreshed;
+            const refresh = new Promise(resolve => { refreshed = resolve; });
+            storage.addMiddleware((action, context) => {
+                if (action.type === 'SESSION_EXPIRED') {
+                    Promise.resolve()
+                        .then(() => context.dispatch('SET_STATE', { token: 'fresh' }))
+                        .then(refreshed);
+                }
+                return action;
+            });
+
+            storage.dispatch('SESSION_EXPIRED', null);
+            expect(storage.getState('token')).toBeNull();
+            await refresh;
+            expect(storage.getState('token')).toBe('fresh');
+        });
+    });
+
+    describe('Frame Bridge', () => {
+        it('should require exact origins on both ends', () => {
+            const frame = { contentWindow: { postMessage: () => {} } };
+            expect(() => storage.bridgeToFrame(frame, '*')).toThrow(expect.objectContaining({ code: 'INVALID_ARGUMENT' }));
+            expect(() => storage.connectToHost([])).toThrow(expect.objectContaining({ code: 'INVALID_ARGUMENT' }));
+            expect(() => storage.connectToHost(['https://app.example', '*'])).toThrow(expect.objectContaining({ code: 'INVALID_ARGUMENT' }));
+        });
+
+        const FRAME_ORIGIN = 'https://frame.example';
+        const HOST_ORIGIN = 'https://host.example';
+        const message = (data, origin, source) => Object.assign(new Event('message'), { data, origin, source });
+
+        it('should mirror a snapshot and later changes into the frame', async () => {
+            const posted = [];
+            const frameWindow = { postMessage: (data, origin) => posted.push([data, origin]) };
+            storage.setState('user.name', 'Ada');
+            storage.setState('draft', 'private');
+
+            const bridge = storage.bridgeToFrame({ contentWindow: frameWindow }, FRAME_ORIGIN, { keys: ['user.*'] });
+            expect(posted).toEqual([[
+                { __wasmStorageFrame: 'snapshot', entries: { 'user.name': 'Ada' }, keys: ['user.*'], readOnly: true },
+                FRAME_ORIGIN
+            ]]);
+
+            posted.length = 0;
+            storage.setState('user.name', 'Grace');
+            storage.setState('draft', 'changed');
+            storage.remove('user.name');
+            await new Promise(resolve => setTimeout(resolve, 0));
+            expect(posted).toEqual([
+                [{ __wasmStorageFrame: 'change', key: 'user.name', value: 'Grace' }, FRAME_ORIGIN],
+                [{ __wasmStorageFrame: 'change', key: 'user.name', value: null }, FRAME_ORIGIN]
+            ]);
+
+            // A frame that loaded late says hello and gets a fresh snapshot
+            posted.length = 0;
+            window.dispatchEvent(message({ __wasmStorageFrame: 'hello' }, FRAME_ORIGIN, frameWindow));
+            expect(posted).toEqual([[
+                { __wasmStorageFrame: 'snapshot', entries: {}, keys: ['user.*'], readOnly: true },
+                FRAME_ORIGIN
+            ]]);
+            bridge.close();
+        });
+
+        it('should drop frame messages with the wrong origin or source', () => {
+            const frameWindow = { postMessage: () => {} };
+            const bridge = storage.bridgeToFrame({ contentWindow: frameWindow }, FRAME_ORIGIN, { keys: ['user.*'], readOnly: false });
+            const set = { __wasmStorageFrame: 'set', key: 'user.name', value: 'Mallory' };
+
+            window.dispatchEvent(message(set, 'https://evil.example', frameWindow));
+            window.dispatchEvent(message(set, FRAME_ORIGIN, { postMessage: () => {} }));
+            window.dispatchEvent(message(set, FRAME_ORIGIN, null));
+            expect(storage.getState('user.name')).toBeNull();
+
+            window.dispatchEvent(message({ ...set, value: 'Ada' }, FRAME_ORIGIN, frameWindow));
+            expect(storage.getState('user.name')).toBe('Ada');
+            // Unmirrored keys stay out of reach even when writable
+            window.dispatchEvent(message({ ...set, key: 'draft' }, FRAME_ORIGIN, frameWindow));
+            expect(storage.getState('draft')).toBeNull();
+            bridge.close();
+        });
+
+        it('should ignore frame writes to a read-only bridge', () => {
+            const frameWindow = { postMessage: () => {} };
+            const bridge = storage.bridgeToFrame({ contentWindow: frameWindow }, FRAME_ORIGIN);
+            window.dispatchEvent(message({ __wasmStorageFrame: 'set', key: 'user.name', value: 'Mallory' }, FRAME_ORIGIN, frameWindow));
+            expect(storage.getState('user.name')).toBeNull();
+            bridge.close();
+        });
+
+        it('should apply the host snapshot and reject local writes to read-only keys', async () => {
+            // A top-level test window is its own parent
+            const posted = [];
+            const postMessage = vi.spyOn(window, 'postMessage').mockImplementation((data, origin) => posted.push([data, origin]));
+            const bridge = storage.connectToHost([HOST_ORIGIN]);
+            expect(posted).toEqual([[{ __wasmStorageFrame: 'hello' }, HOST_ORIGIN]]);
+
+            const snapshot = { __wasmStorageFrame: 'snapshot', entries: { 'user.name': 'Ada' }, keys: ['user.*'], readOnly: true };
+            window.dispatchEvent(message(snapshot, 'https://evil.example', window));
+            window.dispatchEvent(message(snapshot, HOST_ORIGIN, { postMessage: () => {} }));
+            expect(storage.getState('user.name')).toBeNull();
+
+            window.dispatchEvent(message(snapshot, HOST_ORIGIN, window));
+            expect(storage.getState('user.name')).toBe('Ada');
+            window.dispatchEvent(message({ __wasmStorageFrame: 'change', key: 'user.name', value: 'Grace' }, HOST_ORIGIN, window));
+            expect(storage.getState('user.name')).toBe('Grace');
+
+            expect(() => storage.setState('user.name', 'Mallory')).toThrow(expect.objectContaining({ code: 'INVALID_STATE' }));
+            expect(() => storage.remove('user.name')).toThrow(expect.objectContaining({ code: 'INVALID_STATE' }));
+            storage.setState('draft', 'mine');
+            expect(() => storage.clear()).toThrow(expect.objectContaining({ code: 'INVALID_STATE' }));
+            expect(storage.getState('user.name')).toBe('Grace');
+            expect(storage.getState('draft')).toBe('mine');
+
+            // Once the host allows writes, they are sent back to it
+            window.dispatchEvent(message({ ...snapshot, entries: {}, readOnly: false }, HOST_ORIGIN, window));
+            posted.length = 0;
+            storage.setState('user.name', 'Lin');
+            storage.setState('draft', 'still mine');
+            await new Promise(resolve => setTimeout(resolve, 0));
+            expect(posted).toEqual([[{ __wasmStorageFrame: 'set', key: 'user.name', value: 'Lin' }, HOST_ORIGIN]]);
+
+            bridge.close();
+            postMessage.mockRestore();
+        });
+    });
+
+    describe('Service Worker', () => {
+        it('should share a slice with pages and apply their writes', async () => {
+            const posted = [];
+            const page = { postMessage: message => posted.push(message) };
+            globalThis.clients = { matchAll: async () => [page] };
+            globalThis.registration = {};
+            const message = data => Object.assign(new Event('message'), { data, source: page });
+
+            storage.setState('user.name', 'Ada');
+            storage.setState('draft', 'private');
+            const share = storage.shareWithClients(['user.*']);
+            window.dispatchEvent(message({ __wasmStorageServiceWorker: 'hello' }));
+            expect(posted[0].entries).toEqual({ 'user.name': 'Ada' });
+
+            window.dispatchEvent(message({ __wasmStorageServiceWorker: 'set', key: 'user.name', value: 'Grace' }));
+            expect(storage.getState('user.name')).toBe('Grace');
+            await new Promise(resolve => setTimeout(resolve, 0));
+            expect(posted).toContainEqual({ __wasmStorageServiceWorker: 'change', key: 'user.name', value: 'Grace' });
+
+            window.dispatchEvent(message({ __wasmStorageServiceWorker: 'set', key: 'draft', value: 'leaked' }));
+            expect(storage.getState('draft')).toBe('private');
+            share.close();
+            delete globalThis.clients;
+            delete globalThis.registration;
+        });
+    });
+
+    describe('Push Refresh', () => {
+        it('should dispatch the refetch action inside the push event\'s waitUntil', async () => {
+            storage.setUnhandledActions('legacy');
+            const refresh = storage.enablePushRefresh('REFETCH_NEWS', 'news');
+            const push = body => {
+                const extended = [];
+                const event = Object.assign(new Event('push'), {
+                    data: { json: () => body, text: () => JSON.stringify(body) },
+                    waitUntil: promise => extended.push(promise)
+                });
+                globalThis.dispatchEvent(event);
+                return extended;
+            };
+
+            expect(push({ topic: 'sports' })).toEqual([]);
+            const extended = push({ topic: 'news', id: 7 });
+            expect(extended).toHaveLength(1);
+            await extended[0];
+            expect(storage.getState('__actions_REFETCH_NEWS')).toMatchObject({ data: { topic: 'news', id: 7 } });
+
+            refresh.disable();
+            expect(push({ topic: 'news' })).toEqual([]);
+        });
+
+        it('should keep waitUntil pending until async backends have written', async () => {
+            const written = [];
+            let release;
+            const held = new Promise(resolve => { release = resolve; });
+            const cache = {
+                keys: async () => [],
+                put: async (url, response) => { await held; written.push(url); },
+                delete: async () => {}
+            };
+            global.caches = { open: async () => cache };
+            await storage.registerCacheBackend('cache');
+            storage.enablePersistence('offline', { backend: 'cache' });
+            storage.setUnhandledActions('legacy');
+            storage.enablePushRefresh('REFETCH_NEWS');
+
+            const extended = [];
+            globalThis.dispatchEvent(Object.assign(new Event('push'), { data: null, waitUntil: promise => extended.push(promise) }));
+            let settled = false;
+            extended[0].then(() => { settled = true; });
+            await new Promise(resolve => setTimeout(resolve, 0));
+            expect(settled).toBe(false);
+
+            release();
+            await extended[0];
+            expect(written.length).toBeGreaterThan(0);
+            delete global.caches;
+        });
+    });
+
+    describe('Worker Proxy', () => {
+        it('should proxy reads, writes, dispatch and subscriptions over postMessage', async () => {
+            storage.setUnhandledActions('legacy');
+            const { port1, port2 } = new MessageChannel();
+            const host = storage.hostWorkerClients(port1);
+            const client = await createWorkerStoreClient('../pkg/wasm_storage.js', port2);
+
+            const changes = [];
+            const subscription = await client.subscribe(event => changes.push([event.key, event.value]));
+            await client.setState('count', 3);
+            expect(storage.getState('count')).toBe(3);
+            expect(await client.getState('count')).toBe(3);
+
+            await client.dispatch('INCREMENT', 2);
+            expect(await client.getState('__actions_INCREMENT')).toBe(2);
+            storage.registerValidator('age', (value) => value >= 0 || 'must not be negative');
+            await expect(client.setState('age', -1)).rejects.toMatchObject({ code: 'VALIDATION_FAILED' });
+
+            await new Promise(resolve => setTimeout(resolve, 0));
+            expect(changes).toContainEqual(['count', 3]);
+            await client.unsubscribe(subscription);
+            client.close();
+            host.close();
+            port1.close();
+        });
+    });
+
+    describe('Shared Memory', () => {
+        it('should let another store read shared keys from the buffer', async () => {
+            const { storage: worker } = await createWasmStorage('../pkg/wasm_storage.js');
+            storage.setState('body.mass', 2.5);
+            const buffer = storage.enableSharedMemory(['body.mass', 'body.position', 'body.name'], { slotBytes: 64 });
+            worker.attachSharedMemory(buffer);
+            expect(worker.sharedValue('body.mass')).toBe(2.5);
+
+            const version = worker.sharedVersion();
+            storage.setState('body.position', new Float64Array([1, 2, 3]));
+            storage.setState('body.name', 'crate');
+            expect(worker.sharedVersion()).toBe(version + 2);
+            expect(Array.from(worker.sharedValue('body.position'))).toEqual([1, 2, 3]);
+            expect(worker.sharedValue('body.name')).toBe('crate');
+
+            expect(() => storage.setState('body.name', { nested: true })).toThrow();
+            expect(() => storage.setState('body.name', 'x'.repeat(65))).toThrow();
+            storage.removeState('body.mass');
+            expect(worker.sharedValue('body.mass')).toBeUndefined();
+        });
+    });
+
+    describe('Leader Election', () => {
+        beforeEach(() => {
+            const channels = [];
+            global.BroadcastChannel = class {
+                constructor(name) {
+                    this.name = name;
+                    channels.push(this);
+                }
+                postMessage(data) {
+                    channels
+                        .filter(channel => channel !== this && channel.name === this.name && !channel.closed)
+                        .forEach(channel => channel.onmessage?.({ data }));
+                }
+                close() {
+                    this.closed = true;
+                }
+            };
+        });
+
+        it('should elect one tab and fail over when it closes', async () => {
+            const { storage: otherTab } = await createWasmStorage('../pkg/wasm_storage.js');
+            const options = { heartbeatMs: 20, timeoutMs: 60 };
+            storage.enableLeaderElection('tabs', options);
+            await new Promise(resolve => setTimeout(resolve, 30));
+            expect(storage.isLeader()).toBe(true);
+
+            const changes = [];
+            otherTab.enableLeaderElection('tabs', { ...options, onChange: (event) => changes.push(event.isLeader) });
+            expect(otherTab.isLeader()).toBe(false);
+            expect(otherTab.leaderId()).toBe(storage.leaderId());
+
+            storage.destroy();
+            expect(otherTab.isLeader()).toBe(true);
+            expect(changes).toEqual([true]);
+            otherTab.destroy();
+        });
+    });
+
+    describe('Remote Sync', () => {
+        let sockets;
+
+        beforeEach(() => {
+            sockets = [];
+            global.WebSocket = class {
+                constructor(url) {
+                    this.url = url;
+                    this.readyState = 0;
+                    this.sent = [];
+                    sockets.push(this);
+                }
+                send(data) {
+                    this.sent.push(JSON.parse(data));
+                }
+                close() {
+                    this.readyState = 3;
+                }
+                open() {
+                    this.readyState = 1;
+                    this.onopen?.({});
+                }
+            };
+        });
+
+        it('should queue mutations while offline and apply pushed ones', () => {
+            const remote = storage.connectRemote('wss://sync.example/ws', { keys: ['todo.*'] });
+            storage.setState('todo.1', 'draft');
+            storage.setState('todo.1', 'final');
+            storage.setState('ui.theme', 'dark');
+            expect(remote.queued).toBe(1);
+
+            sockets[0].open();
+            expect(remote.status).toBe('open');
+            expect(sockets[0].sent).toMatchObject([{ kind: 'mutation', key: 'todo.1', value: 'final' }]);
+
+            sockets[0].onmessage({ data: JSON.stringify({ kind: 'mutation', key: 'todo.2', value: 'from server' }) });
+            expect(storage.getState('todo.2')).toBe('from server');
+            expect(sockets[0].sent).toHaveLength(1);
+
+            remote.close();
+            expect(remote.status).toBe('closed');
+        });
+
+        it('should pass divergent writes to the conflict resolver', () => {
+            const remote = storage.connectRemote('wss://sync.example/ws');
+            sockets[0].open();
+            const push = (value) => sockets[0].onmessage({ data: JSON.stringify({ kind: 'mutation', key: 'note', value }) });
+            push('v1');
+
+            const conflicts = [];
+            storage.setConflictResolver((key, local, remote, base) => {
+                conflicts.push({ key, local, remote, base });
+                return `${local} | ${remote}`;
+            });
+            sockets[0].readyState = 3;
+            storage.setState('note', 'offline edit');
+            push('server edit');
+
+            expect(conflicts).toEqual([{ key: 'note', local: 'offline edit', remote: 'server edit', base: 'v1' }]);
+            expect(storage.getState('note')).toBe('offline edit | server edit');
+            expect(remote.queued).toBe(1);
+        });
+    });
+
+    describe('Peer Sync', () => {
+        // Both ends of a data channel; messages wait in `sent` until delivered
+        const channel = () => ({
+            readyState: 'open',
+            sent: [],
+            send(data) {
+                this.sent.push(data);
+            }
+        });
+        const deliver = (from, to) => {
+            const messages = from.sent.splice(0);
+            messages.forEach(data => to.onmessage({ data }));
+            return messages.map(data => JSON.parse(data));
+        };
+        let peer, ours, theirs;
+
+        beforeEach(async () => {
+            ({ storage: peer } = await createWasmStorage('../pkg/wasm_storage.js'));
+            ours = channel();
+            theirs = channel();
+        });
+
+        it('should apply peer writes without echoing them back', () => {
+            const local = storage.syncWithPeer(ours, ['doc']);
+            const remote = peer.syncWithPeer(theirs, ['doc']);
+            deliver(ours, theirs);
+            deliver(theirs, ours);
+
+            storage.setState('doc', 'v1');
+            storage.setState('draft', 'not synced');
+            expect(deliver(ours, theirs)).toMatchObject([{ kind: 'set', entry: { key: 'doc', value: 'v1' } }]);
+            expect(peer.getState('doc')).toBe('v1');
+            expect(peer.getState('draft')).toBeNull();
+            expect(theirs.sent).toEqual([]);
+
+            peer.remove('doc');
+            expect(deliver(theirs, ours)).toMatchObject([{ kind: 'set', entry: { key: 'doc', value: null } }]);
+            expect(storage.getState('doc')).toBeNull();
+            expect(ours.sent).toEqual([]);
+            local.close();
+            remote.close();
+        });
+
+        it('should settle crossing writes by CRDT stamp on both peers', () => {
+            storage.enableCrdt('a');
+            peer.enableCrdt('b');
+            storage.syncWithPeer(ours, ['doc']);
+            peer.syncWithPeer(theirs, ['doc']);
+
+            storage.setState('doc', 'from a');
+            peer.setState('doc', 'from b');
+            deliver(ours, theirs);
+            deliver(theirs, ours);
+
+            // Equal counters tie-break on actor ID: "b" > "a"
+            expect(storage.getState('doc')).toBe('from b');
+            expect(peer.getState('doc')).toBe('from b');
+            expect(storage.crdtStamp('doc')).toEqual({ counter: 1, actor: 'b' });
+            expect([ours.sent, theirs.sent]).toEqual([[], []]);
+        });
+
+        it('should send crossing writes to the conflict resolver', () => {
+            const conflicts = [];
+            const resolver = (key, local, remote, base) => {
+                conflicts.push({ key, local, remote, base });
+                return [local, remote].sort().join(' + ');
+            };
+            storage.setConflictResolver(resolver);
+            peer.setConflictResolver(resolver);
+            storage.syncWithPeer(ours, ['doc']);
+            peer.syncWithPeer(theirs, ['doc']);
+            deliver(ours, theirs);
+            deliver(theirs, ours);
+
+            storage.setState('doc', 'mine');
+            peer.setState('doc', 'theirs');
+            deliver(ours, theirs);
+            // The peer's write crossed ours, and its resolved value follows it
+            expect(deliver(theirs, ours)).toMatchObject([
+                { entry: { value: 'theirs', base: null } },
+                { entry: { value: 'mine + theirs', base: 'mine' } }
+            ]);
+            expect(conflicts).toEqual([
+                { key: 'doc', local: 'theirs', remote: 'mine', base: null },
+                { key: 'doc', local: 'mine', remote: 'theirs', base: null }
+            ]);
+
+            // Each side sends its resolved value, which the other already has
+            expect(deliver(ours, theirs)).toMatchObject([{ entry: { value: 'mine + theirs', base: 'theirs' } }]);
+            expect(theirs.sent).toEqual([]);
+            expect(storage.getState('doc')).toBe('mine + theirs');
+            expect(peer.getState('doc')).toBe('mine + theirs');
+            expect(conflicts).toHaveLength(2);
+        });
+    });
+
+    describe('CRDT Mode', () => {
+        it('should converge on the same winner for concurrent writes', async () => {
+            const { storage: laptop } = await createWasmStorage('../pkg/wasm_storage.js');
+            storage.enableCrdt('phone');
+            laptop.enableCrdt('laptop');
+
+            storage.setState('title', 'From phone');
+            laptop.setState('title', 'From laptop');
+            laptop.setState('body', 'Laptop body');
+            laptop.remove('body');
+
+            const fromPhone = storage.crdtExport();
+            const fromLaptop = laptop.crdtExport();
+            storage.crdtMergeAll(fromLaptop);
+            laptop.crdtMergeAll(fromPhone);
+
+            // Equal counters tie-break on actor ID: "phone" > "laptop"
+            expect(storage.getState('title')).toBe('From phone');
+            expect(laptop.getState('title')).toBe('From phone');
+            expect(storage.crdtStamp('body')).toEqual({ counter: 3, actor: 'laptop' });
+            expect(storage.crdtMerge('body', 'stale', { counter: 2, actor: 'laptop' })).toBe(false);
+            expect(storage.getState('body')).toBeNull();
+        });
+
+        it('should not keep the stamp of a merge that was rejected', () => {
+            storage.enableCrdt('phone');
+            storage.setState('title', 'Draft');
+            storage.registerValidator('title', (value) => typeof value === 'string' || 'must be a string');
+
+            expect(() => storage.crdtMerge('title', 42, { counter: 9, actor: 'laptop' })).toThrow();
+            expect(storage.crdtStamp('title')).toEqual({ counter: 1, actor: 'phone' });
+
+            expect(storage.crdtMerge('title', 'From laptop', { counter: 5, actor: 'laptop' })).toBe(true);
+            expect(storage.getState('title')).toBe('From laptop');
+            expect(storage.crdtStamp('title')).toEqual({ counter: 5, actor: 'laptop' });
+        });
+    });
+
+    describe('Server-Sent Events', () => {
+        let source;
+
+        beforeEach(() => {
+            global.EventSource = class {
+                constructor(url) {
+                    this.url = url;
+                    this.readyState = 1;
+                    this.listeners = {};
+                    source = this;
+                }
+                addEventListener(name, listener) {
+                    (this.listeners[name] ||= []).push(listener);
+                }
+                removeEventListener(name, listener) {
+                    this.listeners[name] = (this.listeners[name] || []).filter(l => l !== listener);
+                }
+                emit(type, data) {
+                    (this.listeners[type] || []).forEach(l => l({ type, data, lastEventId: '' }));
+                }
+                close() {
+                    this.readyState = 2;
+                }
+            };
+        });
+
+        it('should dispatch mapped events through middleware', () => {
+            const seen = [];
+            storage.addMiddleware((action) => {
+                seen.push([action.type, action.payload]);
+                return action;
+            });
+            const sse = storage.subscribeSse('/events', ({ event, data }) =>
+                event === 'price' ? { type: 'PRICE_UPDATED', payload: data } : null,
+                { events: ['price', 'heartbeat'] });
+
+            source.emit('price', JSON.stringify({ symbol: 'ACME', price: 42 }));
+            source.emit('heartbeat', '');
+
+            expect(seen).toEqual([['PRICE_UPDATED', { symbol: 'ACME', price: 42 }]]);
+            sse.close();
+            expect(source.readyState).toBe(2);
+        });
+    });
+
+    describe('REST Sync', () => {
+        let server, requests;
+
+        beforeEach(() => {
+            server = {};
+            requests = [];
+            global.fetch = async (url, init) => {
+                requests.push({ url, method: init.method, body: init.body && JSON.parse(init.body) });
+                if (init.method === 'PATCH') {
+                    Object.assign(server, JSON.parse(init.body));
+                }
+                return { status: 200, json: async () => ({ ...server }) };
+            };
+        });
+
+        it('should push only changes since the last sync', async () => {
+            storage.setState('title', 'Draft');
+            storage.setState('tags', ['a']);
+            expect(await storage.syncPush('/api/doc')).toEqual({ pushed: 2, conflicts: 0 });
+
+            storage.setState('title', 'Final');
+            await storage.syncPush('/api/doc');
+            expect(requests[1].body).toEqual({ title: 'Final' });
+            expect(await storage.syncPush('/api/doc')).toEqual({ pushed: 0, conflicts: 0 });
+        });
+
+        it('should merge pulls and resolve concurrent edits with onConflict', async () => {
+            server = { title: 'Remote', count: 1 };
+            expect(await storage.syncPull('/api/doc')).toEqual({ applied: 2, conflicts: 0 });
+
+            server = { title: 'Remote edit', count: 2 };
+            storage.setState('title', 'Local edit');
+            const conflicts = [];
+            const result = await storage.syncPull('/api/doc', {
+                onConflict: ({ key, local, remote, base }) => {
+                    conflicts.push({ key, local, remote, base });
+                    return `${local} + ${remote}`;
+                }
+            });
+
+            expect(result).toEqual({ applied: 2, conflicts: 1 });
+            expect(conflicts).toEqual([{ key: 'title', local: 'Local edit', remote: 'Remote edit', base: 'Remote' }]);
+            expect(storage.getState('title')).toBe('Local edit + Remote edit');
+            expect(storage.getState('count')).toBe(2);
+        });
     });
 });
 
@@ -143,7 +2481,7 @@ describe('Redux Adapter', () => {
     let adapters;
 
     beforeEach(async () => {
-        const { storage, adapters: wasmAdapters } = await createWasmStorage('../pkg/wasm_storage.js');
+        const { storage, adapters: wasmAdapters } = await createWasmStorage('../pkg/wasm_storage.js', { unhandledActions: 'legacy' });
         adapters = wasmAdapters;
         store = adapters.redux.createStore({ count: 0, todos: [] });
     });
@@ -396,6 +2734,335 @@ describe('State Persistence', () => {
         expect(storage.getState('count')).toBe(100);
         expect(storage.getState('user')).toEqual({ name: 'John' });
     });
+
+    it('should persist and hydrate through enablePersistence', () => {
+        localStorage.setItem('app', JSON.stringify({ data: { count: 7 } }));
+
+        storage.enablePersistence('app');
+        expect(storage.getState('count')).toBe(7);
+
+        storage.setState('count', 8);
+        expect(JSON.parse(localStorage.getItem('app')).data).toEqual({ count: 8 });
+    });
+
+    it('should write behind and flush on demand', async () => {
+        storage.enablePersistence('app', { debounceMs: 20 });
+        storage.setState('count', 1);
+        storage.setState('count', 2);
+        expect(localStorage.getItem('app')).toBeNull();
+
+        storage.flush();
+        expect(JSON.parse(localStorage.getItem('app')).data).toEqual({ count: 2 });
+
+        storage.setState('count', 3);
+        await new Promise(resolve => setTimeout(resolve, 40));
+        expect(JSON.parse(localStorage.getItem('app')).data).toEqual({ count: 3 });
+    });
+
+    it('should recover logged changes after the page dies between snapshots', async () => {
+        storage.enablePersistence('app', { wal: { compactAfter: 3 } });
+        storage.setState('count', 1);
+        storage.setState('draft', 'hello');
+        storage.removeState('count');
+        expect(localStorage.getItem('app::wal').split('\n')).toHaveLength(3);
+
+        // The page is killed here: a new store starts from what reached storage
+        const { storage: reloaded } = await createWasmStorage('../pkg/wasm_storage.js');
+        reloaded.enablePersistence('app', { wal: true });
+
+        expect(reloaded.getState('draft')).toBe('hello');
+        expect(reloaded.getState('count')).toBeNull();
+        expect(localStorage.getItem('app::wal')).toBeNull();
+        expect(JSON.parse(localStorage.getItem('app')).data).toEqual({ draft: 'hello' });
+    });
+
+    it('should keep transient keys out of the snapshot and the log', () => {
+        const writes = [];
+        storage.registerBackend('memory', {
+            getItem: () => null,
+            setItem: (key, value) => writes.push([key, value]),
+            removeItem: () => {}
+        });
+        storage.enablePersistence('app', { backend: 'memory', wal: { compactAfter: 100 } });
+        storage.setState('count', 1);
+        writes.length = 0;
+
+        storage.setTransient('toast', 'Saved!', 'navigation');
+        storage.setTransient('toast', 'Saved again!', 'navigation');
+        expect(writes).toEqual([]);
+        storage.endTransientScope('navigation');
+        expect(writes).toEqual([]);
+
+        storage.setState('count', 2);
+        expect(writes.every(([, value]) => !value.includes('Saved'))).toBe(true);
+        expect(writes.some(([key]) => key === 'app::wal')).toBe(true);
+    });
+
+    it('should persist through a registered backend', () => {
+        const items = new Map();
+        storage.registerBackend('memory', {
+            getItem: (key) => items.get(key) ?? null,
+            setItem: (key, value) => items.set(key, value),
+            removeItem: (key) => items.delete(key)
+        });
+
+        storage.enablePersistence('app', { backend: 'memory', wal: true });
+        storage.setState('count', 1);
+
+        expect(localStorage.getItem('app')).toBeNull();
+        expect(items.get('app::wal')).toContain('"count"');
+        expect(() => storage.enablePersistence('app', { backend: 'missing' })).toThrow();
+    });
+
+    it('should hydrate from and write through to Cache Storage', async () => {
+        const entries = new Map();
+        const cache = {
+            keys: async () => [...entries.keys()].map(url => ({ url })),
+            match: async (request) => new Response(entries.get(request.url ?? request)),
+            put: async (url, response) => entries.set(`https://app.test${url}`, await response.text()),
+            delete: async (url) => entries.delete(`https://app.test${url}`)
+        };
+        global.caches = { open: async () => cache };
+        entries.set('https://app.test/__wasm_storage__/offline', JSON.stringify({ data: { inbox: 3 } }));
+
+        await storage.registerCacheBackend('cache');
+        storage.enablePersistence('offline', { backend: 'cache' });
+        expect(storage.getState('inbox')).toBe(3);
+
+        storage.setState('inbox', 4);
+        await new Promise(resolve => setTimeout(resolve, 0));
+        expect(JSON.parse(entries.get('https://app.test/__wasm_storage__/offline')).data).toEqual({ inbox: 4 });
+        delete global.caches;
+    });
+
+    it('should reject the OPFS backend outside a worker', async () => {
+        await expect(storage.registerOpfsBackend('files', { storageKeys: ['app'] })).rejects.toBeDefined();
+    });
+
+    it('should only persist keys allowed by persist_config', () => {
+        localStorage.setItem('app', JSON.stringify({ data: { 'auth.secret': 'old', 'user.name': 'Ada' } }));
+        storage.persistConfig({ keyPrefixes: ['user.', 'auth.'], exclude: ['auth.secret'] });
+
+        storage.enablePersistence('app');
+        expect(storage.getState('auth.secret')).toBeNull();
+
+        storage.setState('auth.secret', 'token');
+        storage.setState('auth.user', 'ada');
+        storage.setState('ui.sidebarOpen', true);
+
+        expect(JSON.parse(localStorage.getItem('app')).data).toEqual({ 'user.name': 'Ada', 'auth.user': 'ada' });
+    });
+
+    it('should encrypt persisted values and decrypt them on hydration', async () => {
+        await storage.enableEncryption('correct horse battery staple', { iterations: 1000 });
+        storage.enablePersistence('vault');
+        storage.setState('token', 'secret-token');
+        await new Promise(resolve => setTimeout(resolve, 50));
+
+        const saved = localStorage.getItem('vault');
+        expect(saved).not.toContain('secret-token');
+        expect(JSON.parse(saved).data.token.__sealed).toBeDefined();
+
+        const { storage: reloaded } = await createWasmStorage('../pkg/wasm_storage.js');
+        expect(() => reloaded.enablePersistence('vault')).toThrow();
+
+        await reloaded.enableEncryption('correct horse battery staple', { iterations: 1000 });
+        const hydrated = new Promise(resolve => reloaded.onInit(resolve));
+        reloaded.enablePersistence('vault');
+        await hydrated;
+        expect(reloaded.getState('token')).toBe('secret-token');
+    });
+
+    it('should reject persisted snapshots modified outside the store', async () => {
+        storage.enableSigning('app-secret');
+        storage.enablePersistence('signed');
+        storage.setState('role', 'user');
+
+        const { storage: reloaded } = await createWasmStorage('../pkg/wasm_storage.js');
+        reloaded.enableSigning('app-secret');
+        reloaded.enablePersistence('signed');
+        expect(reloaded.getState('role')).toBe('user');
+
+        localStorage.setItem('signed', localStorage.getItem('signed').replace('"user"', '"admin"'));
+        const { storage: tampered } = await createWasmStorage('../pkg/wasm_storage.js');
+        tampered.enableSigning('app-secret');
+        let error;
+        try {
+            tampered.enablePersistence('signed');
+        } catch (e) {
+            error = e;
+        }
+        expect(error.code).toBe('SIGNATURE_INVALID');
+        expect(tampered.getState('role')).toBeNull();
+    });
+
+    it('should compress large persisted values and inflate them on hydration', async () => {
+        const rows = Array.from({ length: 500 }, (_, i) => ({ id: i, status: 'active' }));
+        storage.enableCompression({ thresholdBytes: 256 });
+        storage.enablePersistence('compressed');
+        storage.setState('rows', rows);
+        storage.setState('small', 'tiny');
+
+        const saved = JSON.parse(localStorage.getItem('compressed')).data;
+        expect(typeof saved.rows.__deflate).toBe('string');
+        expect(saved.rows.__deflate.length).toBeLessThan(JSON.stringify(rows).length / 4);
+        expect(saved.small).toBe('tiny');
+
+        const { storage: reloaded } = await createWasmStorage('../pkg/wasm_storage.js');
+        reloaded.enablePersistence('compressed');
+        expect(reloaded.getState('rows')).toEqual(rows);
+
+        const stream = storage.exportIncremental(0);
+        expect(JSON.parse(stream).ops[0].value.__deflate).toBeDefined();
+        const { storage: imported } = await createWasmStorage('../pkg/wasm_storage.js');
+        imported.importIncremental(stream);
+        expect(imported.getState('rows')).toEqual(rows);
+    });
+
+    it('should migrate persisted state to the schema version', () => {
+        localStorage.setItem('app', JSON.stringify({ data: { name: 'Ada Lovelace' } }));
+        storage.setSchemaVersion(2);
+        storage.registerMigration(0, 1, (data) => ({ user: { name: data.name } }));
+        storage.registerMigration(1, 2, (data) => {
+            const [first, last] = data.user.name.split(' ');
+            data.user = { first, last };
+        });
+
+        storage.enablePersistence('app');
+
+        expect(storage.getState('name')).toBeNull();
+        expect(storage.getState('user')).toEqual({ first: 'Ada', last: 'Lovelace' });
+        expect(JSON.parse(localStorage.getItem('app')).version).toBe(2);
+    });
+
+    it('should refuse state persisted by a newer schema', () => {
+        localStorage.setItem('app', JSON.stringify({ version: 3, data: { count: 1 } }));
+        storage.setSchemaVersion(2);
+
+        expect(() => storage.enablePersistence('app')).toThrow();
+    });
+
+    it('should stop persisting low-priority keys near the quota', () => {
+        const reports = [];
+        storage.enablePersistence('app', {
+            quotaBytes: 400,
+            threshold: 0.5,
+            priorities: { session: 'must', feedCache: 'cache' },
+            onReport: (report) => reports.push(report)
+        });
+
+        storage.setState('session', 'token');
+        storage.setState('feedCache', 'x'.repeat(200));
+
+        const saved = JSON.parse(localStorage.getItem('app')).data;
+        expect(saved.session).toBe('token');
+        expect(saved.feedCache).toBeUndefined();
+        expect(storage.getState('feedCache')).toHaveLength(200);
+        expect(reports[0].dropped).toEqual(['feedCache']);
+        expect(storage.droppedPersistKeys()).toEqual(['feedCache']);
+    });
+
+    it('should evict dropped keys from memory when requested', () => {
+        storage.enablePersistence('app', {
+            quotaBytes: 400,
+            threshold: 0.5,
+            evict: true,
+            priorities: { feedCache: 'low' }
+        });
+
+        storage.setState('feedCache', 'x'.repeat(200));
+
+        expect(storage.getState('feedCache')).toBeNull();
+    });
+
+    it('should evict by policy when the backend rejects a write for quota', () => {
+        const items = new Map();
+        storage.registerBackend('small', {
+            getItem: (key) => items.get(key) ?? null,
+            setItem: (key, value) => {
+                if (value.length > 300) {
+                    throw Object.assign(new Error('full'), { name: 'QuotaExceededError' });
+                }
+                items.set(key, value);
+            },
+            removeItem: (key) => items.delete(key)
+        });
+        const events = [];
+        storage.onQuotaExceeded(event => events.push(event));
+        storage.setEvictionPolicy('oldestFirst');
+        storage.enablePersistence('app', { backend: 'small', priorities: { session: 'must' } });
+
+        storage.setState('session', 'abc');
+        storage.setState('draft', 'd'.repeat(100));
+        storage.setState('history', 'h'.repeat(100));
+        storage.setState('latest', 'l'.repeat(100));
+
+        expect(events).toHaveLength(1);
+        expect(events[0]).toMatchObject({ storageKey: 'app', evicted: ['draft'], recovered: true });
+        expect(storage.getState('draft')).toBeNull();
+        expect(Object.keys(JSON.parse(items.get('app')).data).sort()).toEqual(['history', 'latest', 'session']);
+    });
+
+    it('should rebuild state from the logged actions', async () => {
+        storage.registerReducer('DEPOSIT', 'increment balance by payload.amount');
+        expect(storage.enableEventSourcing({ logKey: 'ledger' })).toBe(0);
+        storage.dispatch('DEPOSIT', { amount: 50 });
+        storage.dispatch('DEPOSIT', { amount: 25 });
+        expect(storage.eventLog().map(entry => [entry.seq, entry.type, entry.payload])).toEqual([
+            [1, 'DEPOSIT', { amount: 50 }],
+            [2, 'DEPOSIT', { amount: 25 }]
+        ]);
+
+        const { storage: restored } = await createWasmStorage('../pkg/wasm_storage.js');
+        restored.registerReducer('DEPOSIT', 'increment balance by payload.amount');
+        expect(restored.enableEventSourcing({ logKey: 'ledger' })).toBe(2);
+        expect(restored.getState('balance')).toBe(75);
+
+        restored.truncateEventLog(1);
+        expect(restored.getState('balance')).toBe(50);
+        expect(restored.compactToSnapshot()).toBe(1);
+        expect(localStorage.getItem('ledger')).toBeNull();
+        restored.dispatch('DEPOSIT', { amount: 5 });
+        expect(restored.replay()).toBe(1);
+        expect(restored.getState('balance')).toBe(55);
+        expect(() => restored.truncateEventLog(0)).toThrow(expect.objectContaining({ code: 'INVALID_ARGUMENT' }));
+    });
+
+    it('should hold the Web Lock while writing and wait while another tab has it', async () => {
+        let heldElsewhere = true;
+        const granted = [];
+        Object.defineProperty(global.navigator, 'locks', {
+            configurable: true,
+            value: {
+                request: async (name, options, callback) => {
+                    if (heldElsewhere && options.ifAvailable) {
+                        return callback(null);
+                    }
+                    while (heldElsewhere) {
+                        await new Promise(resolve => setTimeout(resolve, 5));
+                    }
+                    granted.push(name);
+                    return callback({ name, mode: 'exclusive' });
+                }
+            }
+        });
+        storage.enablePersistence('app');
+        storage.enableWebLocks({ timeoutMs: 1000 });
+        const contended = [];
+        storage.onLockContended(event => contended.push(event));
+
+        storage.setState('draft', 'v1');
+        storage.setState('draft', 'v2');
+        await new Promise(resolve => setTimeout(resolve, 20));
+        expect(localStorage.getItem('app')).toBeNull();
+        expect(contended).toEqual([{ lock: 'wasm-storage:app', queued: 1 }]);
+
+        heldElsewhere = false;
+        await new Promise(resolve => setTimeout(resolve, 20));
+        expect(granted).toEqual(['wasm-storage:app']);
+        expect(JSON.parse(localStorage.getItem('app')).data).toEqual({ draft: 'v2' });
+        delete global.navigator.locks;
+    });
 });
 
 // tests/async-actions.test.js
@@ -403,7 +3070,7 @@ describe('Async Actions', () => {
     let storage, asyncManager;
 
     beforeEach(async () => {
-        const { storage: wasmStorage } = await createWasmStorage('../pkg/wasm_storage.js');
+        const { storage: wasmStorage } = await createWasmStorage('../pkg/wasm_storage.js', { unhandledActions: 'legacy' });
         const { AsyncActionManager } = await import('../src/wasm-storage.js');
         
         storage = wasmStorage;
@@ -0,0 +1,202 @@
+use crate::error::WasmStorageError;
+use crate::origin::Origin;
+use crate::WasmStorage;
+use wasm_bindgen::prelude::*;
+use std::collections::HashMap;
+use wasm_bindgen::JsCast;
+
+/// Lifecycle boundary after which a transient key is dropped.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum TransientScope {
+    Navigation,
+    Visibility,
+    Session,
+}
+
+impl TransientScope {
+    fn parse(scope: &str) -> Result<TransientScope, JsValue> {
+        match scope {
+            "navigation" => Ok(TransientScope::Navigation),
+            "visibility" => Ok(TransientScope::Visibility),
+            "session" => Ok(TransientScope::Session),
+            _ => Err(WasmStorageError::invalid_argument(format!(
+                "Unknown transient scope \"{}\" (expected navigation, visibility or session)",
+                scope
+            )).into()),
+        }
+    }
+}
+
+/// Transient keys and their scopes, plus the key `set_transient` is
+/// writing, so that write doesn't make the key regular again.
+#[derive(Default)]
+pub(crate) struct TransientKeys {
+    scopes: HashMap<String, TransientScope>,
+    // The key, and whether it held a persisted value before
+    writing: Option<(String, bool)>,
+}
+
+impl TransientKeys {
+    pub(crate) fn contains(&self, key: &str) -> bool {
+        self.scopes.contains_key(key)
+    }
+}
+
+/// How persistence treats a write, see `settle_transient`.
+pub(crate) enum TransientWrite {
+    Regular,
+    Transient,
+    // A persisted key written by `set_transient`
+    BecameTransient,
+}
+
+type EventListener = (web_sys::EventTarget, &'static str, Closure<dyn FnMut(web_sys::Event)>);
+
+// Lifecycle listeners, installed once on the first transient write
+pub(crate) struct TransientHooks {
+    listeners: Vec<EventListener>,
+}
+
+#[wasm_bindgen]
+impl WasmStorage {
+    /// Stores a value that is dropped automatically on the next `scope`
+    /// boundary: `"navigation"` (popstate/hashchange), `"visibility"` (tab
+    /// hidden) or `"session"` (pagehide). Transient keys are never persisted.
+    #[wasm_bindgen]
+    pub fn set_transient(&mut self, key: &str, value: JsValue, scope: &str) -> Result<(), JsValue> {
+        let scope = TransientScope::parse(scope)?;
+        self.install_transient_hooks()?;
+        let was_transient = self.is_transient(key)?;
+        let persisted = !was_transient && self.state.lock().map_err(WasmStorageError::lock_poisoned)?.contains_key(key);
+        {
+            let mut transient = self.transient.lock().map_err(WasmStorageError::lock_poisoned)?;
+            transient.scopes.insert(key.to_string(), scope);
+            transient.writing = Some((key.to_string(), persisted));
+        }
+        let result = self.set_state(key, value);
+        self.transient.lock().map_err(WasmStorageError::lock_poisoned)?.writing = None;
+        if result.is_err() && !was_transient {
+            self.forget_transient(key)?;
+        }
+        result
+    }
+
+    #[wasm_bindgen]
+    pub fn is_transient(&self, key: &str) -> Result<bool, JsValue> {
+        let transient = self.transient.lock().map_err(WasmStorageError::lock_poisoned)?;
+        Ok(transient.contains(key))
+    }
+
+    /// Drops every transient key of `scope` now; routers using `pushState`
+    /// (which fires no event) call this with `"navigation"`.
+    #[wasm_bindgen]
+    pub fn end_transient_scope(&mut self, scope: &str) -> Result<(), JsValue> {
+        let scope = TransientScope::parse(scope)?;
+        self.expire_transient(scope)
+    }
+}
+
+impl WasmStorage {
+    fn forget_transient(&self, key: &str) -> Result<(), JsValue> {
+        self.transient
+            .lock()
+            .map_err(WasmStorageError::lock_poisoned)?
+            .scopes
+            .remove(key);
+        Ok(())
+    }
+
+    pub(crate) fn forget_all_transient(&self) -> Result<(), JsValue> {
+        self.transient.lock().map_err(WasmStorageError::lock_poisoned)?.scopes.clear();
+        Ok(())
+    }
+
+    /// Called for every write: `set_transient`'s own write keeps the key
+    /// transient, any other write makes it regular again, and removing a
+    /// transient key needs no persisting since its value never was.
+    pub(crate) fn settle_transient(&self, key: &str, present: bool) -> Result<TransientWrite, JsValue> {
+        let mut transient = self.transient.lock().map_err(WasmStorageError::lock_poisoned)?;
+        match &transient.writing {
+            Some((writing, true)) if writing == key => return Ok(TransientWrite::BecameTransient),
+            Some((writing, false)) if writing == key => return Ok(TransientWrite::Transient),
+            _ => {}
+        }
+        let was_transient = transient.scopes.remove(key).is_some();
+        Ok(if was_transient && !present { TransientWrite::Transient } else { TransientWrite::Regular })
+    }
+
+    fn expire_transient(&mut self, scope: TransientScope) -> Result<(), JsValue> {
+        let expired: Vec<String> = {
+            let transient = self.transient.lock().map_err(WasmStorageError::lock_poisoned)?;
+            transient
+                .scopes
+                .iter()
+                .filter(|(_, key_scope)| **key_scope == scope)
+                .map(|(key, _)| key.clone())
+                .collect()
+        };
+        self.with_origin(Origin::System, |store| {
+            for key in expired {
+                store.remove_state(&key)?;
+            }
+            Ok(())
+        })
+    }
+
+    fn install_transient_hooks(&self) -> Result<(), JsValue> {
+        let mut hooks = self.transient_hooks.lock().map_err(WasmStorageError::lock_poisoned)?;
+        if hooks.is_some() {
+            return Ok(());
+        }
+        // Outside a window (workers) only end_transient_scope applies
+        let Some(window) = web_sys::window() else {
+            return Ok(());
+        };
+
+        let mut listeners = Vec::new();
+        let mut listen = |target: &web_sys::EventTarget, event: &'static str, scope: TransientScope| -> Result<(), JsValue> {
+            let mut store = self.clone();
+            let listener = Closure::wrap(Box::new(move |_: web_sys::Event| {
+                if scope == TransientScope::Visibility && !is_document_hidden() {
+                    return;
+                }
+                if let Err(e) = store.expire_transient(scope) {
+                    log_warn!("Failed to expire transient state: {:?}", e);
+                }
+            }) as Box<dyn FnMut(web_sys::Event)>);
+            target.add_event_listener_with_callback(event, listener.as_ref().unchecked_ref())?;
+            listeners.push((target.clone(), event, listener));
+            Ok(())
+        };
+
+        listen(&window, "popstate", TransientScope::Navigation)?;
+        listen(&window, "hashchange", TransientScope::Navigation)?;
+        if let Ok(navigation) = js_sys::Reflect::get(&window, &JsValue::from_str("navigation")) {
+            if let Some(navigation) = navigation.dyn_ref::<web_sys::EventTarget>() {
+                listen(navigation, "navigatesuccess", TransientScope::Navigation)?;
+            }
+        }
+        if let Some(document) = window.document() {
+            listen(&document, "visibilitychange", TransientScope::Visibility)?;
+        }
+        listen(&window, "pagehide", TransientScope::Session)?;
+
+        *hooks = Some(TransientHooks { listeners });
+        Ok(())
+    }
+
+    /// Detaches the lifecycle listeners again, on `destroy`.
+    pub(crate) fn remove_transient_hooks(&self) -> Result<(), JsValue> {
+        let hooks = self.transient_hooks.lock().map_err(WasmStorageError::lock_poisoned)?.take();
+        for (target, event, listener) in hooks.into_iter().flat_map(|hooks| hooks.listeners) {
+            target.remove_event_listener_with_callback(event, listener.as_ref().unchecked_ref())?;
+        }
+        Ok(())
+    }
+}
+
+fn is_document_hidden() -> bool {
+    web_sys::window()
+        .and_then(|window| window.document())
+        .is_some_and(|document| document.hidden())
+}
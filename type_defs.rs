@@ -0,0 +1,64 @@
+use crate::error::WasmStorageError;
+use crate::json_schema::property_name;
+use crate::validators::Validator;
+use crate::WasmStorage;
+use wasm_bindgen::prelude::*;
+
+#[wasm_bindgen]
+impl WasmStorage {
+    /// Registers a JSON Schema validator for each key of `schema`
+    /// (`{ key: jsonSchema }`), which `type_definitions` then turns into
+    /// TypeScript types.
+    #[wasm_bindgen]
+    pub fn define_schema(&mut self, schema: js_sys::Object) -> Result<(), JsValue> {
+        for entry in js_sys::Object::entries(&schema).iter() {
+            let pair = js_sys::Array::from(&entry);
+            let key = pair.get(0).as_string().unwrap_or_default();
+            let key_schema = pair.get(1);
+            if !key_schema.is_object() || key_schema.is_function() {
+                return Err(WasmStorageError::invalid_argument(format!("The schema for \"{}\" must be a JSON Schema object", key))
+                    .with("key", key.as_str())
+                    .into());
+            }
+            self.register_validator(&key, key_schema)?;
+        }
+        Ok(())
+    }
+
+    /// TypeScript declarations for every key with a JSON Schema validator:
+    /// a `StorageState` interface of key -> value type and a
+    /// `TypedWasmStorage` interface typing the wrapper's reads and writes by
+    /// key. Glob patterns aren't keys and are left out. Write the result to
+    /// a `.d.ts` file at build time.
+    #[wasm_bindgen]
+    pub fn type_definitions(&self) -> Result<String, JsValue> {
+        let validators = self.validators.lock().map_err(WasmStorageError::lock_poisoned)?;
+        let members: Vec<String> = validators
+            .iter()
+            .filter(|(pattern, _)| !pattern.contains(['*', '?']))
+            .filter_map(|(key, validator)| match validator {
+                Validator::Schema(schema) => Some(format!("    {}: {};", property_name(key), schema.typescript(1))),
+                Validator::Function(_) => None,
+            })
+            .collect();
+        drop(validators);
+
+        let mut definitions = String::from("// Generated by type_definitions(); regenerate after changing schemas.\n\n");
+        definitions.push_str("export interface StorageState {\n");
+        for member in members {
+            definitions.push_str(&member);
+            definitions.push('\n');
+        }
+        definitions.push_str(
+            "}\n\n\
+             export interface TypedWasmStorage {\n    \
+             getState<K extends keyof StorageState>(key: K): StorageState[K] | null;\n    \
+             getStateCloned<K extends keyof StorageState>(key: K): StorageState[K] | null;\n    \
+             setState<K extends keyof StorageState>(key: K, value: StorageState[K]): void;\n    \
+             has(key: keyof StorageState): boolean;\n    \
+             remove(key: keyof StorageState): void;\n\
+             }\n",
+        );
+        Ok(definitions)
+    }
+}
@@ -0,0 +1,147 @@
+use crate::error::WasmStorageError;
+use crate::WasmStorage;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+
+// The binding serde-wasm-bindgen fills objects with. Its `preserve`
+// passthrough for `JsValue`s only exists from 0.5, so the store's JS values
+// can't go through serde itself; this plain property write is what makes
+// its conversions fast, replacing a fallible `Reflect` call per key.
+#[wasm_bindgen]
+extern "C" {
+    #[wasm_bindgen(extends = js_sys::Object)]
+    pub(crate) type PlainObject;
+
+    #[wasm_bindgen(method, indexing_setter)]
+    pub(crate) fn set(this: &PlainObject, key: &str, value: &JsValue);
+}
+
+#[wasm_bindgen]
+impl WasmStorage {
+    /// Typed reads: each throws a `WasmStorageError` with code `KEY_MISSING`
+    /// or `TYPE_MISMATCH` (context `{ key, expected, actual }`) instead of
+    /// handing back something the caller didn't expect.
+    #[wasm_bindgen]
+    pub fn get_string(&self, key: &str) -> Result<String, JsValue> {
+        let value = self.get_required(key)?;
+        value
+            .as_string()
+            .ok_or_else(|| WasmStorageError::type_mismatch(key, "string", &value).into())
+    }
+
+    #[wasm_bindgen]
+    pub fn get_number(&self, key: &str) -> Result<f64, JsValue> {
+        let value = self.get_required(key)?;
+        value
+            .as_f64()
+            .ok_or_else(|| WasmStorageError::type_mismatch(key, "number", &value).into())
+    }
+
+    #[wasm_bindgen]
+    pub fn get_bool(&self, key: &str) -> Result<bool, JsValue> {
+        let value = self.get_required(key)?;
+        value
+            .as_bool()
+            .ok_or_else(|| WasmStorageError::type_mismatch(key, "boolean", &value).into())
+    }
+
+    /// Accepts plain objects only; arrays and null are type mismatches.
+    #[wasm_bindgen]
+    pub fn get_object(&self, key: &str) -> Result<js_sys::Object, JsValue> {
+        let value = self.get_required(key)?;
+        if !value.is_object() || js_sys::Array::is_array(&value) || value.is_function() {
+            return Err(WasmStorageError::type_mismatch(key, "object", &value).into());
+        }
+        Ok(value.unchecked_into())
+    }
+}
+
+impl WasmStorage {
+    /// Rust-side write of any `Serialize` value, converted with
+    /// serde-wasm-bindgen in one pass; maps become plain objects so JS
+    /// readers see the same shape as a `set_state` from JS.
+    pub fn set_state_serde<T: Serialize + ?Sized>(&mut self, key: &str, value: &T) -> Result<(), JsValue> {
+        let serializer = serde_wasm_bindgen::Serializer::new().serialize_maps_as_objects(true);
+        let value = value
+            .serialize(&serializer)
+            .map_err(|error| WasmStorageError::invalid_argument(format!("Can't convert the value for \"{}\": {}", key, error)).with("key", key))?;
+        self.set_state(key, value)
+    }
+
+    /// Rust-side typed read; a value that doesn't deserialize into `T` is a
+    /// TYPE_MISMATCH, a missing key KEY_MISSING.
+    pub fn get_state_serde<T: DeserializeOwned>(&self, key: &str) -> Result<T, JsValue> {
+        let value = self.get_required(key)?;
+        serde_wasm_bindgen::from_value(value.clone())
+            .map_err(|_| WasmStorageError::type_mismatch(key, std::any::type_name::<T>(), &value).into())
+    }
+
+    fn get_required(&self, key: &str) -> Result<JsValue, JsValue> {
+        // Denied keys fail as denied, not as missing
+        self.ensure_readable(key)?;
+        if !self.has(key)? {
+            return Err(WasmStorageError::key_missing(key).into());
+        }
+        self.get_state(key)
+    }
+}
+
+#[cfg(all(test, target_arch = "wasm32"))]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+    use std::collections::BTreeMap;
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Profile {
+        name: String,
+        tags: Vec<String>,
+        scores: BTreeMap<String, u32>,
+    }
+
+    // Fails the way a value with no JS form would
+    struct Unserializable;
+
+    impl Serialize for Unserializable {
+        fn serialize<S: serde::Serializer>(&self, _serializer: S) -> Result<S::Ok, S::Error> {
+            Err(serde::ser::Error::custom("no JS form"))
+        }
+    }
+
+    fn code(error: &JsValue) -> Option<String> {
+        js_sys::Reflect::get(error, &JsValue::from_str("code")).ok()?.as_string()
+    }
+
+    #[wasm_bindgen_test]
+    fn round_trips_serde_values() {
+        let mut storage = WasmStorage::new(None);
+        let profile = Profile {
+            name: "Ada".to_string(),
+            tags: vec!["admin".to_string()],
+            scores: BTreeMap::from([("chess".to_string(), 3)]),
+        };
+        storage.set_state_serde("profile", &profile).unwrap();
+
+        assert_eq!(storage.get_state_serde::<Profile>("profile").unwrap(), profile);
+        // Maps arrive as plain objects, not JS Maps
+        let scores = js_sys::Reflect::get(&storage.get_state("profile").unwrap(), &JsValue::from_str("scores")).unwrap();
+        assert_eq!(js_sys::Reflect::get(&scores, &JsValue::from_str("chess")).unwrap().as_f64(), Some(3.0));
+    }
+
+    #[wasm_bindgen_test]
+    fn rejects_values_serde_cannot_convert() {
+        let mut storage = WasmStorage::new(None);
+        let error = storage.set_state_serde("broken", &Unserializable).unwrap_err();
+        assert_eq!(code(&error).as_deref(), Some("INVALID_ARGUMENT"));
+        assert!(!storage.has("broken").unwrap());
+
+        storage.set_state("name", JsValue::from_str("Ada")).unwrap();
+        let error = storage.get_state_serde::<u32>("name").unwrap_err();
+        assert_eq!(code(&error).as_deref(), Some("TYPE_MISMATCH"));
+        let error = storage.get_state_serde::<u32>("missing").unwrap_err();
+        assert_eq!(code(&error).as_deref(), Some("KEY_MISSING"));
+    }
+}
@@ -0,0 +1,84 @@
+use crate::error::WasmStorageError;
+use crate::origin::Origin;
+use crate::{StorageEvent, WasmStorage};
+use wasm_bindgen::prelude::*;
+
+/// What happens to an action no reducer or effect handles.
+#[derive(Clone, Copy, Default, PartialEq)]
+enum Mode {
+    #[default]
+    Ignore,
+    Error,
+    // Keep the payload under `__actions_<type>`, as the store used to
+    Legacy,
+}
+
+impl Mode {
+    fn parse(mode: &str) -> Result<Mode, JsValue> {
+        match mode {
+            "ignore" => Ok(Mode::Ignore),
+            "error" => Ok(Mode::Error),
+            "legacy" => Ok(Mode::Legacy),
+            _ => Err(WasmStorageError::invalid_argument(format!("Unknown unhandled action mode \"{}\" (expected ignore, error or legacy)", mode))
+                .with("mode", mode)
+                .into()),
+        }
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct UnhandledActions {
+    mode: Mode,
+    hook: Option<js_sys::Function>,
+}
+
+#[wasm_bindgen]
+impl WasmStorage {
+    /// Calls `callback({ type, payload, timestamp })` for every dispatched
+    /// action that no built-in, reducer or effect handles; pass null to
+    /// remove it. Replayed actions don't call it.
+    #[wasm_bindgen]
+    pub fn on_unhandled_action(&mut self, callback: Option<js_sys::Function>) -> Result<(), JsValue> {
+        self.unhandled.lock().map_err(WasmStorageError::lock_poisoned)?.hook = callback;
+        Ok(())
+    }
+
+    /// Sets what happens to unhandled actions besides the hook: `"ignore"`
+    /// (the default) leaves the state alone, `"error"` fails the dispatch
+    /// with INVALID_ARGUMENT, and `"legacy"` stores the payload under
+    /// `__actions_<type>` like earlier versions did, effects or not.
+    #[wasm_bindgen]
+    pub fn set_unhandled_actions(&mut self, mode: &str) -> Result<(), JsValue> {
+        self.unhandled.lock().map_err(WasmStorageError::lock_poisoned)?.mode = Mode::parse(mode)?;
+        Ok(())
+    }
+}
+
+impl WasmStorage {
+    /// Runs for actions the built-ins and reducers didn't handle.
+    pub(crate) fn handle_unhandled(&mut self, event: &StorageEvent) -> Result<(), JsValue> {
+        let (mode, hook) = {
+            let unhandled = self.unhandled.lock().map_err(WasmStorageError::lock_poisoned)?;
+            (unhandled.mode, unhandled.hook.clone())
+        };
+        if mode == Mode::Legacy {
+            let actions_key = format!("__actions_{}", event.action_type);
+            self.ensure_write_allowed(&actions_key, Some(&event.payload))?;
+            self.harden(&event.payload)?;
+            self.state.lock().map_err(WasmStorageError::lock_poisoned)?.insert(actions_key, event.payload.clone());
+            self.invalidate_snapshot()?;
+        }
+        if self.has_effect_for(&event.action_type)? || self.current_origin()? == Origin::Replay {
+            return Ok(());
+        }
+        if let Some(hook) = hook {
+            hook.call1(&JsValue::NULL, &event.to_json()?.into())?;
+        }
+        if mode == Mode::Error {
+            return Err(WasmStorageError::invalid_argument(format!("No reducer or effect handles action \"{}\"", event.action_type))
+                .with("actionType", event.action_type.as_str())
+                .into());
+        }
+        Ok(())
+    }
+}
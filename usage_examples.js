@@ -371,7 +371,7 @@ class DevToolsIntegration {
         // Connect to Redux DevTools if available
         if (typeof window !== 'undefined' && window.__REDUX_DEVTOOLS_EXTENSION__) {
             this.devTools = window.__REDUX_DEVTOOLS_EXTENSION__.connect({
-                name: 'WASM Storage'
+                name: this.wasmStorage.name ? `WASM Storage (${this.wasmStorage.name})` : 'WASM Storage'
             });
             
             this.devTools.init(this.wasmStorage.getAllState());
@@ -0,0 +1,93 @@
+use crate::error::{ErrorCode, WasmStorageError};
+use crate::json_schema::Schema;
+use crate::{glob_match, WasmStorage};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+
+#[derive(Clone)]
+pub(crate) enum Validator {
+    Function(js_sys::Function),
+    Schema(Box<Schema>),
+}
+
+impl Validator {
+    // A function may return false, a message string or throw to reject
+    fn check(&self, key: &str, value: &JsValue) -> Result<(), String> {
+        match self {
+            Validator::Schema(schema) => schema.validate(value, "$"),
+            Validator::Function(validator) => match validator.call2(&JsValue::NULL, value, &JsValue::from_str(key)) {
+                Ok(verdict) if verdict.as_bool() == Some(false) => Err("rejected by validator".to_string()),
+                Ok(verdict) => match verdict.as_string() {
+                    Some(message) => Err(message),
+                    None => Ok(()),
+                },
+                Err(error) => Err(error_message(&error)),
+            },
+        }
+    }
+}
+
+fn error_message(error: &JsValue) -> String {
+    if let Some(message) = error.as_string() {
+        return message;
+    }
+    js_sys::Reflect::get(error, &JsValue::from_str("message"))
+        .ok()
+        .and_then(|message| message.as_string())
+        .unwrap_or_else(|| format!("{:?}", error))
+}
+
+/// Validators by key or glob pattern, in registration order.
+pub(crate) type Validators = Vec<(String, Validator)>;
+
+#[wasm_bindgen]
+impl WasmStorage {
+    /// Validates every value written to keys matching `pattern` (a key or a
+    /// glob such as `user.*`), including reducer output. `validator` is either
+    /// a function `(value, key)` returning false or an error message to
+    /// reject, or a JSON Schema object checked inside the module without
+    /// calling back into JS. Registering the same pattern again replaces it.
+    #[wasm_bindgen]
+    pub fn register_validator(&mut self, pattern: &str, validator: JsValue) -> Result<(), JsValue> {
+        let validator = match validator.dyn_into::<js_sys::Function>() {
+            Ok(function) => Validator::Function(function),
+            Err(schema) => Validator::Schema(Box::new(Schema::compile(&schema)?)),
+        };
+        let mut validators = self.validators.lock().map_err(WasmStorageError::lock_poisoned)?;
+        validators.retain(|(registered, _)| registered != pattern);
+        validators.push((pattern.to_string(), validator));
+        Ok(())
+    }
+
+    #[wasm_bindgen]
+    pub fn unregister_validator(&mut self, pattern: &str) -> Result<(), JsValue> {
+        let mut validators = self.validators.lock().map_err(WasmStorageError::lock_poisoned)?;
+        validators.retain(|(registered, _)| registered != pattern);
+        Ok(())
+    }
+}
+
+impl WasmStorage {
+    /// Runs every validator matching `key`, then checks the key's state
+    /// machine, that the key isn't mirrored read-only from a host frame and
+    /// that a shared key's slot can hold the value; the error names the key
+    /// and the first violation.
+    pub(crate) fn validate(&self, key: &str, value: &JsValue) -> Result<(), JsValue> {
+        let matching: Vec<Validator> = {
+            let validators = self.validators.lock().map_err(WasmStorageError::lock_poisoned)?;
+            validators
+                .iter()
+                .filter(|(pattern, _)| glob_match(pattern, key))
+                .map(|(_, validator)| validator.clone())
+                .collect()
+        };
+        for validator in matching.iter() {
+            if let Err(message) = validator.check(key, value) {
+                return Err(WasmStorageError::new(ErrorCode::ValidationFailed, format!("Invalid value for \"{}\": {}", key, message)).with("key", key).with("reason", message.as_str()).into());
+            }
+        }
+        self.check_transition(key, value)?;
+        self.check_frame_writable(key)?;
+        self.check_shareable(key, value)
+    }
+}
@@ -0,0 +1,144 @@
+use crate::error::WasmStorageError;
+use crate::subscriptions::Subscription;
+use crate::WasmStorage;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+
+/// The keys a view can see.
+#[derive(Clone)]
+enum Scope {
+    Keys(Vec<String>),
+    Prefix(String),
+}
+
+impl Scope {
+    fn from_js(keys_or_prefix: &JsValue) -> Result<Scope, JsValue> {
+        if let Some(prefix) = keys_or_prefix.as_string() {
+            return Ok(Scope::Prefix(prefix));
+        }
+        let keys = keys_or_prefix
+            .dyn_ref::<js_sys::Array>()
+            .ok_or_else(|| WasmStorageError::invalid_argument("create_view expects an array of keys or a key prefix"))?;
+        let keys = keys
+            .iter()
+            .map(|key| key.as_string().ok_or_else(|| WasmStorageError::invalid_argument("View keys must be strings").with("key", key).into()))
+            .collect::<Result<Vec<String>, JsValue>>()?;
+        Ok(Scope::Keys(keys))
+    }
+
+    fn contains(&self, key: &str) -> bool {
+        match self {
+            Scope::Keys(keys) => keys.iter().any(|k| k == key),
+            Scope::Prefix(prefix) => key.starts_with(prefix.as_str()),
+        }
+    }
+
+    // Passes a change event on, or an aggregate event with the changes in
+    // scope; None when nothing in it is visible
+    fn filter_event(&self, event: &JsValue) -> Result<Option<JsValue>, JsValue> {
+        if let Some(key) = js_sys::Reflect::get(event, &JsValue::from_str("key"))?.as_string() {
+            return Ok(self.contains(&key).then(|| event.clone()));
+        }
+        let changes = js_sys::Reflect::get(event, &JsValue::from_str("changes"))?;
+        let Some(changes) = changes.dyn_ref::<js_sys::Array>() else {
+            return Ok(None);
+        };
+        let visible: js_sys::Array = changes
+            .iter()
+            .filter(|change| {
+                js_sys::Reflect::get(change, &JsValue::from_str("key"))
+                    .ok()
+                    .and_then(|key| key.as_string())
+                    .is_some_and(|key| self.contains(&key))
+            })
+            .collect();
+        if visible.length() == 0 {
+            return Ok(None);
+        }
+        let filtered = js_sys::Object::new();
+        js_sys::Reflect::set(&filtered, &JsValue::from_str("changes"), &visible)?;
+        js_sys::Reflect::set(&filtered, &JsValue::from_str("timestamp"), &js_sys::Reflect::get(event, &JsValue::from_str("timestamp"))?)?;
+        Ok(Some(filtered.into()))
+    }
+}
+
+/// A read-only window on some keys of a store, from `create_view`. It reads
+/// the live state and can subscribe, but has no way to write.
+#[wasm_bindgen]
+pub struct StoreView {
+    store: WasmStorage,
+    scope: Scope,
+}
+
+#[wasm_bindgen]
+impl WasmStorage {
+    /// A read-only handle on the keys in `keys_or_prefix` (an array of keys,
+    /// or a prefix string) for third-party plugins that should observe some
+    /// state but never change it. Unlike `read_only_view`, keys outside the
+    /// view can't be read or heard.
+    #[wasm_bindgen]
+    pub fn create_view(&self, keys_or_prefix: JsValue) -> Result<StoreView, JsValue> {
+        Ok(StoreView { store: self.clone(), scope: Scope::from_js(&keys_or_prefix)? })
+    }
+}
+
+#[wasm_bindgen]
+impl StoreView {
+    /// The value of `key`, or null when unset. Keys outside the view fail
+    /// with INVALID_ARGUMENT.
+    #[wasm_bindgen]
+    pub fn get(&self, key: &str) -> Result<JsValue, JsValue> {
+        if !self.scope.contains(key) {
+            return Err(WasmStorageError::invalid_argument(format!("Key \"{}\" is outside this view", key)).with("key", key).into());
+        }
+        self.store.get_state(key)
+    }
+
+    #[wasm_bindgen]
+    pub fn has(&self, key: &str) -> Result<bool, JsValue> {
+        Ok(self.scope.contains(key) && self.store.has(key)?)
+    }
+
+    /// The set keys in the view, sorted.
+    #[wasm_bindgen]
+    pub fn keys(&self) -> Result<js_sys::Array, JsValue> {
+        let keys: Vec<String> = self
+            .store
+            .state
+            .lock()
+            .map_err(WasmStorageError::lock_poisoned)?
+            .keys()
+            .filter(|key| self.scope.contains(key))
+            .cloned()
+            .collect();
+        let mut keys = self.store.retain_readable(keys, |key| key)?;
+        keys.sort();
+        Ok(keys.iter().map(|key| JsValue::from_str(key)).collect())
+    }
+
+    /// The set keys in the view and their values, as an object.
+    #[wasm_bindgen]
+    pub fn get_all(&self) -> Result<js_sys::Object, JsValue> {
+        let all = js_sys::Object::new();
+        for key in self.keys()?.iter() {
+            let value = self.store.get_state(&key.as_string().unwrap_or_default())?;
+            js_sys::Reflect::set(&all, &key, &value)?;
+        }
+        Ok(all)
+    }
+
+    /// Like the store's `subscribe`, with the same options, but only hears
+    /// changes to keys in the view. Aggregate listeners get the changes in
+    /// the view, and nothing when none of them are.
+    #[wasm_bindgen]
+    pub fn subscribe(&mut self, callback: js_sys::Function, options: Option<js_sys::Object>) -> Result<Subscription, JsValue> {
+        let scope = self.scope.clone();
+        let listener = Closure::wrap(Box::new(move |event: JsValue| -> Result<(), JsValue> {
+            if let Some(event) = scope.filter_event(&event)? {
+                callback.call1(&JsValue::NULL, &event)?;
+            }
+            Ok(())
+        }) as Box<dyn FnMut(JsValue) -> Result<(), JsValue>>);
+        self.store.subscribe(listener.into_js_value().unchecked_into(), options)
+    }
+}
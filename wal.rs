@@ -0,0 +1,129 @@
+use crate::backends::Backend;
+use crate::persistence::js_string;
+use crate::signing::Signing;
+use wasm_bindgen::prelude::*;
+
+const DEFAULT_COMPACT_AFTER: u32 = 200;
+
+/// Append-only log of the writes made since the last snapshot, stored next
+/// to it in the backend. Appending one entry is much cheaper than writing a
+/// whole snapshot, so every change reaches storage immediately; snapshots
+/// (compactions) can then be infrequent. On startup, entries newer than the
+/// snapshot are replayed onto it.
+pub(crate) struct Wal {
+    key: String,
+    seq: u64,
+    length: u32,
+    compact_after: u32,
+}
+
+/// Backend item holding the log for `storage_key`.
+pub(crate) fn wal_key(storage_key: &str) -> String {
+    format!("{}::wal", storage_key)
+}
+
+impl Wal {
+    pub(crate) fn new(storage_key: &str, compact_after: Option<u32>) -> Wal {
+        Wal {
+            key: wal_key(storage_key),
+            seq: 0,
+            length: 0,
+            compact_after: compact_after.unwrap_or(DEFAULT_COMPACT_AFTER).max(1),
+        }
+    }
+
+    /// Sequence number of the last appended entry; snapshots record it so
+    /// recovery can skip the entries they already contain.
+    pub(crate) fn seq(&self) -> u64 {
+        self.seq
+    }
+
+    pub(crate) fn needs_compaction(&self) -> bool {
+        self.length >= self.compact_after
+    }
+
+    /// Appends a set (`value_json` is Some), removal (None) or, with no key,
+    /// a clear; signed when `signing` is given.
+    pub(crate) fn append(
+        &mut self,
+        backend: &Backend,
+        signing: Option<&Signing>,
+        key: Option<&str>,
+        value_json: Option<&str>,
+    ) -> Result<(), JsValue> {
+        self.seq += 1;
+        let entry = match (key, value_json) {
+            (None, _) => format!("{{\"seq\":{},\"op\":\"clear\"}}", self.seq),
+            (Some(key), None) => format!("{{\"seq\":{},\"op\":\"remove\",\"key\":{}}}", self.seq, js_string(key)),
+            (Some(key), Some(json)) => {
+                format!("{{\"seq\":{},\"op\":\"set\",\"key\":{},\"value\":{}}}", self.seq, js_string(key), json)
+            }
+        };
+        let entry = match signing {
+            Some(signing) => signing.sign_object(&entry),
+            None => entry,
+        };
+        backend.append_line(&self.key, &entry)?;
+        self.length += 1;
+        Ok(())
+    }
+
+    /// Drops every entry, once a snapshot containing them has been written.
+    pub(crate) fn truncate(&mut self, backend: &Backend) -> Result<(), JsValue> {
+        backend.remove_item(&self.key)?;
+        self.length = 0;
+        Ok(())
+    }
+
+    /// Applies the entries after `snapshot_seq` to `data`, the snapshot's raw
+    /// key -> value object, and continues numbering after the last entry. A
+    /// torn final entry (the page died mid-write) is ignored; a complete entry
+    /// that fails signature verification is an error.
+    pub(crate) fn replay(
+        &mut self,
+        backend: &Backend,
+        signing: Option<&Signing>,
+        data: &js_sys::Object,
+        snapshot_seq: u64,
+    ) -> Result<u32, JsValue> {
+        self.seq = snapshot_seq;
+        let Some(log) = backend.get_item(&self.key)? else {
+            return Ok(0);
+        };
+
+        let mut replayed = 0;
+        for line in log.lines() {
+            let Ok(mut entry) = js_sys::JSON::parse(line) else {
+                continue;
+            };
+            if let Some(signing) = signing {
+                entry = js_sys::JSON::parse(&signing.verify_object(line, "Write-ahead log entry")?)?;
+            }
+            let field = |name: &str| js_sys::Reflect::get(&entry, &JsValue::from_str(name)).unwrap_or(JsValue::UNDEFINED);
+            let seq = field("seq").as_f64().unwrap_or(0.0) as u64;
+            self.seq = self.seq.max(seq);
+            self.length += 1;
+            if seq <= snapshot_seq {
+                continue;
+            }
+
+            let key = field("key");
+            match field("op").as_string().as_deref() {
+                Some("set") => {
+                    js_sys::Reflect::set(data, &key, &field("value"))?;
+                }
+                Some("remove") => {
+                    js_sys::Reflect::delete_property(data, &key)?;
+                }
+                Some("clear") => {
+                    for stale in js_sys::Object::keys(data).iter() {
+                        js_sys::Reflect::delete_property(data, &stale)?;
+                    }
+                }
+                _ => continue,
+            }
+            replayed += 1;
+        }
+        Ok(replayed)
+    }
+}
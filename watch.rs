@@ -0,0 +1,123 @@
+use crate::error::WasmStorageError;
+use crate::subscriptions::{immediate, Subscription};
+use crate::WasmStorage;
+use crate::sync::{Lock, Shared};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+
+#[wasm_bindgen]
+impl WasmStorage {
+    /// Calls `callback(value, previous, event)` when `key` changes in a way
+    /// `comparator(previous, value)` considers significant, e.g. crossing a
+    /// threshold. `previous` is the value at the last notification (or when
+    /// watching began), so small changes can add up to a significant one.
+    /// Without a comparator every change is significant. Exceptions thrown by
+    /// either function are reported like listener errors. With
+    /// `options.immediate`, `callback(value, null, event)` also runs at once
+    /// with the current value.
+    #[wasm_bindgen]
+    pub fn watch(
+        &mut self,
+        key: &str,
+        comparator: Option<js_sys::Function>,
+        callback: js_sys::Function,
+        options: Option<js_sys::Object>,
+    ) -> Result<Subscription, JsValue> {
+        self.ensure_readable(key)?;
+        let first_call = immediate(&options)?.then(|| callback.clone());
+        let watched = key.to_string();
+        let initial = self.state.lock().map_err(WasmStorageError::lock_poisoned)?.get(key).cloned();
+        let last_notified = Lock::new(initial.unwrap_or(JsValue::NULL));
+
+        let listener = Closure::wrap(Box::new(move |event: JsValue| -> Result<(), JsValue> {
+            let key = js_sys::Reflect::get(&event, &JsValue::from_str("key"))?.as_string();
+            if key.as_deref() != Some(watched.as_str()) {
+                return Ok(());
+            }
+            let value = js_sys::Reflect::get(&event, &JsValue::from_str("value"))?;
+            let previous = last_notified.lock().map_err(WasmStorageError::lock_poisoned)?.clone();
+            if let Some(comparator) = &comparator {
+                if !comparator.call2(&JsValue::NULL, &previous, &value)?.is_truthy() {
+                    return Ok(());
+                }
+            }
+            *last_notified.lock().map_err(WasmStorageError::lock_poisoned)? = value.clone();
+            callback.call3(&JsValue::NULL, &value, &previous, &event)?;
+            Ok(())
+        }) as Box<dyn FnMut(JsValue) -> Result<(), JsValue>>);
+
+        let subscription = self.subscribe(listener.into_js_value().unchecked_into(), None)?;
+        if let Some(callback) = first_call {
+            let change = self.current_change(key)?;
+            callback.call3(&JsValue::NULL, &change.value, &JsValue::NULL, &change.to_event()?.into())?;
+        }
+        Ok(subscription)
+    }
+
+    /// Resolves with the value of `key` the next time it changes, or the
+    /// next time `predicate(value, event)` passes, then unsubscribes. A
+    /// throwing predicate rejects the promise.
+    #[wasm_bindgen]
+    pub fn once(&mut self, key: &str, predicate: Option<js_sys::Function>) -> Result<js_sys::Promise, JsValue> {
+        self.next_change(key, predicate, false)
+    }
+
+    /// Like `once`, but resolves at once when the current value already
+    /// passes: "wait until the auth token is set". Without a predicate any
+    /// value other than null or undefined passes.
+    #[wasm_bindgen]
+    pub fn wait_for(&mut self, key: &str, predicate: Option<js_sys::Function>) -> Result<js_sys::Promise, JsValue> {
+        let current = self.current_change(key)?;
+        let passes = match &predicate {
+            Some(predicate) => predicate.call2(&JsValue::NULL, &current.value, &current.to_event()?.into())?.is_truthy(),
+            None => !current.value.is_null_or_undefined(),
+        };
+        if passes {
+            return Ok(js_sys::Promise::resolve(&current.value));
+        }
+        self.next_change(key, predicate, true)
+    }
+}
+
+impl WasmStorage {
+    // `present` makes a missing predicate require a value that isn't null or undefined
+    fn next_change(&mut self, key: &str, predicate: Option<js_sys::Function>, present: bool) -> Result<js_sys::Promise, JsValue> {
+        let mut settle = None;
+        let promise = js_sys::Promise::new(&mut |resolve, reject| settle = Some((resolve, reject)));
+        let Some((resolve, reject)) = settle else {
+            return Ok(promise);
+        };
+
+        let watched = key.to_string();
+        // Taken by the change that settles the promise
+        let pending: Shared<Lock<Option<Subscription>>> = Shared::new(Lock::new(None));
+        let settled = pending.clone();
+        let listener = Closure::wrap(Box::new(move |event: JsValue| -> Result<(), JsValue> {
+            let key = js_sys::Reflect::get(&event, &JsValue::from_str("key"))?.as_string();
+            if key.as_deref() != Some(watched.as_str()) {
+                return Ok(());
+            }
+            let value = js_sys::Reflect::get(&event, &JsValue::from_str("value"))?;
+            let passes = match &predicate {
+                Some(predicate) => predicate.call2(&JsValue::NULL, &value, &event).map(|passes| passes.is_truthy()),
+                None => Ok(!present || !value.is_null_or_undefined()),
+            };
+            if passes.as_ref().is_ok_and(|passes| !passes) {
+                return Ok(());
+            }
+            let Some(subscription) = settled.lock().map_err(WasmStorageError::lock_poisoned)?.take() else {
+                return Ok(());
+            };
+            subscription.unsubscribe()?;
+            match passes {
+                Ok(_) => resolve.call1(&JsValue::NULL, &value)?,
+                Err(error) => reject.call1(&JsValue::NULL, &error)?,
+            };
+            Ok(())
+        }) as Box<dyn FnMut(JsValue) -> Result<(), JsValue>>);
+
+        let subscription = self.subscribe(listener.into_js_value().unchecked_into(), None)?;
+        *pending.lock().map_err(WasmStorageError::lock_poisoned)? = Some(subscription);
+        Ok(promise)
+    }
+}
@@ -0,0 +1,314 @@
+use crate::error::WasmStorageError;
+use crate::subscriptions::Subscription;
+use crate::WasmStorage;
+use std::collections::HashMap;
+use crate::sync::{Lock, Shared};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+
+// Tags every message so a worker can run other postMessage traffic too
+const PROTOCOL: &str = "__wasmStorage";
+
+// Client subscription ID -> the host-side subscription and its listener
+type HostSubscriptions = Shared<Lock<HashMap<u32, (Subscription, Closure<dyn FnMut(JsValue)>)>>>;
+
+/// Serves a store to `WorkerStoreClient`s over postMessage. Create it in the
+/// worker that owns the real store, so reducers, middleware and persistence
+/// run off the main thread.
+#[wasm_bindgen]
+pub struct WorkerStoreHost {
+    endpoint: JsValue,
+    subscriptions: HostSubscriptions,
+    on_message: Closure<dyn FnMut(web_sys::MessageEvent)>,
+}
+
+struct ClientShared {
+    endpoint: JsValue,
+    next_id: Lock<u32>,
+    // Request ID -> (resolve, reject) of its promise
+    pending: Lock<HashMap<u32, (js_sys::Function, js_sys::Function)>>,
+    listeners: Lock<HashMap<u32, js_sys::Function>>,
+}
+
+/// Main-thread proxy for a store owned by a worker's `WorkerStoreHost`.
+/// Every call resolves once the worker has handled it; subscribers are
+/// called with the worker's change events.
+#[wasm_bindgen]
+pub struct WorkerStoreClient {
+    shared: Shared<ClientShared>,
+    on_message: Closure<dyn FnMut(web_sys::MessageEvent)>,
+}
+
+// Calls `method` on a Worker, MessagePort or worker global scope
+fn call(endpoint: &JsValue, method: &str, args: &[&JsValue]) -> Result<JsValue, JsValue> {
+    let function = js_sys::Reflect::get(endpoint, &JsValue::from_str(method))?
+        .dyn_into::<js_sys::Function>()
+        .map_err(|_| WasmStorageError::invalid_argument(format!("Endpoint has no {} method", method)))?;
+    let args: js_sys::Array = args.iter().copied().collect();
+    function.apply(endpoint, &args)
+}
+
+fn listen(endpoint: &JsValue, on_message: &Closure<dyn FnMut(web_sys::MessageEvent)>) -> Result<(), JsValue> {
+    call(endpoint, "addEventListener", &[&JsValue::from_str("message"), on_message.as_ref()])?;
+    // MessagePorts only deliver once started
+    if js_sys::Reflect::get(endpoint, &JsValue::from_str("start"))?.is_function() {
+        call(endpoint, "start", &[])?;
+    }
+    Ok(())
+}
+
+fn message(kind: &str, id: u32) -> Result<js_sys::Object, JsValue> {
+    let message = js_sys::Object::new();
+    js_sys::Reflect::set(&message, &JsValue::from_str(PROTOCOL), &JsValue::from_str(kind))?;
+    js_sys::Reflect::set(&message, &JsValue::from_str("id"), &JsValue::from(id))?;
+    Ok(message)
+}
+
+fn field(message: &JsValue, name: &str) -> JsValue {
+    js_sys::Reflect::get(message, &JsValue::from_str(name)).unwrap_or(JsValue::UNDEFINED)
+}
+
+// Store errors are wasm class instances, which postMessage can't clone
+fn transferable_error(error: &JsValue) -> JsValue {
+    let code = field(error, "code");
+    if !code.is_string() {
+        return error.clone();
+    }
+    let plain = js_sys::Object::new();
+    for name in ["code", "message", "context"] {
+        let _ = js_sys::Reflect::set(&plain, &JsValue::from_str(name), &field(error, name));
+    }
+    plain.into()
+}
+
+#[wasm_bindgen]
+impl WorkerStoreHost {
+    /// Answers requests arriving on `endpoint` (default: the worker's global
+    /// scope; a MessagePort also works).
+    #[wasm_bindgen(constructor)]
+    pub fn new(store: &WasmStorage, endpoint: Option<JsValue>) -> Result<WorkerStoreHost, JsValue> {
+        let endpoint = endpoint.filter(|endpoint| !endpoint.is_undefined()).unwrap_or_else(|| js_sys::global().into());
+        let subscriptions: HostSubscriptions = Shared::new(Lock::new(HashMap::new()));
+
+        let mut host_store = store.clone();
+        let host_endpoint = endpoint.clone();
+        let host_subscriptions = subscriptions.clone();
+        let on_message = Closure::wrap(Box::new(move |event: web_sys::MessageEvent| {
+            let request = event.data();
+            if field(&request, PROTOCOL).as_string().as_deref() != Some("request") {
+                return;
+            }
+            let id = field(&request, "id").as_f64().unwrap_or_default() as u32;
+            let result = serve(&mut host_store, &host_endpoint, &host_subscriptions, id, &request);
+            let reply = message("response", id).and_then(|reply| {
+                match result {
+                    Ok(value) => js_sys::Reflect::set(&reply, &JsValue::from_str("value"), &value)?,
+                    Err(error) => js_sys::Reflect::set(&reply, &JsValue::from_str("error"), &transferable_error(&error))?,
+                };
+                call(&host_endpoint, "postMessage", &[&reply])
+            });
+            if let Err(e) = reply {
+                log_warn!("Worker store host failed to reply to request {}: {:?}", id, e);
+            }
+        }) as Box<dyn FnMut(web_sys::MessageEvent)>);
+        listen(&endpoint, &on_message)?;
+        Ok(WorkerStoreHost { endpoint, subscriptions, on_message })
+    }
+
+    /// Stops answering requests and drops every client subscription.
+    #[wasm_bindgen]
+    pub fn close(&self) -> Result<(), JsValue> {
+        call(&self.endpoint, "removeEventListener", &[&JsValue::from_str("message"), self.on_message.as_ref()])?;
+        let subscriptions = std::mem::take(&mut *self.subscriptions.lock().map_err(WasmStorageError::lock_poisoned)?);
+        for (_, (subscription, _)) in subscriptions {
+            subscription.unsubscribe()?;
+        }
+        Ok(())
+    }
+}
+
+fn serve(
+    store: &mut WasmStorage,
+    endpoint: &JsValue,
+    subscriptions: &HostSubscriptions,
+    id: u32,
+    request: &JsValue,
+) -> Result<JsValue, JsValue> {
+    let op = field(request, "op").as_string().unwrap_or_default();
+    let key = || {
+        field(request, "key")
+            .as_string()
+            .ok_or_else(|| JsValue::from(WasmStorageError::invalid_argument(format!("Worker store request \"{}\" needs a key", op))))
+    };
+    match op.as_str() {
+        "get" => store.get_state(&key()?),
+        "getAll" => store.get_all_state(),
+        "set" => store.set_state(&key()?, field(request, "value")).map(|_| JsValue::UNDEFINED),
+        "remove" => store.remove_state(&key()?).map(|_| JsValue::UNDEFINED),
+        "dispatch" => {
+            let action_type = field(request, "actionType").as_string().unwrap_or_default();
+            store.dispatch(&action_type, field(request, "payload")).map(|_| JsValue::UNDEFINED)
+        }
+        "subscribe" => {
+            let change_endpoint = endpoint.clone();
+            let listener = Closure::wrap(Box::new(move |event: JsValue| {
+                let posted = message("change", id).and_then(|change| {
+                    js_sys::Reflect::set(&change, &JsValue::from_str("event"), &event)?;
+                    call(&change_endpoint, "postMessage", &[&change])
+                });
+                if let Err(e) = posted {
+                    log_warn!("Worker store host failed to post a change: {:?}", e);
+                }
+            }) as Box<dyn FnMut(JsValue)>);
+            let options = field(request, "options").dyn_into::<js_sys::Object>().ok();
+            let subscription = store.subscribe(listener.as_ref().unchecked_ref::<js_sys::Function>().clone(), options)?;
+            subscriptions.lock().map_err(WasmStorageError::lock_poisoned)?.insert(id, (subscription, listener));
+            Ok(JsValue::from(id))
+        }
+        "unsubscribe" => {
+            let subscription = field(request, "subscription").as_f64().unwrap_or_default() as u32;
+            let removed = subscriptions.lock().map_err(WasmStorageError::lock_poisoned)?.remove(&subscription);
+            if let Some((subscription, _)) = removed {
+                subscription.unsubscribe()?;
+            }
+            Ok(JsValue::UNDEFINED)
+        }
+        _ => Err(WasmStorageError::invalid_argument(format!("Unknown worker store request: {}", op)).into()),
+    }
+}
+
+#[wasm_bindgen]
+impl WorkerStoreClient {
+    /// Talks to the `WorkerStoreHost` behind `endpoint`, a Worker or MessagePort.
+    #[wasm_bindgen(constructor)]
+    pub fn new(endpoint: JsValue) -> Result<WorkerStoreClient, JsValue> {
+        let shared = Shared::new(ClientShared {
+            endpoint,
+            next_id: Lock::new(0),
+            pending: Lock::new(HashMap::new()),
+            listeners: Lock::new(HashMap::new()),
+        });
+        let message_shared = shared.clone();
+        let on_message = Closure::wrap(Box::new(move |event: web_sys::MessageEvent| {
+            if let Err(e) = message_shared.receive(&event.data()) {
+                log_warn!("Worker store client failed to handle a message: {:?}", e);
+            }
+        }) as Box<dyn FnMut(web_sys::MessageEvent)>);
+        listen(&shared.endpoint, &on_message)?;
+        Ok(WorkerStoreClient { shared, on_message })
+    }
+
+    #[wasm_bindgen]
+    pub fn get_state(&self, key: &str) -> Result<js_sys::Promise, JsValue> {
+        self.shared.request("get", &[("key", JsValue::from_str(key))])
+    }
+
+    #[wasm_bindgen]
+    pub fn get_all_state(&self) -> Result<js_sys::Promise, JsValue> {
+        self.shared.request("getAll", &[])
+    }
+
+    #[wasm_bindgen]
+    pub fn set_state(&self, key: &str, value: JsValue) -> Result<js_sys::Promise, JsValue> {
+        self.shared.request("set", &[("key", JsValue::from_str(key)), ("value", value)])
+    }
+
+    #[wasm_bindgen]
+    pub fn remove_state(&self, key: &str) -> Result<js_sys::Promise, JsValue> {
+        self.shared.request("remove", &[("key", JsValue::from_str(key))])
+    }
+
+    #[wasm_bindgen]
+    pub fn dispatch(&self, action_type: &str, payload: JsValue) -> Result<js_sys::Promise, JsValue> {
+        self.shared.request("dispatch", &[("actionType", JsValue::from_str(action_type)), ("payload", payload)])
+    }
+
+    /// Calls `callback` with each change event of the worker's store and
+    /// resolves to an ID for `unsubscribe`. `options` go to the worker's
+    /// `subscribe` (`origins`, `aggregate`, `delivery`).
+    #[wasm_bindgen]
+    pub fn subscribe(&self, callback: js_sys::Function, options: Option<js_sys::Object>) -> Result<js_sys::Promise, JsValue> {
+        let id = self.shared.next_id()?;
+        self.shared.listeners.lock().map_err(WasmStorageError::lock_poisoned)?.insert(id, callback);
+        let options = options.map(JsValue::from).unwrap_or(JsValue::UNDEFINED);
+        self.shared.send("subscribe", id, &[("options", options)])
+    }
+
+    #[wasm_bindgen]
+    pub fn unsubscribe(&self, id: u32) -> Result<js_sys::Promise, JsValue> {
+        self.shared.listeners.lock().map_err(WasmStorageError::lock_poisoned)?.remove(&id);
+        self.shared.request("unsubscribe", &[("subscription", JsValue::from(id))])
+    }
+
+    /// Stops listening to the worker; pending requests are rejected.
+    #[wasm_bindgen]
+    pub fn close(&self) -> Result<(), JsValue> {
+        call(&self.shared.endpoint, "removeEventListener", &[&JsValue::from_str("message"), self.on_message.as_ref()])?;
+        self.shared.listeners.lock().map_err(WasmStorageError::lock_poisoned)?.clear();
+        let pending = std::mem::take(&mut *self.shared.pending.lock().map_err(WasmStorageError::lock_poisoned)?);
+        let closed = JsValue::from(WasmStorageError::invalid_state("Worker store client was closed"));
+        for (_, (_, reject)) in pending {
+            reject.call1(&JsValue::NULL, &closed)?;
+        }
+        Ok(())
+    }
+}
+
+impl ClientShared {
+    fn next_id(&self) -> Result<u32, JsValue> {
+        let mut next_id = self.next_id.lock().map_err(WasmStorageError::lock_poisoned)?;
+        *next_id += 1;
+        Ok(*next_id)
+    }
+
+    fn request(&self, op: &str, fields: &[(&str, JsValue)]) -> Result<js_sys::Promise, JsValue> {
+        let id = self.next_id()?;
+        self.send(op, id, fields)
+    }
+
+    fn send(&self, op: &str, id: u32, fields: &[(&str, JsValue)]) -> Result<js_sys::Promise, JsValue> {
+        let request = message("request", id)?;
+        js_sys::Reflect::set(&request, &JsValue::from_str("op"), &JsValue::from_str(op))?;
+        for (name, value) in fields {
+            js_sys::Reflect::set(&request, &JsValue::from_str(name), value)?;
+        }
+
+        let mut settle = None;
+        let promise = js_sys::Promise::new(&mut |resolve, reject| settle = Some((resolve, reject)));
+        if let Some(settle) = settle {
+            self.pending.lock().map_err(WasmStorageError::lock_poisoned)?.insert(id, settle);
+        }
+        if let Err(error) = call(&self.endpoint, "postMessage", &[&request]) {
+            self.pending.lock().map_err(WasmStorageError::lock_poisoned)?.remove(&id);
+            return Err(error);
+        }
+        Ok(promise)
+    }
+
+    fn receive(&self, data: &JsValue) -> Result<(), JsValue> {
+        let id = field(data, "id").as_f64().unwrap_or_default() as u32;
+        match field(data, PROTOCOL).as_string().as_deref() {
+            Some("response") => {
+                let settle = self.pending.lock().map_err(WasmStorageError::lock_poisoned)?.remove(&id);
+                let Some((resolve, reject)) = settle else {
+                    return Ok(());
+                };
+                let error = field(data, "error");
+                if error.is_undefined() {
+                    resolve.call1(&JsValue::NULL, &field(data, "value"))?;
+                } else {
+                    reject.call1(&JsValue::NULL, &error)?;
+                }
+                Ok(())
+            }
+            Some("change") => {
+                let listener = self.listeners.lock().map_err(WasmStorageError::lock_poisoned)?.get(&id).cloned();
+                if let Some(listener) = listener {
+                    listener.call1(&JsValue::NULL, &field(data, "event"))?;
+                }
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+}